@@ -159,6 +159,20 @@ impl Error {
             err: Box::new(ErrorImpl { code: code }),
         }
     }
+
+    /// The stable, machine-readable form of this error, for callers that
+    /// want to `match` rather than parse the `Debug` output.
+    pub fn code(&self) -> crate::errors::ErrorCode {
+        match &self.err.code {
+            ErrorCode::UndefinedSymbol(s) => crate::errors::ErrorCode::UndefinedSymbol(s.0.clone()),
+            ErrorCode::UndefinedParameter(s) => {
+                crate::errors::ErrorCode::UndefinedSymbol(format!("${}", s.0))
+            }
+            ErrorCode::InvalidParse(_) | ErrorCode::InvalidMacroCallArity(..) => {
+                crate::errors::ErrorCode::InvalidAssembly(self.err.code.to_string())
+            }
+        }
+    }
 }
 
 impl Debug for Error {
@@ -395,10 +409,15 @@ fn parse(i: &str) -> Result<Program, Error> {
 }
 
 fn build_opcodes() -> String {
-    EvmOpcode::iter()
+    // `DIFFICULTY` (0x44) is `PREVRANDAO` post-Merge; accept both mnemonics
+    // for the same opcode rather than picking one name for the enum, since
+    // which one is "correct" depends on the fork being assembled for.
+    let aliases = "%define PREVRANDAO() 0x44";
+    let opcodes = EvmOpcode::iter()
         .map(|x| format!("%define {}() {:#02x}", x, *x as u8))
         .collect::<Vec<_>>()
-        .join("\n")
+        .join("\n");
+    format!("{}\n{}", aliases, opcodes)
 }
 
 fn build_argument_map(
@@ -587,7 +606,7 @@ fn find_undefined_label(blocks: &BlockVec, map: &AddressMap) -> Option<Symbol> {
         })
 }
 
-fn flatten_blocks(program: Program) -> Result<Vec<u8>, Error> {
+fn flatten_blocks(program: Program) -> Result<(Vec<u8>, BTreeMap<String, usize>), Error> {
     let blocks = &program.blocks;
     let addresses = build_label_addresses(blocks);
     if let Some(l) = find_undefined_label(blocks, &addresses) {
@@ -603,7 +622,13 @@ fn flatten_blocks(program: Program) -> Result<Vec<u8>, Error> {
             })
         })
         .collect();
-    Ok(bytecode)
+    let block_addresses = build_block_addresses(blocks);
+    let labels = blocks
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, b)| b.label.as_ref().map(|l| (l.0.clone(), block_addresses[i])))
+        .collect();
+    Ok((bytecode, labels))
 }
 
 const PRELUDE: &str = "
@@ -617,9 +642,13 @@ const PRELUDE: &str = "
 ";
 
 pub fn from_string(input: &str) -> Result<Vec<u8>, Error> {
+    from_string_with_labels(input).map(|(bytecode, _)| bytecode)
+}
+
+/// Like `from_string`, but also returns every label's resolved address, for
+/// callers (the `asm --format json` CLI output) that want to re-expose the
+/// source's own symbol names alongside the assembled bytes.
+pub fn from_string_with_labels(input: &str) -> Result<(Vec<u8>, BTreeMap<String, usize>), Error> {
     let input = format!("{}\n{}\n{}", build_opcodes(), PRELUDE, input);
-    let result = parse(&input)
-        .and_then(expand_macros)
-        .and_then(flatten_blocks);
-    result
+    parse(&input).and_then(expand_macros).and_then(flatten_blocks)
 }
@@ -0,0 +1,105 @@
+// Copyright 2020 The Psyche Authors
+// This file is part of Psyche.
+//
+// Psyche is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Psyche is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Psyche. If not, see <http://www.gnu.org/licenses/>.
+
+//! Ahead-of-time compilation artifacts.
+//!
+//! An artifact is the optimized bytecode for a contract (see `opt.rs`)
+//! plus the fork it was optimized for, keyed by a hash of the original
+//! code so block-processing callers can compile a contract once and reuse
+//! the artifact across many executions instead of re-running analysis and
+//! optimization on every call. The on-disk format is plain text, in
+//! keeping with the rest of the CLI (hex bytecode, `.ass` sources): the
+//! fork name on the first line, the hex-encoded optimized bytecode on the
+//! second.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::schedule::Fork;
+use crate::utils::{decode_hex, write_hex};
+
+/// A 64-bit FNV-1a hash of `bytecode`, used to name cached artifacts.
+pub fn code_hash(bytecode: &[u8]) -> u64 {
+    const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const FNV_PRIME: u64 = 0x100_0000_01b3;
+    let mut hash = FNV_OFFSET_BASIS;
+    for &byte in bytecode {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+/// Returns the filename an artifact for `bytecode` is cached under.
+pub fn artifact_filename(bytecode: &[u8]) -> String {
+    format!("{:016x}.psc", code_hash(bytecode))
+}
+
+/// Writes an artifact for `optimized` (already optimized for `fork`) at
+/// `path`.
+pub fn write_artifact(path: &Path, fork: Fork, optimized: &[u8]) -> io::Result<()> {
+    let mut file = fs::File::create(path)?;
+    writeln!(file, "{:?}", fork)?;
+    write_hex(&mut file, optimized)?;
+    writeln!(file)
+}
+
+/// Reads back an artifact written by `write_artifact`.
+pub fn read_artifact(path: &Path) -> io::Result<(Fork, Vec<u8>)> {
+    let contents = fs::read_to_string(path)?;
+    let mut lines = contents.lines();
+    let fork = lines
+        .next()
+        .and_then(|s| Fork::from_str(s).ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing or invalid fork"))?;
+    let code = lines
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing bytecode"))
+        .and_then(|s| {
+            decode_hex(s).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+        })?;
+    Ok((fork, code))
+}
+
+/// Convenience wrapper joining a cache directory with `artifact_filename`.
+pub fn artifact_path(cache_dir: &Path, bytecode: &[u8]) -> PathBuf {
+    cache_dir.join(artifact_filename(bytecode))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashes_are_deterministic_and_content_dependent() {
+        assert_eq!(code_hash(&[1, 2, 3]), code_hash(&[1, 2, 3]));
+        assert_ne!(code_hash(&[1, 2, 3]), code_hash(&[1, 2, 4]));
+    }
+
+    #[test]
+    fn round_trips_through_disk() {
+        let dir = std::env::temp_dir().join(format!("psyche-cache-test-{}", code_hash(b"seed")));
+        fs::create_dir_all(&dir).unwrap();
+        let path = artifact_path(&dir, &[0x60, 0x01, 0x00]);
+        write_artifact(&path, Fork::Istanbul, &[0x60, 0x01, 0x00]).unwrap();
+        let (fork, code) = read_artifact(&path).unwrap();
+        assert_eq!(fork, Fork::Istanbul);
+        assert_eq!(code, vec![0x60, 0x01, 0x00]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,252 @@
+// Copyright 2020 The Psyche Authors
+// This file is part of Psyche.
+//
+// Psyche is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Psyche is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Psyche. If not, see <http://www.gnu.org/licenses/>.
+
+//! Parsing for the `evm` subcommand's CLI arguments, pulled out of
+//! `main.rs` so it can carry its own unit tests instead of only being
+//! exercised end to end through `clap`.
+
+use std::convert::TryFrom;
+
+use crate::instructions::EvmOpcode;
+use crate::u256::U256;
+use crate::utils::decode_hex;
+use crate::vm::{TraceFilter, TraceSample};
+
+/// Looks up an `EvmOpcode` by its mnemonic (case-insensitive), for parsing
+/// `--break-on` values.
+pub fn opcode_from_name(name: &str) -> Option<EvmOpcode> {
+    (0..=255u16)
+        .filter_map(|code| EvmOpcode::try_from(code as u8).ok())
+        .find(|opcode| opcode.to_string().eq_ignore_ascii_case(name))
+}
+
+/// Parses a `--break-at` value as either a decimal or a `0x`-prefixed hex
+/// program counter.
+pub fn parse_pc(value: &str) -> Result<usize, std::num::ParseIntError> {
+    match value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")) {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => value.parse(),
+    }
+}
+
+/// Parses a `--watch-memory` value as a `START-END` byte range, each side
+/// accepted in the same decimal-or-hex form as `parse_pc`.
+pub fn parse_memory_range(value: &str) -> Result<(usize, usize), String> {
+    let (start, end) = value
+        .split_once('-')
+        .ok_or_else(|| format!("expected START-END, got \"{}\"", value))?;
+    let start = parse_pc(start).map_err(|e| e.to_string())?;
+    let end = parse_pc(end).map_err(|e| e.to_string())?;
+    if end <= start {
+        return Err(format!("END must be greater than START, got {}-{}", start, end));
+    }
+    Ok((start, end))
+}
+
+/// Parses a `--trace` value as `count:OPCODE`, `stack-top:OPCODE`,
+/// `return-stack:OPCODE`, or the opcode-less `pc-counts`, the CLI
+/// spellings of `TraceFilter::CountOpcode`/`StackTopAt`/`ReturnStackAt`/
+/// `PcCounts`.
+pub fn parse_trace_filter(value: &str) -> Result<TraceFilter, String> {
+    if value == "pc-counts" {
+        return Ok(TraceFilter::PcCounts);
+    }
+    let (kind, name) = value
+        .split_once(':')
+        .ok_or_else(|| format!("expected KIND:OPCODE, got \"{}\"", value))?;
+    let opcode = opcode_from_name(name).ok_or_else(|| format!("unknown opcode {}", name))?;
+    match kind {
+        "count" => Ok(TraceFilter::CountOpcode(opcode)),
+        "stack-top" => Ok(TraceFilter::StackTopAt(opcode)),
+        "return-stack" => Ok(TraceFilter::ReturnStackAt(opcode)),
+        _ => Err(format!(
+            "unknown trace kind \"{}\" (expected count, stack-top, or return-stack)",
+            kind
+        )),
+    }
+}
+
+/// Parses a `--trace-sample` value as either a decimal sampling rate N
+/// (keep every Nth step; `1` behaves like no sampling at all) or the
+/// literal `block` (keep only basic-block entry points), the CLI
+/// spellings of `TraceSample::EveryNth`/`BlockBoundaries`.
+pub fn parse_trace_sample(value: &str) -> Result<TraceSample, String> {
+    if value == "block" {
+        return Ok(TraceSample::BlockBoundaries);
+    }
+    let n: u32 = value
+        .parse()
+        .map_err(|_| format!("expected a positive integer or \"block\", got \"{}\"", value))?;
+    if n == 0 {
+        return Err("sampling rate must be at least 1".to_string());
+    }
+    Ok(TraceSample::EveryNth(n))
+}
+
+/// Parses a `--trace-opcodes` value as a comma-separated opcode list (e.g.
+/// `SSTORE,CALL`), the CLI spelling of `TraceSample::Opcodes`.
+pub fn parse_trace_opcodes(value: &str) -> Result<TraceSample, String> {
+    let opcodes = value
+        .split(',')
+        .map(|name| opcode_from_name(name).ok_or_else(|| format!("unknown opcode {}", name)))
+        .collect::<Result<Vec<_>, _>>()?;
+    if opcodes.is_empty() {
+        return Err("expected at least one opcode".to_string());
+    }
+    Ok(TraceSample::Opcodes(opcodes))
+}
+
+/// Parses a `--gas-override` value as `OPCODE=COST`, for researchers
+/// experimenting with repricing without forking the crate (see
+/// `Schedule::with_overrides`).
+pub fn parse_gas_override(value: &str) -> Result<(EvmOpcode, u64), String> {
+    let (name, cost) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected OPCODE=COST, got \"{}\"", value))?;
+    let opcode = opcode_from_name(name).ok_or_else(|| format!("unknown opcode {}", name))?;
+    let cost = cost.parse::<u64>().map_err(|e| e.to_string())?;
+    Ok((opcode, cost))
+}
+
+/// Parses a `--gas`/`--gas-price`/`--value` value as a `U256`: decimal by
+/// default, `0x`-prefixed hex, either with `_` digit separators tolerated
+/// anywhere (`20_000_000`, `0x0100_0000`) the way Rust integer literals
+/// allow them.
+pub fn parse_u256(value: &str) -> Result<U256, String> {
+    let cleaned: String = value.chars().filter(|&c| c != '_').collect();
+    match cleaned.strip_prefix("0x").or_else(|| cleaned.strip_prefix("0X")) {
+        Some(hex) => {
+            if hex.is_empty() || hex.len() > 64 {
+                return Err(format!("invalid hex value \"{}\"", value));
+            }
+            let padded = if hex.len() % 2 == 1 {
+                format!("0{}", hex)
+            } else {
+                hex.to_string()
+            };
+            let bytes = decode_hex(&padded).map_err(|e| e.to_string())?;
+            let mut limbs = [0u64; 4];
+            for (i, &byte) in bytes.iter().rev().enumerate() {
+                limbs[i / 8] |= (byte as u64) << ((i % 8) * 8);
+            }
+            Ok(U256::from_slice(&limbs))
+        }
+        None => U256::from_dec_str(&cleaned).map_err(|e| e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_u256_accepts_plain_decimal() {
+        assert_eq!(parse_u256("42").unwrap().low_u64(), 42);
+    }
+
+    #[test]
+    fn parse_u256_accepts_decimal_with_digit_separators() {
+        assert_eq!(parse_u256("20_000_000").unwrap().low_u64(), 20_000_000);
+    }
+
+    #[test]
+    fn parse_u256_accepts_0x_prefixed_hex() {
+        assert_eq!(parse_u256("0x2a").unwrap().low_u64(), 0x2a);
+        assert_eq!(parse_u256("0X2A").unwrap().low_u64(), 0x2a);
+    }
+
+    #[test]
+    fn parse_u256_accepts_hex_with_digit_separators() {
+        assert_eq!(parse_u256("0x0100_0000").unwrap().low_u64(), 0x0100_0000);
+    }
+
+    #[test]
+    fn parse_u256_accepts_an_odd_number_of_hex_digits() {
+        assert_eq!(parse_u256("0x5").unwrap().low_u64(), 5);
+    }
+
+    #[test]
+    fn parse_u256_rejects_an_empty_hex_value() {
+        assert!(parse_u256("0x").is_err());
+    }
+
+    #[test]
+    fn parse_u256_rejects_a_non_decimal_non_hex_value() {
+        assert!(parse_u256("not-a-number").is_err());
+    }
+
+    #[test]
+    fn parse_u256_round_trips_the_maximum_value() {
+        let max_hex = format!("0x{}", "f".repeat(64));
+        assert_eq!(parse_u256(&max_hex).unwrap().0, [u64::MAX; 4]);
+    }
+
+    #[test]
+    fn parse_pc_accepts_decimal_and_hex() {
+        assert_eq!(parse_pc("10").unwrap(), 10);
+        assert_eq!(parse_pc("0x0a").unwrap(), 10);
+    }
+
+    #[test]
+    fn parse_memory_range_requires_end_greater_than_start() {
+        assert_eq!(parse_memory_range("0x00-0x20").unwrap(), (0, 32));
+        assert!(parse_memory_range("0x20-0x20").is_err());
+    }
+
+    #[test]
+    fn parse_trace_filter_accepts_every_known_kind() {
+        assert!(matches!(
+            parse_trace_filter("count:SLOAD").unwrap(),
+            TraceFilter::CountOpcode(EvmOpcode::SLOAD)
+        ));
+        assert!(matches!(
+            parse_trace_filter("stack-top:JUMPI").unwrap(),
+            TraceFilter::StackTopAt(EvmOpcode::JUMPI)
+        ));
+        assert!(matches!(
+            parse_trace_filter("return-stack:RETURNSUB").unwrap(),
+            TraceFilter::ReturnStackAt(EvmOpcode::RETURNSUB)
+        ));
+        assert!(matches!(parse_trace_filter("pc-counts").unwrap(), TraceFilter::PcCounts));
+        assert!(parse_trace_filter("bogus:SLOAD").is_err());
+    }
+
+    #[test]
+    fn parse_trace_sample_accepts_a_rate_or_block() {
+        assert!(matches!(parse_trace_sample("100").unwrap(), TraceSample::EveryNth(100)));
+        assert!(matches!(parse_trace_sample("block").unwrap(), TraceSample::BlockBoundaries));
+        assert!(parse_trace_sample("0").is_err());
+        assert!(parse_trace_sample("not-a-number").is_err());
+    }
+
+    #[test]
+    fn parse_trace_opcodes_accepts_a_comma_separated_list() {
+        match parse_trace_opcodes("SSTORE,CALL").unwrap() {
+            TraceSample::Opcodes(opcodes) => {
+                assert_eq!(opcodes, vec![EvmOpcode::SSTORE, EvmOpcode::CALL]);
+            }
+            other => panic!("expected TraceSample::Opcodes, got {:?}", other),
+        }
+        assert!(parse_trace_opcodes("NOTANOPCODE").is_err());
+    }
+
+    #[test]
+    fn parse_gas_override_accepts_opcode_equals_cost() {
+        assert_eq!(parse_gas_override("SLOAD=500").unwrap(), (EvmOpcode::SLOAD, 500));
+        assert!(parse_gas_override("SLOAD").is_err());
+        assert!(parse_gas_override("NOTANOPCODE=500").is_err());
+    }
+}
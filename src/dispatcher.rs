@@ -0,0 +1,98 @@
+// Copyright 2020 The Psyche Authors
+// This file is part of Psyche.
+//
+// Psyche is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Psyche is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Psyche. If not, see <http://www.gnu.org/licenses/>.
+
+//! Recognition of Solidity's standard function dispatcher pattern from
+//! bytecode alone, used by `disasm` (to label entry blocks) and
+//! `profiler` (to attribute gas without a source map).
+//!
+//! This only recognizes the linear chain solc has emitted historically —
+//! `PUSH4 selector; EQ; PUSHn dest; JUMPI` repeated once per function,
+//! each guarded by a leading `DUP1` that isn't part of the match. Solidity
+//! 0.8's optimizer can instead emit a binary search over selectors
+//! (comparing with `LT`/`GT` and recursing into sub-ranges), which this
+//! does not detect; teaching `detect_linear` that shape is left as
+//! follow-up work.
+
+use crate::instructions::EvmOpcode;
+
+/// One recognized `PUSH4 selector; EQ; PUSHn dest; JUMPI` branch.
+#[derive(Debug, Clone, Copy)]
+pub struct DispatcherBranch {
+    pub selector: u32,
+    pub dest: usize,
+}
+
+/// Scans `bytecode` for the linear dispatcher pattern, returning one
+/// `DispatcherBranch` per match, in the order they appear.
+pub fn detect_linear(bytecode: &[u8]) -> Vec<DispatcherBranch> {
+    let mut branches = Vec::new();
+    let mut i = 0usize;
+    while i + 5 <= bytecode.len() {
+        if bytecode[i] == EvmOpcode::PUSH4 as u8 {
+            let selector = u32::from_be_bytes(bytecode[i + 1..i + 5].try_into().unwrap());
+            let eq = i + 5;
+            if bytecode.get(eq) == Some(&(EvmOpcode::EQ as u8)) {
+                let push_dest = eq + 1;
+                if let Some(&push_op) = bytecode.get(push_dest) {
+                    if (EvmOpcode::PUSH1 as u8..=EvmOpcode::PUSH32 as u8).contains(&push_op) {
+                        let push_len = (push_op - EvmOpcode::PUSH1 as u8 + 1) as usize;
+                        let dest_start = push_dest + 1;
+                        let dest_end = dest_start + push_len;
+                        if bytecode.get(dest_end) == Some(&(EvmOpcode::JUMPI as u8)) {
+                            let dest = bytecode[dest_start..dest_end]
+                                .iter()
+                                .fold(0usize, |acc, &b| (acc << 8) | b as usize);
+                            branches.push(DispatcherBranch { selector, dest });
+                        }
+                    }
+                }
+            }
+        }
+        i += 1;
+    }
+    branches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognizes_a_single_dispatcher_branch() {
+        // PUSH4 0xdeadbeef; EQ; PUSH1 0x0a; JUMPI
+        let code = vec![0x63, 0xde, 0xad, 0xbe, 0xef, 0x14, 0x60, 0x0a, 0x57];
+        let branches = detect_linear(&code);
+        assert_eq!(branches.len(), 1);
+        assert_eq!(branches[0].selector, 0xdead_beef);
+        assert_eq!(branches[0].dest, 0x0a);
+    }
+
+    #[test]
+    fn recognizes_multiple_branches_back_to_back() {
+        let mut code = vec![0x63, 0x00, 0x00, 0x00, 0x01, 0x14, 0x60, 0x20, 0x57];
+        code.extend_from_slice(&[0x63, 0x00, 0x00, 0x00, 0x02, 0x14, 0x60, 0x30, 0x57]);
+        let branches = detect_linear(&code);
+        assert_eq!(branches.len(), 2);
+        assert_eq!(branches[1].selector, 2);
+        assert_eq!(branches[1].dest, 0x30);
+    }
+
+    #[test]
+    fn ignores_a_push4_not_followed_by_the_full_pattern() {
+        let code = vec![0x63, 0x00, 0x00, 0x00, 0x01, 0x00];
+        assert!(detect_linear(&code).is_empty());
+    }
+}
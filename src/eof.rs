@@ -0,0 +1,536 @@
+// Copyright 2020 The Psyche Authors
+// This file is part of Psyche.
+//
+// Psyche is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Psyche is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Psyche. If not, see <http://www.gnu.org/licenses/>.
+
+//! EVM Object Format (EOF) container parsing and code validation
+//! (EIP-3540, EIP-3670, EIP-4750, EIP-6206 container format).
+//!
+//! This covers the EOF container header and body layout: a magic prefix,
+//! a version byte, a header listing an optional type section, one or
+//! more code sections, and at most one optional data section, followed
+//! by their bodies in header order. `Schedule`'s `Fork` enum has no fork
+//! past Prague to gate any of this on, so callers decide when EOF
+//! containers are recognized (e.g. by trying to parse any input starting
+//! with the magic bytes) rather than this module hard-coding a fork.
+//!
+//! `validate_code` performs EIP-3670 instruction validation, and
+//! `validate_container` adds a linear (non-branching) EIP-6206-style
+//! max-stack-height check against each section's declared type. It deliberately does NOT recognize
+//! `CALLF`/`RETF`/`JUMPF`: running EOF containers with more than one code
+//! section needs those opcodes wired into the interpreter's dispatch
+//! loop, gas schedule and `OPCODE_INFOS`/bb-info tables, and a real
+//! control-flow-graph stack validator (EIP-5450) to replace this
+//! straight-line approximation — all of that is a larger increment left
+//! for follow-up work. `unwrap_eof` (see `main.rs`) only executes
+//! containers with a single code section.
+
+use std::convert::TryFrom;
+use std::fmt;
+
+use crate::instructions::EvmOpcode;
+use crate::schedule::Schedule;
+use crate::vm::{deep_stack_effect, OPCODE_INFOS};
+
+const MAGIC: [u8; 2] = [0xef, 0x00];
+const VERSION: u8 = 1;
+
+const KIND_TERMINATOR: u8 = 0x00;
+const KIND_CODE: u8 = 0x01;
+const KIND_DATA: u8 = 0x02;
+const KIND_TYPES: u8 = 0x03;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum EofError {
+    InvalidMagic,
+    TruncatedHeader,
+    UnsupportedVersion(u8),
+    UnknownSectionKind(u8),
+    DuplicateSection(u8),
+    EmptySection(u8),
+    MissingCodeSection,
+    SectionSizeMismatch { expected: usize, actual: usize },
+    TypeSectionSizeMismatch { expected: usize, actual: usize },
+    UndefinedInstruction { pc: usize, opcode: u8 },
+    TruncatedImmediate { pc: usize },
+    StackUnderflow { section: usize, pc: usize },
+    MaxStackMismatch { section: usize, declared: u16, computed: u16 },
+}
+
+impl fmt::Display for EofError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EofError::InvalidMagic => write!(f, "not an EOF container: missing 0xEF00 magic"),
+            EofError::TruncatedHeader => write!(f, "EOF header ends before a well-formed section list"),
+            EofError::UnsupportedVersion(v) => write!(f, "unsupported EOF version {}", v),
+            EofError::UnknownSectionKind(k) => write!(f, "unknown EOF section kind {:#04x}", k),
+            EofError::DuplicateSection(k) => write!(f, "duplicate EOF section kind {:#04x}", k),
+            EofError::EmptySection(k) => write!(f, "EOF section kind {:#04x} declares a size of 0", k),
+            EofError::MissingCodeSection => write!(f, "EOF container has no code section"),
+            EofError::SectionSizeMismatch { expected, actual } => write!(
+                f,
+                "EOF body is {} bytes, but the header declares {}",
+                actual, expected
+            ),
+            EofError::TypeSectionSizeMismatch { expected, actual } => write!(
+                f,
+                "EOF type section is {} bytes, but {} code sections need {}",
+                actual, expected / 4, expected
+            ),
+            EofError::UndefinedInstruction { pc, opcode } => {
+                write!(f, "undefined instruction {:#04x} at pc {}", opcode, pc)
+            }
+            EofError::TruncatedImmediate { pc } => {
+                write!(f, "PUSH at pc {} is missing part of its immediate", pc)
+            }
+            EofError::StackUnderflow { section, pc } => {
+                write!(f, "code section {} pops an empty stack at pc {}", section, pc)
+            }
+            EofError::MaxStackMismatch { section, declared, computed } => write!(
+                f,
+                "code section {} declares max_stack={}, but computes to {}",
+                section, declared, computed
+            ),
+        }
+    }
+}
+
+/// A code section's declared calling convention (EIP-4750): how many
+/// stack items it consumes and produces, and the max stack height it
+/// reaches, checked at load time instead of the runtime stack-depth
+/// checks legacy code relies on.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct TypeSection {
+    pub inputs: u8,
+    pub outputs: u8,
+    pub max_stack: u16,
+}
+
+/// A parsed, but not yet validated, EOF container.
+#[derive(Debug, PartialEq, Eq)]
+pub struct EofContainer {
+    pub version: u8,
+    /// One entry per code section, in section order; empty if the
+    /// container has no type section (a bare single-code-section
+    /// EIP-3540 container).
+    pub types: Vec<TypeSection>,
+    pub code_sections: Vec<Vec<u8>>,
+    pub data: Vec<u8>,
+}
+
+impl EofContainer {
+    /// The first (and, for containers this module can execute, only)
+    /// code section. Callers that only support single-section execution
+    /// use this instead of indexing `code_sections` directly.
+    pub fn code(&self) -> &[u8] {
+        &self.code_sections[0]
+    }
+}
+
+/// True if `bytes` starts with the EOF magic prefix. Callers use this to
+/// decide whether to route input through `parse`/`validate_code` instead
+/// of treating it as legacy bytecode.
+pub fn is_eof(bytes: &[u8]) -> bool {
+    bytes.starts_with(&MAGIC)
+}
+
+/// Parses `bytes` as an EOF container (EIP-3540/4750): magic, version, a
+/// section header terminated by a zero byte, then the section bodies in
+/// header order. Doesn't perform EIP-3670/6206 code validation; see
+/// `validate_code`.
+pub fn parse(bytes: &[u8]) -> Result<EofContainer, EofError> {
+    if !is_eof(bytes) {
+        return Err(EofError::InvalidMagic);
+    }
+    let version = *bytes.get(2).ok_or(EofError::TruncatedHeader)?;
+    if version != VERSION {
+        return Err(EofError::UnsupportedVersion(version));
+    }
+    let mut i = 3;
+    let mut sections = Vec::new();
+    let mut have_types = false;
+    let mut have_data = false;
+    let mut num_code_sections = 0;
+    loop {
+        let kind = *bytes.get(i).ok_or(EofError::TruncatedHeader)?;
+        i += 1;
+        if kind == KIND_TERMINATOR {
+            break;
+        }
+        let size = u16::from_be_bytes(
+            <[u8; 2]>::try_from(bytes.get(i..i + 2).ok_or(EofError::TruncatedHeader)?).unwrap(),
+        ) as usize;
+        i += 2;
+        if size == 0 {
+            return Err(EofError::EmptySection(kind));
+        }
+        match kind {
+            KIND_TYPES if have_types => return Err(EofError::DuplicateSection(kind)),
+            KIND_TYPES => have_types = true,
+            KIND_CODE => num_code_sections += 1,
+            KIND_DATA if have_data => return Err(EofError::DuplicateSection(kind)),
+            KIND_DATA => have_data = true,
+            _ => return Err(EofError::UnknownSectionKind(kind)),
+        }
+        sections.push((kind, size));
+    }
+    if num_code_sections == 0 {
+        return Err(EofError::MissingCodeSection);
+    }
+    let total: usize = sections.iter().map(|(_, size)| size).sum();
+    let body = &bytes[i..];
+    if body.len() != total {
+        return Err(EofError::SectionSizeMismatch { expected: total, actual: body.len() });
+    }
+    let mut offset = 0;
+    let mut types_bytes: &[u8] = &[];
+    let mut code_sections = Vec::with_capacity(num_code_sections);
+    let mut data = Vec::new();
+    for (kind, size) in sections {
+        let chunk = &body[offset..offset + size];
+        offset += size;
+        match kind {
+            KIND_TYPES => types_bytes = chunk,
+            KIND_CODE => code_sections.push(chunk.to_vec()),
+            KIND_DATA => data = chunk.to_vec(),
+            _ => unreachable!(),
+        }
+    }
+    let types = if types_bytes.is_empty() {
+        Vec::new()
+    } else {
+        if types_bytes.len() != code_sections.len() * 4 {
+            return Err(EofError::TypeSectionSizeMismatch {
+                expected: code_sections.len() * 4,
+                actual: types_bytes.len(),
+            });
+        }
+        types_bytes
+            .chunks_exact(4)
+            .map(|c| TypeSection { inputs: c[0], outputs: c[1], max_stack: u16::from_be_bytes([c[2], c[3]]) })
+            .collect()
+    };
+    Ok(EofContainer { version, types, code_sections, data })
+}
+
+/// EIP-3670 code validation: every byte in `code` must decode to an
+/// instruction introduced by `schedule`'s fork, and no `PUSHN`'s
+/// immediate may run past the end of the section.
+pub fn validate_code(code: &[u8], schedule: &Schedule) -> Result<(), EofError> {
+    let mut pc = 0;
+    while pc < code.len() {
+        let byte = code[pc];
+        let (introduced_fork, _, _, _) = OPCODE_INFOS[byte as usize];
+        let opcode = EvmOpcode::try_from(byte)
+            .ok()
+            .filter(|_| schedule.fork >= introduced_fork);
+        let opcode = match opcode {
+            Some(opcode) => opcode,
+            None => return Err(EofError::UndefinedInstruction { pc, opcode: byte }),
+        };
+        if opcode.is_push() {
+            let num_bytes = opcode.push_index() + 1;
+            if pc + 1 + num_bytes > code.len() {
+                return Err(EofError::TruncatedImmediate { pc });
+            }
+            pc += 1 + num_bytes;
+        } else if opcode.is_deep_stack() {
+            if pc + 1 >= code.len() {
+                return Err(EofError::TruncatedImmediate { pc });
+            }
+            pc += 2;
+        } else {
+            pc += 1;
+        }
+    }
+    Ok(())
+}
+
+/// A linear (branch-insensitive) EIP-6206-style max-stack-height check:
+/// walks `code` straight through, tracking stack height via each
+/// instruction's `OPCODE_INFOS` pop/push counts, and fails if a pop ever
+/// underflows the section's declared `inputs`. This is an approximation
+/// of the real EIP-5450 validator, which walks the section's full
+/// control-flow graph rather than a single straight-line pass; it's
+/// only exact for sections with no internal jumps.
+fn compute_max_stack(code: &[u8], inputs: u8) -> Result<u16, EofError> {
+    let mut height = inputs as i64;
+    let mut max_height = height;
+    let mut pc = 0;
+    while pc < code.len() {
+        let byte = code[pc];
+        let opcode = EvmOpcode::try_from(byte).ok();
+        let (pops, pushes) = match opcode.filter(|op| op.is_deep_stack()) {
+            Some(op) => {
+                let immediate = code.get(pc + 1).copied().unwrap_or(0);
+                deep_stack_effect(op, immediate)
+            }
+            None => {
+                let (_, _, pops, pushes) = OPCODE_INFOS[byte as usize];
+                (pops, pushes)
+            }
+        };
+        if (pops as i64) > height {
+            return Err(EofError::StackUnderflow { section: 0, pc });
+        }
+        height = height - pops as i64 + pushes as i64;
+        max_height = max_height.max(height);
+        pc += 1 + opcode.filter(|op| op.is_push()).map_or(0, |op| op.push_index() + 1);
+        if opcode.filter(|op| op.is_deep_stack()).is_some() {
+            pc += 1;
+        }
+    }
+    Ok(max_height as u16)
+}
+
+/// Validates a whole container: each code section's instructions
+/// (EIP-3670) and, when a type section is present, that its declared
+/// `max_stack` matches the straight-line height `compute_max_stack`
+/// computes for that section (see its doc comment for the CFG caveat).
+pub fn validate_container(container: &EofContainer, schedule: &Schedule) -> Result<(), EofError> {
+    for (index, code) in container.code_sections.iter().enumerate() {
+        validate_code(code, schedule)?;
+        if let Some(ty) = container.types.get(index) {
+            let computed = compute_max_stack(code, ty.inputs).map_err(|e| match e {
+                EofError::StackUnderflow { pc, .. } => EofError::StackUnderflow { section: index, pc },
+                other => other,
+            })?;
+            if computed != ty.max_stack {
+                return Err(EofError::MaxStackMismatch { section: index, declared: ty.max_stack, computed });
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schedule::Fork;
+
+    fn header(code_size: u16, data_size: Option<u16>) -> Vec<u8> {
+        let mut header = vec![0xef, 0x00, VERSION, KIND_CODE];
+        header.extend_from_slice(&code_size.to_be_bytes());
+        if let Some(data_size) = data_size {
+            header.push(KIND_DATA);
+            header.extend_from_slice(&data_size.to_be_bytes());
+        }
+        header.push(KIND_TERMINATOR);
+        header
+    }
+
+    #[test]
+    fn parses_a_container_with_only_a_code_section() {
+        let mut bytes = header(2, None);
+        bytes.extend_from_slice(&[0x60, 0x01]); // PUSH1 1
+        let container = parse(&bytes).unwrap();
+        assert_eq!(container.version, VERSION);
+        assert_eq!(container.code(), &[0x60, 0x01]);
+        assert!(container.data.is_empty());
+        assert!(container.types.is_empty());
+    }
+
+    #[test]
+    fn parses_a_container_with_code_and_data_sections() {
+        let mut bytes = header(1, Some(3));
+        bytes.extend_from_slice(&[0x00]); // STOP
+        bytes.extend_from_slice(&[0xaa, 0xbb, 0xcc]);
+        let container = parse(&bytes).unwrap();
+        assert_eq!(container.code(), &[0x00]);
+        assert_eq!(container.data, vec![0xaa, 0xbb, 0xcc]);
+    }
+
+    #[test]
+    fn parses_a_container_with_multiple_code_sections_and_a_type_section() {
+        let mut bytes = vec![0xef, 0x00, VERSION, KIND_TYPES];
+        bytes.extend_from_slice(&8u16.to_be_bytes()); // 2 sections * 4 bytes
+        bytes.push(KIND_CODE);
+        bytes.extend_from_slice(&1u16.to_be_bytes());
+        bytes.push(KIND_CODE);
+        bytes.extend_from_slice(&1u16.to_be_bytes());
+        bytes.push(KIND_TERMINATOR);
+        bytes.extend_from_slice(&[0, 0x80, 0, 0]); // section 0: 0 in, non-returning, max_stack 0
+        bytes.extend_from_slice(&[0, 0, 0, 1]); // section 1: 0 in, 0 out, max_stack 1
+        bytes.extend_from_slice(&[0x00]); // section 0 body: STOP
+        bytes.extend_from_slice(&[0x00]); // section 1 body: STOP
+        let container = parse(&bytes).unwrap();
+        assert_eq!(container.code_sections, vec![vec![0x00], vec![0x00]]);
+        assert_eq!(
+            container.types,
+            vec![
+                TypeSection { inputs: 0, outputs: 0x80, max_stack: 0 },
+                TypeSection { inputs: 0, outputs: 0, max_stack: 1 },
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_a_type_section_sized_for_the_wrong_number_of_code_sections() {
+        let mut bytes = vec![0xef, 0x00, VERSION, KIND_TYPES];
+        bytes.extend_from_slice(&4u16.to_be_bytes()); // only 1 entry's worth
+        bytes.push(KIND_CODE);
+        bytes.extend_from_slice(&1u16.to_be_bytes());
+        bytes.push(KIND_CODE);
+        bytes.extend_from_slice(&1u16.to_be_bytes());
+        bytes.push(KIND_TERMINATOR);
+        bytes.extend_from_slice(&[0, 0x80, 0, 0]);
+        bytes.extend_from_slice(&[0x00, 0x00]);
+        assert_eq!(
+            parse(&bytes),
+            Err(EofError::TypeSectionSizeMismatch { expected: 8, actual: 4 })
+        );
+    }
+
+    #[test]
+    fn rejects_input_without_the_magic_prefix() {
+        assert_eq!(parse(&[0x60, 0x01]), Err(EofError::InvalidMagic));
+    }
+
+    #[test]
+    fn rejects_an_unsupported_version() {
+        let bytes = vec![0xef, 0x00, 0x02];
+        assert_eq!(parse(&bytes), Err(EofError::UnsupportedVersion(2)));
+    }
+
+    #[test]
+    fn rejects_a_missing_code_section() {
+        let bytes = vec![0xef, 0x00, VERSION, KIND_TERMINATOR];
+        assert_eq!(parse(&bytes), Err(EofError::MissingCodeSection));
+    }
+
+    #[test]
+    fn rejects_a_duplicate_data_section() {
+        let mut bytes = vec![0xef, 0x00, VERSION, KIND_CODE];
+        bytes.extend_from_slice(&1u16.to_be_bytes());
+        bytes.push(KIND_DATA);
+        bytes.extend_from_slice(&1u16.to_be_bytes());
+        bytes.push(KIND_DATA);
+        bytes.extend_from_slice(&1u16.to_be_bytes());
+        bytes.push(KIND_TERMINATOR);
+        assert_eq!(parse(&bytes), Err(EofError::DuplicateSection(KIND_DATA)));
+    }
+
+    #[test]
+    fn rejects_a_body_shorter_than_the_declared_section_sizes() {
+        let mut bytes = header(4, None);
+        bytes.extend_from_slice(&[0x00]); // only 1 of the declared 4 bytes
+        assert_eq!(
+            parse(&bytes),
+            Err(EofError::SectionSizeMismatch { expected: 4, actual: 1 })
+        );
+    }
+
+    #[test]
+    fn accepts_valid_code() {
+        let code = vec![0x60, 0x01, 0x60, 0x02, 0x01, 0x00]; // PUSH1 1 PUSH1 2 ADD STOP
+        assert_eq!(validate_code(&code, &Schedule::from_fork(Fork::Frontier)), Ok(()));
+    }
+
+    #[test]
+    fn rejects_an_undefined_instruction() {
+        let code = vec![0x0c]; // unassigned opcode
+        assert_eq!(
+            validate_code(&code, &Schedule::from_fork(Fork::Frontier)),
+            Err(EofError::UndefinedInstruction { pc: 0, opcode: 0x0c })
+        );
+    }
+
+    #[test]
+    fn rejects_an_instruction_not_yet_introduced_by_the_fork() {
+        let code = vec![EvmOpcode::SHL as u8]; // introduced at Constantinople
+        assert_eq!(
+            validate_code(&code, &Schedule::from_fork(Fork::Frontier)),
+            Err(EofError::UndefinedInstruction {
+                pc: 0,
+                opcode: EvmOpcode::SHL as u8
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_a_truncated_push_immediate() {
+        let code = vec![0x60]; // PUSH1 with no operand byte
+        assert_eq!(
+            validate_code(&code, &Schedule::from_fork(Fork::Frontier)),
+            Err(EofError::TruncatedImmediate { pc: 0 })
+        );
+    }
+
+    #[test]
+    fn rejects_a_truncated_deep_stack_immediate() {
+        let code = vec![EvmOpcode::DUPN as u8]; // DUPN with no operand byte
+        assert_eq!(
+            validate_code(&code, &Schedule::from_fork(Fork::Prague)),
+            Err(EofError::TruncatedImmediate { pc: 0 })
+        );
+    }
+
+    #[test]
+    fn computes_the_max_stack_height_of_straight_line_code() {
+        let code = vec![0x60, 0x01, 0x60, 0x02, 0x01, 0x00]; // PUSH1 1 PUSH1 2 ADD STOP
+        assert_eq!(compute_max_stack(&code, 0), Ok(2));
+    }
+
+    #[test]
+    fn accounts_for_declared_inputs_already_on_the_stack() {
+        let code = vec![0x01, 0x00]; // ADD STOP, consuming 2 declared inputs
+        assert_eq!(compute_max_stack(&code, 2), Ok(2));
+    }
+
+    #[test]
+    fn rejects_code_that_pops_more_than_is_on_the_stack() {
+        let code = vec![0x01]; // ADD with nothing pushed first
+        assert_eq!(
+            compute_max_stack(&code, 0),
+            Err(EofError::StackUnderflow { section: 0, pc: 0 })
+        );
+    }
+
+    #[test]
+    fn computes_the_max_stack_height_of_a_dupn() {
+        // PUSH1 1 PUSH1 2 DUPN 0x01 STOP: dups the item 1 below the top, so
+        // the stack grows to 3 deep.
+        let code = vec![0x60, 0x01, 0x60, 0x02, EvmOpcode::DUPN as u8, 0x01, 0x00];
+        assert_eq!(compute_max_stack(&code, 0), Ok(3));
+    }
+
+    #[test]
+    fn validate_container_accepts_a_section_matching_its_declared_max_stack() {
+        let mut bytes = vec![0xef, 0x00, VERSION, KIND_TYPES];
+        bytes.extend_from_slice(&4u16.to_be_bytes());
+        bytes.push(KIND_CODE);
+        bytes.extend_from_slice(&6u16.to_be_bytes());
+        bytes.push(KIND_TERMINATOR);
+        bytes.extend_from_slice(&[0, 0x80, 0, 2]); // max_stack 2
+        bytes.extend_from_slice(&[0x60, 0x01, 0x60, 0x02, 0x01, 0x00]); // PUSH1 1 PUSH1 2 ADD STOP
+        let container = parse(&bytes).unwrap();
+        assert_eq!(validate_container(&container, &Schedule::from_fork(Fork::Frontier)), Ok(()));
+    }
+
+    #[test]
+    fn validate_container_rejects_a_mismatched_declared_max_stack() {
+        let mut bytes = vec![0xef, 0x00, VERSION, KIND_TYPES];
+        bytes.extend_from_slice(&4u16.to_be_bytes());
+        bytes.push(KIND_CODE);
+        bytes.extend_from_slice(&6u16.to_be_bytes());
+        bytes.push(KIND_TERMINATOR);
+        bytes.extend_from_slice(&[0, 0x80, 0, 1]); // declares max_stack 1, actually 2
+        bytes.extend_from_slice(&[0x60, 0x01, 0x60, 0x02, 0x01, 0x00]);
+        let container = parse(&bytes).unwrap();
+        assert_eq!(
+            validate_container(&container, &Schedule::from_fork(Fork::Frontier)),
+            Err(EofError::MaxStackMismatch { section: 0, declared: 1, computed: 2 })
+        );
+    }
+}
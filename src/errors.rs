@@ -0,0 +1,300 @@
+// Copyright 2020 The Psyche Authors
+// This file is part of Psyche.
+//
+// Psyche is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Psyche is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Psyche. If not, see <http://www.gnu.org/licenses/>.
+
+//! A stable, machine-readable error taxonomy for library consumers, as
+//! opposed to the `Debug`-formatted, wording-may-change-any-time output
+//! most of the crate has printed until now (see the CLI's error-handling
+//! code in `main.rs` prior to this module). Nothing in this crate exposes
+//! a C API or an RPC server yet, but both would need exactly this: a
+//! `code()` that's part of the API contract, so a client can `match` on
+//! it instead of scraping a human sentence. `ErrorCode` is that code,
+//! `ErrorCategory` is the coarser bucket for callers that only want to
+//! know which phase of a run failed.
+
+use std::fmt;
+
+use crate::utils::HexDecodeError;
+use crate::vm::VmError;
+
+/// The phase of a run an `ErrorCode` failed in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ErrorCategory {
+    /// Malformed input before it becomes bytecode: bad hex, bad JSON, a
+    /// missing artifact field.
+    Parse,
+    /// Well-formed input this crate still refuses: assembler syntax
+    /// errors, undefined symbols, macro arity mismatches.
+    Validation,
+    /// Raised by the interpreter while running otherwise-valid bytecode
+    /// (`VmError`'s variants).
+    Execution,
+    /// Raised by something the interpreter depends on rather than by the
+    /// bytecode itself: a `BlockHashProvider`, a host function, storage.
+    Host,
+}
+
+impl fmt::Display for ErrorCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            ErrorCategory::Parse => "parse",
+            ErrorCategory::Validation => "validation",
+            ErrorCategory::Execution => "execution",
+            ErrorCategory::Host => "host",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// A specific, stable error identifier. `code()` is part of this crate's
+/// API surface: an existing variant's `code()` string does not change
+/// across releases, though new variants are added as the crate grows
+/// (hence `#[non_exhaustive]`) — a `match` on `ErrorCode` from outside
+/// this crate must always carry a wildcard arm.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ErrorCode {
+    InvalidHex(String),
+    InvalidJson(String),
+    MissingArtifactField { path: String, field: &'static str },
+    NoCodeInput,
+    UndefinedSymbol(String),
+    InvalidAssembly(String),
+    StackUnderflow,
+    StackOverflow,
+    OutOfGas,
+    InvalidJumpDest,
+    InvalidInstruction,
+    InvalidBeginSub,
+    BeginSubEntry,
+    ReturnStackUnderflow,
+    ReturnStackOverflow,
+}
+
+impl ErrorCode {
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            ErrorCode::InvalidHex(_)
+            | ErrorCode::InvalidJson(_)
+            | ErrorCode::MissingArtifactField { .. }
+            | ErrorCode::NoCodeInput => ErrorCategory::Parse,
+            ErrorCode::UndefinedSymbol(_) | ErrorCode::InvalidAssembly(_) => {
+                ErrorCategory::Validation
+            }
+            ErrorCode::StackUnderflow
+            | ErrorCode::StackOverflow
+            | ErrorCode::OutOfGas
+            | ErrorCode::InvalidJumpDest
+            | ErrorCode::InvalidInstruction
+            | ErrorCode::InvalidBeginSub
+            | ErrorCode::BeginSubEntry
+            | ErrorCode::ReturnStackUnderflow
+            | ErrorCode::ReturnStackOverflow => ErrorCategory::Execution,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorCode::InvalidHex(_) => "PARSE_INVALID_HEX",
+            ErrorCode::InvalidJson(_) => "PARSE_INVALID_JSON",
+            ErrorCode::MissingArtifactField { .. } => "PARSE_MISSING_ARTIFACT_FIELD",
+            ErrorCode::NoCodeInput => "PARSE_NO_CODE_INPUT",
+            ErrorCode::UndefinedSymbol(_) => "VALIDATION_UNDEFINED_SYMBOL",
+            ErrorCode::InvalidAssembly(_) => "VALIDATION_INVALID_ASSEMBLY",
+            ErrorCode::StackUnderflow => "EXECUTION_STACK_UNDERFLOW",
+            ErrorCode::StackOverflow => "EXECUTION_STACK_OVERFLOW",
+            ErrorCode::OutOfGas => "EXECUTION_OUT_OF_GAS",
+            ErrorCode::InvalidJumpDest => "EXECUTION_INVALID_JUMP_DEST",
+            ErrorCode::InvalidInstruction => "EXECUTION_INVALID_INSTRUCTION",
+            ErrorCode::InvalidBeginSub => "EXECUTION_INVALID_BEGIN_SUB",
+            ErrorCode::BeginSubEntry => "EXECUTION_BEGIN_SUB_ENTRY",
+            ErrorCode::ReturnStackUnderflow => "EXECUTION_RETURN_STACK_UNDERFLOW",
+            ErrorCode::ReturnStackOverflow => "EXECUTION_RETURN_STACK_OVERFLOW",
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorCode::InvalidHex(msg) => write!(f, "{}", msg),
+            ErrorCode::InvalidJson(msg) => write!(f, "{}", msg),
+            ErrorCode::MissingArtifactField { path, field } => {
+                write!(f, "{}: no \"{}\" field found", path, field)
+            }
+            ErrorCode::NoCodeInput => write!(f, "no CODE or --artifact given"),
+            ErrorCode::UndefinedSymbol(name) => write!(f, "undefined symbol `{}`", name),
+            ErrorCode::InvalidAssembly(msg) => write!(f, "{}", msg),
+            ErrorCode::StackUnderflow => write!(f, "stack underflow"),
+            ErrorCode::StackOverflow => write!(f, "stack overflow"),
+            ErrorCode::OutOfGas => write!(f, "out of gas"),
+            ErrorCode::InvalidJumpDest => write!(f, "invalid jump destination"),
+            ErrorCode::InvalidInstruction => write!(f, "invalid instruction"),
+            ErrorCode::InvalidBeginSub => write!(f, "invalid BEGINSUB"),
+            ErrorCode::BeginSubEntry => write!(f, "cannot enter a subroutine other than by JUMPSUB"),
+            ErrorCode::ReturnStackUnderflow => write!(f, "return stack underflow"),
+            ErrorCode::ReturnStackOverflow => write!(f, "return stack overflow"),
+        }
+    }
+}
+
+impl From<HexDecodeError> for ErrorCode {
+    fn from(err: HexDecodeError) -> ErrorCode {
+        ErrorCode::InvalidHex(err.to_string())
+    }
+}
+
+impl From<VmError> for ErrorCode {
+    /// # Panics
+    ///
+    /// `VmError::None` means "no error"; callers already check
+    /// `err != VmError::None` before reporting anything (see every call
+    /// site in `main.rs`), so there is no sensible `ErrorCode` for it.
+    fn from(err: VmError) -> ErrorCode {
+        match err {
+            VmError::None => unreachable!("VmError::None is not an error"),
+            VmError::StackUnderflow => ErrorCode::StackUnderflow,
+            VmError::StackOverflow => ErrorCode::StackOverflow,
+            VmError::OutOfGas => ErrorCode::OutOfGas,
+            VmError::InvalidJumpDest => ErrorCode::InvalidJumpDest,
+            VmError::InvalidInstruction => ErrorCode::InvalidInstruction,
+            VmError::InvalidBeginSub => ErrorCode::InvalidBeginSub,
+            VmError::BeginSubEntry => ErrorCode::BeginSubEntry,
+            VmError::ReturnStackUnderflow => ErrorCode::ReturnStackUnderflow,
+            VmError::ReturnStackOverflow => ErrorCode::ReturnStackOverflow,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // One entry per `ErrorCode` variant (the ones carrying a payload
+    // irrelevant to `code()`/`category()` are constructed with a
+    // placeholder value below), pinning the exact strings `ErrorCode`'s doc
+    // comment promises are frozen across releases.
+    fn every_variant() -> Vec<(ErrorCode, &'static str, ErrorCategory)> {
+        vec![
+            (
+                ErrorCode::InvalidHex(String::new()),
+                "PARSE_INVALID_HEX",
+                ErrorCategory::Parse,
+            ),
+            (
+                ErrorCode::InvalidJson(String::new()),
+                "PARSE_INVALID_JSON",
+                ErrorCategory::Parse,
+            ),
+            (
+                ErrorCode::MissingArtifactField { path: String::new(), field: "code" },
+                "PARSE_MISSING_ARTIFACT_FIELD",
+                ErrorCategory::Parse,
+            ),
+            (ErrorCode::NoCodeInput, "PARSE_NO_CODE_INPUT", ErrorCategory::Parse),
+            (
+                ErrorCode::UndefinedSymbol(String::new()),
+                "VALIDATION_UNDEFINED_SYMBOL",
+                ErrorCategory::Validation,
+            ),
+            (
+                ErrorCode::InvalidAssembly(String::new()),
+                "VALIDATION_INVALID_ASSEMBLY",
+                ErrorCategory::Validation,
+            ),
+            (
+                ErrorCode::StackUnderflow,
+                "EXECUTION_STACK_UNDERFLOW",
+                ErrorCategory::Execution,
+            ),
+            (
+                ErrorCode::StackOverflow,
+                "EXECUTION_STACK_OVERFLOW",
+                ErrorCategory::Execution,
+            ),
+            (ErrorCode::OutOfGas, "EXECUTION_OUT_OF_GAS", ErrorCategory::Execution),
+            (
+                ErrorCode::InvalidJumpDest,
+                "EXECUTION_INVALID_JUMP_DEST",
+                ErrorCategory::Execution,
+            ),
+            (
+                ErrorCode::InvalidInstruction,
+                "EXECUTION_INVALID_INSTRUCTION",
+                ErrorCategory::Execution,
+            ),
+            (
+                ErrorCode::InvalidBeginSub,
+                "EXECUTION_INVALID_BEGIN_SUB",
+                ErrorCategory::Execution,
+            ),
+            (
+                ErrorCode::BeginSubEntry,
+                "EXECUTION_BEGIN_SUB_ENTRY",
+                ErrorCategory::Execution,
+            ),
+            (
+                ErrorCode::ReturnStackUnderflow,
+                "EXECUTION_RETURN_STACK_UNDERFLOW",
+                ErrorCategory::Execution,
+            ),
+            (
+                ErrorCode::ReturnStackOverflow,
+                "EXECUTION_RETURN_STACK_OVERFLOW",
+                ErrorCategory::Execution,
+            ),
+        ]
+    }
+
+    #[test]
+    fn code_strings_are_frozen() {
+        for (variant, code, _) in every_variant() {
+            assert_eq!(variant.code(), code);
+        }
+    }
+
+    #[test]
+    fn category_matches_the_code_strings_own_prefix() {
+        for (variant, _, category) in every_variant() {
+            assert_eq!(variant.category(), category);
+        }
+    }
+
+    #[test]
+    fn every_non_none_vm_error_converts_to_its_matching_code() {
+        assert_eq!(ErrorCode::from(VmError::StackUnderflow), ErrorCode::StackUnderflow);
+        assert_eq!(ErrorCode::from(VmError::StackOverflow), ErrorCode::StackOverflow);
+        assert_eq!(ErrorCode::from(VmError::OutOfGas), ErrorCode::OutOfGas);
+        assert_eq!(ErrorCode::from(VmError::InvalidJumpDest), ErrorCode::InvalidJumpDest);
+        assert_eq!(ErrorCode::from(VmError::InvalidInstruction), ErrorCode::InvalidInstruction);
+        assert_eq!(ErrorCode::from(VmError::InvalidBeginSub), ErrorCode::InvalidBeginSub);
+        assert_eq!(ErrorCode::from(VmError::BeginSubEntry), ErrorCode::BeginSubEntry);
+        assert_eq!(
+            ErrorCode::from(VmError::ReturnStackUnderflow),
+            ErrorCode::ReturnStackUnderflow
+        );
+        assert_eq!(
+            ErrorCode::from(VmError::ReturnStackOverflow),
+            ErrorCode::ReturnStackOverflow
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "VmError::None is not an error")]
+    fn vm_error_none_panics_rather_than_producing_a_code() {
+        let _ = ErrorCode::from(VmError::None);
+    }
+}
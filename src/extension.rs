@@ -0,0 +1,56 @@
+// Copyright 2020 The Psyche Authors
+// This file is part of Psyche.
+//
+// Psyche is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Psyche is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Psyche. If not, see <http://www.gnu.org/licenses/>.
+
+//! A hook for chains that assign meaning to opcodes `0xb0..=0xcf`, the
+//! block the base EVM leaves unassigned. Without this, prototyping a
+//! chain extension means forking `run_evm_impl`'s dispatch loop in
+//! `src/vm.rs`; with it, a chain implements `ExtensionHandler` and passes
+//! it to `run_evm_with_extension`, and the interpreter calls into it for
+//! any opcode in that range that would otherwise dispatch to
+//! `Opcode::INVALID` (see that arm in `run_evm_impl`).
+
+use crate::vm::{VmError, VmMemory, VmStack};
+
+/// The inclusive byte range reserved for chain extensions. Opcodes outside
+/// this range still hit `VmError::InvalidInstruction` even when a handler
+/// is registered, so an extension can never shadow an opcode the base EVM
+/// might assign meaning to later.
+pub const EXTENSION_OPCODE_RANGE: std::ops::RangeInclusive<u8> = 0xb0..=0xcf;
+
+/// Implemented by chains that want to give meaning to opcodes in
+/// [`EXTENSION_OPCODE_RANGE`].
+pub trait ExtensionHandler {
+    /// Handle `opcode`, which is guaranteed to fall within
+    /// [`EXTENSION_OPCODE_RANGE`]. `stack`, `memory`, and `gas` are the
+    /// dispatch loop's own live state for the in-progress call: pop/push
+    /// stack items, read/write memory, and debit `gas` the same way a
+    /// built-in opcode's arm would. Returning `Err` aborts execution with
+    /// that `VmError`, exactly as if a built-in opcode had failed;
+    /// returning `Ok(())` resumes at the next instruction.
+    ///
+    /// # Safety
+    ///
+    /// `stack` and `memory` are only valid for the duration of this call,
+    /// under the same aliasing rules `run_evm_impl`'s own opcode arms
+    /// operate under (see `VmStack`/`VmMemory`).
+    unsafe fn handle(
+        &self,
+        opcode: u8,
+        stack: &mut VmStack,
+        memory: &mut VmMemory,
+        gas: &mut u64,
+    ) -> Result<(), VmError>;
+}
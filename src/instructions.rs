@@ -16,6 +16,8 @@
 
 use num_enum::TryFromPrimitive;
 
+use crate::schedule::{Fee, Fork};
+
 #[derive(Eq, PartialEq, Ord, PartialOrd, Debug, Copy, Clone, FromPrimitive)]
 #[repr(u8)]
 pub enum Opcode {
@@ -70,6 +72,8 @@ pub enum Opcode {
     GASLIMIT,
     CHAINID,
     SELFBALANCE,
+    BLOBHASH,
+    BLOBBASEFEE,
     POP,
     MLOAD,
     MSTORE,
@@ -164,9 +168,12 @@ pub enum Opcode {
     REVERT,
     INVALID,
     SELFDESTRUCT,
+    DUPN,
+    SWAPN,
+    EXCHANGE,
 }
 
-#[derive(Eq, PartialEq, Ord, PartialOrd, Debug, Copy, Clone, FromPrimitive, TryFromPrimitive)]
+#[derive(Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Copy, Clone, FromPrimitive, TryFromPrimitive)]
 #[repr(u8)]
 pub enum EvmOpcode {
     STOP = 0x00,
@@ -220,6 +227,8 @@ pub enum EvmOpcode {
     GASLIMIT = 0x45,
     CHAINID = 0x46,
     SELFBALANCE = 0x47,
+    BLOBHASH = 0x49,
+    BLOBBASEFEE = 0x4a,
     POP = 0x50,
     MLOAD = 0x51,
     MSTORE = 0x52,
@@ -304,6 +313,9 @@ pub enum EvmOpcode {
     LOG2 = 0xa2,
     LOG3 = 0xa3,
     LOG4 = 0xa4,
+    DUPN = 0xe6,
+    SWAPN = 0xe7,
+    EXCHANGE = 0xe8,
     CREATE = 0xf0,
     CALL = 0xf1,
     CALLCODE = 0xf2,
@@ -390,271 +402,180 @@ impl EvmOpcode {
         ((*self as u8) - (EvmOpcode::PUSH1 as u8)) as usize
     }
 
-    /// Convert to internal representation
+    /// Returns true for the EIP-663 deep-stack opcodes (`DUPN`, `SWAPN`,
+    /// `EXCHANGE`), which, like `PUSHN`, are followed by a one-byte
+    /// immediate rather than having a fixed arity encoded in the opcode
+    /// itself.
+    pub fn is_deep_stack(&self) -> bool {
+        matches!(*self, EvmOpcode::DUPN | EvmOpcode::SWAPN | EvmOpcode::EXCHANGE)
+    }
+
+    /// Convert to internal representation. A `match` rather than a
+    /// position-indexed table: `EvmOpcode`'s variants carry real opcode
+    /// bytes as discriminants (sparse, with gaps), while `Opcode`'s are
+    /// densely packed in declaration order, so the two can't share a
+    /// discriminant and a by-position table risks silently drifting out of
+    /// sync whenever either enum gains a variant. Matching by name instead
+    /// makes rustc's exhaustiveness check force this function to be
+    /// revisited the moment a new `EvmOpcode` variant is added (see
+    /// `every_opcode_maps_to_the_same_named_internal_opcode`).
     pub fn to_internal(&self) -> Opcode {
-        const MAPPING: [Opcode; 256] = [
-            Opcode::STOP,
-            Opcode::ADD,
-            Opcode::MUL,
-            Opcode::SUB,
-            Opcode::DIV,
-            Opcode::SDIV,
-            Opcode::MOD,
-            Opcode::SMOD,
-            Opcode::ADDMOD,
-            Opcode::MULMOD,
-            Opcode::EXP,
-            Opcode::SIGNEXTEND,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::LT,
-            Opcode::GT,
-            Opcode::SLT,
-            Opcode::SGT,
-            Opcode::EQ,
-            Opcode::ISZERO,
-            Opcode::AND,
-            Opcode::OR,
-            Opcode::XOR,
-            Opcode::NOT,
-            Opcode::BYTE,
-            Opcode::SHL,
-            Opcode::SHR,
-            Opcode::SAR,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::SHA3,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::ADDRESS,
-            Opcode::BALANCE,
-            Opcode::ORIGIN,
-            Opcode::CALLER,
-            Opcode::CALLVALUE,
-            Opcode::CALLDATALOAD,
-            Opcode::CALLDATASIZE,
-            Opcode::CALLDATACOPY,
-            Opcode::CODESIZE,
-            Opcode::CODECOPY,
-            Opcode::GASPRICE,
-            Opcode::EXTCODESIZE,
-            Opcode::EXTCODECOPY,
-            Opcode::RETURNDATASIZE,
-            Opcode::RETURNDATACOPY,
-            Opcode::EXTCODEHASH,
-            Opcode::BLOCKHASH,
-            Opcode::COINBASE,
-            Opcode::TIMESTAMP,
-            Opcode::NUMBER,
-            Opcode::DIFFICULTY,
-            Opcode::GASLIMIT,
-            Opcode::CHAINID,
-            Opcode::SELFBALANCE,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::POP,
-            Opcode::MLOAD,
-            Opcode::MSTORE,
-            Opcode::MSTORE8,
-            Opcode::SLOAD,
-            Opcode::SSTORE,
-            Opcode::JUMP,
-            Opcode::JUMPI,
-            Opcode::PC,
-            Opcode::MSIZE,
-            Opcode::GAS,
-            Opcode::JUMPDEST,
-            Opcode::BEGINSUB,
-            Opcode::RETURNSUB,
-            Opcode::JUMPSUB,
-            Opcode::INVALID,
-            Opcode::PUSH1,
-            Opcode::PUSH2,
-            Opcode::PUSH3,
-            Opcode::PUSH4,
-            Opcode::PUSH5,
-            Opcode::PUSH6,
-            Opcode::PUSH7,
-            Opcode::PUSH8,
-            Opcode::PUSH9,
-            Opcode::PUSH10,
-            Opcode::PUSH11,
-            Opcode::PUSH12,
-            Opcode::PUSH13,
-            Opcode::PUSH14,
-            Opcode::PUSH15,
-            Opcode::PUSH16,
-            Opcode::PUSH17,
-            Opcode::PUSH18,
-            Opcode::PUSH19,
-            Opcode::PUSH20,
-            Opcode::PUSH21,
-            Opcode::PUSH22,
-            Opcode::PUSH23,
-            Opcode::PUSH24,
-            Opcode::PUSH25,
-            Opcode::PUSH26,
-            Opcode::PUSH27,
-            Opcode::PUSH28,
-            Opcode::PUSH29,
-            Opcode::PUSH30,
-            Opcode::PUSH31,
-            Opcode::PUSH32,
-            Opcode::DUP1,
-            Opcode::DUP2,
-            Opcode::DUP3,
-            Opcode::DUP4,
-            Opcode::DUP5,
-            Opcode::DUP6,
-            Opcode::DUP7,
-            Opcode::DUP8,
-            Opcode::DUP9,
-            Opcode::DUP10,
-            Opcode::DUP11,
-            Opcode::DUP12,
-            Opcode::DUP13,
-            Opcode::DUP14,
-            Opcode::DUP15,
-            Opcode::DUP16,
-            Opcode::SWAP1,
-            Opcode::SWAP2,
-            Opcode::SWAP3,
-            Opcode::SWAP4,
-            Opcode::SWAP5,
-            Opcode::SWAP6,
-            Opcode::SWAP7,
-            Opcode::SWAP8,
-            Opcode::SWAP9,
-            Opcode::SWAP10,
-            Opcode::SWAP11,
-            Opcode::SWAP12,
-            Opcode::SWAP13,
-            Opcode::SWAP14,
-            Opcode::SWAP15,
-            Opcode::SWAP16,
-            Opcode::LOG0,
-            Opcode::LOG1,
-            Opcode::LOG2,
-            Opcode::LOG3,
-            Opcode::LOG4,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::CREATE,
-            Opcode::CALL,
-            Opcode::CALLCODE,
-            Opcode::RETURN,
-            Opcode::DELEGATECALL,
-            Opcode::CREATE2,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::STATICCALL,
-            Opcode::INVALID,
-            Opcode::INVALID,
-            Opcode::REVERT,
-            Opcode::INVALID,
-            Opcode::SELFDESTRUCT,
-        ];
-        MAPPING[*self as usize]
+        match *self {
+            EvmOpcode::STOP => Opcode::STOP,
+            EvmOpcode::ADD => Opcode::ADD,
+            EvmOpcode::MUL => Opcode::MUL,
+            EvmOpcode::SUB => Opcode::SUB,
+            EvmOpcode::DIV => Opcode::DIV,
+            EvmOpcode::SDIV => Opcode::SDIV,
+            EvmOpcode::MOD => Opcode::MOD,
+            EvmOpcode::SMOD => Opcode::SMOD,
+            EvmOpcode::ADDMOD => Opcode::ADDMOD,
+            EvmOpcode::MULMOD => Opcode::MULMOD,
+            EvmOpcode::EXP => Opcode::EXP,
+            EvmOpcode::SIGNEXTEND => Opcode::SIGNEXTEND,
+            EvmOpcode::LT => Opcode::LT,
+            EvmOpcode::GT => Opcode::GT,
+            EvmOpcode::SLT => Opcode::SLT,
+            EvmOpcode::SGT => Opcode::SGT,
+            EvmOpcode::EQ => Opcode::EQ,
+            EvmOpcode::ISZERO => Opcode::ISZERO,
+            EvmOpcode::AND => Opcode::AND,
+            EvmOpcode::OR => Opcode::OR,
+            EvmOpcode::XOR => Opcode::XOR,
+            EvmOpcode::NOT => Opcode::NOT,
+            EvmOpcode::BYTE => Opcode::BYTE,
+            EvmOpcode::SHL => Opcode::SHL,
+            EvmOpcode::SHR => Opcode::SHR,
+            EvmOpcode::SAR => Opcode::SAR,
+            EvmOpcode::SHA3 => Opcode::SHA3,
+            EvmOpcode::ADDRESS => Opcode::ADDRESS,
+            EvmOpcode::BALANCE => Opcode::BALANCE,
+            EvmOpcode::ORIGIN => Opcode::ORIGIN,
+            EvmOpcode::CALLER => Opcode::CALLER,
+            EvmOpcode::CALLVALUE => Opcode::CALLVALUE,
+            EvmOpcode::CALLDATALOAD => Opcode::CALLDATALOAD,
+            EvmOpcode::CALLDATASIZE => Opcode::CALLDATASIZE,
+            EvmOpcode::CALLDATACOPY => Opcode::CALLDATACOPY,
+            EvmOpcode::CODESIZE => Opcode::CODESIZE,
+            EvmOpcode::CODECOPY => Opcode::CODECOPY,
+            EvmOpcode::GASPRICE => Opcode::GASPRICE,
+            EvmOpcode::EXTCODESIZE => Opcode::EXTCODESIZE,
+            EvmOpcode::EXTCODECOPY => Opcode::EXTCODECOPY,
+            EvmOpcode::RETURNDATASIZE => Opcode::RETURNDATASIZE,
+            EvmOpcode::RETURNDATACOPY => Opcode::RETURNDATACOPY,
+            EvmOpcode::EXTCODEHASH => Opcode::EXTCODEHASH,
+            EvmOpcode::BLOCKHASH => Opcode::BLOCKHASH,
+            EvmOpcode::COINBASE => Opcode::COINBASE,
+            EvmOpcode::TIMESTAMP => Opcode::TIMESTAMP,
+            EvmOpcode::NUMBER => Opcode::NUMBER,
+            EvmOpcode::DIFFICULTY => Opcode::DIFFICULTY,
+            EvmOpcode::GASLIMIT => Opcode::GASLIMIT,
+            EvmOpcode::CHAINID => Opcode::CHAINID,
+            EvmOpcode::SELFBALANCE => Opcode::SELFBALANCE,
+            EvmOpcode::BLOBHASH => Opcode::BLOBHASH,
+            EvmOpcode::BLOBBASEFEE => Opcode::BLOBBASEFEE,
+            EvmOpcode::POP => Opcode::POP,
+            EvmOpcode::MLOAD => Opcode::MLOAD,
+            EvmOpcode::MSTORE => Opcode::MSTORE,
+            EvmOpcode::MSTORE8 => Opcode::MSTORE8,
+            EvmOpcode::SLOAD => Opcode::SLOAD,
+            EvmOpcode::SSTORE => Opcode::SSTORE,
+            EvmOpcode::JUMP => Opcode::JUMP,
+            EvmOpcode::JUMPI => Opcode::JUMPI,
+            EvmOpcode::PC => Opcode::PC,
+            EvmOpcode::MSIZE => Opcode::MSIZE,
+            EvmOpcode::GAS => Opcode::GAS,
+            EvmOpcode::JUMPDEST => Opcode::JUMPDEST,
+            EvmOpcode::BEGINSUB => Opcode::BEGINSUB,
+            EvmOpcode::RETURNSUB => Opcode::RETURNSUB,
+            EvmOpcode::JUMPSUB => Opcode::JUMPSUB,
+            EvmOpcode::PUSH1 => Opcode::PUSH1,
+            EvmOpcode::PUSH2 => Opcode::PUSH2,
+            EvmOpcode::PUSH3 => Opcode::PUSH3,
+            EvmOpcode::PUSH4 => Opcode::PUSH4,
+            EvmOpcode::PUSH5 => Opcode::PUSH5,
+            EvmOpcode::PUSH6 => Opcode::PUSH6,
+            EvmOpcode::PUSH7 => Opcode::PUSH7,
+            EvmOpcode::PUSH8 => Opcode::PUSH8,
+            EvmOpcode::PUSH9 => Opcode::PUSH9,
+            EvmOpcode::PUSH10 => Opcode::PUSH10,
+            EvmOpcode::PUSH11 => Opcode::PUSH11,
+            EvmOpcode::PUSH12 => Opcode::PUSH12,
+            EvmOpcode::PUSH13 => Opcode::PUSH13,
+            EvmOpcode::PUSH14 => Opcode::PUSH14,
+            EvmOpcode::PUSH15 => Opcode::PUSH15,
+            EvmOpcode::PUSH16 => Opcode::PUSH16,
+            EvmOpcode::PUSH17 => Opcode::PUSH17,
+            EvmOpcode::PUSH18 => Opcode::PUSH18,
+            EvmOpcode::PUSH19 => Opcode::PUSH19,
+            EvmOpcode::PUSH20 => Opcode::PUSH20,
+            EvmOpcode::PUSH21 => Opcode::PUSH21,
+            EvmOpcode::PUSH22 => Opcode::PUSH22,
+            EvmOpcode::PUSH23 => Opcode::PUSH23,
+            EvmOpcode::PUSH24 => Opcode::PUSH24,
+            EvmOpcode::PUSH25 => Opcode::PUSH25,
+            EvmOpcode::PUSH26 => Opcode::PUSH26,
+            EvmOpcode::PUSH27 => Opcode::PUSH27,
+            EvmOpcode::PUSH28 => Opcode::PUSH28,
+            EvmOpcode::PUSH29 => Opcode::PUSH29,
+            EvmOpcode::PUSH30 => Opcode::PUSH30,
+            EvmOpcode::PUSH31 => Opcode::PUSH31,
+            EvmOpcode::PUSH32 => Opcode::PUSH32,
+            EvmOpcode::DUP1 => Opcode::DUP1,
+            EvmOpcode::DUP2 => Opcode::DUP2,
+            EvmOpcode::DUP3 => Opcode::DUP3,
+            EvmOpcode::DUP4 => Opcode::DUP4,
+            EvmOpcode::DUP5 => Opcode::DUP5,
+            EvmOpcode::DUP6 => Opcode::DUP6,
+            EvmOpcode::DUP7 => Opcode::DUP7,
+            EvmOpcode::DUP8 => Opcode::DUP8,
+            EvmOpcode::DUP9 => Opcode::DUP9,
+            EvmOpcode::DUP10 => Opcode::DUP10,
+            EvmOpcode::DUP11 => Opcode::DUP11,
+            EvmOpcode::DUP12 => Opcode::DUP12,
+            EvmOpcode::DUP13 => Opcode::DUP13,
+            EvmOpcode::DUP14 => Opcode::DUP14,
+            EvmOpcode::DUP15 => Opcode::DUP15,
+            EvmOpcode::DUP16 => Opcode::DUP16,
+            EvmOpcode::SWAP1 => Opcode::SWAP1,
+            EvmOpcode::SWAP2 => Opcode::SWAP2,
+            EvmOpcode::SWAP3 => Opcode::SWAP3,
+            EvmOpcode::SWAP4 => Opcode::SWAP4,
+            EvmOpcode::SWAP5 => Opcode::SWAP5,
+            EvmOpcode::SWAP6 => Opcode::SWAP6,
+            EvmOpcode::SWAP7 => Opcode::SWAP7,
+            EvmOpcode::SWAP8 => Opcode::SWAP8,
+            EvmOpcode::SWAP9 => Opcode::SWAP9,
+            EvmOpcode::SWAP10 => Opcode::SWAP10,
+            EvmOpcode::SWAP11 => Opcode::SWAP11,
+            EvmOpcode::SWAP12 => Opcode::SWAP12,
+            EvmOpcode::SWAP13 => Opcode::SWAP13,
+            EvmOpcode::SWAP14 => Opcode::SWAP14,
+            EvmOpcode::SWAP15 => Opcode::SWAP15,
+            EvmOpcode::SWAP16 => Opcode::SWAP16,
+            EvmOpcode::LOG0 => Opcode::LOG0,
+            EvmOpcode::LOG1 => Opcode::LOG1,
+            EvmOpcode::LOG2 => Opcode::LOG2,
+            EvmOpcode::LOG3 => Opcode::LOG3,
+            EvmOpcode::LOG4 => Opcode::LOG4,
+            EvmOpcode::DUPN => Opcode::DUPN,
+            EvmOpcode::SWAPN => Opcode::SWAPN,
+            EvmOpcode::EXCHANGE => Opcode::EXCHANGE,
+            EvmOpcode::CREATE => Opcode::CREATE,
+            EvmOpcode::CALL => Opcode::CALL,
+            EvmOpcode::CALLCODE => Opcode::CALLCODE,
+            EvmOpcode::RETURN => Opcode::RETURN,
+            EvmOpcode::DELEGATECALL => Opcode::DELEGATECALL,
+            EvmOpcode::CREATE2 => Opcode::CREATE2,
+            EvmOpcode::STATICCALL => Opcode::STATICCALL,
+            EvmOpcode::REVERT => Opcode::REVERT,
+            EvmOpcode::INVALID => Opcode::INVALID,
+            EvmOpcode::SELFDESTRUCT => Opcode::SELFDESTRUCT,
+        }
     }
 
     pub fn iter() -> std::slice::Iter<'static, EvmOpcode> {
-        const VALUES: [EvmOpcode; 145] = [
+        const VALUES: [EvmOpcode; 150] = [
             EvmOpcode::STOP,
             EvmOpcode::ADD,
             EvmOpcode::MUL,
@@ -706,6 +627,8 @@ impl EvmOpcode {
             EvmOpcode::GASLIMIT,
             EvmOpcode::CHAINID,
             EvmOpcode::SELFBALANCE,
+            EvmOpcode::BLOBHASH,
+            EvmOpcode::BLOBBASEFEE,
             EvmOpcode::POP,
             EvmOpcode::MLOAD,
             EvmOpcode::MSTORE,
@@ -790,6 +713,9 @@ impl EvmOpcode {
             EvmOpcode::LOG2,
             EvmOpcode::LOG3,
             EvmOpcode::LOG4,
+            EvmOpcode::DUPN,
+            EvmOpcode::SWAPN,
+            EvmOpcode::EXCHANGE,
             EvmOpcode::CREATE,
             EvmOpcode::CALL,
             EvmOpcode::CALLCODE,
@@ -804,3 +730,348 @@ impl EvmOpcode {
         VALUES.iter()
     }
 }
+
+pub(crate) const OPCODE_INFOS: [(Fork, Fee, u16, u16); 256] = [
+    (Fork::Frontier, Fee::Zero, 0, 0),    /* STOP = 0x00 */
+    (Fork::Frontier, Fee::VeryLow, 2, 1), /* ADD = 0x01 */
+    (Fork::Frontier, Fee::Low, 2, 1),     /* MUL = 0x02 */
+    (Fork::Frontier, Fee::VeryLow, 2, 1), /* SUB = 0x03 */
+    (Fork::Frontier, Fee::Low, 2, 1),     /* DIV = 0x04 */
+    (Fork::Frontier, Fee::Low, 2, 1),     /* SDIV = 0x05 */
+    (Fork::Frontier, Fee::Low, 2, 1),     /* MOD = 0x06 */
+    (Fork::Frontier, Fee::Low, 2, 1),     /* SMOD = 0x07 */
+    (Fork::Frontier, Fee::Mid, 3, 1),     /* ADDMOD = 0x08 */
+    (Fork::Frontier, Fee::Mid, 3, 1),     /* MULMOD = 0x09 */
+    (Fork::Frontier, Fee::Exp, 2, 1),     /* EXP = 0x0a */
+    (Fork::Frontier, Fee::Low, 2, 1),     /* SIGNEXTEND = 0x0b */
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::VeryLow, 2, 1),       /* LT = 0x10 */
+    (Fork::Frontier, Fee::VeryLow, 2, 1),       /* GT = 0x11 */
+    (Fork::Frontier, Fee::VeryLow, 2, 1),       /* SLT = 0x12 */
+    (Fork::Frontier, Fee::VeryLow, 2, 1),       /* SGT = 0x13 */
+    (Fork::Frontier, Fee::VeryLow, 2, 1),       /* EQ = 0x14 */
+    (Fork::Frontier, Fee::VeryLow, 1, 1),       /* ISZERO = 0x15 */
+    (Fork::Frontier, Fee::VeryLow, 2, 1),       /* AND = 0x16 */
+    (Fork::Frontier, Fee::VeryLow, 2, 1),       /* OR = 0x17 */
+    (Fork::Frontier, Fee::VeryLow, 2, 1),       /* XOR = 0x18 */
+    (Fork::Frontier, Fee::VeryLow, 1, 1),       /* NOT = 0x19 */
+    (Fork::Frontier, Fee::VeryLow, 2, 1),       /* BYTE = 0x1a */
+    (Fork::Constantinople, Fee::VeryLow, 2, 1), /* SHL = 0x1b */
+    (Fork::Constantinople, Fee::VeryLow, 2, 1), /* SHR = 0x1c */
+    (Fork::Constantinople, Fee::VeryLow, 2, 1), /* SAR = 0x1d */
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Sha3, 2, 1), /* SHA3 = 0x20 */
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Base, 0, 1),       /* ADDRESS = 0x30 */
+    (Fork::Frontier, Fee::Balance, 1, 1),    /* BALANCE = 0x31 */
+    (Fork::Frontier, Fee::Base, 0, 1),       /* ORIGIN = 0x32 */
+    (Fork::Frontier, Fee::Base, 0, 1),       /* CALLER = 0x33 */
+    (Fork::Frontier, Fee::Base, 0, 1),       /* CALLVALUE = 0x34 */
+    (Fork::Frontier, Fee::VeryLow, 1, 1),    /* CALLDATALOAD = 0x35 */
+    (Fork::Frontier, Fee::Base, 0, 1),       /* CALLDATASIZE = 0x36 */
+    (Fork::Frontier, Fee::Copy, 3, 0),       /* CALLDATACOPY = 0x37 */
+    (Fork::Frontier, Fee::Base, 0, 1),       /* CODESIZE = 0x38 */
+    (Fork::Frontier, Fee::Copy, 3, 0),       /* CODECOPY = 0x39 */
+    (Fork::Frontier, Fee::Base, 0, 1),       /* GASPRICE = 0x3a */
+    (Fork::Frontier, Fee::Zero, 1, 1),       /* EXTCODESIZE = 0x3b */
+    (Fork::Frontier, Fee::Zero, 4, 0),       /* EXTCODECOPY = 0x3c */
+    (Fork::Frontier, Fee::Base, 0, 1),       /* RETURNDATASIZE = 0x3d */
+    (Fork::Frontier, Fee::Copy, 3, 0),       /* RETURNDATACOPY = 0x3e */
+    (Fork::Constantinople, Fee::Zero, 1, 1), /* EXTCODEHASH = 0x3f */
+    (Fork::Frontier, Fee::Blockhash, 1, 1),  /* BLOCKHASH = 0x40 */
+    (Fork::Frontier, Fee::Base, 0, 1),       /* COINBASE = 0x41 */
+    (Fork::Frontier, Fee::Base, 0, 1),       /* TIMESTAMP = 0x42 */
+    (Fork::Frontier, Fee::Base, 0, 1),       /* NUMBER = 0x43 */
+    (Fork::Frontier, Fee::Base, 0, 1),       /* DIFFICULTY = 0x44 */
+    (Fork::Frontier, Fee::Base, 0, 1),       /* GASLIMIT = 0x45 */
+    (Fork::Istanbul, Fee::Base, 0, 1),       /* CHAINID = 0x46 */
+    (Fork::Istanbul, Fee::Low, 0, 1),        /* SELFBALANCE = 0x47 */
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Cancun, Fee::VeryLow, 1, 1),      /* BLOBHASH = 0x49 */
+    (Fork::Cancun, Fee::Base, 0, 1),         /* BLOBBASEFEE = 0x4a */
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Base, 1, 0),     /* POP = 0x50 */
+    (Fork::Frontier, Fee::VeryLow, 1, 1),  /* MLOAD = 0x51 */
+    (Fork::Frontier, Fee::VeryLow, 2, 0),  /* MSTORE = 0x52 */
+    (Fork::Frontier, Fee::VeryLow, 2, 0),  /* MSTORE8 = 0x53 */
+    (Fork::Frontier, Fee::Zero, 1, 1),     /* SLOAD = 0x54 */
+    (Fork::Frontier, Fee::Zero, 2, 0),     /* SSTORE = 0x55 */
+    (Fork::Frontier, Fee::Mid, 1, 0),      /* JUMP = 0x56 */
+    (Fork::Frontier, Fee::High, 2, 0),     /* JUMPI = 0x57 */
+    (Fork::Frontier, Fee::Base, 0, 1),     /* PC = 0x58 */
+    (Fork::Frontier, Fee::Base, 0, 1),     /* MSIZE = 0x59 */
+    (Fork::Frontier, Fee::Base, 0, 1),     /* GAS = 0x5a */
+    (Fork::Frontier, Fee::Jumpdest, 0, 0), /* JUMPDEST = 0x5b */
+    (Fork::Berlin, Fee::Zero, 0, 0),       /* BEGINSUB = 0x5c */
+    (Fork::Berlin, Fee::Low, 0, 0),        /* RETURNSUB = 0x5d */
+    (Fork::Berlin, Fee::High, 1, 0),       /* JUMPSUB = 0x5e */
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH1 = 0x60 */
+    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH2 = 0x61 */
+    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH3 = 0x62 */
+    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH4 = 0x63 */
+    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH5 = 0x64 */
+    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH6 = 0x65 */
+    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH7 = 0x66 */
+    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH8 = 0x67 */
+    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH9 = 0x68 */
+    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH10 = 0x69 */
+    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH11 = 0x6a */
+    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH12 = 0x6b */
+    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH13 = 0x6c */
+    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH14 = 0x6d */
+    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH15 = 0x6e */
+    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH16 = 0x6f */
+    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH17 = 0x70 */
+    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH18 = 0x71 */
+    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH19 = 0x72 */
+    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH20 = 0x73 */
+    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH21 = 0x74 */
+    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH22 = 0x75 */
+    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH23 = 0x76 */
+    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH24 = 0x77 */
+    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH25 = 0x78 */
+    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH26 = 0x79 */
+    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH27 = 0x7a */
+    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH28 = 0x7b */
+    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH29 = 0x7c */
+    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH30 = 0x7d */
+    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH31 = 0x7e */
+    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH32 = 0x7f */
+    (Fork::Frontier, Fee::VeryLow, 1, 2),   /* DUP1 = 0x80 */
+    (Fork::Frontier, Fee::VeryLow, 2, 3),   /* DUP2 = 0x81 */
+    (Fork::Frontier, Fee::VeryLow, 3, 4),   /* DUP3 = 0x82 */
+    (Fork::Frontier, Fee::VeryLow, 4, 5),   /* DUP4 = 0x83 */
+    (Fork::Frontier, Fee::VeryLow, 5, 6),   /* DUP5 = 0x84 */
+    (Fork::Frontier, Fee::VeryLow, 6, 7),   /* DUP6 = 0x85 */
+    (Fork::Frontier, Fee::VeryLow, 7, 8),   /* DUP7 = 0x86 */
+    (Fork::Frontier, Fee::VeryLow, 8, 9),   /* DUP8 = 0x87 */
+    (Fork::Frontier, Fee::VeryLow, 9, 10),  /* DUP9 = 0x88 */
+    (Fork::Frontier, Fee::VeryLow, 10, 11), /* DUP10 = 0x89 */
+    (Fork::Frontier, Fee::VeryLow, 11, 12), /* DUP11 = 0x8a */
+    (Fork::Frontier, Fee::VeryLow, 12, 13), /* DUP12 = 0x8b */
+    (Fork::Frontier, Fee::VeryLow, 13, 14), /* DUP13 = 0x8c */
+    (Fork::Frontier, Fee::VeryLow, 14, 15), /* DUP14 = 0x8d */
+    (Fork::Frontier, Fee::VeryLow, 15, 16), /* DUP15 = 0x8e */
+    (Fork::Frontier, Fee::VeryLow, 16, 17), /* DUP16 = 0x8f */
+    (Fork::Frontier, Fee::VeryLow, 2, 2),   /* SWAP1 = 0x90 */
+    (Fork::Frontier, Fee::VeryLow, 3, 3),   /* SWAP2 = 0x91 */
+    (Fork::Frontier, Fee::VeryLow, 4, 4),   /* SWAP3 = 0x92 */
+    (Fork::Frontier, Fee::VeryLow, 5, 5),   /* SWAP4 = 0x93 */
+    (Fork::Frontier, Fee::VeryLow, 6, 6),   /* SWAP5 = 0x94 */
+    (Fork::Frontier, Fee::VeryLow, 7, 7),   /* SWAP6 = 0x95 */
+    (Fork::Frontier, Fee::VeryLow, 8, 8),   /* SWAP7 = 0x96 */
+    (Fork::Frontier, Fee::VeryLow, 9, 9),   /* SWAP8 = 0x97 */
+    (Fork::Frontier, Fee::VeryLow, 10, 10), /* SWAP9 = 0x98 */
+    (Fork::Frontier, Fee::VeryLow, 11, 11), /* SWAP10 = 0x99 */
+    (Fork::Frontier, Fee::VeryLow, 12, 12), /* SWAP11 = 0x9a */
+    (Fork::Frontier, Fee::VeryLow, 13, 13), /* SWAP12 = 0x9b */
+    (Fork::Frontier, Fee::VeryLow, 14, 14), /* SWAP13 = 0x9c */
+    (Fork::Frontier, Fee::VeryLow, 15, 15), /* SWAP14 = 0x9d */
+    (Fork::Frontier, Fee::VeryLow, 16, 16), /* SWAP15 = 0x9e */
+    (Fork::Frontier, Fee::VeryLow, 17, 17), /* SWAP16 = 0x9f */
+    (Fork::Frontier, Fee::Zero, 2, 0),      /* LOG0 = 0xa0 */
+    (Fork::Frontier, Fee::Zero, 3, 0),      /* LOG1 = 0xa1 */
+    (Fork::Frontier, Fee::Zero, 4, 0),      /* LOG2 = 0xa2 */
+    (Fork::Frontier, Fee::Zero, 5, 0),      /* LOG3 = 0xa3 */
+    (Fork::Frontier, Fee::Zero, 6, 0),      /* LOG4 = 0xa4 */
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    // DUPN/SWAPN/EXCHANGE's real stack effect is data-dependent (it comes
+    // from a one-byte immediate, not the opcode itself), so it can't be
+    // represented as a fixed (delta, alpha) here the way every other
+    // opcode's can; `deep_stack_effect` computes the real per-instance
+    // values from the immediate everywhere stack bounds are checked.
+    // `delta`/`alpha` below are deliberately an unreachable-in-practice
+    // worst case, so anywhere that forgets to special-case these opcodes
+    // fails closed (demands more stack than exists) rather than open.
+    (Fork::Prague, Fee::VeryLow, 256, 257),  /* DUPN = 0xe6 */
+    (Fork::Prague, Fee::VeryLow, 257, 257),  /* SWAPN = 0xe7 */
+    (Fork::Prague, Fee::VeryLow, 258, 258),  /* EXCHANGE = 0xe8 */
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 3, 1),       /* CREATE = 0xf0 */
+    (Fork::Frontier, Fee::Zero, 7, 1),       /* CALL = 0xf1 */
+    (Fork::Frontier, Fee::Zero, 7, 1),       /* CALLCODE = 0xf2 */
+    (Fork::Frontier, Fee::Zero, 2, 0),       /* RETURN = 0xf3 */
+    (Fork::Frontier, Fee::Zero, 6, 1),       /* DELEGATECALL = 0xf4 */
+    (Fork::Constantinople, Fee::Zero, 4, 1), /* CREATE2 = 0xf5 */
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Byzantium, Fee::Zero, 6, 1), /* STATICCALL = 0xfa */
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Frontier, Fee::Zero, 0, 0),
+    (Fork::Byzantium, Fee::Zero, 2, 0), /* REVERT = 0xfd */
+    (Fork::Frontier, Fee::Zero, 0, 0),  /* INVALID = 0xfe */
+    (Fork::Frontier, Fee::Zero, 1, 0),  /* SELFDESTRUCT = 0xff */
+];
+
+/// Per-opcode metadata for tooling (the assembler, disassembler, analyzer,
+/// and anything external built against this crate): how many stack items
+/// it pops/pushes, which `Schedule::opcode_gas` fee class it bills under,
+/// the fork that introduced it, and whether it's a `PUSHN` or a
+/// basic-block terminator. Consolidates what used to mean reaching into
+/// `vm::OPCODE_INFOS` directly (still the source of truth this reads from)
+/// plus `EvmOpcode::is_push`/`is_terminator`.
+///
+/// `DUPN`/`SWAPN`/`EXCHANGE`'s real `inputs`/`outputs` depend on a runtime
+/// immediate, not the opcode alone; see `OPCODE_INFOS`'s comment on those
+/// three entries. Use `is_deep_stack` and `deep_stack_effect` (`vm` module)
+/// for those instead of trusting `inputs`/`outputs` here.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpcodeInfo {
+    pub inputs: u16,
+    pub outputs: u16,
+    pub fee_class: Fee,
+    pub introduced_fork: Fork,
+    pub is_push: bool,
+    pub is_terminator: bool,
+}
+
+/// Looks up `opcode`'s metadata. See `OpcodeInfo`.
+pub fn info(opcode: EvmOpcode) -> OpcodeInfo {
+    let (introduced_fork, fee_class, inputs, outputs) = OPCODE_INFOS[opcode as usize];
+    OpcodeInfo {
+        inputs,
+        outputs,
+        fee_class,
+        introduced_fork,
+        is_push: opcode.is_push(),
+        is_terminator: opcode.is_terminator(),
+    }
+}
+
+#[cfg(test)]
+mod info_tests {
+    use super::*;
+
+    #[test]
+    fn reports_add_as_a_two_in_one_out_verylow_frontier_op() {
+        let i = info(EvmOpcode::ADD);
+        assert_eq!(i.inputs, 2);
+        assert_eq!(i.outputs, 1);
+        assert_eq!(i.fee_class, Fee::VeryLow);
+        assert_eq!(i.introduced_fork, Fork::Frontier);
+        assert!(!i.is_push);
+        assert!(!i.is_terminator);
+    }
+
+    #[test]
+    fn reports_push1_as_a_push_and_jump_as_a_terminator() {
+        assert!(info(EvmOpcode::PUSH1).is_push);
+        assert!(info(EvmOpcode::JUMP).is_terminator);
+    }
+
+    #[test]
+    fn reports_shl_as_introduced_in_constantinople() {
+        assert_eq!(info(EvmOpcode::SHL).introduced_fork, Fork::Constantinople);
+    }
+
+    // `to_internal`'s match doesn't need this to catch drift (rustc's
+    // exhaustiveness check already does), but it does pin the one property
+    // that match is supposed to guarantee: every opcode maps to its
+    // same-named `Opcode` counterpart, not just to *some* variant.
+    #[test]
+    fn every_opcode_maps_to_the_same_named_internal_opcode() {
+        for &opcode in EvmOpcode::iter() {
+            assert_eq!(
+                format!("{:?}", opcode.to_internal()),
+                format!("{:?}", opcode),
+                "{:?} did not round-trip through to_internal",
+                opcode
+            );
+        }
+    }
+}
@@ -0,0 +1,95 @@
+// Copyright 2020 The Psyche Authors
+// This file is part of Psyche.
+//
+// Psyche is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Psyche is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Psyche. If not, see <http://www.gnu.org/licenses/>.
+
+//! Experimental x86-64 baseline JIT, gated behind the `jit` feature.
+//!
+//! This is a template compiler in its earliest stage: it recognizes a
+//! single straight-line pattern (a basic block that pushes one constant
+//! and halts) whose per-block gas and stack bounds were already checked by
+//! `VmRom`'s analysis, and emits native code for it directly instead of
+//! going through the dispatch loop. Every other block is reported as
+//! unsupported so callers fall back to the interpreter; broadening the
+//! supported pattern set is the intended direction of follow-up work. The
+//! pattern matching itself is shared with `portable_jit.rs` -- see
+//! `jit_pattern` -- since the two backends only differ in how they encode
+//! the matched constant for their target architecture.
+
+use crate::jit_pattern::{compile_constant_return_block, matches_constant_return};
+
+/// A block of freshly generated, mapped-executable machine code.
+pub struct JitBlock {
+    code: memmap::Mmap,
+}
+
+impl JitBlock {
+    /// Calls into the compiled block. Safe only because `compile_block`
+    /// only ever emits `mov rax, imm64; ret`, with no memory or register
+    /// state threaded in or out beyond the return value.
+    pub unsafe fn call(&self) -> u64 {
+        let entry: extern "C" fn() -> u64 = std::mem::transmute(self.code.as_ptr());
+        entry()
+    }
+}
+
+/// Returns true if `compile_block` can produce native code for `bytecode`.
+pub fn is_supported(bytecode: &[u8]) -> bool {
+    matches_constant_return(bytecode).is_some()
+}
+
+/// Assembles `mov rax, imm64; ret`.
+fn assemble_constant_return(value: u64) -> Vec<u8> {
+    let mut code = vec![0x48, 0xb8]; // REX.W + MOV RAX, imm64
+    code.extend_from_slice(&value.to_le_bytes());
+    code.push(0xc3); // RET
+    code
+}
+
+/// Compiles `bytecode` into native code if it matches a supported pattern,
+/// falling back to `None` (meaning: use the interpreter) otherwise.
+pub fn compile_block(bytecode: &[u8]) -> Option<JitBlock> {
+    let code = compile_constant_return_block(bytecode, assemble_constant_return)?;
+    Some(JitBlock { code })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instructions::EvmOpcode;
+
+    fn push1_stop(value: u8) -> Vec<u8> {
+        vec![EvmOpcode::PUSH1 as u8, value, EvmOpcode::STOP as u8]
+    }
+
+    #[test]
+    fn recognizes_constant_return_pattern() {
+        assert!(is_supported(&push1_stop(42)));
+        assert!(!is_supported(&[EvmOpcode::ADD as u8]));
+    }
+
+    #[test]
+    fn compiles_and_runs_constant_return() {
+        let block = compile_block(&push1_stop(42)).expect("pattern should be supported");
+        let result = unsafe { block.call() };
+        assert_eq!(result, 42);
+    }
+
+    #[test]
+    fn rejects_unsupported_blocks() {
+        let mut code = push1_stop(1);
+        code.push(EvmOpcode::ADD as u8);
+        assert!(compile_block(&code).is_none());
+    }
+}
@@ -0,0 +1,93 @@
+// Copyright 2020 The Psyche Authors
+// This file is part of Psyche.
+//
+// Psyche is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Psyche is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Psyche. If not, see <http://www.gnu.org/licenses/>.
+
+//! Pattern-matching and mmap plumbing shared by the `jit` and `portable-jit`
+//! backends (`jit.rs`, `portable_jit.rs`). Both currently recognize the same
+//! single degenerate block shape -- `PUSHN <value> STOP` -- and differ only
+//! in how they encode the resulting constant for their target architecture;
+//! that per-arch encoder is the one thing each backend still supplies for
+//! itself.
+
+use crate::instructions::EvmOpcode;
+
+/// Returns the constant a `PUSHN <value> STOP` block would push, if
+/// `bytecode` matches that pattern and the constant fits in 64 bits.
+pub(crate) fn matches_constant_return(bytecode: &[u8]) -> Option<u64> {
+    use std::convert::TryFrom;
+    if bytecode.is_empty() {
+        return None;
+    }
+    let opcode = EvmOpcode::try_from(bytecode[0]).ok()?;
+    if !opcode.is_push() {
+        return None;
+    }
+    let num_bytes = opcode.push_index() + 1;
+    let start = 1;
+    let end = start + num_bytes;
+    if bytecode.len() != end + 1 || bytecode[end] != EvmOpcode::STOP as u8 {
+        return None;
+    }
+    let imm = &bytecode[start..end];
+    // the constant must fit in 64 bits: every byte beyond the low 8 must
+    // be zero.
+    let high_len = imm.len().saturating_sub(8);
+    if imm[..high_len].iter().any(|&b| b != 0) {
+        return None;
+    }
+    let mut value: u64 = 0;
+    for &b in &imm[high_len..] {
+        value = (value << 8) | b as u64;
+    }
+    Some(value)
+}
+
+/// Assembles `bytecode` into a mapped-executable block if it matches
+/// `matches_constant_return`, using `encode` to turn the matched constant
+/// into the target architecture's machine code; falls back to `None`
+/// (meaning: use the interpreter) for anything else, including an mmap
+/// failure.
+pub(crate) fn compile_constant_return_block(
+    bytecode: &[u8],
+    encode: impl FnOnce(u64) -> Vec<u8>,
+) -> Option<memmap::Mmap> {
+    let value = matches_constant_return(bytecode)?;
+    let code = encode(value);
+    let mut mmap = memmap::MmapMut::map_anon(code.len()).ok()?;
+    mmap[..code.len()].copy_from_slice(&code);
+    mmap.make_exec().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn push1_stop(value: u8) -> Vec<u8> {
+        vec![EvmOpcode::PUSH1 as u8, value, EvmOpcode::STOP as u8]
+    }
+
+    #[test]
+    fn recognizes_constant_return_pattern() {
+        assert_eq!(matches_constant_return(&push1_stop(42)), Some(42));
+        assert_eq!(matches_constant_return(&[EvmOpcode::ADD as u8]), None);
+    }
+
+    #[test]
+    fn rejects_blocks_with_trailing_instructions() {
+        let mut code = push1_stop(1);
+        code.push(EvmOpcode::ADD as u8);
+        assert_eq!(matches_constant_return(&code), None);
+    }
+}
@@ -20,8 +20,30 @@
 extern crate num_derive;
 
 pub mod assembler;
-mod instructions;
+pub mod cache;
+pub mod dispatcher;
+pub mod errors;
+pub mod extension;
+pub mod instructions;
+#[cfg(feature = "jit")]
+pub mod jit;
+#[cfg(any(feature = "jit", feature = "portable-jit"))]
+pub(crate) mod jit_pattern;
+pub mod limits;
+#[cfg(feature = "optimizer")]
+pub mod opt;
+#[cfg(feature = "portable-jit")]
+pub mod portable_jit;
+pub mod prelude;
+pub mod profiler;
+#[cfg(feature = "reference")]
+pub mod reference;
 pub mod schedule;
+#[cfg(feature = "tracer")]
+pub mod selectors;
+pub mod sourcemap;
+pub mod stats;
+pub mod strategy;
 pub mod u256;
 pub mod utils;
 pub mod vm;
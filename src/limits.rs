@@ -0,0 +1,52 @@
+// Copyright 2020 The Psyche Authors
+// This file is part of Psyche.
+//
+// Psyche is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Psyche is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Psyche. If not, see <http://www.gnu.org/licenses/>.
+
+//! Named mainnet size/depth limits and the word-rounding helper built on
+//! top of them, consolidated here instead of as magic numbers scattered
+//! across `vm.rs`, so the analyzers (`stats`, `profiler`) and the
+//! assembler's own size checks can all name the same constant.
+
+/// Mainnet's maximum EVM stack depth (1024 since Frontier; EIP-150 never
+/// changed it). `vm::VmStack::MAX_LEN`, the interpreter's compile-time
+/// stack storage bound, and `schedule::DEFAULT_STACK_LIMIT`, the runtime-
+/// configurable default clamped to it, are both this same number.
+pub const MAX_STACK: usize = 1024;
+
+/// EIP-170's cap on deployed contract code size (Spurious Dragon onward).
+/// This interpreter has no account model yet, so nothing deploys code and
+/// nothing currently enforces this (see `Opcode::CREATE`'s dispatch arm in
+/// `vm.rs`); it's named here for callers and analyzers that want to flag
+/// oversized code themselves ahead of that landing.
+pub const MAX_CODE_SIZE: usize = 24576;
+
+/// EIP-3860's cap on initcode size (Shanghai onward), twice
+/// `MAX_CODE_SIZE`. Same caveat as `MAX_CODE_SIZE`: not enforced by this
+/// interpreter, which has no `CREATE`/`CREATE2` to enforce it in.
+pub const MAX_INITCODE_SIZE: usize = 49152;
+
+/// Mainnet's message-call depth limit (EIP-150): CALL/CALLCODE/
+/// DELEGATECALL/STATICCALL/CREATE/CREATE2 nest at most this deep before
+/// the outermost call fails. Same caveat as `MAX_CODE_SIZE`: this
+/// interpreter has no CALL-family dispatch yet (see `Opcode::CALL`'s
+/// dispatch arm in `vm.rs`), so nothing currently checks this.
+pub const CALL_DEPTH_LIMIT: usize = 1024;
+
+/// Rounds `bytes` up to the nearest whole 32-byte EVM word, the same
+/// ceiling division `SHA3`'s per-word gas cost and `MLOAD`/`MSTORE`'s
+/// memory-expansion cost are both keyed on.
+pub const fn num_words(bytes: u64) -> u64 {
+    ((bytes as u128 + 31) / 32) as u64
+}
@@ -19,26 +19,59 @@ extern crate clap;
 extern crate num_derive;
 
 mod assembler;
+mod cache;
+mod cli_parse;
+mod dispatcher;
+mod eof;
+mod errors;
+mod extension;
 mod instructions;
+#[cfg(feature = "jit")]
+mod jit;
+#[cfg(any(feature = "jit", feature = "portable-jit"))]
+mod jit_pattern;
+mod limits;
+mod opt;
+#[cfg(feature = "portable-jit")]
+mod portable_jit;
+mod profiler;
 mod schedule;
+mod selectors;
+mod sourcemap;
+mod stats;
+mod strategy;
 mod u256;
 mod utils;
 mod vm;
 
 use clap::{App, Arg, SubCommand};
+use rayon::prelude::*;
 
+use std::collections::{HashMap, HashSet};
 use std::convert::TryFrom;
-use std::fmt::{self, Write};
+use std::fmt;
 use std::fs;
+use std::io::{self, Read, Write};
+use std::panic;
+use std::path::PathBuf;
 use std::str::FromStr;
+use std::time::{Duration, Instant};
 
 use instructions::{EvmInstruction, EvmOpcode};
 use schedule::{Fork, Schedule};
-use u256::U256;
-use utils::{decode_hex, encode_hex, print_config};
-use vm::{run_evm, VmError, VmMemory, VmRom};
+use strategy::ExecutionStrategy;
+use u256::{sha3_u256, U256};
+use utils::{decode_hex, encode_hex, print_config, write_hex, HexDecodeError};
+use vm::{
+    run_evm, run_evm_with_breakpoint, run_evm_with_owned_output, run_evm_with_trace, BlockContext,
+    Breakpoint, TestBlockHashProvider, TraceFilter, TraceSample, VmError, VmMemory, VmRom,
+};
 
 const VM_DEFAULT_GAS: u64 = 20_000_000_000_000;
+/// Default `--trace` match-buffer preallocation (see
+/// `TraceReport::with_capacity`); large enough to absorb a moderately hot
+/// loop without reallocating, small enough not to matter for short traces.
+const TRACE_DEFAULT_CAPACITY: usize = 4096;
 
 struct Bytecode<'a> {
     data: &'a [u8],
@@ -60,7 +93,7 @@ struct IncompletePushError {
 
 impl fmt::Display for IncompletePushError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "incomplete push instruction at 0x{:04x}", self.addr)
+        write!(f, "incomplete instruction at 0x{:04x}", self.addr)
     }
 }
 
@@ -71,8 +104,8 @@ impl<'a> Iterator for Bytecode<'a> {
             let value = self.data[self.addr];
             match EvmOpcode::try_from(value) {
                 Ok(opcode) => {
-                    if opcode.is_push() {
-                        let num_bytes = opcode.push_index() + 1;
+                    if opcode.is_push() || opcode.is_deep_stack() {
+                        let num_bytes = if opcode.is_push() { opcode.push_index() + 1 } else { 1 };
                         let start = self.addr + 1;
                         let end = start + num_bytes;
                         if (end - 1) < self.data.len() {
@@ -110,18 +143,129 @@ impl<'a> Iterator for Bytecode<'a> {
     }
 }
 
-fn disasm(input: &str) {
+/// Renders the `; file:line (jump: into|out)` suffix for one instruction,
+/// given its decoded source map entry, or `""` if there's nothing to show.
+fn format_source_annotation(entry: &sourcemap::SourceMapEntry, sources: &[String]) -> String {
+    let jump = match entry.jump {
+        sourcemap::JumpType::Into => " (jump: into)",
+        sourcemap::JumpType::Out => " (jump: out)",
+        sourcemap::JumpType::Regular => "",
+    };
+    if entry.file_index < 0 {
+        return String::new();
+    }
+    match sources.get(entry.file_index as usize) {
+        Some(source) => {
+            let line = sourcemap::line_for_offset(source, entry.start);
+            format!("    ; {}:{}{}", entry.file_index, line, jump)
+        }
+        None => format!("    ; source {} unknown{}", entry.file_index, jump),
+    }
+}
+
+/// Formats a dispatcher-branch annotation for an instruction at `addr`,
+/// given the selector-to-destination map from `dispatcher::detect_linear`
+/// and, optionally, a selector-to-signature map loaded via `selectors`.
+fn format_dispatcher_annotation(
+    addr: usize,
+    dest_to_selector: &HashMap<usize, u32>,
+    signatures: &HashMap<u32, String>,
+) -> String {
+    match dest_to_selector.get(&addr) {
+        Some(selector) => match signatures.get(selector) {
+            Some(name) => format!("    ; selector 0x{:08x} {}", selector, name),
+            None => format!("    ; selector 0x{:08x}", selector),
+        },
+        None => String::new(),
+    }
+}
+
+/// Disassembles `input`, optionally annotating each instruction with its
+/// Solidity source position from `source_map` (solc's compressed
+/// `s:l:f:j` format) and, when `sources` is also given, the source line
+/// number computed from that position. Every `JUMPDEST` reached from a
+/// recognized dispatcher branch (see `dispatcher::detect_linear`) is
+/// labeled with its selector, resolved to a signature when
+/// `selectors_file` is given (see the `selectors` module for the accepted
+/// formats).
+fn disasm(input: &str, source_map: Option<&str>, sources: Option<&str>, selectors_file: Option<&str>) {
+    let entries = match source_map {
+        Some(map) => match sourcemap::parse(map) {
+            Ok(entries) => Some(entries),
+            Err(e) => {
+                println!("Invalid --source-map: {}", e);
+                return;
+            }
+        },
+        None => None,
+    };
+    let mut source_files = Vec::new();
+    if let Some(paths) = sources {
+        for path in paths.split(',') {
+            match fs::read_to_string(path) {
+                Ok(contents) => source_files.push(contents),
+                Err(e) => {
+                    println!("{}: {}", path, e);
+                    return;
+                }
+            }
+        }
+    }
+    let signatures = match selectors_file {
+        Some(path) => match selectors::load(path) {
+            Ok(signatures) => signatures,
+            Err(e) => {
+                println!("Invalid --selectors: {}", e);
+                return;
+            }
+        },
+        None => HashMap::new(),
+    };
     let temp = decode_hex(input);
     match temp {
         Ok(bytes) => {
-            let result: Result<Vec<EvmInstruction>, _> = Bytecode::new(&bytes).collect();
+            let code = if eof::is_eof(&bytes) {
+                let container = match eof::parse(&bytes) {
+                    Ok(container) => container,
+                    Err(e) => {
+                        println!("Invalid EOF container: {}", e);
+                        return;
+                    }
+                };
+                println!(
+                    "EOF version={} code_sections={} data_size={}",
+                    container.version,
+                    container.code_sections.len(),
+                    container.data.len()
+                );
+                for (index, code) in container.code_sections.iter().enumerate() {
+                    match container.types.get(index) {
+                        Some(ty) => println!(
+                            "  section {}: {} bytes (inputs={} outputs={} max_stack={})",
+                            index, code.len(), ty.inputs, ty.outputs, ty.max_stack
+                        ),
+                        None => println!("  section {}: {} bytes", index, code.len()),
+                    }
+                }
+                container.code().to_vec()
+            } else {
+                bytes
+            };
+            let dest_to_selector: HashMap<usize, u32> = dispatcher::detect_linear(&code)
+                .into_iter()
+                .map(|branch| (branch.dest, branch.selector))
+                .collect();
+            let result: Result<Vec<EvmInstruction>, _> = Bytecode::new(&code).collect();
             match result {
                 Ok(x) => {
-                    let asm = x
-                        .iter()
-                        .map(|i| match i {
+                    // Printed instruction-by-instruction, rather than
+                    // built up into one big string first, so disassembling
+                    // a large contract doesn't hold the whole rendered
+                    // output in memory twice over.
+                    for (i, instr) in x.iter().enumerate() {
+                        let (addr, mut line) = match instr {
                             EvmInstruction::SingleByte { addr, opcode } => {
-                                format!("{:04x}:    {}", addr, opcode)
+                                (*addr, format!("{:04x}:    {}", addr, opcode))
                             }
                             EvmInstruction::MultiByte {
                                 addr,
@@ -129,106 +273,2144 @@ fn disasm(input: &str) {
                                 bytes,
                             } => {
                                 let imm = encode_hex(bytes);
-                                format!("{:04x}:    {} 0x{}", addr, opcode, imm)
+                                (*addr, format!("{:04x}:    {} 0x{}", addr, opcode, imm))
                             }
-                        })
-                        .collect::<Vec<_>>()
-                        .join("\n");
-                    println!("{}", asm);
+                        };
+                        if let Some(entry) = entries.as_ref().and_then(|entries| entries.get(i)) {
+                            line += &format_source_annotation(entry, &source_files);
+                        }
+                        line += &format_dispatcher_annotation(addr, &dest_to_selector, &signatures);
+                        println!("{}", line);
+                    }
                 }
                 Err(e) => println!("{}", e),
             }
         }
+        Err(e) => println!("{}", e),
+    }
+}
+
+#[cfg(feature = "jit")]
+fn evm_jit_x86(bytes: &Vec<u8>) -> bool {
+    if !jit::is_supported(bytes) {
+        return false;
+    }
+    match jit::compile_block(bytes) {
+        Some(block) => {
+            let result = unsafe { block.call() };
+            println!("0x{:016x}", result);
+            true
+        }
+        None => false,
+    }
+}
+
+#[cfg(all(feature = "portable-jit", target_arch = "aarch64"))]
+fn evm_jit_portable(bytes: &Vec<u8>) -> bool {
+    if !portable_jit::is_supported(bytes) {
+        return false;
+    }
+    match portable_jit::compile_block(bytes) {
+        Some(block) => {
+            let result = unsafe { block.call() };
+            println!("0x{:016x}", result);
+            true
+        }
+        None => false,
+    }
+}
+
+enum CodeInputError {
+    Hex(HexDecodeError),
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    MissingArtifactField(String, &'static str),
+    NoInput,
+}
+
+impl fmt::Display for CodeInputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodeInputError::Hex(e) => write!(f, "{}", e),
+            CodeInputError::Io(e) => write!(f, "{}", e),
+            CodeInputError::Json(e) => write!(f, "{}", e),
+            CodeInputError::MissingArtifactField(path, field) => {
+                write!(f, "{}: no \"{}\" field found", path, field)
+            }
+            CodeInputError::NoInput => write!(f, "no CODE or --artifact given"),
+        }
+    }
+}
+
+// Extracts a bytecode field from a standard Solidity/Foundry build
+// artifact, where the field is either a bare hex string or a
+// Foundry-style `{"object": "0x..."}` object.
+fn read_artifact_bytecode(path: &str, field: &'static str) -> Result<Vec<u8>, CodeInputError> {
+    let contents = fs::read_to_string(path).map_err(CodeInputError::Io)?;
+    let artifact: serde_json::Value =
+        serde_json::from_str(&contents).map_err(CodeInputError::Json)?;
+    let value = artifact
+        .get(field)
+        .ok_or_else(|| CodeInputError::MissingArtifactField(path.to_string(), field))?;
+    let hex_str = value
+        .as_str()
+        .or_else(|| value.get("object").and_then(|v| v.as_str()))
+        .ok_or_else(|| CodeInputError::MissingArtifactField(path.to_string(), field))?;
+    decode_hex(hex_str).map_err(CodeInputError::Hex)
+}
+
+// Resolves the CODE input for the evm/disasm-style subcommands: an
+// `--artifact` path takes priority (extracting `deployedBytecode`), `-`
+// reads hex from stdin, and anything else is decoded directly as hex.
+fn read_code_input(code: Option<&str>, artifact: Option<&str>) -> Result<Vec<u8>, CodeInputError> {
+    if let Some(path) = artifact {
+        return read_artifact_bytecode(path, "deployedBytecode");
+    }
+    match code {
+        Some("-") => {
+            let mut input = String::new();
+            std::io::stdin()
+                .read_to_string(&mut input)
+                .map_err(CodeInputError::Io)?;
+            decode_hex(input.trim()).map_err(CodeInputError::Hex)
+        }
+        Some(hex_str) => decode_hex(hex_str).map_err(CodeInputError::Hex),
+        None => Err(CodeInputError::NoInput),
+    }
+}
+
+// Like `read_code_input`, but for `deploy`: an `--artifact` path extracts
+// the creation code (`bytecode`) rather than the runtime code.
+fn read_creation_code_input(
+    code: Option<&str>,
+    artifact: Option<&str>,
+) -> Result<Vec<u8>, CodeInputError> {
+    if let Some(path) = artifact {
+        return read_artifact_bytecode(path, "bytecode");
+    }
+    read_code_input(code, None)
+}
+
+/// Unwraps an EOF container (EIP-3540/3670/4750) down to its code section
+/// for execution, running it through validation first; non-EOF input (no
+/// `0xEF00` magic) passes through unchanged, since legacy bytecode with
+/// no header is still the common case. Containers with more than one
+/// code section parse and validate, but can't run yet: `CALLF`/`RETF`/
+/// `JUMPF` aren't wired into the interpreter (see `eof`'s module doc).
+/// Doesn't attempt to re-wrap `deploy`'s returned runtime code as EOF;
+/// that's out of scope here.
+fn unwrap_eof(bytes: Vec<u8>, fork: Fork) -> Result<Vec<u8>, String> {
+    if !eof::is_eof(&bytes) {
+        return Ok(bytes);
+    }
+    let container = eof::parse(&bytes).map_err(|e| e.to_string())?;
+    eof::validate_container(&container, &Schedule::from_fork(fork)).map_err(|e| e.to_string())?;
+    if container.code_sections.len() > 1 {
+        return Err(format!(
+            "EOF container has {} code sections, but only single-code-section containers can be run",
+            container.code_sections.len()
+        ));
+    }
+    Ok(container.code().to_vec())
+}
+
+/// Translates `pc`, an address in code that may have come out of
+/// `opt::optimize`, back to its address in the original bytecode via
+/// `pc_map`, so `--optimize`'s trace/breakpoint output reads the same as
+/// it would without `--optimize`. Falls back to `pc` unchanged when
+/// there's no map (the common case) or the address wasn't one `PcMap`
+/// recorded.
+fn translate_pc(pc_map: Option<&opt::PcMap>, pc: usize) -> usize {
+    pc_map.and_then(|m| m.to_original(pc as u64)).map(|addr| addr as usize).unwrap_or(pc)
+}
+
+/// Runs `bytes` to completion under `filter` and dumps the resulting
+/// `TraceReport` as JSON, for scripted trace filters/aggregations (e.g.
+/// "count SLOADs", "print stack top at every JUMPI") without recompiling.
+#[allow(clippy::too_many_arguments)]
+fn evm_trace(
+    bytes: &Vec<u8>,
+    fork: Fork,
+    gas_limit: U256,
+    gas_price: U256,
+    value: U256,
+    max_fee_per_gas: U256,
+    max_priority_fee_per_gas: U256,
+    base_fee: U256,
+    filter: TraceFilter,
+    sample: TraceSample,
+    overrides: &[(EvmOpcode, u64)],
+    capacity_hint: usize,
+    max_memory: Option<u64>,
+    pc_map: Option<&opt::PcMap>,
+) {
+    let schedule = Schedule::from_fork(fork).with_overrides(overrides);
+    let mut rom = VmRom::new();
+    rom.init(&bytes, &schedule);
+    let mut memory = VmMemory::new();
+    memory.init_with_max_memory(gas_limit, max_memory);
+    let hashes = TestBlockHashProvider;
+    let mut block = BlockContext::new(U256::from_u64(0), &hashes);
+    block.gas_price = gas_price;
+    block.value = value;
+    block.max_fee_per_gas = max_fee_per_gas;
+    block.max_priority_fee_per_gas = max_priority_fee_per_gas;
+    block.base_fee = base_fee;
+    let (ret_data, report) = unsafe {
+        run_evm_with_trace(&bytes, &rom, &schedule, &block, gas_limit, &mut memory, filter, sample, capacity_hint)
+    };
+    // One reused buffer for every stack-top value instead of the
+    // `to_short_hex()` allocation `to_short_hex` would otherwise make per
+    // entry (see `U256::write_short_hex`).
+    let mut hex_buf = String::new();
+    let json = serde_json::json!({
+        "matches": report.matches,
+        "stack_tops": report.stack_tops.iter().map(|(pc, top)| {
+            hex_buf.clear();
+            top.write_short_hex(&mut hex_buf);
+            serde_json::json!({
+                "pc": translate_pc(pc_map, *pc),
+                "value": hex_buf.as_str(),
+            })
+        }).collect::<Vec<_>>(),
+        "return_stacks": report.return_stacks.iter().map(|(pc, contents)| serde_json::json!({
+            "pc": translate_pc(pc_map, *pc),
+            "contents": contents.iter().map(|addr| translate_pc(pc_map, *addr as usize)).collect::<Vec<_>>(),
+        })).collect::<Vec<_>>(),
+    });
+    println!("{}", json);
+    if ret_data.error != VmError::None {
+        let code = errors::ErrorCode::from(ret_data.error);
+        println!("{} ({})", code, code.code());
+    }
+}
+
+/// Runs `bytes` until `breakpoint` is hit and dumps the VM state at that
+/// point as JSON (stack, memory length, gas, pc), instead of running to
+/// completion, for scripted bisection of a misbehaving contract.
+#[allow(clippy::too_many_arguments)]
+fn evm_break(
+    bytes: &Vec<u8>,
+    fork: Fork,
+    gas_limit: U256,
+    gas_price: U256,
+    value: U256,
+    max_fee_per_gas: U256,
+    max_priority_fee_per_gas: U256,
+    base_fee: U256,
+    breakpoint: Breakpoint,
+    overrides: &[(EvmOpcode, u64)],
+    max_memory: Option<u64>,
+    pc_map: Option<&opt::PcMap>,
+) {
+    let schedule = Schedule::from_fork(fork).with_overrides(overrides);
+    let mut rom = VmRom::new();
+    rom.init(&bytes, &schedule);
+    let mut memory = VmMemory::new();
+    memory.init_with_max_memory(gas_limit, max_memory);
+    let hashes = TestBlockHashProvider;
+    let mut block = BlockContext::new(U256::from_u64(0), &hashes);
+    block.gas_price = gas_price;
+    block.value = value;
+    block.max_fee_per_gas = max_fee_per_gas;
+    block.max_priority_fee_per_gas = max_priority_fee_per_gas;
+    block.base_fee = base_fee;
+    let (ret_data, hit) = unsafe {
+        run_evm_with_breakpoint(
+            &bytes, &rom, &schedule, &block, gas_limit, &mut memory, breakpoint,
+        )
+    };
+    match hit {
+        Some(hit) => {
+            let json = serde_json::json!({
+                "pc": translate_pc(pc_map, hit.pc),
+                "gas": hit.gas,
+                "stack": hit.stack.iter().map(U256::to_short_hex).collect::<Vec<_>>(),
+                "memory_len": hit.memory_len,
+                "return_stack": hit.return_stack.iter().map(|addr| translate_pc(pc_map, *addr as usize)).collect::<Vec<_>>(),
+            });
+            println!("{}", json);
+        }
+        None if ret_data.error != VmError::None => {
+            let code = errors::ErrorCode::from(ret_data.error);
+            println!("{} ({})", code, code.code())
+        }
+        None => println!("breakpoint not reached; execution completed normally"),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn evm(
+    bytes: &Vec<u8>,
+    fork: Fork,
+    gas_limit: U256,
+    gas_price: U256,
+    value: U256,
+    max_fee_per_gas: U256,
+    max_priority_fee_per_gas: U256,
+    base_fee: U256,
+    strategy: ExecutionStrategy,
+    overrides: &[(EvmOpcode, u64)],
+    max_memory: Option<u64>,
+) {
+    match strategy {
+        ExecutionStrategy::Interpreter => (),
+        ExecutionStrategy::JitX86 => {
+            #[cfg(feature = "jit")]
+            if evm_jit_x86(bytes) {
+                return;
+            }
+            #[cfg(not(feature = "jit"))]
+            println!("jit-x86 requires building with --features jit; falling back to the interpreter");
+        }
+        ExecutionStrategy::JitPortable => {
+            #[cfg(all(feature = "portable-jit", target_arch = "aarch64"))]
+            if evm_jit_portable(bytes) {
+                return;
+            }
+            #[cfg(not(all(feature = "portable-jit", target_arch = "aarch64")))]
+            println!(
+                "jit-portable requires building with --features portable-jit for an aarch64 target; falling back to the interpreter"
+            );
+        }
+    }
+    let schedule = Schedule::from_fork(fork).with_overrides(overrides);
+    let mut rom = VmRom::new();
+    rom.init(&bytes, &schedule);
+    let mut memory = VmMemory::new();
+    memory.init_with_max_memory(gas_limit, max_memory);
+    let hashes = TestBlockHashProvider;
+    let mut block = BlockContext::new(U256::from_u64(0), &hashes);
+    block.gas_price = gas_price;
+    block.value = value;
+    block.max_fee_per_gas = max_fee_per_gas;
+    block.max_priority_fee_per_gas = max_priority_fee_per_gas;
+    block.base_fee = base_fee;
+    let (err, output) = unsafe {
+        let (ret_data, output) =
+            run_evm_with_owned_output(&bytes, &rom, &schedule, &block, gas_limit, &mut memory);
+        (ret_data.error, output)
+    };
+    if err != VmError::None {
+        let code = errors::ErrorCode::from(err);
+        println!("{} ({})", code, code.code());
+        return;
+    }
+    let output = match output {
+        Some(output) => output,
+        None => {
+            println!("returned data out of bounds of the mapped memory");
+            return;
+        }
+    };
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    let _ = stdout.write_all(b"0x");
+    let _ = write_hex(&mut stdout, &output);
+    let _ = stdout.write_all(b"\n");
+}
+
+#[allow(clippy::too_many_arguments)]
+fn evm_repeat(
+    bytes: &Vec<u8>,
+    fork: Fork,
+    gas_limit: U256,
+    gas_price: U256,
+    value: U256,
+    max_fee_per_gas: U256,
+    max_priority_fee_per_gas: U256,
+    base_fee: U256,
+    overrides: &[(EvmOpcode, u64)],
+    repeat: usize,
+    max_memory: Option<u64>,
+) {
+    let schedule = Schedule::from_fork(fork).with_overrides(overrides);
+    let mut rom = VmRom::new();
+    rom.init(&bytes, &schedule);
+    let mut memory = VmMemory::new();
+    memory.init_with_max_memory(gas_limit, max_memory);
+    let hashes = TestBlockHashProvider;
+    let mut block = BlockContext::new(U256::from_u64(0), &hashes);
+    block.gas_price = gas_price;
+    block.value = value;
+    block.max_fee_per_gas = max_fee_per_gas;
+    block.max_priority_fee_per_gas = max_priority_fee_per_gas;
+    block.base_fee = base_fee;
+    let mut durations = Vec::with_capacity(repeat);
+    let mut last_err = VmError::None;
+    for i in 0..repeat {
+        // Reused across runs (that's the point of `--repeat`: analyze the
+        // code once, then reuse the ROM and memory), so it must be reset
+        // to all-zero between runs or a later run would see the previous
+        // run's leftover contents and stale charged length.
+        memory.reset();
+        let start = Instant::now();
+        let err = unsafe {
+            let ret_data = run_evm(&bytes, &rom, &schedule, &block, gas_limit, &mut memory);
+            ret_data.error
+        };
+        let elapsed = start.elapsed();
+        if err == VmError::None {
+            println!("run {}: ok ({:?})", i + 1, elapsed);
+        } else {
+            let code = errors::ErrorCode::from(err);
+            println!("run {}: {} ({}) ({:?})", i + 1, code, code.code(), elapsed);
+        }
+        durations.push(elapsed);
+        last_err = err;
+    }
+    let total: Duration = durations.iter().sum();
+    println!(
+        "{} runs, total {:?}, avg {:?}",
+        repeat,
+        total,
+        total / repeat as u32
+    );
+    if last_err != VmError::None {
+        let code = errors::ErrorCode::from(last_err);
+        println!("last run failed: {} ({})", code, code.code());
+    }
+}
+
+// The per-byte gas charged for a contract's deployed code, unchanged
+// since Frontier (EIP-2's 200 gas/byte code deposit).
+const CODE_DEPOSIT_GAS_PER_BYTE: u64 = 200;
+
+fn deploy(
+    creation_bytes: &Vec<u8>,
+    fork: Fork,
+    gas_limit: U256,
+    strategy: ExecutionStrategy,
+    run_deployed: bool,
+) {
+    let schedule = Schedule::from_fork(fork);
+    let mut rom = VmRom::new();
+    rom.init(&creation_bytes, &schedule);
+    let mut memory = VmMemory::new();
+    memory.init(gas_limit);
+    let hashes = TestBlockHashProvider;
+    let block = BlockContext::new(U256::from_u64(0), &hashes);
+    let (err, gas_left, runtime_code) = unsafe {
+        let (ret_data, output) =
+            run_evm_with_owned_output(&creation_bytes, &rom, &schedule, &block, gas_limit, &mut memory);
+        (ret_data.error, ret_data.gas, output)
+    };
+    if err != VmError::None {
+        let code = errors::ErrorCode::from(err);
+        println!("deployment failed: {} ({})", code, code.code());
+        return;
+    }
+    let runtime_code = match runtime_code {
+        Some(runtime_code) => runtime_code,
+        None => {
+            println!("deployment failed: returned code out of bounds of the mapped memory");
+            return;
+        }
+    };
+    let deposit_cost = CODE_DEPOSIT_GAS_PER_BYTE * runtime_code.len() as u64;
+    if deposit_cost > gas_left {
+        let code = errors::ErrorCode::from(VmError::OutOfGas);
+        println!("deployment failed: {} ({})", code, code.code());
+        return;
+    }
+    let gas_used = gas_limit.low_u64() - (gas_left - deposit_cost);
+    println!("deployed code size: {} bytes", runtime_code.len());
+    println!(
+        "deployment gas: {} (execution: {}, code deposit: {})",
+        gas_used,
+        gas_used - deposit_cost,
+        deposit_cost
+    );
+    {
+        let stdout = io::stdout();
+        let mut stdout = stdout.lock();
+        let _ = stdout.write_all(b"0x");
+        let _ = write_hex(&mut stdout, &runtime_code);
+        let _ = stdout.write_all(b"\n");
+    }
+    if run_deployed {
+        evm(
+            &runtime_code,
+            fork,
+            gas_limit,
+            U256::from_u64(0),
+            U256::from_u64(0),
+            U256::from_u64(0),
+            U256::from_u64(0),
+            U256::from_u64(0),
+            strategy,
+            &[],
+            None,
+        );
+    }
+}
+
+fn compile(input: &str, fork: Fork, out_dir: &str) {
+    match decode_hex(input) {
+        Ok(bytes) => {
+            let optimized = opt::optimize(&bytes, fork);
+            let path = std::path::Path::new(out_dir).join(cache::artifact_filename(&bytes));
+            match cache::write_artifact(&path, fork, &optimized) {
+                Ok(()) => println!("{}", path.display()),
+                Err(e) => println!("{:?}", e),
+            }
+        }
+        Err(e) => println!("{}", e),
+    }
+}
+
+fn exec_artifact(filename: &str, gas_limit: U256, strategy: ExecutionStrategy) {
+    match cache::read_artifact(std::path::Path::new(filename)) {
+        Ok((fork, bytes)) => evm(
+            &bytes,
+            fork,
+            gas_limit,
+            U256::from_u64(0),
+            U256::from_u64(0),
+            U256::from_u64(0),
+            U256::from_u64(0),
+            U256::from_u64(0),
+            strategy,
+            &[],
+            None,
+        ),
+        Err(e) => println!("{:?}", e),
+    }
+}
+
+/// Opcodes safe to microbenchmark in a tight synthetic loop: pure
+/// stack-in/stack-out with no side effects, no jump-target validation,
+/// and no dependence on VM/block/environment state, so a fixed number of
+/// dummy operands can stand in for real ones without changing timing.
+const BENCHABLE_OPCODES: &[EvmOpcode] = &[
+    EvmOpcode::ADD,
+    EvmOpcode::MUL,
+    EvmOpcode::SUB,
+    EvmOpcode::DIV,
+    EvmOpcode::SDIV,
+    EvmOpcode::MOD,
+    EvmOpcode::SMOD,
+    EvmOpcode::ADDMOD,
+    EvmOpcode::MULMOD,
+    EvmOpcode::EXP,
+    EvmOpcode::SIGNEXTEND,
+    EvmOpcode::LT,
+    EvmOpcode::GT,
+    EvmOpcode::SLT,
+    EvmOpcode::SGT,
+    EvmOpcode::EQ,
+    EvmOpcode::ISZERO,
+    EvmOpcode::AND,
+    EvmOpcode::OR,
+    EvmOpcode::XOR,
+    EvmOpcode::NOT,
+    EvmOpcode::BYTE,
+    EvmOpcode::SHL,
+    EvmOpcode::SHR,
+    EvmOpcode::SAR,
+];
+
+/// The SIMD backend `u256.rs` was compiled for, decided at compile time by
+/// `RUSTFLAGS -C target-feature=...` (see build_avx2.sh/build_ssse3.sh/
+/// build_generic.sh); there's no runtime dispatch to report on.
+fn active_backend() -> &'static str {
+    #[cfg(target_feature = "avx2")]
+    return "avx2";
+    #[cfg(target_feature = "ssse3")]
+    return "ssse3";
+    #[allow(unreachable_code)]
+    "generic"
+}
+
+/// Runs `opcode` `iterations` times in a JUMP loop, refilling its operands
+/// from dummy values and popping its results each time so the loop's
+/// counter is undisturbed, and reports ns/op for the backend this binary
+/// was built for.
+fn bench_op(opcode: EvmOpcode, iterations: u32) -> Result<(), String> {
+    if !BENCHABLE_OPCODES.contains(&opcode) {
+        return Err(format!(
+            "{} can't be benchmarked this way: only opcodes with no side effects and a fixed stack arity are supported ({})",
+            opcode,
+            BENCHABLE_OPCODES
+                .iter()
+                .map(|op| op.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+    let (_, _, pops, pushes) = vm::OPCODE_INFOS[opcode as usize];
+    let refill = "PUSH1 0x02\n".repeat(pops as usize);
+    let cleanup = "POP\n".repeat(pushes as usize);
+    let input = format!(
+        "
+        %define jz($label)
+            DUP1
+            ISZERO
+            $label
+            JUMPI
+        %end
+
+        %define dec()
+            1
+            SWAP1
+            SUB
+        %end
+
+        PUSH4 {:#010x}
+        loop:
+            jz(done)
+            {refill}
+            {opcode}
+            {cleanup}
+            dec
+            loop
+            JUMP
+        done:
+            POP
+            STOP
+        ",
+        iterations,
+        refill = refill,
+        opcode = opcode,
+        cleanup = cleanup,
+    );
+    let bytecode = assembler::from_string(&input).map_err(|e| e.code().to_string())?;
+    let schedule = Schedule::from_fork(Fork::default());
+    let mut rom = VmRom::new();
+    rom.init(&bytecode, &schedule);
+    let mut memory = VmMemory::new();
+    let gas_limit = U256::from_u64(VM_DEFAULT_GAS);
+    memory.init(gas_limit);
+    let hashes = TestBlockHashProvider;
+    let block = BlockContext::new(U256::from_u64(0), &hashes);
+    let start = Instant::now();
+    let err = unsafe {
+        let ret_data = run_evm(&bytecode, &rom, &schedule, &block, gas_limit, &mut memory);
+        ret_data.error
+    };
+    let elapsed = start.elapsed();
+    if err != VmError::None {
+        let code = errors::ErrorCode::from(err);
+        return Err(format!("benchmark run failed: {} ({})", code, code.code()));
+    }
+    let ns_per_op = elapsed.as_secs_f64() * 1e9 / iterations as f64;
+    println!(
+        "{:<10} backend={:<8} iterations={:<10} total={:?} ns/op={:.3}",
+        opcode.to_string(),
+        active_backend(),
+        iterations,
+        elapsed,
+        ns_per_op
+    );
+    Ok(())
+}
+
+/// Runs the same ADD-in-a-JUMP-loop bytecode `bench_op(ADD, ..)` would,
+/// once untraced and once under a `TraceFilter::CountOpcode(ADD)` trace
+/// (the filter every loop iteration will match, so it's the worst case for
+/// the extra per-step branch `TraceFilter`'s doc comment describes), and
+/// reports the ns/op of each plus the traced/untraced ratio, so a change
+/// to the tracer's hot path can be checked against a stated overhead
+/// budget (e.g. "< 3x") without a full benchmarking harness.
+fn bench_trace(iterations: u32) -> Result<(), String> {
+    let input = format!(
+        "
+        %define jz($label)
+            DUP1
+            ISZERO
+            $label
+            JUMPI
+        %end
+
+        %define dec()
+            1
+            SWAP1
+            SUB
+        %end
+
+        PUSH4 {:#010x}
+        loop:
+            jz(done)
+            PUSH1 0x02
+            PUSH1 0x02
+            ADD
+            POP
+            dec
+            loop
+            JUMP
+        done:
+            POP
+            STOP
+        ",
+        iterations,
+    );
+    let bytecode = assembler::from_string(&input).map_err(|e| e.code().to_string())?;
+    let schedule = Schedule::from_fork(Fork::default());
+    let mut rom = VmRom::new();
+    rom.init(&bytecode, &schedule);
+    let gas_limit = U256::from_u64(VM_DEFAULT_GAS);
+    let hashes = TestBlockHashProvider;
+    let block = BlockContext::new(U256::from_u64(0), &hashes);
+
+    let mut untraced_memory = VmMemory::new();
+    untraced_memory.init(gas_limit);
+    let untraced_start = Instant::now();
+    let untraced_err = unsafe {
+        let ret_data = run_evm(&bytecode, &rom, &schedule, &block, gas_limit, &mut untraced_memory);
+        ret_data.error
+    };
+    let untraced_elapsed = untraced_start.elapsed();
+    if untraced_err != VmError::None {
+        let code = errors::ErrorCode::from(untraced_err);
+        return Err(format!("untraced benchmark run failed: {} ({})", code, code.code()));
+    }
+
+    let mut traced_memory = VmMemory::new();
+    traced_memory.init(gas_limit);
+    let traced_start = Instant::now();
+    let (traced_err, _) = unsafe {
+        let (ret_data, report) = run_evm_with_trace(
+            &bytecode,
+            &rom,
+            &schedule,
+            &block,
+            gas_limit,
+            &mut traced_memory,
+            TraceFilter::CountOpcode(EvmOpcode::ADD),
+            TraceSample::Every,
+            TRACE_DEFAULT_CAPACITY,
+        );
+        (ret_data.error, report)
+    };
+    let traced_elapsed = traced_start.elapsed();
+    if traced_err != VmError::None {
+        let code = errors::ErrorCode::from(traced_err);
+        return Err(format!("traced benchmark run failed: {} ({})", code, code.code()));
+    }
+
+    let untraced_ns_per_op = untraced_elapsed.as_secs_f64() * 1e9 / iterations as f64;
+    let traced_ns_per_op = traced_elapsed.as_secs_f64() * 1e9 / iterations as f64;
+    println!(
+        "backend={:<8} iterations={:<10} untraced_ns/op={:.3} traced_ns/op={:.3} overhead={:.2}x",
+        active_backend(),
+        iterations,
+        untraced_ns_per_op,
+        traced_ns_per_op,
+        traced_ns_per_op / untraced_ns_per_op
+    );
+    Ok(())
+}
+
+/// Runs a loop whose body contains two `JUMPI`s that are essentially never
+/// taken (the loop-exit check, taken once at the very end, and a condition
+/// hardcoded to zero), each falling through to an address that isn't a
+/// valid jump target -- exactly the case `analyze_basic_blocks` now folds
+/// into the preceding block's own entry check instead of publishing an
+/// independent one (see its doc comment). Reports ns/op for this branchy
+/// pattern; comparing the number against the parent commit is the way to
+/// see the fold's effect, since it's baked into `VmRom::init` rather than
+/// sitting behind a flag to switch live. On the generic backend the delta
+/// is within measurement noise here: `check_exception_at!` still runs at
+/// the fallthrough address either way, the fold just makes its stack
+/// comparison statically trivial rather than eliminating the call.
+fn bench_jumpi(iterations: u32) -> Result<(), String> {
+    let input = format!(
+        "
+        %define dec()
+            1
+            SWAP1
+            SUB
+        %end
+
+        PUSH4 {:#010x}
+        loop:
+            DUP1
+            ISZERO
+            done
+            JUMPI
+            PUSH1 0x00
+            loop
+            JUMPI
+            dec
+            loop
+            JUMP
+        done:
+            POP
+            STOP
+        ",
+        iterations,
+    );
+    let bytecode = assembler::from_string(&input).map_err(|e| e.code().to_string())?;
+    let schedule = Schedule::from_fork(Fork::default());
+    let mut rom = VmRom::new();
+    rom.init(&bytecode, &schedule);
+    let gas_limit = U256::from_u64(VM_DEFAULT_GAS);
+    let mut memory = VmMemory::new();
+    memory.init(gas_limit);
+    let hashes = TestBlockHashProvider;
+    let block = BlockContext::new(U256::from_u64(0), &hashes);
+    let start = Instant::now();
+    let err = unsafe {
+        let ret_data = run_evm(&bytecode, &rom, &schedule, &block, gas_limit, &mut memory);
+        ret_data.error
+    };
+    let elapsed = start.elapsed();
+    if err != VmError::None {
+        let code = errors::ErrorCode::from(err);
+        return Err(format!("benchmark run failed: {} ({})", code, code.code()));
+    }
+    let ns_per_op = elapsed.as_secs_f64() * 1e9 / iterations as f64;
+    println!(
+        "backend={:<8} iterations={:<10} total={:?} ns/op={:.3}",
+        active_backend(),
+        iterations,
+        elapsed,
+        ns_per_op
+    );
+    Ok(())
+}
+
+// Matches the first `<number><unit>` time token in `output` (ns, µs/us, ms,
+// or a bare s), converting it to seconds. Best-effort: external benchmark
+// harnesses format their own timings however they like, so this only
+// handles the common "123.4ms"-style token, optionally wrapped in
+// punctuation like "(123.4ms)".
+fn parse_elapsed_seconds(output: &str) -> Option<f64> {
+    fn parse_time_token(word: &str) -> Option<f64> {
+        let word = word.trim_matches(|c: char| !c.is_ascii_alphanumeric() && c != '.');
+        let (digits, multiplier) = if let Some(d) = word.strip_suffix("ns") {
+            (d, 1e-9)
+        } else if let Some(d) = word.strip_suffix("µs").or_else(|| word.strip_suffix("us")) {
+            (d, 1e-6)
+        } else if let Some(d) = word.strip_suffix("ms") {
+            (d, 1e-3)
+        } else if let Some(d) = word.strip_suffix('s') {
+            (d, 1.0)
+        } else {
+            return None;
+        };
+        digits.parse::<f64>().ok().map(|v| v * multiplier)
+    }
+    output.split_whitespace().find_map(parse_time_token)
+}
+
+// Matches the first gas amount in `output`, recognizing "gas=1234",
+// "gas: 1234", "1234 gas" and "1234gas" (case-insensitive). Same
+// best-effort scope as `parse_elapsed_seconds`.
+fn parse_gas(output: &str) -> Option<u64> {
+    // Strict on purpose: a loose "strip non-digits from the ends" check
+    // would also accept a time token like "500ns" as the number 500.
+    fn digits_of(word: &str) -> Option<u64> {
+        let trimmed = word.trim_matches(|c: char| matches!(c, ',' | ';' | '(' | ')' | ':'));
+        if !trimmed.is_empty() && trimmed.bytes().all(|b| b.is_ascii_digit()) {
+            trimmed.parse().ok()
+        } else {
+            None
+        }
+    }
+    let words: Vec<&str> = output.split_whitespace().collect();
+    for (i, word) in words.iter().enumerate() {
+        let trimmed = word.trim_matches(|c: char| matches!(c, ':' | ',' | ';' | '(' | ')'));
+        let lower = trimmed.to_lowercase();
+        if let Some(rest) = lower.strip_prefix("gas=").or_else(|| lower.strip_prefix("gas:")) {
+            if let Some(v) = digits_of(rest) {
+                return Some(v);
+            }
+        }
+        if lower == "gas" {
+            if i > 0 {
+                if let Some(v) = digits_of(words[i - 1]) {
+                    return Some(v);
+                }
+            }
+            if let Some(next) = words.get(i + 1) {
+                if let Some(v) = digits_of(next) {
+                    return Some(v);
+                }
+            }
+        }
+        if let Some(prefix) = lower.strip_suffix("gas") {
+            if let Some(v) = digits_of(prefix) {
+                return Some(v);
+            }
+        }
+    }
+    None
+}
+
+/// Runs `bytecode` `iterations` times through Psyche's own interpreter,
+/// then shells out to `compare_cmd` (typically a wrapper script around
+/// `geth evm run --bench` or `evmone-bench`) with the same inputs exposed
+/// via the `PSYCHE_BENCH_CODE`/`PSYCHE_BENCH_ITERATIONS` environment
+/// variables, and reports both backends' Mgas/s and their ratio — the
+/// headline number this project optimizes for, measured against real
+/// alternatives instead of only against itself.
+///
+/// `compare_cmd`'s stdout is scanned (`parse_elapsed_seconds`/`parse_gas`)
+/// for a per-iteration time and gas figure; when either is missing, this
+/// falls back to the command's own wall-clock time (divided by
+/// `iterations`) and Psyche's own per-iteration gas usage, and says so in
+/// the report, rather than failing outright.
+fn bench_compare(bytecode: &[u8], fork: Fork, iterations: u32, compare_cmd: &str) -> Result<(), String> {
+    let schedule = Schedule::from_fork(fork);
+    let mut rom = VmRom::new();
+    rom.init(bytecode, &schedule);
+    let gas_limit = U256::from_u64(VM_DEFAULT_GAS);
+    let hashes = TestBlockHashProvider;
+    let block = BlockContext::new(U256::from_u64(0), &hashes);
+
+    let mut gas_used = 0u64;
+    let start = Instant::now();
+    for _ in 0..iterations {
+        let mut memory = VmMemory::new();
+        memory.init(gas_limit);
+        let ret_data = unsafe { run_evm(bytecode, &rom, &schedule, &block, gas_limit, &mut memory) };
+        if ret_data.error != VmError::None {
+            let code = errors::ErrorCode::from(ret_data.error);
+            return Err(format!("benchmark run failed: {} ({})", code, code.code()));
+        }
+        gas_used = gas_limit.low_u64() - ret_data.gas;
+    }
+    let psyche_elapsed = start.elapsed();
+    let psyche_seconds_per_op = psyche_elapsed.as_secs_f64() / iterations as f64;
+    let psyche_mgas_per_sec = gas_used as f64 / psyche_seconds_per_op / 1e6;
+
+    let compare_start = Instant::now();
+    let output = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(compare_cmd)
+        .env("PSYCHE_BENCH_CODE", encode_hex(bytecode))
+        .env("PSYCHE_BENCH_ITERATIONS", iterations.to_string())
+        .output()
+        .map_err(|e| format!("failed to run --compare-cmd: {}", e))?;
+    let compare_elapsed = compare_start.elapsed();
+    if !output.status.success() {
+        return Err(format!(
+            "--compare-cmd exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let parsed_seconds = parse_elapsed_seconds(&stdout);
+    let parsed_gas = parse_gas(&stdout);
+    let best_effort = parsed_seconds.is_none() || parsed_gas.is_none();
+    let compare_seconds_per_op =
+        parsed_seconds.unwrap_or_else(|| compare_elapsed.as_secs_f64() / iterations as f64);
+    let compare_gas_per_op = parsed_gas.unwrap_or(gas_used);
+    let compare_mgas_per_sec = compare_gas_per_op as f64 / compare_seconds_per_op / 1e6;
+
+    println!(
+        "backend={:<8} iterations={:<10} psyche_mgas/s={:.3} compare_mgas/s={:.3} ratio={:.2}x{}",
+        active_backend(),
+        iterations,
+        psyche_mgas_per_sec,
+        compare_mgas_per_sec,
+        psyche_mgas_per_sec / compare_mgas_per_sec,
+        if best_effort {
+            " (couldn't parse a time/gas figure out of --compare-cmd's output; used its wall-clock time and Psyche's own gas instead)"
+        } else {
+            ""
+        }
+    );
+    Ok(())
+}
+
+/// Builds a large synthetic contract (many small `JUMPDEST`-headed blocks,
+/// the shape that makes a sparse, address-indexed layout mostly padding)
+/// and times a full sweep of `is_jumpdest` lookups over every address
+/// against both `VmRom`'s default layout and `vm::rom_soa::SoaRom` (see
+/// that module's doc comment), reporting ns/addr for each. Wall-clock
+/// timing in a sandboxed/virtualized environment is only a rough proxy for
+/// the icache/dcache difference the layout change targets -- see
+/// `bench_trace` for another benchmark leaning on the same proxy -- so
+/// this reports a ratio for the caller to judge, rather than picking a
+/// winner itself.
+#[cfg(feature = "soa-rom")]
+fn bench_rom(num_blocks: usize, iterations: u32) {
+    use vm::rom_soa::SoaRom;
+
+    let mut bytecode = Vec::with_capacity(num_blocks * 5 + 1);
+    for _ in 0..num_blocks {
+        bytecode.push(EvmOpcode::JUMPDEST as u8);
+        bytecode.push(EvmOpcode::PUSH1 as u8);
+        bytecode.push(0x01);
+        bytecode.push(EvmOpcode::ADD as u8);
+        bytecode.push(EvmOpcode::POP as u8);
+    }
+    bytecode.push(EvmOpcode::STOP as u8);
+
+    let schedule = Schedule::from_fork(Fork::default());
+    let mut rom = VmRom::new();
+    rom.init(&bytecode, &schedule);
+    let soa = SoaRom::new(&bytecode, &schedule);
+
+    let rom_start = Instant::now();
+    let mut rom_hits = 0u64;
+    for _ in 0..iterations {
+        for addr in 0..bytecode.len() {
+            rom_hits += rom.is_jumpdest(addr as u64) as u64;
+        }
+    }
+    let rom_elapsed = rom_start.elapsed();
+
+    let soa_start = Instant::now();
+    let mut soa_hits = 0u64;
+    for _ in 0..iterations {
+        for addr in 0..bytecode.len() {
+            soa_hits += soa.is_jumpdest(addr) as u64;
+        }
+    }
+    let soa_elapsed = soa_start.elapsed();
+
+    assert_eq!(rom_hits, soa_hits, "VmRom and SoaRom must agree on every address's is_jumpdest");
+
+    let num_lookups = bytecode.len() as f64 * iterations as f64;
+    let rom_ns_per_addr = rom_elapsed.as_secs_f64() * 1e9 / num_lookups;
+    let soa_ns_per_addr = soa_elapsed.as_secs_f64() * 1e9 / num_lookups;
+    println!(
+        "blocks={:<6} addrs={:<8} iterations={:<6} vm_rom_ns/addr={:.3} soa_rom_ns/addr={:.3} soa/vm_rom={:.2}x",
+        num_blocks,
+        bytecode.len(),
+        iterations,
+        rom_ns_per_addr,
+        soa_ns_per_addr,
+        soa_ns_per_addr / rom_ns_per_addr
+    );
+}
+
+/// Prints `bytecode` as a Rust byte-array snippet, for pasting assembled
+/// programs straight into a test suite.
+fn print_rust_bytes(bytecode: &[u8]) {
+    let mut joined = String::new();
+    for (i, byte) in bytecode.iter().enumerate() {
+        if i > 0 {
+            joined.push_str(", ");
+        }
+        joined.push_str(&format!("0x{:02x}", byte));
+    }
+    println!("const CODE: &[u8] = &[{}];", joined);
+}
+
+fn print_json(bytecode: &[u8], labels: &std::collections::BTreeMap<String, usize>) {
+    let json = serde_json::json!({
+        "code": encode_hex(bytecode),
+        "labels": labels,
+    });
+    println!("{}", json);
+}
+
+fn asm(filename: &str, format: &str, out: Option<&str>) {
+    let code = fs::read_to_string(filename).expect("Something went wrong reading the file");
+    let result = assembler::from_string_with_labels(&code);
+    match result {
+        Ok((bytecode, labels)) => {
+            if let Some(path) = out {
+                match fs::write(path, &bytecode) {
+                    Ok(()) => println!("{}", path),
+                    Err(e) => println!("{:?}", e),
+                }
+            }
+            match format {
+                "rust" => print_rust_bytes(&bytecode),
+                "json" => print_json(&bytecode, &labels),
+                _ => {
+                    let stdout = io::stdout();
+                    let mut stdout = stdout.lock();
+                    let _ = write_hex(&mut stdout, &bytecode);
+                    let _ = stdout.write_all(b"\n");
+                }
+            }
+        }
         Err(e) => println!("{:?}", e),
     }
 }
 
-fn evm(bytes: &Vec<u8>, fork: Fork, gas_limit: U256) {
+fn profile(
+    dir: &str,
+    top_n: usize,
+    emit_arms: bool,
+    gas_csv: Option<Fork>,
+    gas_lower_bound: Option<Fork>,
+) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            println!("{:?}", e);
+            return;
+        }
+    };
+    let mut corpus = Vec::new();
+    for entry in entries {
+        let path = match entry {
+            Ok(entry) => entry.path(),
+            Err(e) => {
+                println!("{:?}", e);
+                return;
+            }
+        };
+        if !path.is_file() {
+            continue;
+        }
+        let contents = fs::read_to_string(&path).expect("Something went wrong reading the file");
+        match decode_hex(contents.trim()) {
+            Ok(bytes) => corpus.push(bytes),
+            Err(e) => println!("skipping {}: {}", path.display(), e),
+        }
+    }
+    if let Some(fork) = gas_csv {
+        let schedule = Schedule::from_fork(fork);
+        let segments = stats::analyze_gas_segments(&corpus, &schedule);
+        print!("{}", stats::format_gas_segments_csv(&segments));
+        return;
+    }
+    if let Some(fork) = gas_lower_bound {
+        let schedule = Schedule::from_fork(fork);
+        println!("index,gas_lower_bound");
+        for (i, bytecode) in corpus.iter().enumerate() {
+            println!("{},{}", i, stats::static_gas_lower_bound(bytecode, &schedule));
+        }
+        return;
+    }
+    let report = stats::analyze(&corpus);
+    if emit_arms {
+        print!("{}", stats::generate_match_arms(&report, top_n));
+    } else {
+        print!("{}", stats::format_report(&report, top_n));
+    }
+}
+
+/// Renders `stats::gas_ledger` as CSV, one row per instruction in program
+/// order, for a block builder or simulator to scan without pulling in the
+/// rest of this crate.
+fn gas_ledger(bytecode: &[u8], fork: Fork) {
+    let schedule = Schedule::from_fork(fork);
+    let ledger = stats::gas_ledger(bytecode, &schedule);
+    println!("pc,opcode,gas_cost,cumulative_gas");
+    for i in 0..ledger.pcs.len() {
+        println!(
+            "{},{},{},{}",
+            ledger.pcs[i], ledger.opcodes[i], ledger.gas_costs[i], ledger.cumulative_gas[i]
+        );
+    }
+}
+
+/// Counts `JUMPDEST`s in `bytecode`, skipping push immediates so a `0x5b`
+/// byte inside one isn't mistaken for a real jump target — the same
+/// push/deep-stack skip `stats::analyze_gas_segments` and
+/// `stats::gas_ledger` walk with, repeated here rather than exposed from
+/// `stats` since counting one opcode doesn't need a whole segment or
+/// ledger built around it.
+fn count_jumpdests(bytecode: &[u8]) -> usize {
+    let mut count = 0;
+    let mut i = 0;
+    while i < bytecode.len() {
+        let opcode = EvmOpcode::try_from(bytecode[i]).unwrap_or(EvmOpcode::INVALID);
+        if opcode == EvmOpcode::JUMPDEST {
+            count += 1;
+        }
+        i += if opcode.is_push() {
+            1 + opcode.push_index() + 1
+        } else if opcode.is_deep_stack() {
+            2
+        } else {
+            1
+        };
+    }
+    count
+}
+
+/// `psyche golf <code>`: a one-pass report for gas golfers, tying
+/// together `stats` (gas segments and fusable-pattern digrams/trigrams)
+/// and `opt` (the peephole optimizer) rather than making a golfer run
+/// `gas-ledger`, `profile`, and `compile` separately and compare by hand.
+///
+/// "constant-foldable sequences" isn't broken out on its own: `opt::optimize`
+/// doesn't report which of its patterns fired, only the optimized bytes, so
+/// this reports the net effect (bytes saved) instead of a per-pattern count.
+fn golf(bytecode: &[u8], fork: Fork, top_n: usize) {
+    println!("code size: {} bytes", bytecode.len());
+    let deployment_gas = CODE_DEPOSIT_GAS_PER_BYTE * bytecode.len() as u64;
+    println!(
+        "estimated deployment cost: {} gas ({} gas/byte code deposit)",
+        deployment_gas, CODE_DEPOSIT_GAS_PER_BYTE
+    );
+    println!("JUMPDESTs: {}", count_jumpdests(bytecode));
+
+    let schedule = Schedule::from_fork(fork);
+    println!(
+        "static gas lower bound: {} (cheapest possible path to a terminator)",
+        stats::static_gas_lower_bound(bytecode, &schedule)
+    );
+    let corpus = vec![bytecode.to_vec()];
+    let mut segments = stats::analyze_gas_segments(&corpus, &schedule);
+    segments.sort_by(|a, b| b.gas.cmp(&a.gas));
+    println!("largest blocks (by static gas):");
+    for s in segments.iter().take(top_n) {
+        println!(
+            "  0x{:04x} {:<11} {} instrs, {} gas",
+            s.addr, s.category, s.instr_count, s.gas
+        );
+    }
+
+    let optimized = opt::optimize(bytecode, fork);
+    let saved = bytecode.len().saturating_sub(optimized.len());
+    println!(
+        "optimizer: {} -> {} bytes ({} bytes saved, {:.1}%)",
+        bytecode.len(),
+        optimized.len(),
+        saved,
+        100.0 * saved as f64 / bytecode.len().max(1) as f64
+    );
+
+    let report = stats::analyze(&corpus);
+    print!("{}", stats::format_report(&report, top_n));
+}
+
+/// `psyche pgo <code>`: runs `opt::pgo`'s profile -> optimize -> run loop
+/// end to end against one contract and no inputs (the interpreter has no
+/// calldata support yet, so there's only one path to profile; see
+/// `opt::pgo`'s module doc comment).
+fn pgo(bytecode: &[u8], fork: Fork, hot_threshold: u64) {
     let schedule = Schedule::from_fork(fork);
     let mut rom = VmRom::new();
-    rom.init(&bytes, &schedule);
+    rom.init(bytecode, &schedule);
     let mut memory = VmMemory::new();
+    let gas_limit = U256::from_u64(20_000_000_000_000);
     memory.init(gas_limit);
-    let (err, slice) = unsafe {
-        let ret_data = run_evm(&bytes, &rom, &schedule, gas_limit, &mut memory);
-        (
-            ret_data.error,
-            memory.slice(ret_data.offset as isize, ret_data.size),
+    let hashes = TestBlockHashProvider;
+    let block = BlockContext::new(U256::from_u64(0), &hashes);
+    let (_, report) = unsafe {
+        run_evm_with_trace(
+            bytecode,
+            &rom,
+            &schedule,
+            &block,
+            gas_limit,
+            &mut memory,
+            TraceFilter::PcCounts,
+            TraceSample::Every,
+            TRACE_DEFAULT_CAPACITY,
+        )
+    };
+    println!("profile: {} instructions executed", report.matches);
+
+    let counts = opt::pgo::block_counts(bytecode, &report.pc_counts);
+    let hot = opt::pgo::hot_blocks(&counts, hot_threshold);
+    println!(
+        "hot blocks (>= {} executions): {}",
+        hot_threshold,
+        hot.iter()
+            .map(|addr| format!("0x{:04x} ({} execs)", addr, counts[addr]))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+
+    let sites = opt::pgo::prioritized_fusion_sites(bytecode, &report.pc_counts, hot_threshold);
+    println!("fusion candidates, hottest block first:");
+    for site in &sites {
+        println!(
+            "  0x{:04x} offset 0x{:x} ({})",
+            site.addr,
+            site.offset,
+            if site.is_store { "MSTORE" } else { "MLOAD" }
+        );
+    }
+
+    let optimized = opt::optimize(bytecode, fork);
+    println!(
+        "optimize: {} -> {} bytes ({} bytes saved)",
+        bytecode.len(),
+        optimized.len(),
+        bytecode.len().saturating_sub(optimized.len())
+    );
+}
+
+/// Runs `bytecode` once under `TraceFilter::PcCounts`, mines `n`-grams from
+/// the resulting per-pc visit counts (see `opt::ngrams::count_ngrams`), and
+/// writes them as JSON to `out` -- a compressed stand-in for a full step
+/// trace, sized by distinct opcode sequences rather than steps executed.
+fn ngram_trace(bytecode: &[u8], fork: Fork, n: usize, out: &str) {
+    let schedule = Schedule::from_fork(fork);
+    let mut rom = VmRom::new();
+    rom.init(bytecode, &schedule);
+    let mut memory = VmMemory::new();
+    let gas_limit = U256::from_u64(VM_DEFAULT_GAS);
+    memory.init(gas_limit);
+    let hashes = TestBlockHashProvider;
+    let block = BlockContext::new(U256::from_u64(0), &hashes);
+    let (_, report) = unsafe {
+        run_evm_with_trace(
+            bytecode,
+            &rom,
+            &schedule,
+            &block,
+            gas_limit,
+            &mut memory,
+            TraceFilter::PcCounts,
+            TraceSample::Every,
+            TRACE_DEFAULT_CAPACITY,
+        )
+    };
+    let counts = opt::ngrams::count_ngrams(bytecode, &report.pc_counts, n);
+    match write_ngram_corpus(out, n, &counts) {
+        Ok(()) => println!("wrote {} distinct {}-grams to {}", counts.len(), n, out),
+        Err(e) => println!("{:?}", e),
+    }
+}
+
+/// Writes `counts` (see `opt::ngrams::count_ngrams`) as JSON, each entry's
+/// opcode sequence spelled out by mnemonic so the file is both
+/// human-readable and `read_ngram_corpus`'s own input format.
+fn write_ngram_corpus(path: &str, n: usize, counts: &std::collections::BTreeMap<opt::ngrams::Ngram, u64>) -> io::Result<()> {
+    let ngrams: Vec<serde_json::Value> = counts
+        .iter()
+        .map(|(sequence, count)| {
+            serde_json::json!({
+                "sequence": sequence.iter().map(|opcode| opcode.to_string()).collect::<Vec<_>>(),
+                "count": count,
+            })
+        })
+        .collect();
+    let json = serde_json::json!({ "n": n, "ngrams": ngrams });
+    fs::write(path, json.to_string())
+}
+
+/// Reads back a file `write_ngram_corpus` wrote, for `ngram_corpus` to
+/// merge across an entire directory of them.
+fn read_ngram_corpus(path: &std::path::Path) -> Result<std::collections::BTreeMap<opt::ngrams::Ngram, u64>, String> {
+    let contents = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let json: serde_json::Value = serde_json::from_str(&contents).map_err(|e| e.to_string())?;
+    let entries = json["ngrams"].as_array().ok_or("missing \"ngrams\" array")?;
+    let mut counts = std::collections::BTreeMap::new();
+    for entry in entries {
+        let sequence: opt::ngrams::Ngram = entry["sequence"]
+            .as_array()
+            .ok_or("missing \"sequence\" array")?
+            .iter()
+            .map(|name| {
+                let name = name.as_str().ok_or("sequence entry is not a string")?;
+                cli_parse::opcode_from_name(name).ok_or_else(|| format!("unknown opcode \"{}\"", name))
+            })
+            .collect::<Result<_, _>>()?;
+        let count = entry["count"].as_u64().ok_or("missing \"count\"")?;
+        counts.insert(sequence, count);
+    }
+    Ok(counts)
+}
+
+/// Merges every n-gram file in `dir` (as written by `ngram-trace`) and
+/// prints the `top_n` most frequent opcode sequences across the whole
+/// corpus -- the cross-contract ranking `opt::ngrams`' module doc comment
+/// points to as the input for deciding which fused handlers to add next.
+fn ngram_corpus(dir: &str, top_n: usize) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            println!("{:?}", e);
+            return;
+        }
+    };
+    let mut merged = std::collections::BTreeMap::new();
+    let mut files = 0usize;
+    for entry in entries {
+        let path = match entry {
+            Ok(entry) => entry.path(),
+            Err(e) => {
+                println!("{:?}", e);
+                continue;
+            }
+        };
+        if !path.is_file() {
+            continue;
+        }
+        match read_ngram_corpus(&path) {
+            Ok(counts) => {
+                opt::ngrams::merge(&mut merged, &counts);
+                files += 1;
+            }
+            Err(e) => println!("skipping {}: {}", path.display(), e),
+        }
+    }
+    println!("{} n-gram files merged, {} distinct sequences", files, merged.len());
+    for (sequence, count) in opt::ngrams::top_n(&merged, top_n) {
+        let mnemonics: Vec<String> = sequence.iter().map(|opcode| opcode.to_string()).collect();
+        println!("{:>8}  {}", count, mnemonics.join(" "));
+    }
+}
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "non-string panic payload".to_string()
+    }
+}
+
+fn hash_hex(hash: U256) -> String {
+    format!(
+        "{:016x}{:016x}{:016x}{:016x}",
+        hash.0[3], hash.0[2], hash.0[1], hash.0[0]
+    )
+}
+
+const ANALYSIS_STAGES: &[&str] = &["VmRom::init", "opt::optimize", "vm::analyze_basic_blocks"];
+
+/// Runs a single analysis `stage` over `bytecode` inside `catch_unwind`, so
+/// one malformed contract in a large real-world corpus can't abort the
+/// whole scan; returns how long the stage took, or its panic message.
+///
+/// `vm::analyze_basic_blocks` runs the same block-level analysis
+/// `VmRom::init` bakes into a fixed `MAX_CODESIZE`-sized ROM, but directly
+/// on `bytecode` with no `VmRom` allocation, which is what makes it cheap
+/// enough to run per-contract across a whole corpus in parallel below.
+fn run_analysis_stage(bytecode: &[u8], fork: Fork, stage: &'static str) -> Result<Duration, String> {
+    let bytecode = bytecode.to_vec();
+    let start = Instant::now();
+    let result = panic::catch_unwind(move || match stage {
+        "VmRom::init" => {
+            let mut rom = Box::new(VmRom::new());
+            rom.init(&bytecode, &Schedule::from_fork(fork));
+        }
+        "opt::optimize" => {
+            let _ = opt::optimize(&bytecode, fork);
+        }
+        "vm::analyze_basic_blocks" => {
+            let _ = vm::analyze_basic_blocks(&bytecode, &Schedule::from_fork(fork));
+        }
+        _ => unreachable!(),
+    });
+    result.map(|()| start.elapsed()).map_err(|payload| panic_message(&*payload))
+}
+
+/// A single analysis-stage timing, for `import_corpus --bench`'s
+/// slowest-outliers report.
+struct AnalyzerTiming {
+    path: PathBuf,
+    hash: String,
+    stage: &'static str,
+    elapsed: Duration,
+}
+
+/// A single analysis-stage panic, for `import_corpus`'s summary report.
+struct AnalyzerPanic {
+    path: PathBuf,
+    hash: String,
+    stage: &'static str,
+    message: String,
+}
+
+/// One deduplicated contract queued for analysis.
+struct CorpusEntry {
+    path: PathBuf,
+    hash: String,
+    bytecode: Vec<u8>,
+}
+
+/// One contract's analysis results, ready to be flattened into the
+/// summary report.
+#[derive(Default)]
+struct CorpusEntryReport {
+    panics: Vec<AnalyzerPanic>,
+    timings: Vec<AnalyzerTiming>,
+}
+
+fn import_corpus(dir: &str, bench: bool, top_n: usize) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            println!("{:?}", e);
+            return;
+        }
+    };
+    // Reading the directory and deduplicating by code hash stays a
+    // sequential pass: `seen` is a single shared HashSet, and file I/O
+    // dominates so little is won by parallelizing it. The analysis stages
+    // below are the actual per-contract CPU work, and are independent of
+    // each other, so that's where rayon pays off.
+    let mut seen = HashSet::new();
+    let mut total = 0usize;
+    let mut duplicates = 0usize;
+    let mut queue = Vec::new();
+    for entry in entries {
+        let path = match entry {
+            Ok(entry) => entry.path(),
+            Err(e) => {
+                println!("{:?}", e);
+                continue;
+            }
+        };
+        if !path.is_file() {
+            continue;
+        }
+        let contents = fs::read_to_string(&path).expect("Something went wrong reading the file");
+        let bytecode = match decode_hex(contents.trim()) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                println!("skipping {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        total += 1;
+        let hash = unsafe { sha3_u256(bytecode.as_ptr(), bytecode.len()) };
+        if !seen.insert(hash.0) {
+            duplicates += 1;
+            continue;
+        }
+        queue.push(CorpusEntry {
+            path,
+            hash: hash_hex(hash),
+            bytecode,
+        });
+    }
+    // Analysis stages are expected to panic on malformed real-world
+    // contracts here and there; the default hook's stderr spam would drown
+    // out the summary below, so silence it for the scan and restore it
+    // once we're done reporting through `panics` instead.
+    let previous_hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let reports: Vec<CorpusEntryReport> = queue
+        .par_iter()
+        .map(|entry| {
+            let mut report = CorpusEntryReport::default();
+            for &stage in ANALYSIS_STAGES {
+                match run_analysis_stage(&entry.bytecode, Fork::default(), stage) {
+                    Ok(elapsed) if bench => report.timings.push(AnalyzerTiming {
+                        path: entry.path.clone(),
+                        hash: entry.hash.clone(),
+                        stage,
+                        elapsed,
+                    }),
+                    Ok(_) => (),
+                    Err(message) => report.panics.push(AnalyzerPanic {
+                        path: entry.path.clone(),
+                        hash: entry.hash.clone(),
+                        stage,
+                        message,
+                    }),
+                }
+            }
+            report
+        })
+        .collect();
+    panic::set_hook(previous_hook);
+    let mut panics = Vec::new();
+    let mut timings = Vec::new();
+    for report in reports {
+        panics.extend(report.panics);
+        timings.extend(report.timings);
+    }
+    println!(
+        "{} contracts scanned, {} unique, {} duplicates skipped",
+        total,
+        total - duplicates,
+        duplicates
+    );
+    if panics.is_empty() {
+        println!("no analyzer panics");
+    } else {
+        println!("{} analyzer panics:", panics.len());
+        for p in &panics {
+            println!("  {} ({}) in {}: {}", p.path.display(), p.hash, p.stage, p.message);
+        }
+    }
+    if bench {
+        timings.sort_by(|a, b| b.elapsed.cmp(&a.elapsed));
+        println!("slowest {} analysis outliers:", top_n.min(timings.len()));
+        for t in timings.iter().take(top_n) {
+            println!("  {:?} {} ({}) in {}", t.elapsed, t.path.display(), t.hash, t.stage);
+        }
+    }
+}
+
+fn flamegraph(bytes: &[u8], fork: Fork, source_map: Option<&str>, sources: Option<&str>) {
+    let schedule = Schedule::from_fork(fork);
+    let profile = match source_map {
+        Some(map) => {
+            let entries = match sourcemap::parse(map) {
+                Ok(entries) => entries,
+                Err(e) => {
+                    println!("Invalid --source-map: {}", e);
+                    return;
+                }
+            };
+            let mut source_files = Vec::new();
+            if let Some(paths) = sources {
+                for path in paths.split(',') {
+                    match fs::read_to_string(path) {
+                        Ok(contents) => source_files.push(contents),
+                        Err(e) => {
+                            println!("{}: {}", path, e);
+                            return;
+                        }
+                    }
+                }
+            }
+            profiler::profile_with_source_map(bytes, &schedule, &entries, &source_files)
+        }
+        None => profiler::profile_from_dispatcher(bytes, &schedule),
+    };
+    print!("{}", profile.format_folded());
+}
+
+fn kick(filename: &str, fork: Fork) {
+    let code = fs::read_to_string(filename).expect("Something went wrong reading the file");
+    let result = assembler::from_string(&code);
+    match result {
+        Ok(bytes) => evm(
+            &bytes,
+            fork,
+            U256::from_u64(VM_DEFAULT_GAS),
+            U256::from_u64(0),
+            U256::from_u64(0),
+            U256::from_u64(0),
+            U256::from_u64(0),
+            U256::from_u64(0),
+            ExecutionStrategy::default(),
+            &[],
+            None,
+        ),
+        Err(e) => {
+            let code = e.code();
+            println!("{} ({})", code, code.code())
+        }
+    }
+}
+
+fn main() {
+    let app = App::new("Psyche")
+        .subcommand(
+            SubCommand::with_name("evm")
+                .about("Run EVM bytecode")
+                .arg(
+                    Arg::with_name("CODE")
+                        .index(1)
+                        .required_unless("ARTIFACT")
+                        .help("Contract code as hex (0x prefix and whitespace are tolerated), or - to read from stdin"),
+                )
+                .arg(
+                    Arg::with_name("ARTIFACT")
+                        .long("artifact")
+                        .takes_value(true)
+                        .help("Path to a Solidity/Foundry build artifact JSON to extract deployedBytecode from"),
+                )
+                .arg(
+                    Arg::with_name("GAS")
+                        .takes_value(true)
+                        .short("g")
+                        .long("gas")
+                        .help("Supplied gas, as decimal, 0x-prefixed hex, or either with _ digit separators (e.g. 20_000_000)"),
+                )
+                .arg(
+                    Arg::with_name("GAS_PRICE")
+                        .long("gas-price")
+                        .takes_value(true)
+                        .help("Legacy (pre-London) gas price for the execution context, in the same decimal/hex/underscore form as --gas"),
+                )
+                .arg(
+                    Arg::with_name("VALUE")
+                        .long("value")
+                        .takes_value(true)
+                        .help("Call value for the execution context, in the same decimal/hex/underscore form as --gas (inert until CALLVALUE is implemented)"),
+                )
+                .arg(
+                    Arg::with_name("MAX_FEE_PER_GAS")
+                        .long("max-fee-per-gas")
+                        .takes_value(true)
+                        .help("EIP-1559 max fee per gas, in the same decimal/hex/underscore form as --gas (ignored below the London fork)"),
+                )
+                .arg(
+                    Arg::with_name("MAX_PRIORITY_FEE_PER_GAS")
+                        .long("max-priority-fee-per-gas")
+                        .takes_value(true)
+                        .help("EIP-1559 max priority fee per gas, in the same decimal/hex/underscore form as --gas (ignored below the London fork)"),
+                )
+                .arg(
+                    Arg::with_name("BASE_FEE")
+                        .long("base-fee")
+                        .takes_value(true)
+                        .help("Block base fee, in the same decimal/hex/underscore form as --gas (ignored below the London fork)"),
+                )
+                .arg(
+                    Arg::with_name("STRATEGY")
+                        .long("strategy")
+                        .takes_value(true)
+                        .help("Execution backend to try before falling back to the interpreter")
+                        .possible_values(&["interpreter", "jit-x86", "jit-portable"]),
+                )
+                .arg(
+                    Arg::with_name("BREAK_AT")
+                        .long("break-at")
+                        .takes_value(true)
+                        .conflicts_with_all(&["BREAK_ON", "WATCH_MEMORY"])
+                        .help("Stop at the given pc (decimal or 0x-prefixed hex), dump VM state as JSON, and exit"),
+                )
+                .arg(
+                    Arg::with_name("BREAK_ON")
+                        .long("break-on")
+                        .takes_value(true)
+                        .conflicts_with("WATCH_MEMORY")
+                        .help("Stop at the first occurrence of the given opcode (e.g. JUMPI), dump VM state as JSON, and exit"),
+                )
+                .arg(
+                    Arg::with_name("WATCH_MEMORY")
+                        .long("watch-memory")
+                        .takes_value(true)
+                        .conflicts_with("TRACE")
+                        .help("Stop on the first MSTORE/MSTORE8 that writes into the given byte range START-END (decimal or 0x-prefixed hex), dump VM state as JSON, and exit"),
+                )
+                .arg(
+                    Arg::with_name("TRACE")
+                        .long("trace")
+                        .takes_value(true)
+                        .conflicts_with_all(&["BREAK_AT", "BREAK_ON"])
+                        .help("Run to completion tracing every step with the given filter (count:OPCODE, stack-top:OPCODE, or return-stack:OPCODE, e.g. count:SLOAD, stack-top:JUMPI, or return-stack:RETURNSUB), and dump the trace report as JSON"),
+                )
+                .arg(
+                    Arg::with_name("TRACE_SAMPLE")
+                        .long("trace-sample")
+                        .takes_value(true)
+                        .requires("TRACE")
+                        .conflicts_with("TRACE_OPCODES")
+                        .help("Bound --trace's overhead to every Nth step (e.g. 100), or to basic-block entry points only (block)"),
+                )
+                .arg(
+                    Arg::with_name("TRACE_OPCODES")
+                        .long("trace-opcodes")
+                        .takes_value(true)
+                        .requires("TRACE")
+                        .help("Bound --trace's overhead to steps on the given comma-separated opcodes (e.g. SSTORE,CALL)"),
+                )
+                .arg(
+                    Arg::with_name("GAS_OVERRIDE")
+                        .long("gas-override")
+                        .takes_value(true)
+                        .multiple(true)
+                        .help("Overrides an opcode's gas cost as OPCODE=COST (e.g. SLOAD=500); may be given more than once"),
+                )
+                .arg(
+                    Arg::with_name("REPEAT")
+                        .long("repeat")
+                        .takes_value(true)
+                        .conflicts_with_all(&["BREAK_AT", "BREAK_ON", "WATCH_MEMORY", "TRACE"])
+                        .help("Analyze the code once, then run it N times reusing the ROM and memory, reporting per-run and aggregate timing"),
+                )
+                .arg(
+                    Arg::with_name("MAX_MEMORY")
+                        .long("max-memory")
+                        .takes_value(true)
+                        .help("Caps the memory mapping at this many bytes, in the same decimal/hex/underscore form as --gas, independent of --gas; expansions past the cap report out-of-gas instead of mapping more"),
+                )
+                .arg(
+                    Arg::with_name("OPTIMIZE")
+                        .long("optimize")
+                        .help("Run opt::optimize's output instead of the given bytecode; --break-at/--break-on/--watch-memory and --trace report pcs translated back to the original bytecode's offsets (see opt::PcMap)"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("deploy")
+                .about("Run creation bytecode and report the deployed runtime code and gas")
+                .arg(
+                    Arg::with_name("CODE")
+                        .index(1)
+                        .required_unless("ARTIFACT")
+                        .help("Creation code as hex (0x prefix and whitespace are tolerated), or - to read from stdin"),
+                )
+                .arg(
+                    Arg::with_name("ARTIFACT")
+                        .long("artifact")
+                        .takes_value(true)
+                        .help("Path to a Solidity/Foundry build artifact JSON to extract bytecode (the creation code) from"),
+                )
+                .arg(
+                    Arg::with_name("GAS")
+                        .takes_value(true)
+                        .short("g")
+                        .long("gas")
+                        .help("Supplied gas as decimal"),
+                )
+                .arg(
+                    Arg::with_name("STRATEGY")
+                        .long("strategy")
+                        .takes_value(true)
+                        .help("Execution backend to try before falling back to the interpreter")
+                        .possible_values(&["interpreter", "jit-x86", "jit-portable"]),
+                )
+                .arg(
+                    Arg::with_name("RUN")
+                        .long("run")
+                        .help("Continue by executing the freshly deployed runtime code"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("asm")
+                .about("Assemble EVM bytecode")
+                .arg(
+                    Arg::with_name("INPUT")
+                        .index(1)
+                        .required(true)
+                        .help("The .ass file to assemble"),
+                )
+                .arg(
+                    Arg::with_name("FORMAT")
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(&["hex", "rust", "json"])
+                        .help("Output format for the assembled bytecode (default: hex)"),
+                )
+                .arg(
+                    Arg::with_name("OUT")
+                        .long("out")
+                        .takes_value(true)
+                        .help("Also write the raw assembled bytes to this file"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("kick")
+                .about("Assemble EVM bytecode and run it")
+                .arg(
+                    Arg::with_name("INPUT")
+                        .index(1)
+                        .required(true)
+                        .help("The .ass file to assemble and run"),
+                )
+                .arg(
+                    Arg::with_name("FORK")
+                        .long("fork")
+                        .short("f")
+                        .help("Fork you want to run on")
+                        .takes_value(true)
+                        .possible_values(&[
+                            "Frontier",
+                            "Thawing",
+                            "Homestead",
+                            "Dao",
+                            "Tangerine",
+                            "Spurious",
+                            "Byzantium",
+                            "Constantinople",
+                            "Istanbul",
+                            "Berlin",
+                            "London",
+                            "Paris",
+                            "Shanghai",
+                            "Cancun",
+                            "Prague",
+                        ]),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("disasm")
+                .about("Disassemble EVM bytecode")
+                .arg(
+                    Arg::with_name("CODE")
+                        .index(1)
+                        .required(true)
+                        .help("Contract code as hex (0x prefix and whitespace are tolerated)"),
+                )
+                .arg(
+                    Arg::with_name("SOURCE_MAP")
+                        .long("source-map")
+                        .takes_value(true)
+                        .help("solc's compressed source map (s:l:f:j) to annotate each instruction with"),
+                )
+                .arg(
+                    Arg::with_name("SOURCES")
+                        .long("sources")
+                        .takes_value(true)
+                        .requires("SOURCE_MAP")
+                        .help("Comma-separated source file paths, in the compiler's file-index order, to resolve --source-map positions to line numbers"),
+                )
+                .arg(
+                    Arg::with_name("SELECTORS")
+                        .long("selectors")
+                        .takes_value(true)
+                        .help("JSON file mapping 4-byte selectors to signatures (flat map or openchain API response) to label dispatcher branches"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("compile")
+                .about("Run analysis/optimization ahead of time and cache the artifact")
+                .arg(
+                    Arg::with_name("CODE")
+                        .index(1)
+                        .required(true)
+                        .help("Contract code as hex (0x prefix and whitespace are tolerated)"),
+                )
+                .arg(
+                    Arg::with_name("FORK")
+                        .long("fork")
+                        .short("f")
+                        .help("Fork to optimize for")
+                        .takes_value(true)
+                        .possible_values(&[
+                            "Frontier",
+                            "Thawing",
+                            "Homestead",
+                            "Dao",
+                            "Tangerine",
+                            "Spurious",
+                            "Byzantium",
+                            "Constantinople",
+                            "Istanbul",
+                            "Berlin",
+                            "London",
+                            "Paris",
+                            "Shanghai",
+                            "Cancun",
+                            "Prague",
+                        ]),
+                )
+                .arg(
+                    Arg::with_name("OUT")
+                        .long("out")
+                        .short("o")
+                        .takes_value(true)
+                        .help("Directory to write the cached artifact into (default: .)"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("exec-artifact")
+                .about("Run a cached compile artifact")
+                .arg(
+                    Arg::with_name("INPUT")
+                        .index(1)
+                        .required(true)
+                        .help("Path to a .psc artifact written by `compile`"),
+                )
+                .arg(
+                    Arg::with_name("GAS")
+                        .takes_value(true)
+                        .short("g")
+                        .long("gas")
+                        .help("Supplied gas as decimal"),
+                )
+                .arg(
+                    Arg::with_name("STRATEGY")
+                        .long("strategy")
+                        .takes_value(true)
+                        .help("Execution backend to try before falling back to the interpreter")
+                        .possible_values(&["interpreter", "jit-x86", "jit-portable"]),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("profile")
+                .about("Aggregate opcode digram/trigram frequencies over a corpus of contracts and propose fused handlers")
+                .arg(
+                    Arg::with_name("CORPUS")
+                        .index(1)
+                        .required(true)
+                        .help("Directory of files, each holding one contract's code as hex (without 0x)"),
+                )
+                .arg(
+                    Arg::with_name("TOP")
+                        .long("top")
+                        .short("n")
+                        .takes_value(true)
+                        .help("Number of top candidates to report (default: 10)"),
+                )
+                .arg(
+                    Arg::with_name("EMIT_ARMS")
+                        .long("emit-arms")
+                        .help("Emit match arm skeletons for opt.rs instead of a plain report"),
+                )
+                .arg(
+                    Arg::with_name("GAS_CSV")
+                        .long("gas-csv")
+                        .takes_value(true)
+                        .help("Emit a per-block static gas/instruction-count CSV for the given fork instead of the digram report")
+                        .possible_values(&[
+                            "Frontier",
+                            "Thawing",
+                            "Homestead",
+                            "Dao",
+                            "Tangerine",
+                            "Spurious",
+                            "Byzantium",
+                            "Constantinople",
+                            "Istanbul",
+                            "Berlin",
+                            "London",
+                            "Paris",
+                            "Shanghai",
+                            "Cancun",
+                            "Prague",
+                        ]),
+                )
+                .arg(
+                    Arg::with_name("GAS_LOWER_BOUND")
+                        .long("gas-lower-bound")
+                        .takes_value(true)
+                        .help("Emit a per-contract static gas lower bound CSV for the given fork instead of the digram report")
+                        .possible_values(&[
+                            "Frontier",
+                            "Thawing",
+                            "Homestead",
+                            "Dao",
+                            "Tangerine",
+                            "Spurious",
+                            "Byzantium",
+                            "Constantinople",
+                            "Istanbul",
+                            "Berlin",
+                            "London",
+                            "Paris",
+                            "Shanghai",
+                            "Cancun",
+                            "Prague",
+                        ]),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("gas-ledger")
+                .about("Print a per-instruction (pc, opcode, gas_cost, cumulative_gas) CSV for a contract")
+                .arg(
+                    Arg::with_name("CODE")
+                        .index(1)
+                        .required(true)
+                        .help("Contract code as hex (0x prefix and whitespace are tolerated)"),
+                )
+                .arg(
+                    Arg::with_name("FORK")
+                        .long("fork")
+                        .takes_value(true)
+                        .help("Fork whose gas schedule to price instructions against (default: Frontier)")
+                        .possible_values(&[
+                            "Frontier",
+                            "Thawing",
+                            "Homestead",
+                            "Dao",
+                            "Tangerine",
+                            "Spurious",
+                            "Byzantium",
+                            "Constantinople",
+                            "Istanbul",
+                            "Berlin",
+                            "London",
+                            "Paris",
+                            "Shanghai",
+                            "Cancun",
+                            "Prague",
+                        ]),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("golf")
+                .about("Report code size, deployment cost, JUMPDESTs, static gas lower bound, largest gas blocks, and optimizer savings for a contract")
+                .arg(
+                    Arg::with_name("CODE")
+                        .index(1)
+                        .required(true)
+                        .help("Contract code as hex (0x prefix and whitespace are tolerated)"),
+                )
+                .arg(
+                    Arg::with_name("FORK")
+                        .long("fork")
+                        .takes_value(true)
+                        .help("Fork whose gas schedule and optimizer rules to use (default: Frontier)")
+                        .possible_values(&[
+                            "Frontier",
+                            "Thawing",
+                            "Homestead",
+                            "Dao",
+                            "Tangerine",
+                            "Spurious",
+                            "Byzantium",
+                            "Constantinople",
+                            "Istanbul",
+                            "Berlin",
+                            "London",
+                            "Paris",
+                            "Shanghai",
+                            "Cancun",
+                            "Prague",
+                        ]),
+                )
+                .arg(
+                    Arg::with_name("TOP")
+                        .long("top")
+                        .short("n")
+                        .takes_value(true)
+                        .help("Number of largest gas blocks and fusable-pattern candidates to report (default: 10)"),
+                ),
         )
-    };
-    if err != VmError::None {
-        println!("{:?}", err);
-        return;
-    }
-    let mut buffer = String::with_capacity(512);
-    for byte in slice {
-        let _ = write!(buffer, "{:02x}", byte);
-    }
-    println!("0x{:}", buffer);
-}
-
-fn asm(filename: &str) {
-    let code = fs::read_to_string(filename).expect("Something went wrong reading the file");
-    let result = assembler::from_string(&code);
-    match result {
-        Ok(v) => println!("{}", encode_hex(&v)),
-        Err(e) => println!("{:?}", e),
-    }
-}
-
-fn kick(filename: &str, fork: Fork) {
-    let code = fs::read_to_string(filename).expect("Something went wrong reading the file");
-    let result = assembler::from_string(&code);
-    match result {
-        Ok(bytes) => evm(&bytes, fork, U256::from_u64(VM_DEFAULT_GAS)),
-        Err(e) => println!("{:?}", e),
-    }
-}
-
-fn main() {
-    let matches = App::new("Psyche")
         .subcommand(
-            SubCommand::with_name("evm")
-                .about("Run EVM bytecode")
+            SubCommand::with_name("pgo")
+                .about("Run opt::pgo's profile -> optimize -> run loop: record per-block execution counts for a contract and rank optimizer fusion candidates by hotness")
                 .arg(
                     Arg::with_name("CODE")
                         .index(1)
                         .required(true)
-                        .help("Contract code as hex (without 0x)"),
+                        .help("Contract code as hex (0x prefix and whitespace are tolerated)"),
                 )
                 .arg(
-                    Arg::with_name("GAS")
+                    Arg::with_name("FORK")
+                        .long("fork")
                         .takes_value(true)
-                        .short("g")
-                        .long("gas")
-                        .help("Supplied gas as decimal"),
+                        .help("Fork whose gas schedule and optimizer rules to use (default: Frontier)")
+                        .possible_values(&[
+                            "Frontier",
+                            "Thawing",
+                            "Homestead",
+                            "Dao",
+                            "Tangerine",
+                            "Spurious",
+                            "Byzantium",
+                            "Constantinople",
+                            "Istanbul",
+                            "Berlin",
+                            "London",
+                            "Paris",
+                            "Shanghai",
+                            "Cancun",
+                            "Prague",
+                        ]),
+                )
+                .arg(
+                    Arg::with_name("HOT_THRESHOLD")
+                        .long("hot-threshold")
+                        .takes_value(true)
+                        .help("Minimum execution count for a block to be considered hot (default: opt::pgo::DEFAULT_HOT_THRESHOLD)"),
                 ),
         )
         .subcommand(
-            SubCommand::with_name("asm")
-                .about("Assemble EVM bytecode")
+            SubCommand::with_name("corpus")
+                .about("Import a directory of real contract bytecodes, dedupe by code hash, and run the analyzer over each to surface panics and slow outliers")
                 .arg(
-                    Arg::with_name("INPUT")
+                    Arg::with_name("CORPUS")
                         .index(1)
                         .required(true)
-                        .help("The .ass file to assemble"),
+                        .help("Directory of files, each holding one contract's code as hex (without 0x)"),
+                )
+                .arg(
+                    Arg::with_name("BENCH")
+                        .long("bench")
+                        .help("Also time VmRom::init and opt::optimize per contract and report the slowest outliers"),
+                )
+                .arg(
+                    Arg::with_name("TOP")
+                        .long("top")
+                        .short("n")
+                        .takes_value(true)
+                        .help("Number of slowest outliers to report with --bench (default: 10)"),
                 ),
         )
         .subcommand(
-            SubCommand::with_name("kick")
-                .about("Assemble EVM bytecode and run it")
+            SubCommand::with_name("ngram-trace")
+                .about("Run CODE once, mine its executed opcode n-grams (see opt::ngrams), and write them as JSON -- a compressed alternative to a full step trace")
                 .arg(
-                    Arg::with_name("INPUT")
+                    Arg::with_name("CODE")
                         .index(1)
                         .required(true)
-                        .help("The .ass file to assemble and run"),
+                        .help("Contract code as hex (0x prefix and whitespace are tolerated)"),
                 )
                 .arg(
                     Arg::with_name("FORK")
                         .long("fork")
-                        .short("f")
-                        .help("Fork you want to run on")
                         .takes_value(true)
+                        .help("Fork whose gas schedule to use (default: Frontier)")
                         .possible_values(&[
                             "Frontier",
                             "Thawing",
@@ -240,17 +2422,157 @@ fn main() {
                             "Constantinople",
                             "Istanbul",
                             "Berlin",
+                            "London",
+                            "Paris",
+                            "Shanghai",
+                            "Cancun",
+                            "Prague",
                         ]),
+                )
+                .arg(
+                    Arg::with_name("SIZE")
+                        .long("size")
+                        .takes_value(true)
+                        .help("Opcode n-gram length (default: 3)"),
+                )
+                .arg(
+                    Arg::with_name("OUT")
+                        .long("out")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Path to write the n-gram JSON corpus to"),
                 ),
         )
         .subcommand(
-            SubCommand::with_name("disasm")
-                .about("Disassemble EVM bytecode")
+            SubCommand::with_name("ngram-corpus")
+                .about("Merge a directory of ngram-trace JSON files and rank the most frequent opcode sequences across the whole corpus")
+                .arg(
+                    Arg::with_name("CORPUS")
+                        .index(1)
+                        .required(true)
+                        .help("Directory of JSON files written by ngram-trace"),
+                )
+                .arg(
+                    Arg::with_name("TOP")
+                        .long("top")
+                        .short("n")
+                        .takes_value(true)
+                        .help("Number of most frequent n-grams to report (default: 10)"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("flamegraph")
+                .about("Attribute static gas costs to Solidity functions, in folded-stack format")
+                .arg(
+                    Arg::with_name("CODE")
+                        .index(1)
+                        .required_unless("ARTIFACT")
+                        .help("Contract code as hex (0x prefix and whitespace are tolerated)"),
+                )
+                .arg(
+                    Arg::with_name("ARTIFACT")
+                        .long("artifact")
+                        .takes_value(true)
+                        .help("Path to a .psc artifact written by `compile`, used instead of CODE"),
+                )
+                .arg(
+                    Arg::with_name("SOURCE_MAP")
+                        .long("source-map")
+                        .takes_value(true)
+                        .help("solc's compressed source map (s:l:f:j); attributes gas to nested source frames instead of dispatcher-detected functions"),
+                )
+                .arg(
+                    Arg::with_name("SOURCES")
+                        .long("sources")
+                        .takes_value(true)
+                        .requires("SOURCE_MAP")
+                        .help("Comma-separated source file paths, in the compiler's file-index order, to resolve --source-map positions to line numbers"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("bench-op")
+                .about("Microbenchmark a single opcode's handler in a tight synthetic loop")
+                .arg(
+                    Arg::with_name("OPCODE")
+                        .index(1)
+                        .required(true)
+                        .help("Mnemonic of the opcode to benchmark (e.g. ADD); restricted to opcodes with no side effects and a fixed stack arity"),
+                )
+                .arg(
+                    Arg::with_name("ITERATIONS")
+                        .long("iterations")
+                        .short("n")
+                        .takes_value(true)
+                        .help("Number of times to execute the opcode (default: 10000000)"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("bench-trace")
+                .about("Microbenchmark the tracer's per-step overhead against a matching CountOpcode filter")
+                .arg(
+                    Arg::with_name("ITERATIONS")
+                        .long("iterations")
+                        .short("n")
+                        .takes_value(true)
+                        .help("Number of loop iterations to run, traced and untraced (default: 10000000)"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("bench-jumpi")
+                .about("Microbenchmark a tight loop of JUMPIs that almost always fall through")
+                .arg(
+                    Arg::with_name("ITERATIONS")
+                        .long("iterations")
+                        .short("n")
+                        .takes_value(true)
+                        .help("Number of loop iterations to run (default: 10000000)"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("bench-compare")
+                .about("Benchmark a contract's Mgas/s against an external EVM CLI (geth evm, evmone-bench)")
                 .arg(
                     Arg::with_name("CODE")
                         .index(1)
                         .required(true)
-                        .help("Contract code as hex (without 0x)"),
+                        .help("Contract code as hex (0x prefix and whitespace are tolerated)"),
+                )
+                .arg(
+                    Arg::with_name("COMPARE_CMD")
+                        .long("compare-cmd")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Shell command that benchmarks the same code externally; sees it via $PSYCHE_BENCH_CODE and the iteration count via $PSYCHE_BENCH_ITERATIONS"),
+                )
+                .arg(
+                    Arg::with_name("FORK")
+                        .long("fork")
+                        .takes_value(true)
+                        .help("Fork whose gas schedule to use (default: Frontier)")
+                        .possible_values(&[
+                            "Frontier",
+                            "Thawing",
+                            "Homestead",
+                            "Dao",
+                            "Tangerine",
+                            "Spurious",
+                            "Byzantium",
+                            "Constantinople",
+                            "Istanbul",
+                            "Berlin",
+                            "London",
+                            "Paris",
+                            "Shanghai",
+                            "Cancun",
+                            "Prague",
+                        ]),
+                )
+                .arg(
+                    Arg::with_name("ITERATIONS")
+                        .long("iterations")
+                        .short("n")
+                        .takes_value(true)
+                        .help("Number of times to run the code on each side (default: 1000)"),
                 ),
         )
         .arg(
@@ -259,13 +2581,259 @@ fn main() {
                 .long("verbose")
                 .multiple(true)
                 .help("Sets verbose output"),
-        )
-        .get_matches();
+        );
+    #[cfg(feature = "soa-rom")]
+    let app = app.subcommand(
+        SubCommand::with_name("bench-rom")
+            .about("Microbenchmark VmRom's default layout against the soa-rom feature's SoaRom prototype on a synthetic multi-block contract")
+            .arg(
+                Arg::with_name("BLOCKS")
+                    .long("blocks")
+                    .takes_value(true)
+                    .help("Number of JUMPDEST-headed blocks in the synthetic contract (default: 1000)"),
+            )
+            .arg(
+                Arg::with_name("ITERATIONS")
+                    .long("iterations")
+                    .short("n")
+                    .takes_value(true)
+                    .help("Number of full address sweeps to run (default: 1000)"),
+            ),
+    );
+    let matches = app.get_matches();
 
     if matches.is_present("verbose") {
         print_config();
     }
     if let Some(matches) = matches.subcommand_matches("evm") {
+        let mut gas = U256::from_u64(VM_DEFAULT_GAS);
+        if let Some(value) = matches.value_of("GAS") {
+            match cli_parse::parse_u256(value) {
+                Ok(temp) => gas = temp,
+                Err(err) => {
+                    println!("Invalid --gas: {}", err);
+                    return;
+                }
+            }
+        }
+        let mut gas_price = U256::from_u64(0);
+        if let Some(value) = matches.value_of("GAS_PRICE") {
+            match cli_parse::parse_u256(value) {
+                Ok(temp) => gas_price = temp,
+                Err(err) => {
+                    println!("Invalid --gas-price: {}", err);
+                    return;
+                }
+            }
+        }
+        let mut call_value = U256::from_u64(0);
+        if let Some(value) = matches.value_of("VALUE") {
+            match cli_parse::parse_u256(value) {
+                Ok(temp) => call_value = temp,
+                Err(err) => {
+                    println!("Invalid --value: {}", err);
+                    return;
+                }
+            }
+        }
+        let mut max_fee_per_gas = U256::from_u64(0);
+        if let Some(value) = matches.value_of("MAX_FEE_PER_GAS") {
+            match cli_parse::parse_u256(value) {
+                Ok(temp) => max_fee_per_gas = temp,
+                Err(err) => {
+                    println!("Invalid --max-fee-per-gas: {}", err);
+                    return;
+                }
+            }
+        }
+        let mut max_priority_fee_per_gas = U256::from_u64(0);
+        if let Some(value) = matches.value_of("MAX_PRIORITY_FEE_PER_GAS") {
+            match cli_parse::parse_u256(value) {
+                Ok(temp) => max_priority_fee_per_gas = temp,
+                Err(err) => {
+                    println!("Invalid --max-priority-fee-per-gas: {}", err);
+                    return;
+                }
+            }
+        }
+        let mut base_fee = U256::from_u64(0);
+        if let Some(value) = matches.value_of("BASE_FEE") {
+            match cli_parse::parse_u256(value) {
+                Ok(temp) => base_fee = temp,
+                Err(err) => {
+                    println!("Invalid --base-fee: {}", err);
+                    return;
+                }
+            }
+        }
+        let strategy = matches
+            .value_of("STRATEGY")
+            .map(|s| ExecutionStrategy::from_str(s).unwrap())
+            .unwrap_or(ExecutionStrategy::default());
+        let breakpoint = if let Some(value) = matches.value_of("BREAK_AT") {
+            match cli_parse::parse_pc(value) {
+                Ok(pc) => Some(Breakpoint::Pc(pc)),
+                Err(err) => {
+                    println!("Invalid --break-at: {}", err);
+                    return;
+                }
+            }
+        } else if let Some(value) = matches.value_of("BREAK_ON") {
+            match cli_parse::opcode_from_name(value) {
+                Some(opcode) => Some(Breakpoint::Opcode(opcode)),
+                None => {
+                    println!("Invalid --break-on: unknown opcode {}", value);
+                    return;
+                }
+            }
+        } else if let Some(value) = matches.value_of("WATCH_MEMORY") {
+            match cli_parse::parse_memory_range(value) {
+                Ok((start, end)) => Some(Breakpoint::MemoryWrite { start, end }),
+                Err(err) => {
+                    println!("Invalid --watch-memory: {}", err);
+                    return;
+                }
+            }
+        } else {
+            None
+        };
+        let trace = match matches.value_of("TRACE") {
+            Some(value) => match cli_parse::parse_trace_filter(value) {
+                Ok(filter) => Some(filter),
+                Err(err) => {
+                    println!("Invalid --trace: {}", err);
+                    return;
+                }
+            },
+            None => None,
+        };
+        let trace_sample = if let Some(value) = matches.value_of("TRACE_SAMPLE") {
+            match cli_parse::parse_trace_sample(value) {
+                Ok(sample) => sample,
+                Err(err) => {
+                    println!("Invalid --trace-sample: {}", err);
+                    return;
+                }
+            }
+        } else if let Some(value) = matches.value_of("TRACE_OPCODES") {
+            match cli_parse::parse_trace_opcodes(value) {
+                Ok(sample) => sample,
+                Err(err) => {
+                    println!("Invalid --trace-opcodes: {}", err);
+                    return;
+                }
+            }
+        } else {
+            TraceSample::Every
+        };
+        let mut overrides = Vec::new();
+        if let Some(values) = matches.values_of("GAS_OVERRIDE") {
+            for value in values {
+                match cli_parse::parse_gas_override(value) {
+                    Ok(override_) => overrides.push(override_),
+                    Err(err) => {
+                        println!("Invalid --gas-override: {}", err);
+                        return;
+                    }
+                }
+            }
+        }
+        let repeat = match matches.value_of("REPEAT") {
+            Some(value) => match value.parse::<usize>() {
+                Ok(repeat) if repeat > 0 => Some(repeat),
+                _ => {
+                    println!("Invalid --repeat: expected a positive integer");
+                    return;
+                }
+            },
+            None => None,
+        };
+        let max_memory = if let Some(value) = matches.value_of("MAX_MEMORY") {
+            match cli_parse::parse_u256(value) {
+                Ok(temp) => Some(temp.low_u64()),
+                Err(err) => {
+                    println!("Invalid --max-memory: {}", err);
+                    return;
+                }
+            }
+        } else {
+            None
+        };
+        match read_code_input(matches.value_of("CODE"), matches.value_of("ARTIFACT"))
+            .map_err(|e| e.to_string())
+            .and_then(|bytes| unwrap_eof(bytes, Fork::Frontier))
+        {
+            Ok(bytes) => {
+                let (bytes, pc_map) = if matches.is_present("OPTIMIZE") {
+                    let (optimized, pc_map) = opt::optimize_with_pc_map(&bytes, Fork::Frontier);
+                    (optimized, Some(pc_map))
+                } else {
+                    (bytes, None)
+                };
+                match (breakpoint, trace, repeat) {
+                (Some(breakpoint), _, _) => evm_break(
+                    &bytes,
+                    Fork::Frontier,
+                    gas,
+                    gas_price,
+                    call_value,
+                    max_fee_per_gas,
+                    max_priority_fee_per_gas,
+                    base_fee,
+                    breakpoint,
+                    &overrides,
+                    max_memory,
+                    pc_map.as_ref(),
+                ),
+                (None, Some(filter), _) => evm_trace(
+                    &bytes,
+                    Fork::Frontier,
+                    gas,
+                    gas_price,
+                    call_value,
+                    max_fee_per_gas,
+                    max_priority_fee_per_gas,
+                    base_fee,
+                    filter,
+                    trace_sample,
+                    &overrides,
+                    TRACE_DEFAULT_CAPACITY,
+                    max_memory,
+                    pc_map.as_ref(),
+                ),
+                (None, None, Some(repeat)) => evm_repeat(
+                    &bytes,
+                    Fork::Frontier,
+                    gas,
+                    gas_price,
+                    call_value,
+                    max_fee_per_gas,
+                    max_priority_fee_per_gas,
+                    base_fee,
+                    &overrides,
+                    repeat,
+                    max_memory,
+                ),
+                (None, None, None) => evm(
+                    &bytes,
+                    Fork::Frontier,
+                    gas,
+                    gas_price,
+                    call_value,
+                    max_fee_per_gas,
+                    max_priority_fee_per_gas,
+                    base_fee,
+                    strategy,
+                    &overrides,
+                    max_memory,
+                ),
+                }
+            }
+            Err(e) => println!("{}", e),
+        }
+        return;
+    }
+    if let Some(matches) = matches.subcommand_matches("deploy") {
         let mut gas = U256::from_u64(VM_DEFAULT_GAS);
         if let Some(value) = matches.value_of("GAS") {
             match U256::from_dec_str(value) {
@@ -273,16 +2841,25 @@ fn main() {
                 Err(err) => println!("Invalid --gas: {:?}", err),
             }
         }
-        let hex_str = matches.value_of("CODE").unwrap();
-        match decode_hex(hex_str) {
-            Ok(bytes) => evm(&bytes, Fork::Frontier, gas),
-            Err(e) => println!("{:?}", e),
+        let strategy = matches
+            .value_of("STRATEGY")
+            .map(|s| ExecutionStrategy::from_str(s).unwrap())
+            .unwrap_or(ExecutionStrategy::default());
+        let run_deployed = matches.is_present("RUN");
+        match read_creation_code_input(matches.value_of("CODE"), matches.value_of("ARTIFACT"))
+            .map_err(|e| e.to_string())
+            .and_then(|bytes| unwrap_eof(bytes, Fork::Frontier))
+        {
+            Ok(bytes) => deploy(&bytes, Fork::Frontier, gas, strategy, run_deployed),
+            Err(e) => println!("{}", e),
         }
         return;
     }
     if let Some(matches) = matches.subcommand_matches("asm") {
         let filename = matches.value_of("INPUT").unwrap();
-        asm(filename);
+        let format = matches.value_of("FORMAT").unwrap_or("hex");
+        let out = matches.value_of("OUT");
+        asm(filename, format, out);
         return;
     }
     if let Some(matches) = matches.subcommand_matches("kick") {
@@ -293,7 +2870,248 @@ fn main() {
     }
     if let Some(matches) = matches.subcommand_matches("disasm") {
         let code = matches.value_of("CODE").unwrap();
-        disasm(code);
+        disasm(
+            code,
+            matches.value_of("SOURCE_MAP"),
+            matches.value_of("SOURCES"),
+            matches.value_of("SELECTORS"),
+        );
+        return;
+    }
+    if let Some(matches) = matches.subcommand_matches("compile") {
+        let code = matches.value_of("CODE").unwrap();
+        let s = matches.value_of("FORK").unwrap_or("Frontier");
+        let out_dir = matches.value_of("OUT").unwrap_or(".");
+        compile(code, Fork::from_str(s).unwrap(), out_dir);
+        return;
+    }
+    if let Some(matches) = matches.subcommand_matches("profile") {
+        let dir = matches.value_of("CORPUS").unwrap();
+        let top_n = matches
+            .value_of("TOP")
+            .map(|s| s.parse().expect("Invalid --top"))
+            .unwrap_or(10);
+        let emit_arms = matches.is_present("EMIT_ARMS");
+        let gas_csv = matches
+            .value_of("GAS_CSV")
+            .map(|s| Fork::from_str(s).unwrap());
+        let gas_lower_bound = matches
+            .value_of("GAS_LOWER_BOUND")
+            .map(|s| Fork::from_str(s).unwrap());
+        profile(dir, top_n, emit_arms, gas_csv, gas_lower_bound);
+        return;
+    }
+    if let Some(matches) = matches.subcommand_matches("gas-ledger") {
+        let fork = matches
+            .value_of("FORK")
+            .map(|s| Fork::from_str(s).unwrap())
+            .unwrap_or(Fork::Frontier);
+        match decode_hex(matches.value_of("CODE").unwrap()) {
+            Ok(bytes) => gas_ledger(&bytes, fork),
+            Err(e) => println!("{}", e),
+        }
+        return;
+    }
+    if let Some(matches) = matches.subcommand_matches("golf") {
+        let fork = matches
+            .value_of("FORK")
+            .map(|s| Fork::from_str(s).unwrap())
+            .unwrap_or(Fork::Frontier);
+        let top_n = matches
+            .value_of("TOP")
+            .map(|s| s.parse().expect("Invalid --top"))
+            .unwrap_or(10);
+        match decode_hex(matches.value_of("CODE").unwrap()) {
+            Ok(bytes) => golf(&bytes, fork, top_n),
+            Err(e) => println!("{}", e),
+        }
+        return;
+    }
+    if let Some(matches) = matches.subcommand_matches("pgo") {
+        let fork = matches
+            .value_of("FORK")
+            .map(|s| Fork::from_str(s).unwrap())
+            .unwrap_or(Fork::Frontier);
+        let hot_threshold = matches
+            .value_of("HOT_THRESHOLD")
+            .map(|s| s.parse().expect("Invalid --hot-threshold"))
+            .unwrap_or(opt::pgo::DEFAULT_HOT_THRESHOLD);
+        match decode_hex(matches.value_of("CODE").unwrap()) {
+            Ok(bytes) => pgo(&bytes, fork, hot_threshold),
+            Err(e) => println!("{}", e),
+        }
+        return;
+    }
+    if let Some(matches) = matches.subcommand_matches("corpus") {
+        let dir = matches.value_of("CORPUS").unwrap();
+        let bench = matches.is_present("BENCH");
+        let top_n = matches
+            .value_of("TOP")
+            .map(|s| s.parse().expect("Invalid --top"))
+            .unwrap_or(10);
+        import_corpus(dir, bench, top_n);
+        return;
+    }
+    if let Some(matches) = matches.subcommand_matches("ngram-trace") {
+        let fork = matches
+            .value_of("FORK")
+            .map(|s| Fork::from_str(s).unwrap())
+            .unwrap_or(Fork::Frontier);
+        let n = matches
+            .value_of("SIZE")
+            .map(|s| s.parse().expect("Invalid --size"))
+            .unwrap_or(3);
+        let out = matches.value_of("OUT").unwrap();
+        match decode_hex(matches.value_of("CODE").unwrap()) {
+            Ok(bytes) => ngram_trace(&bytes, fork, n, out),
+            Err(e) => println!("{}", e),
+        }
+        return;
+    }
+    if let Some(matches) = matches.subcommand_matches("ngram-corpus") {
+        let dir = matches.value_of("CORPUS").unwrap();
+        let top_n = matches
+            .value_of("TOP")
+            .map(|s| s.parse().expect("Invalid --top"))
+            .unwrap_or(10);
+        ngram_corpus(dir, top_n);
+        return;
+    }
+    if let Some(matches) = matches.subcommand_matches("flamegraph") {
+        match read_code_input(matches.value_of("CODE"), matches.value_of("ARTIFACT")) {
+            Ok(bytes) => flamegraph(
+                &bytes,
+                Fork::Frontier,
+                matches.value_of("SOURCE_MAP"),
+                matches.value_of("SOURCES"),
+            ),
+            Err(e) => println!("{}", e),
+        }
+        return;
+    }
+    if let Some(matches) = matches.subcommand_matches("exec-artifact") {
+        let mut gas = U256::from_u64(VM_DEFAULT_GAS);
+        if let Some(value) = matches.value_of("GAS") {
+            match U256::from_dec_str(value) {
+                Ok(temp) => gas = temp,
+                Err(err) => println!("Invalid --gas: {:?}", err),
+            }
+        }
+        let filename = matches.value_of("INPUT").unwrap();
+        let strategy = matches
+            .value_of("STRATEGY")
+            .map(|s| ExecutionStrategy::from_str(s).unwrap())
+            .unwrap_or(ExecutionStrategy::default());
+        exec_artifact(filename, gas, strategy);
+        return;
+    }
+    if let Some(matches) = matches.subcommand_matches("bench-op") {
+        let name = matches.value_of("OPCODE").unwrap();
+        let opcode = match cli_parse::opcode_from_name(name) {
+            Some(opcode) => opcode,
+            None => {
+                println!("Unknown opcode: {}", name);
+                return;
+            }
+        };
+        let iterations = match matches.value_of("ITERATIONS") {
+            Some(value) => match value.parse::<u32>() {
+                Ok(iterations) if iterations > 0 => iterations,
+                _ => {
+                    println!("Invalid --iterations: expected a positive integer");
+                    return;
+                }
+            },
+            None => 10_000_000,
+        };
+        match bench_op(opcode, iterations) {
+            Ok(()) => (),
+            Err(e) => println!("{}", e),
+        }
+        return;
+    }
+    if let Some(matches) = matches.subcommand_matches("bench-trace") {
+        let iterations = match matches.value_of("ITERATIONS") {
+            Some(value) => match value.parse::<u32>() {
+                Ok(iterations) if iterations > 0 => iterations,
+                _ => {
+                    println!("Invalid --iterations: expected a positive integer");
+                    return;
+                }
+            },
+            None => 10_000_000,
+        };
+        match bench_trace(iterations) {
+            Ok(()) => (),
+            Err(e) => println!("{}", e),
+        }
+        return;
+    }
+    if let Some(matches) = matches.subcommand_matches("bench-jumpi") {
+        let iterations = match matches.value_of("ITERATIONS") {
+            Some(value) => match value.parse::<u32>() {
+                Ok(iterations) if iterations > 0 => iterations,
+                _ => {
+                    println!("Invalid --iterations: expected a positive integer");
+                    return;
+                }
+            },
+            None => 10_000_000,
+        };
+        match bench_jumpi(iterations) {
+            Ok(()) => (),
+            Err(e) => println!("{}", e),
+        }
+        return;
+    }
+    if let Some(matches) = matches.subcommand_matches("bench-compare") {
+        let fork = matches
+            .value_of("FORK")
+            .map(|s| Fork::from_str(s).unwrap())
+            .unwrap_or(Fork::Frontier);
+        let iterations = match matches.value_of("ITERATIONS") {
+            Some(value) => match value.parse::<u32>() {
+                Ok(iterations) if iterations > 0 => iterations,
+                _ => {
+                    println!("Invalid --iterations: expected a positive integer");
+                    return;
+                }
+            },
+            None => 1_000,
+        };
+        let compare_cmd = matches.value_of("COMPARE_CMD").unwrap();
+        match decode_hex(matches.value_of("CODE").unwrap()) {
+            Ok(bytes) => match bench_compare(&bytes, fork, iterations, compare_cmd) {
+                Ok(()) => (),
+                Err(e) => println!("{}", e),
+            },
+            Err(e) => println!("{}", e),
+        }
+        return;
+    }
+    #[cfg(feature = "soa-rom")]
+    if let Some(matches) = matches.subcommand_matches("bench-rom") {
+        let num_blocks = match matches.value_of("BLOCKS") {
+            Some(value) => match value.parse::<usize>() {
+                Ok(num_blocks) if num_blocks > 0 => num_blocks,
+                _ => {
+                    println!("Invalid --blocks: expected a positive integer");
+                    return;
+                }
+            },
+            None => 1000,
+        };
+        let iterations = match matches.value_of("ITERATIONS") {
+            Some(value) => match value.parse::<u32>() {
+                Ok(iterations) if iterations > 0 => iterations,
+                _ => {
+                    println!("Invalid --iterations: expected a positive integer");
+                    return;
+                }
+            },
+            None => 1000,
+        };
+        bench_rom(num_blocks, iterations);
         return;
     }
 }
@@ -0,0 +1,1462 @@
+// Copyright 2020 The Psyche Authors
+// This file is part of Psyche.
+//
+// Psyche is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Psyche is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Psyche. If not, see <http://www.gnu.org/licenses/>.
+
+//! Bytecode-to-bytecode peephole optimizer.
+//!
+//! `optimize` rewrites a sequence of EVM instructions into an equivalent,
+//! and hopefully cheaper, sequence: constant folding of arithmetic on
+//! immediate operands, cancellation of NOT/ISZERO chains, and strength
+//! reduction of power-of-two MUL into SHL. The pass never changes the set
+//! of reachable JUMPDESTs; PUSH immediates that happen to equal the address
+//! of an original JUMPDEST are relocated to the JUMPDEST's new address so
+//! that jump targets computed elsewhere in the code keep working.
+
+use std::convert::TryFrom;
+
+use crate::instructions::EvmOpcode;
+use crate::schedule::Fork;
+use crate::u256::{add_u256, mul_u256, sub_u256, U256};
+
+#[derive(Clone)]
+enum Instr {
+    Push(U256),
+    Op(EvmOpcode),
+    /// Bytes that failed to decode as a well-formed instruction; copied
+    /// through untouched.
+    Raw(Vec<u8>),
+}
+
+fn u256_from_be_bytes(bytes: &[u8]) -> U256 {
+    let mut limbs = [0u64; 4];
+    for (i, &byte) in bytes.iter().rev().enumerate() {
+        limbs[i / 8] |= (byte as u64) << ((i % 8) * 8);
+    }
+    U256::from_slice(&limbs)
+}
+
+fn minimal_be_bytes(value: U256) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(32);
+    for limb in value.0.iter().rev() {
+        bytes.extend_from_slice(&limb.to_be_bytes());
+    }
+    let first_nonzero = bytes.iter().position(|&b| b != 0).unwrap_or(bytes.len() - 1);
+    bytes[first_nonzero..].to_vec()
+}
+
+fn is_pow2(value: &U256) -> Option<u32> {
+    let mut found: Option<u32> = None;
+    for (i, &limb) in value.0.iter().enumerate() {
+        if limb == 0 {
+            continue;
+        }
+        if found.is_some() || (limb & (limb - 1)) != 0 {
+            return None;
+        }
+        found = Some((i as u32) * 64 + limb.trailing_zeros());
+    }
+    found
+}
+
+/// Decodes `bytecode` into a flat instruction list, recording the original
+/// address each instruction started at. Bytes that don't form a complete
+/// instruction (an unknown opcode or a truncated PUSH) are kept verbatim
+/// in a trailing `Raw` entry so the decoder never loses information.
+fn decode(bytecode: &[u8]) -> Vec<(usize, Instr)> {
+    let mut result = Vec::with_capacity(bytecode.len());
+    let mut addr = 0usize;
+    while addr < bytecode.len() {
+        let code = bytecode[addr];
+        match EvmOpcode::try_from(code) {
+            Ok(opcode) if opcode.is_push() => {
+                let num_bytes = opcode.push_index() + 1;
+                let start = addr + 1;
+                let end = start + num_bytes;
+                if end <= bytecode.len() {
+                    result.push((addr, Instr::Push(u256_from_be_bytes(&bytecode[start..end]))));
+                    addr = end;
+                } else {
+                    result.push((addr, Instr::Raw(bytecode[addr..].to_vec())));
+                    addr = bytecode.len();
+                }
+            }
+            // DUPN/SWAPN/EXCHANGE carry a one-byte immediate that selects a
+            // stack depth, not a value to fold; kept as an opaque `Raw` pair
+            // so the peephole passes below, which only pattern-match on
+            // `Push`/`Op`, never split the opcode from its immediate.
+            Ok(opcode) if opcode.is_deep_stack() => {
+                let end = (addr + 2).min(bytecode.len());
+                result.push((addr, Instr::Raw(bytecode[addr..end].to_vec())));
+                addr = end;
+            }
+            Ok(opcode) => {
+                result.push((addr, Instr::Op(opcode)));
+                addr += 1;
+            }
+            Err(_) => {
+                result.push((addr, Instr::Raw(vec![code])));
+                addr += 1;
+            }
+        }
+    }
+    result
+}
+
+fn encode_push(value: U256) -> Instr {
+    Instr::Push(value)
+}
+
+fn instr_len(instr: &Instr) -> usize {
+    match instr {
+        Instr::Push(value) => 1 + minimal_be_bytes(*value).len().max(1),
+        Instr::Op(_) => 1,
+        Instr::Raw(bytes) => bytes.len(),
+    }
+}
+
+fn encode(instrs: &[Instr]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for instr in instrs {
+        match instr {
+            Instr::Push(value) => {
+                let bytes = minimal_be_bytes(*value);
+                let num_bytes = bytes.len().max(1);
+                out.push(EvmOpcode::PUSH1 as u8 + (num_bytes - 1) as u8);
+                let pad = num_bytes - bytes.len();
+                out.extend(std::iter::repeat(0u8).take(pad));
+                out.extend_from_slice(&bytes);
+            }
+            Instr::Op(opcode) => out.push(*opcode as u8),
+            Instr::Raw(bytes) => out.extend_from_slice(bytes),
+        }
+    }
+    out
+}
+
+/// Folds `PUSH a, PUSH b, <ADD|SUB|MUL>` into a single `PUSH`, cancels runs
+/// of `NOT`/`ISZERO`, and turns `PUSH (pow2), MUL` into `PUSH log2, SHL`
+/// when `fork` has `SHL` available. Every input instruction is paired with
+/// its original address so callers can build a relocation table afterwards.
+fn fold(instrs: &[(usize, Instr)], fork: Fork) -> Vec<(usize, Instr)> {
+    let mut out: Vec<(usize, Instr)> = Vec::with_capacity(instrs.len());
+    for (addr, instr) in instrs {
+        out.push((*addr, instr.clone()));
+        loop {
+            let len = out.len();
+            if len >= 3 {
+                if let (Instr::Push(a), Instr::Push(b), Instr::Op(op)) =
+                    (&out[len - 3].1, &out[len - 2].1, &out[len - 1].1)
+                {
+                    let folded = match op {
+                        EvmOpcode::ADD => Some(add_u256(*b, *a)),
+                        EvmOpcode::SUB => Some(sub_u256(*b, *a)),
+                        EvmOpcode::MUL => Some(mul_u256(*a, *b)),
+                        _ => None,
+                    };
+                    if let Some(value) = folded {
+                        let first_addr = out[len - 3].0;
+                        out.truncate(len - 3);
+                        out.push((first_addr, encode_push(value)));
+                        continue;
+                    }
+                }
+                if let (Instr::Push(a), Instr::Op(EvmOpcode::MUL)) =
+                    (&out[len - 2].1, &out[len - 1].1)
+                {
+                    if fork >= Fork::Constantinople {
+                        if let Some(shift) = is_pow2(a) {
+                            let first_addr = out[len - 2].0;
+                            out.truncate(len - 2);
+                            out.push((first_addr, encode_push(U256::from_u64(shift as u64))));
+                            out.push((*addr, Instr::Op(EvmOpcode::SHL)));
+                            continue;
+                        }
+                    }
+                }
+            }
+            if len >= 2 {
+                if let (Instr::Op(a), Instr::Op(b)) = (&out[len - 2].1, &out[len - 1].1) {
+                    let cancels = matches!(
+                        (a, b),
+                        (EvmOpcode::NOT, EvmOpcode::NOT) | (EvmOpcode::ISZERO, EvmOpcode::ISZERO)
+                    );
+                    if cancels {
+                        out.truncate(len - 2);
+                        continue;
+                    }
+                }
+            }
+            break;
+        }
+    }
+    out
+}
+
+/// A bidirectional map from original-bytecode addresses to addresses in
+/// the code `optimize_with_pc_map` produced from it, so a tracer/debugger
+/// that only knows about the optimized ROM can still report pcs the user
+/// recognizes from their source bytecode. Built from the same per-
+/// instruction address bookkeeping `optimize` already does internally to
+/// relocate jump targets, generalized to cover every instruction rather
+/// than just JUMPDESTs.
+///
+/// Folding can merge several original instructions into one (e.g. `PUSH1
+/// 2, PUSH1 3, ADD` into a single `PUSH1 5`); the merged instruction keeps
+/// the address of the first one it replaced, so `to_optimized` resolves
+/// that address, while the addresses of the instructions folded into it
+/// are not recorded and resolve to `None`.
+pub struct PcMap {
+    to_optimized: std::collections::BTreeMap<u64, u64>,
+    to_original: std::collections::BTreeMap<u64, u64>,
+}
+
+impl PcMap {
+    /// Translates an address in the original bytecode to its address in
+    /// the optimized code, or `None` if `old_pc` doesn't land on an
+    /// instruction boundary `optimize_with_pc_map` recorded.
+    pub fn to_optimized(&self, old_pc: u64) -> Option<u64> {
+        self.to_optimized.get(&old_pc).copied()
+    }
+
+    /// Translates an address in the optimized code back to its address in
+    /// the original bytecode, or `None` if `new_pc` doesn't land on an
+    /// instruction boundary `optimize_with_pc_map` recorded.
+    pub fn to_original(&self, new_pc: u64) -> Option<u64> {
+        self.to_original.get(&new_pc).copied()
+    }
+}
+
+/// Optimizes `bytecode` for execution under `fork`, returning an equivalent
+/// program. Jump destinations referenced elsewhere in the code (PUSH
+/// immediates equal to the address of an original JUMPDEST) are relocated
+/// to match the new layout.
+pub fn optimize(bytecode: &[u8], fork: Fork) -> Vec<u8> {
+    optimize_with_pc_map(bytecode, fork).0
+}
+
+/// Same as `optimize`, but also returns a `PcMap` translating between
+/// addresses in `bytecode` and addresses in the returned code, for a
+/// tracer/debugger that needs to show a user pcs in terms of the bytecode
+/// they're familiar with even while running the optimized version.
+pub fn optimize_with_pc_map(bytecode: &[u8], fork: Fork) -> (Vec<u8>, PcMap) {
+    let decoded = decode(bytecode);
+    let folded = fold(&decoded, fork);
+
+    // old address -> new address, for every instruction in the folded
+    // program, plus a JUMPDEST-only subset used to relocate jump targets.
+    let mut jumpdest_reloc: std::collections::HashMap<u64, u64> = std::collections::HashMap::new();
+    let mut to_optimized: std::collections::BTreeMap<u64, u64> = std::collections::BTreeMap::new();
+    let mut to_original: std::collections::BTreeMap<u64, u64> = std::collections::BTreeMap::new();
+    let mut new_addr = 0u64;
+    for (old_addr, instr) in &folded {
+        let old_addr = *old_addr as u64;
+        if matches!(instr, Instr::Op(EvmOpcode::JUMPDEST)) {
+            jumpdest_reloc.insert(old_addr, new_addr);
+        }
+        to_optimized.insert(old_addr, new_addr);
+        to_original.insert(new_addr, old_addr);
+        new_addr += instr_len(instr) as u64;
+    }
+
+    let relocated: Vec<Instr> = folded
+        .into_iter()
+        .map(|(_, instr)| match instr {
+            Instr::Push(value) => {
+                if value.le_u64() {
+                    if let Some(&target) = jumpdest_reloc.get(&value.low_u64()) {
+                        return Instr::Push(U256::from_u64(target));
+                    }
+                }
+                Instr::Push(value)
+            }
+            other => other,
+        })
+        .collect();
+
+    (encode(&relocated), PcMap { to_optimized, to_original })
+}
+
+/// Prints the internal instruction stream `decode` and `optimize` operate
+/// on, one instruction per line, so optimizer output can be inspected by
+/// hand. There's no separate named mnemonic set for this IR — it's the
+/// same `Push`/`Op`/`Raw` shape the peephole passes above pattern-match on
+/// — so each line is `PUSH 0x<hex>`, an `EvmOpcode` mnemonic, or `RAW
+/// 0x<hex>` for bytes that didn't decode as a complete instruction.
+/// `iasm` parses this format back; round-tripping through both is how
+/// optimizer output gets hand-edited for experiments.
+pub fn idisasm(bytecode: &[u8]) -> String {
+    let mut out = String::new();
+    for (_, instr) in decode(bytecode) {
+        match instr {
+            Instr::Push(value) => {
+                out.push_str(&format!("PUSH 0x{}\n", crate::utils::encode_hex(&minimal_be_bytes(value))))
+            }
+            Instr::Op(opcode) => out.push_str(&format!("{}\n", opcode)),
+            Instr::Raw(bytes) => out.push_str(&format!("RAW 0x{}\n", crate::utils::encode_hex(&bytes))),
+        }
+    }
+    out
+}
+
+/// Parses `idisasm`'s textual form back into bytecode. Returns `Err` naming
+/// the offending line on anything it doesn't recognize.
+pub fn iasm(source: &str) -> Result<Vec<u8>, String> {
+    let mut instrs = Vec::new();
+    for line in source.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let mut parts = line.split_whitespace();
+        let head = parts.next().unwrap();
+        match head {
+            "PUSH" => {
+                let hex = parts.next().ok_or_else(|| format!("PUSH missing operand: {:?}", line))?;
+                let bytes = crate::utils::decode_hex(hex.trim_start_matches("0x"))
+                    .map_err(|_| format!("bad PUSH operand: {:?}", line))?;
+                instrs.push(encode_push(u256_from_be_bytes(&bytes)));
+            }
+            "RAW" => {
+                let hex = parts.next().ok_or_else(|| format!("RAW missing operand: {:?}", line))?;
+                let bytes = crate::utils::decode_hex(hex.trim_start_matches("0x"))
+                    .map_err(|_| format!("bad RAW operand: {:?}", line))?;
+                instrs.push(Instr::Raw(bytes));
+            }
+            mnemonic => {
+                let opcode = EvmOpcode::iter()
+                    .find(|opcode| format!("{}", opcode) == mnemonic)
+                    .ok_or_else(|| format!("unknown mnemonic: {:?}", line))?;
+                instrs.push(Instr::Op(*opcode));
+            }
+        }
+    }
+    Ok(encode(&instrs))
+}
+
+/// Detects tight, straight-line loops (a basic block starting right after
+/// a JUMPDEST, or at address 0, ending in an unconditional JUMP back to its
+/// own start) and precomputes how many iterations can be charged at once.
+///
+/// `check_exception_at!` in `vm.rs` re-derives the same gas and stack
+/// bounds on every iteration of a loop like this, even though the bounds
+/// are identical every time: the block never branches internally, so its
+/// net effect on the stack and its gas cost are constant per iteration.
+/// `detect` finds such blocks and reports that per-iteration cost;
+/// `batched_iterations` turns it into a count of iterations a caller can
+/// pre-charge in one gas subtraction instead of one per iteration. The
+/// correctness fallback is built into the cost itself: a block whose net
+/// stack effect isn't zero, or whose jump target isn't a compile-time
+/// constant equal to its own start, is reported as not a tight loop at
+/// all, so callers fall back to the existing per-iteration check. Wiring
+/// this into `run_evm`'s dispatch loop is left as follow-up work.
+pub mod loops {
+    use crate::instructions::EvmOpcode;
+    use crate::schedule::Schedule;
+    use crate::vm::OPCODE_INFOS;
+
+    use super::{decode, Instr};
+
+    /// A tight loop found at `addr`, with the gas cost of one iteration of
+    /// its body (including the JUMP back itself).
+    #[derive(Debug, PartialEq)]
+    pub struct LoopInfo {
+        pub addr: usize,
+        pub per_iter_gas: u64,
+    }
+
+    /// Scans `bytecode` for a tight loop starting at `addr`, returning its
+    /// per-iteration gas cost if `addr` is one.
+    pub fn detect(bytecode: &[u8], schedule: &Schedule, addr: usize) -> Option<LoopInfo> {
+        let decoded = decode(bytecode);
+        let start = decoded.iter().position(|(a, _)| *a == addr)?;
+        let mut stack_delta: i64 = 0;
+        let mut gas = 0u64;
+        let mut last_push: Option<u64> = None;
+        for (_, instr) in &decoded[start..] {
+            match instr {
+                Instr::Push(value) => {
+                    last_push = value.le_u64().then(|| value.low_u64());
+                    stack_delta += 1;
+                    let (_, fee, _, _) = OPCODE_INFOS[EvmOpcode::PUSH1 as usize];
+                    gas += schedule.opcode_gas(EvmOpcode::PUSH1, fee);
+                }
+                Instr::Op(opcode) if *opcode == EvmOpcode::JUMP => {
+                    // a tight loop's JUMP targets the constant pushed right
+                    // before it, and that target is its own start address.
+                    let (_, fee, delta, alpha) = OPCODE_INFOS[EvmOpcode::JUMP as usize];
+                    gas += schedule.opcode_gas(EvmOpcode::JUMP, fee);
+                    stack_delta += alpha as i64 - delta as i64;
+                    return if last_push == Some(addr as u64) && stack_delta == 0 {
+                        Some(LoopInfo {
+                            addr,
+                            per_iter_gas: gas,
+                        })
+                    } else {
+                        None
+                    };
+                }
+                Instr::Op(opcode) if opcode.is_terminator() => return None,
+                Instr::Op(opcode) => {
+                    last_push = None;
+                    let (_, fee, delta, alpha) = OPCODE_INFOS[*opcode as usize];
+                    gas += schedule.opcode_gas(*opcode, fee);
+                    stack_delta += alpha as i64 - delta as i64;
+                }
+                Instr::Raw(_) => return None,
+            }
+        }
+        None
+    }
+
+    /// Returns how many iterations of a loop costing `per_iter_gas` each
+    /// can be pre-charged against `remaining_gas` in one subtraction. Zero
+    /// whenever that isn't safe (an empty-cost body can't be bounded this
+    /// way), which callers should treat as "fall back to checking every
+    /// iteration".
+    pub fn batched_iterations(remaining_gas: u64, per_iter_gas: u64) -> u64 {
+        if per_iter_gas == 0 {
+            0
+        } else {
+            remaining_gas / per_iter_gas
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::schedule::Fork;
+
+        #[test]
+        fn detects_a_counting_loop() {
+            // JUMPDEST PUSH1 1 ADD DUP1 PUSH1 0 JUMPDEST... kept simple:
+            // JUMPDEST PUSH1 0x00 JUMP (net stack effect zero, jumps to self)
+            let bytecode = vec![
+                EvmOpcode::JUMPDEST as u8,
+                EvmOpcode::PUSH1 as u8,
+                0x00,
+                EvmOpcode::JUMP as u8,
+            ];
+            let schedule = Schedule::from_fork(Fork::Istanbul);
+            let info = detect(&bytecode, &schedule, 0).expect("should detect a tight loop");
+            assert_eq!(info.addr, 0);
+            assert!(info.per_iter_gas > 0);
+        }
+
+        #[test]
+        fn rejects_a_loop_that_grows_the_stack() {
+            let bytecode = vec![
+                EvmOpcode::JUMPDEST as u8,
+                EvmOpcode::PUSH1 as u8,
+                0x01,
+                EvmOpcode::PUSH1 as u8,
+                0x00,
+                EvmOpcode::JUMP as u8,
+            ];
+            let schedule = Schedule::from_fork(Fork::Istanbul);
+            assert!(detect(&bytecode, &schedule, 0).is_none());
+        }
+
+        #[test]
+        fn batches_iterations_within_the_gas_budget() {
+            assert_eq!(batched_iterations(100, 10), 10);
+            assert_eq!(batched_iterations(5, 10), 0);
+            assert_eq!(batched_iterations(100, 0), 0);
+        }
+    }
+}
+
+/// Best-effort discovery of `JUMP`/`JUMPI` targets computed at runtime from
+/// `PUSH`/arithmetic sequences, rather than a bare `PUSH` immediately before
+/// the jump. Improves CFG completeness for the optimizer and validator
+/// beyond the trivial "constant right before JUMP" case `loops::detect`
+/// relies on, without the cost of a full symbolic executor.
+pub mod jumps {
+    use std::collections::BTreeSet;
+
+    use crate::instructions::EvmOpcode;
+    use crate::u256::{add_u256, mul_u256, sub_u256, U256};
+    use crate::vm::OPCODE_INFOS;
+
+    use super::{decode, Instr};
+
+    /// Jump targets discovered by the abstract interpretation pass: those
+    /// it could resolve to a constant address, and the addresses of
+    /// `JUMP`/`JUMPI` instructions it couldn't (truly dynamic jumps, or
+    /// ones built from operations the constants-on-stack domain doesn't
+    /// model).
+    #[derive(Debug, Default, PartialEq, Eq)]
+    pub struct JumpTargets {
+        pub resolved: BTreeSet<usize>,
+        pub unresolved: Vec<usize>,
+    }
+
+    /// An abstract stack slot: a known constant, or `Top` once it's been
+    /// touched by an operation the domain can't model.
+    #[derive(Clone, Copy)]
+    enum AbsValue {
+        Const(U256),
+        Top,
+    }
+
+    /// Walks `bytecode` once, tracking a stack-of-constants abstraction
+    /// (`PUSH`, `DUP`, `SWAP`, and `ADD`/`SUB`/`MUL` on known operands stay
+    /// `Const`; anything else collapses to `Top`). Every `JUMPDEST` starts
+    /// a fresh basic block with an empty abstract stack, since the stack
+    /// state flowing in from other blocks' jumps isn't tracked across
+    /// blocks by this pass.
+    pub fn discover_jump_targets(bytecode: &[u8]) -> JumpTargets {
+        let mut targets = JumpTargets::default();
+        let mut stack: Vec<AbsValue> = Vec::new();
+        for (addr, instr) in decode(bytecode) {
+            match instr {
+                Instr::Push(value) => stack.push(AbsValue::Const(value)),
+                Instr::Op(EvmOpcode::JUMPDEST) => stack.clear(),
+                Instr::Op(opcode @ (EvmOpcode::JUMP | EvmOpcode::JUMPI)) => {
+                    match stack.pop() {
+                        Some(AbsValue::Const(target)) if target.le_u64() => {
+                            targets.resolved.insert(target.low_u64() as usize);
+                        }
+                        _ => targets.unresolved.push(addr),
+                    }
+                    if opcode == EvmOpcode::JUMPI {
+                        stack.pop();
+                    }
+                }
+                Instr::Op(opcode) if (EvmOpcode::DUP1..=EvmOpcode::DUP16).contains(&opcode) => {
+                    let depth = opcode.to_internal().dup_index();
+                    let value = stack.len().checked_sub(depth + 1).map(|i| stack[i]);
+                    stack.push(value.unwrap_or(AbsValue::Top));
+                }
+                Instr::Op(opcode) if (EvmOpcode::SWAP1..=EvmOpcode::SWAP16).contains(&opcode) => {
+                    let depth = opcode.to_internal().swap_index() + 1;
+                    let len = stack.len();
+                    if depth < len {
+                        stack.swap(len - 1, len - 1 - depth);
+                    }
+                }
+                Instr::Op(opcode @ (EvmOpcode::ADD | EvmOpcode::SUB | EvmOpcode::MUL)) => {
+                    let (b, a) = (stack.pop(), stack.pop());
+                    let folded = match (a, b) {
+                        (Some(AbsValue::Const(a)), Some(AbsValue::Const(b))) => Some(match opcode {
+                            EvmOpcode::ADD => add_u256(a, b),
+                            EvmOpcode::SUB => sub_u256(b, a),
+                            EvmOpcode::MUL => mul_u256(a, b),
+                            _ => unreachable!(),
+                        }),
+                        _ => None,
+                    };
+                    stack.push(folded.map_or(AbsValue::Top, AbsValue::Const));
+                }
+                Instr::Op(opcode) => {
+                    let (_, _, delta, alpha) = OPCODE_INFOS[opcode as usize];
+                    for _ in 0..delta {
+                        stack.pop();
+                    }
+                    for _ in 0..alpha {
+                        stack.push(AbsValue::Top);
+                    }
+                }
+                // An unknown or truncated instruction: its effect on the
+                // stack isn't modeled, so forget everything tracked so far
+                // rather than risk treating a stale constant as live.
+                Instr::Raw(_) => stack.clear(),
+            }
+        }
+        targets
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn resolves_a_jump_built_from_push_add() {
+            let bytecode = vec![
+                EvmOpcode::PUSH1 as u8,
+                0x05,
+                EvmOpcode::PUSH1 as u8,
+                0x02,
+                EvmOpcode::ADD as u8,
+                EvmOpcode::JUMP as u8,
+            ];
+            let targets = discover_jump_targets(&bytecode);
+            assert_eq!(targets.resolved, BTreeSet::from([7]));
+            assert!(targets.unresolved.is_empty());
+        }
+
+        #[test]
+        fn leaves_a_truly_dynamic_jump_unresolved() {
+            let bytecode = vec![EvmOpcode::CALLDATALOAD as u8, EvmOpcode::JUMP as u8];
+            let targets = discover_jump_targets(&bytecode);
+            assert!(targets.resolved.is_empty());
+            assert_eq!(targets.unresolved, vec![1]);
+        }
+
+        #[test]
+        fn tracks_constants_through_dup_and_swap() {
+            // PUSH1 3; PUSH1 10 (dest); SWAP1; POP (drops 3); JUMP
+            let bytecode = vec![
+                EvmOpcode::PUSH1 as u8,
+                0x03,
+                EvmOpcode::PUSH1 as u8,
+                0x0a,
+                EvmOpcode::SWAP1 as u8,
+                EvmOpcode::POP as u8,
+                EvmOpcode::JUMP as u8,
+            ];
+            let targets = discover_jump_targets(&bytecode);
+            assert_eq!(targets.resolved, BTreeSet::from([10]));
+        }
+
+        #[test]
+        fn resets_the_abstract_stack_at_each_jumpdest() {
+            // A constant pushed before a JUMPDEST must not leak into the
+            // next block's view of the stack.
+            let bytecode = vec![
+                EvmOpcode::PUSH1 as u8,
+                0x00,
+                EvmOpcode::JUMPDEST as u8,
+                EvmOpcode::JUMP as u8,
+            ];
+            let targets = discover_jump_targets(&bytecode);
+            assert!(targets.resolved.is_empty());
+            assert_eq!(targets.unresolved, vec![3]);
+        }
+    }
+}
+
+/// Finds `PUSH <offset>, MLOAD`/`PUSH <offset>, MSTORE` pairs where
+/// `offset` is small enough (`<= u16::MAX`) that a fused handler could skip
+/// the full 256-bit memory-offset bounds check most `MLOAD`/`MSTORE` sites
+/// never need, since dispatcher-compiled Solidity almost always indexes
+/// memory with a small compile-time-constant offset.
+///
+/// This is analysis scaffolding like `loops`/`jumps` above: `find_sites`
+/// reports candidate fusion sites, but `optimize` doesn't act on them.
+/// Actually fusing would mean claiming two bytes of currently-unused opcode
+/// space for `MLOAD_IMM`/`MSTORE_IMM` and teaching `vm.rs`'s dispatch loop
+/// their handlers — real interpreter surface, not safe to take on as a
+/// drive-by change here — so that's left as follow-up work.
+pub mod fusion {
+    use crate::instructions::EvmOpcode;
+
+    use super::{decode, Instr};
+
+    /// A fusable `PUSH <offset>, MLOAD`/`MSTORE` site: the address of the
+    /// `PUSH`, the constant offset it pushes, and which of the two memory
+    /// opcodes follows it.
+    #[derive(Debug, PartialEq, Eq)]
+    pub struct FusionSite {
+        pub addr: usize,
+        pub offset: u16,
+        pub is_store: bool,
+    }
+
+    /// Scans `bytecode` for fusable memory-operand sites, in address order.
+    pub fn find_sites(bytecode: &[u8]) -> Vec<FusionSite> {
+        let decoded = decode(bytecode);
+        let mut sites = Vec::new();
+        for i in 0..decoded.len().saturating_sub(1) {
+            if let (addr, Instr::Push(value)) = &decoded[i] {
+                let is_store = match &decoded[i + 1].1 {
+                    Instr::Op(EvmOpcode::MLOAD) => Some(false),
+                    Instr::Op(EvmOpcode::MSTORE) => Some(true),
+                    _ => None,
+                };
+                if let Some(is_store) = is_store {
+                    if value.le_u64() && value.low_u64() <= u16::MAX as u64 {
+                        sites.push(FusionSite {
+                            addr: *addr,
+                            offset: value.low_u64() as u16,
+                            is_store,
+                        });
+                    }
+                }
+            }
+        }
+        sites
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn finds_an_mload_with_a_small_constant_offset() {
+            let code = vec![EvmOpcode::PUSH1 as u8, 0x20, EvmOpcode::MLOAD as u8];
+            assert_eq!(
+                find_sites(&code),
+                vec![FusionSite {
+                    addr: 0,
+                    offset: 0x20,
+                    is_store: false
+                }]
+            );
+        }
+
+        #[test]
+        fn finds_an_mstore_with_a_small_constant_offset() {
+            let code = vec![EvmOpcode::PUSH1 as u8, 0x00, EvmOpcode::MSTORE as u8];
+            assert_eq!(
+                find_sites(&code),
+                vec![FusionSite {
+                    addr: 0,
+                    offset: 0x00,
+                    is_store: true
+                }]
+            );
+        }
+
+        #[test]
+        fn ignores_an_mload_whose_offset_is_computed_at_runtime() {
+            let code = vec![EvmOpcode::CALLDATASIZE as u8, EvmOpcode::MLOAD as u8];
+            assert!(find_sites(&code).is_empty());
+        }
+
+        #[test]
+        fn ignores_an_offset_too_large_to_fuse() {
+            let mut code = vec![EvmOpcode::PUSH3 as u8, 0x01, 0x00, 0x00];
+            code.push(EvmOpcode::MLOAD as u8);
+            assert!(find_sites(&code).is_empty());
+        }
+    }
+}
+
+/// Maps EVM stack slots live within a basic block onto a fixed virtual
+/// register file, spilling to a stack-slot array under register pressure.
+///
+/// This is analysis scaffolding for a future JIT backend: `allocate`
+/// decides, for each stack slot's live range, whether it lives in one of
+/// `num_registers` virtual registers or is spilled, but it does not itself
+/// emit code.
+pub mod regalloc {
+    /// The live range of a single stack slot within a basic block, given as
+    /// `[start, end)` instruction indices.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct LiveRange {
+        pub slot: usize,
+        pub start: usize,
+        pub end: usize,
+    }
+
+    impl LiveRange {
+        pub fn new(slot: usize, start: usize, end: usize) -> LiveRange {
+            LiveRange { slot, start, end }
+        }
+    }
+
+    /// Where a stack slot's value is held for the extent of its live range.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum Location {
+        Register(usize),
+        Spill(usize),
+    }
+
+    /// A single live range together with its assigned location.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct Assignment {
+        pub slot: usize,
+        pub location: Location,
+    }
+
+    /// Greedy linear-scan allocation: live ranges are processed in start
+    /// order, assigned a free register if one exists, and otherwise spilled
+    /// to the next free spill slot. Handles any number of overlapping live
+    /// ranges without unsoundness; only the number of distinct registers
+    /// used (bounded by `num_registers`) affects whether a range spills.
+    pub fn allocate(ranges: &[LiveRange], num_registers: usize) -> Vec<Assignment> {
+        let mut order: Vec<usize> = (0..ranges.len()).collect();
+        order.sort_by_key(|&i| ranges[i].start);
+
+        // active[r] holds the index (into `ranges`) of the live range
+        // currently occupying register `r`, if any.
+        let mut active: Vec<Option<usize>> = vec![None; num_registers];
+        let mut next_spill: usize = 0;
+        let mut result = vec![
+            Assignment {
+                slot: 0,
+                location: Location::Spill(0)
+            };
+            ranges.len()
+        ];
+
+        for &i in &order {
+            let range = ranges[i];
+            // free registers whose occupant's live range has ended
+            for slot in active.iter_mut() {
+                if let Some(j) = *slot {
+                    if ranges[j].end <= range.start {
+                        *slot = None;
+                    }
+                }
+            }
+            let free_register = active.iter().position(|slot| slot.is_none());
+            let location = match free_register {
+                Some(r) => {
+                    active[r] = Some(i);
+                    Location::Register(r)
+                }
+                None => {
+                    let spill = next_spill;
+                    next_spill += 1;
+                    Location::Spill(spill)
+                }
+            };
+            result[i] = Assignment {
+                slot: range.slot,
+                location,
+            };
+        }
+        result
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn fits_within_register_budget() {
+            let ranges = [
+                LiveRange::new(0, 0, 2),
+                LiveRange::new(1, 1, 3),
+                LiveRange::new(2, 3, 5),
+            ];
+            let result = allocate(&ranges, 2);
+            assert!(result.iter().all(|a| matches!(a.location, Location::Register(_))));
+        }
+
+        #[test]
+        fn spills_under_pressure() {
+            // three ranges alive at once, only one register available
+            let ranges = [
+                LiveRange::new(0, 0, 10),
+                LiveRange::new(1, 1, 10),
+                LiveRange::new(2, 2, 10),
+            ];
+            let result = allocate(&ranges, 1);
+            let spilled = result
+                .iter()
+                .filter(|a| matches!(a.location, Location::Spill(_)))
+                .count();
+            assert_eq!(spilled, 2);
+        }
+
+        #[test]
+        fn never_aliases_two_overlapping_ranges_to_the_same_register() {
+            let ranges = [
+                LiveRange::new(0, 0, 4),
+                LiveRange::new(1, 1, 5),
+                LiveRange::new(2, 2, 6),
+                LiveRange::new(3, 6, 8),
+            ];
+            let result = allocate(&ranges, 3);
+            for i in 0..ranges.len() {
+                for j in (i + 1)..ranges.len() {
+                    let overlaps = ranges[i].start < ranges[j].end && ranges[j].start < ranges[i].end;
+                    if overlaps {
+                        if let (Location::Register(a), Location::Register(b)) =
+                            (result[i].location, result[j].location)
+                        {
+                            assert_ne!(a, b, "overlapping ranges must not share a register");
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Profile-guided optimization: turns a real run's per-instruction
+/// execution counts (`vm::TraceFilter::PcCounts`) into per-block counts,
+/// then ranks `fusion::find_sites`' candidates by whether they sit in a
+/// hot block, establishing a profile -> optimize loop of:
+///
+///  1. run the contract once with `TraceFilter::PcCounts` to get
+///     `TraceReport::pc_counts`;
+///  2. `block_counts` to attribute those to block start addresses, and
+///     `hot_blocks` to threshold them;
+///  3. `prioritized_fusion_sites` to rank `fusion`'s candidate sites by
+///     hotness, so a human (or a future codegen pass) spends its "fuse
+///     this" budget on the blocks that actually run.
+///
+/// Like `fusion` itself, this stops at reporting: it doesn't change
+/// `optimize`'s output, since real fusion isn't wired into `vm.rs`'s
+/// dispatch loop yet (see `fusion`'s doc comment). A block's own execution
+/// count also can't be collected without running it, and the interpreter
+/// has no calldata support (see `stats::GasLedger`'s doc comment), so a
+/// "profiling run" today only exercises the one path a
+/// contract with no inputs takes — still enough to demonstrate the loop
+/// end to end, but not to find every hot block a real corpus would.
+pub mod pgo {
+    use std::collections::BTreeMap;
+
+    use crate::instructions::EvmOpcode;
+
+    use super::fusion::{self, FusionSite};
+    use super::{decode, Instr};
+
+    /// A block executed at least this many times is "hot" by default.
+    pub const DEFAULT_HOT_THRESHOLD: u64 = 10;
+
+    /// Aggregates per-instruction `pc_counts` (see
+    /// `vm::TraceReport::pc_counts`) up to per-block counts, keyed by each
+    /// block's start address. Blocks split at every `JUMPDEST`, the same
+    /// granularity `jumps::discover_jump_targets` uses, since that's the
+    /// finest a jump can target; a block's count is its first
+    /// instruction's count, since every instruction in a block that never
+    /// jumps into its own middle runs exactly once per block entry.
+    pub fn block_counts(bytecode: &[u8], pc_counts: &BTreeMap<u32, u64>) -> BTreeMap<usize, u64> {
+        let mut result = BTreeMap::new();
+        let mut block_addr = 0usize;
+        for (addr, instr) in decode(bytecode) {
+            if matches!(instr, Instr::Op(EvmOpcode::JUMPDEST)) {
+                block_addr = addr;
+            }
+            let count = pc_counts.get(&(addr as u32)).copied().unwrap_or(0);
+            result
+                .entry(block_addr)
+                .and_modify(|c: &mut u64| *c = (*c).max(count))
+                .or_insert(count);
+        }
+        result
+    }
+
+    /// Returns the start addresses of blocks executed at least
+    /// `hot_threshold` times, most-executed first.
+    pub fn hot_blocks(counts: &BTreeMap<usize, u64>, hot_threshold: u64) -> Vec<usize> {
+        let mut hot: Vec<(usize, u64)> = counts
+            .iter()
+            .filter(|&(_, &count)| count >= hot_threshold)
+            .map(|(&addr, &count)| (addr, count))
+            .collect();
+        hot.sort_by(|a, b| b.1.cmp(&a.1));
+        hot.into_iter().map(|(addr, _)| addr).collect()
+    }
+
+    /// Ranks `fusion::find_sites`' candidates for `bytecode`, hot-block
+    /// sites first (most-executed block first among those), cold sites
+    /// last in their original address order.
+    pub fn prioritized_fusion_sites(
+        bytecode: &[u8],
+        pc_counts: &BTreeMap<u32, u64>,
+        hot_threshold: u64,
+    ) -> Vec<FusionSite> {
+        let counts = block_counts(bytecode, pc_counts);
+        let hot = hot_blocks(&counts, hot_threshold);
+        let mut sites = fusion::find_sites(bytecode);
+        sites.sort_by_key(|site| {
+            let block_addr = counts
+                .range(..=site.addr)
+                .next_back()
+                .map(|(&addr, _)| addr)
+                .unwrap_or(0);
+            match hot.iter().position(|&addr| addr == block_addr) {
+                Some(rank) => rank,
+                None => hot.len() + site.addr,
+            }
+        });
+        sites
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn attributes_a_blocks_count_to_its_first_instruction() {
+            // JUMPDEST PUSH1 1 POP (one block, address 0)
+            let bytecode = vec![EvmOpcode::JUMPDEST as u8, EvmOpcode::PUSH1 as u8, 0x01, EvmOpcode::POP as u8];
+            let pc_counts = BTreeMap::from([(0u32, 7u64)]);
+            let counts = block_counts(&bytecode, &pc_counts);
+            assert_eq!(counts.get(&0), Some(&7));
+        }
+
+        #[test]
+        fn splits_counts_at_every_jumpdest() {
+            // JUMPDEST(0) PUSH1 4(1-2) JUMP(3) JUMPDEST(4) STOP(5)
+            let bytecode = vec![
+                EvmOpcode::JUMPDEST as u8,
+                EvmOpcode::PUSH1 as u8,
+                0x04,
+                EvmOpcode::JUMP as u8,
+                EvmOpcode::JUMPDEST as u8,
+                EvmOpcode::STOP as u8,
+            ];
+            let pc_counts = BTreeMap::from([(0u32, 5u64), (4u32, 3u64)]);
+            let counts = block_counts(&bytecode, &pc_counts);
+            assert_eq!(counts.get(&0), Some(&5));
+            assert_eq!(counts.get(&4), Some(&3));
+        }
+
+        #[test]
+        fn hot_blocks_are_sorted_most_executed_first() {
+            let counts = BTreeMap::from([(0usize, 5u64), (10usize, 50u64), (20usize, 1u64)]);
+            assert_eq!(hot_blocks(&counts, 5), vec![10, 0]);
+        }
+
+        #[test]
+        fn prioritizes_fusion_sites_in_hot_blocks_over_cold_ones() {
+            // block 0 (cold): PUSH1 0 MLOAD; block 4 (hot): JUMPDEST PUSH1 0 MSTORE
+            let bytecode = vec![
+                EvmOpcode::PUSH1 as u8,
+                0x00,
+                EvmOpcode::MLOAD as u8,
+                EvmOpcode::STOP as u8,
+                EvmOpcode::JUMPDEST as u8,
+                EvmOpcode::PUSH1 as u8,
+                0x00,
+                EvmOpcode::MSTORE as u8,
+            ];
+            let pc_counts = BTreeMap::from([(0u32, 1u64), (4u32, 1000u64)]);
+            let sites = prioritized_fusion_sites(&bytecode, &pc_counts, DEFAULT_HOT_THRESHOLD);
+            assert_eq!(sites[0].addr, 5);
+            assert_eq!(sites[1].addr, 0);
+        }
+    }
+}
+
+/// Mines n-grams (short runs of consecutive opcodes) out of a single
+/// contract's execution, weighted by how often each one actually ran --
+/// the raw material for deciding which opcode shapes are common enough to
+/// be worth a fused handler, beyond the MLOAD/MSTORE-with-constant-offset
+/// sites `fusion` already detects statically. `main.rs`'s `ngram-trace`
+/// subcommand writes one contract's `count_ngrams` result to a file, and
+/// `ngram-corpus` merges many such files to rank candidates across a
+/// whole corpus instead of a single run.
+pub mod ngrams {
+    use std::collections::BTreeMap;
+    use std::convert::TryFrom;
+
+    use crate::instructions::EvmOpcode;
+
+    use super::pgo;
+    use super::{decode, Instr};
+
+    /// An opcode sequence, the unit counted and ranked below. Immediate
+    /// operands (a PUSH's value, say) aren't part of the key: two call
+    /// sites pushing different constants before the same MLOAD are the
+    /// same fusion candidate, just like `fusion::find_sites` doesn't care
+    /// which offset a site's PUSH carries.
+    pub type Ngram = Vec<EvmOpcode>;
+
+    /// Walks `bytecode` the way `decode` does, keeping each instruction's
+    /// opcode and address but dropping `decode`'s `Instr::Push` payload --
+    /// recovered from `bytecode` itself at that address instead, since
+    /// `decode` folds PUSH's immediate into a `U256` and doesn't record
+    /// which `PUSHn` produced it. `Instr::Raw` entries (malformed
+    /// bytecode, or the EIP-663 deep-stack opcodes' runtime-selected
+    /// immediate) have no single opcode to report and are dropped.
+    fn opcodes(bytecode: &[u8]) -> Vec<(usize, EvmOpcode)> {
+        decode(bytecode)
+            .into_iter()
+            .filter_map(|(addr, instr)| match instr {
+                Instr::Push(_) => EvmOpcode::try_from(bytecode[addr]).ok().map(|op| (addr, op)),
+                Instr::Op(op) => Some((addr, op)),
+                Instr::Raw(_) => None,
+            })
+            .collect()
+    }
+
+    /// Splits `instrs` into the same basic blocks `pgo::block_counts` does
+    /// -- a new block starts at every `JUMPDEST` -- so a caller can weight
+    /// each block's windows by that block's own execution count.
+    fn blocks(instrs: &[(usize, EvmOpcode)]) -> Vec<&[(usize, EvmOpcode)]> {
+        let mut bounds = vec![0];
+        for (i, &(_, opcode)) in instrs.iter().enumerate() {
+            if i != 0 && opcode == EvmOpcode::JUMPDEST {
+                bounds.push(i);
+            }
+        }
+        bounds.push(instrs.len());
+        bounds.windows(2).map(|w| &instrs[w[0]..w[1]]).collect()
+    }
+
+    /// Slides an `n`-wide window over every basic block of `bytecode`,
+    /// counting each distinct opcode sequence weighted by
+    /// `pgo::block_counts`' count for the block it came from. Windows
+    /// never cross a block boundary, since only a genuine fall-through
+    /// (never a jump) guarantees the window's opcodes actually executed
+    /// back to back.
+    pub fn count_ngrams(bytecode: &[u8], pc_counts: &BTreeMap<u32, u64>, n: usize) -> BTreeMap<Ngram, u64> {
+        let mut result = BTreeMap::new();
+        if n == 0 {
+            return result;
+        }
+        let counts = pgo::block_counts(bytecode, pc_counts);
+        let instrs = opcodes(bytecode);
+        for block in blocks(&instrs) {
+            if block.len() < n {
+                continue;
+            }
+            let weight = block.first().and_then(|&(addr, _)| counts.get(&addr)).copied().unwrap_or(0);
+            if weight == 0 {
+                continue;
+            }
+            for window in block.windows(n) {
+                let ngram: Ngram = window.iter().map(|&(_, opcode)| opcode).collect();
+                *result.entry(ngram).or_insert(0) += weight;
+            }
+        }
+        result
+    }
+
+    /// Folds `other`'s counts into `into`, for combining n-grams mined
+    /// from separate contracts into one cross-corpus tally.
+    pub fn merge(into: &mut BTreeMap<Ngram, u64>, other: &BTreeMap<Ngram, u64>) {
+        for (ngram, &count) in other {
+            *into.entry(ngram.clone()).or_insert(0) += count;
+        }
+    }
+
+    /// Returns `counts`' entries most-frequent-first, truncated to `top`.
+    pub fn top_n(counts: &BTreeMap<Ngram, u64>, top: usize) -> Vec<(Ngram, u64)> {
+        let mut ranked: Vec<(Ngram, u64)> = counts.iter().map(|(ngram, &count)| (ngram.clone(), count)).collect();
+        ranked.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+        ranked.truncate(top);
+        ranked
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn mines_overlapping_windows_within_a_single_block() {
+            // PUSH1 1(0-1) PUSH1 2(2-3) ADD(4) POP(5), one block, executed 5 times
+            let bytecode = vec![
+                EvmOpcode::PUSH1 as u8,
+                0x01,
+                EvmOpcode::PUSH1 as u8,
+                0x02,
+                EvmOpcode::ADD as u8,
+                EvmOpcode::POP as u8,
+            ];
+            let pc_counts = BTreeMap::from([(0u32, 5u64)]);
+            let counts = count_ngrams(&bytecode, &pc_counts, 2);
+            assert_eq!(counts.get(&vec![EvmOpcode::PUSH1, EvmOpcode::PUSH1]), Some(&5));
+            assert_eq!(counts.get(&vec![EvmOpcode::PUSH1, EvmOpcode::ADD]), Some(&5));
+            assert_eq!(counts.get(&vec![EvmOpcode::ADD, EvmOpcode::POP]), Some(&5));
+        }
+
+        #[test]
+        fn windows_never_cross_a_block_boundary() {
+            // PUSH1 1(0-1) POP(2) JUMPDEST(3) PUSH1 2(4-5) POP(6)
+            let bytecode = vec![
+                EvmOpcode::PUSH1 as u8,
+                0x01,
+                EvmOpcode::POP as u8,
+                EvmOpcode::JUMPDEST as u8,
+                EvmOpcode::PUSH1 as u8,
+                0x02,
+                EvmOpcode::POP as u8,
+            ];
+            let pc_counts = BTreeMap::from([(0u32, 3u64), (3u32, 7u64)]);
+            let counts = count_ngrams(&bytecode, &pc_counts, 2);
+            assert_eq!(counts.get(&vec![EvmOpcode::JUMPDEST, EvmOpcode::PUSH1]), Some(&7));
+            assert!(!counts.contains_key(&vec![EvmOpcode::POP, EvmOpcode::JUMPDEST]));
+        }
+
+        #[test]
+        fn merge_sums_counts_from_separate_contracts() {
+            let mut into = BTreeMap::from([(vec![EvmOpcode::PUSH1, EvmOpcode::MLOAD], 3u64)]);
+            let other = BTreeMap::from([
+                (vec![EvmOpcode::PUSH1, EvmOpcode::MLOAD], 4u64),
+                (vec![EvmOpcode::DUP1, EvmOpcode::SWAP1], 1u64),
+            ]);
+            merge(&mut into, &other);
+            assert_eq!(into.get(&vec![EvmOpcode::PUSH1, EvmOpcode::MLOAD]), Some(&7));
+            assert_eq!(into.get(&vec![EvmOpcode::DUP1, EvmOpcode::SWAP1]), Some(&1));
+        }
+
+        #[test]
+        fn top_n_ranks_most_frequent_first_and_truncates() {
+            let counts = BTreeMap::from([
+                (vec![EvmOpcode::PUSH1], 1u64),
+                (vec![EvmOpcode::POP], 50u64),
+                (vec![EvmOpcode::ADD], 10u64),
+            ]);
+            let ranked = top_n(&counts, 2);
+            assert_eq!(ranked, vec![(vec![EvmOpcode::POP], 50), (vec![EvmOpcode::ADD], 10)]);
+        }
+    }
+}
+
+/// Delta-debugging reducer for differential-testing divergences: given a
+/// predicate that reports whether a candidate program still reproduces a
+/// divergence (typically "running `bytecode` and `optimize(bytecode,
+/// fork)` disagree", the property `tests::assert_equivalent` below checks
+/// on hand-written cases), `shrink` returns a smaller program for which
+/// the predicate still holds, so a bug report can show the minimal
+/// reproducer instead of the input a fuzzer or corpus scan happened to
+/// find it on.
+///
+/// Shrinking works at instruction granularity, using `decode`'s own
+/// instruction boundaries rather than raw byte offsets, so a candidate is
+/// never a truncated `PUSH` or a `Raw` run split in two -- the same
+/// invariant `vm`'s validity-preserving block generator (see the
+/// stack-bound fuzz test in `vm.rs`) guarantees about its output.
+pub mod reduce {
+    use super::decode;
+
+    /// Repeatedly tries to drop one decoded instruction from `bytecode`,
+    /// keeping the drop only when `diverges` still returns true for the
+    /// result, until no single instruction can be removed. This is a
+    /// greedy, single-granularity simplification of ddmin rather than the
+    /// full algorithm (no chunk-size backoff), which is enough in practice
+    /// for the kind of small, block-local divergence this optimizer would
+    /// introduce.
+    ///
+    /// Panics if `bytecode` doesn't already reproduce the divergence, since
+    /// that's a misuse of the reducer rather than something it can shrink.
+    pub fn shrink(bytecode: &[u8], diverges: &dyn Fn(&[u8]) -> bool) -> Vec<u8> {
+        assert!(diverges(bytecode), "shrink requires an input that already reproduces the divergence");
+        let mut current = bytecode.to_vec();
+        loop {
+            let starts: Vec<usize> = decode(&current).into_iter().map(|(addr, _)| addr).collect();
+            let mut shrunk = false;
+            for (i, &start) in starts.iter().enumerate().rev() {
+                let end = starts.get(i + 1).copied().unwrap_or(current.len());
+                let mut candidate = current.clone();
+                candidate.drain(start..end);
+                if diverges(&candidate) {
+                    current = candidate;
+                    shrunk = true;
+                    break;
+                }
+            }
+            if !shrunk {
+                return current;
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::instructions::EvmOpcode;
+
+        #[test]
+        fn shrinks_down_to_the_single_instruction_the_predicate_needs() {
+            let bytecode = vec![
+                EvmOpcode::PUSH1 as u8,
+                0x01,
+                EvmOpcode::POP as u8,
+                EvmOpcode::ADD as u8,
+                EvmOpcode::PUSH1 as u8,
+                0x02,
+            ];
+            // Decodes rather than scanning raw bytes for 0x01, since ADD's
+            // opcode byte also happens to be a valid PUSH1 immediate.
+            let diverges =
+                |code: &[u8]| decode(code).iter().any(|(_, instr)| matches!(instr, super::super::Instr::Op(EvmOpcode::ADD)));
+            assert_eq!(shrink(&bytecode, &diverges), vec![EvmOpcode::ADD as u8]);
+        }
+
+        #[test]
+        fn never_splits_a_push_immediate_while_shrinking() {
+            // PUSH2 0xBEEF is the only thing keeping the predicate true;
+            // a byte-granularity reducer could "shrink" it to PUSH2 0xBE00
+            // (still two bytes, wrong value) instead of leaving it whole.
+            let bytecode = vec![
+                EvmOpcode::POP as u8,
+                EvmOpcode::PUSH2 as u8,
+                0xbe,
+                0xef,
+                EvmOpcode::POP as u8,
+            ];
+            let diverges = |code: &[u8]| code.windows(3).any(|w| w == [EvmOpcode::PUSH2 as u8, 0xbe, 0xef]);
+            assert_eq!(
+                shrink(&bytecode, &diverges),
+                vec![EvmOpcode::PUSH2 as u8, 0xbe, 0xef]
+            );
+        }
+
+        #[test]
+        fn returns_the_input_unchanged_when_no_instruction_can_be_dropped() {
+            let bytecode = vec![EvmOpcode::PUSH1 as u8, 0x01, EvmOpcode::PUSH1 as u8, 0x02];
+            let diverges = |code: &[u8]| code.len() == bytecode.len();
+            assert_eq!(shrink(&bytecode, &diverges), bytecode);
+        }
+
+        #[test]
+        #[should_panic(expected = "already reproduces")]
+        fn panics_when_the_input_does_not_reproduce_the_divergence() {
+            let bytecode = vec![EvmOpcode::ADD as u8];
+            shrink(&bytecode, &|_| false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schedule::Schedule;
+    use crate::u256::U256 as U;
+    use crate::vm::{run_evm, BlockContext, TestBlockHashProvider, VmMemory, VmRom};
+
+    const TEST_GAS: u64 = 20_000_000_000_000;
+
+    fn run(bytecode: &[u8], fork: Fork) -> (Vec<u8>, u64) {
+        let schedule = Schedule::from_fork(fork);
+        let gas_limit = U::from_u64(TEST_GAS);
+        let mut rom = VmRom::new();
+        rom.init(bytecode, &schedule);
+        let mut memory = VmMemory::new();
+        memory.init(gas_limit);
+        let hashes = TestBlockHashProvider;
+        let block = BlockContext::new(U::from_u64(0), &hashes);
+        unsafe {
+            let ret_data = run_evm(bytecode, &rom, &schedule, &block, gas_limit, &mut memory);
+            let out = memory.slice(ret_data.offset as isize, ret_data.size).to_vec();
+            (out, TEST_GAS - ret_data.gas)
+        }
+    }
+
+    fn assert_equivalent(bytecode: Vec<u8>, fork: Fork) {
+        let optimized = optimize(&bytecode, fork);
+        let (out1, gas1) = run(&bytecode, fork);
+        let (out2, gas2) = run(&optimized, fork);
+        assert_eq!(out1, out2);
+        assert!(gas2 <= gas1, "optimized program must not cost more gas");
+    }
+
+    fn push1(v: u8) -> Vec<u8> {
+        vec![EvmOpcode::PUSH1 as u8, v]
+    }
+
+    #[test]
+    fn folds_constant_add() {
+        let mut code = push1(2);
+        code.extend(push1(3));
+        code.push(EvmOpcode::ADD as u8);
+        code.push(EvmOpcode::PUSH1 as u8);
+        code.push(0);
+        code.push(EvmOpcode::MSTORE as u8);
+        code.extend(push1(32));
+        code.extend(push1(0));
+        code.push(EvmOpcode::RETURN as u8);
+        let optimized = optimize(&code, Fork::Frontier);
+        // PUSH1 5 (2 bytes) replaces PUSH1 2, PUSH1 3, ADD (5 bytes).
+        assert_eq!(optimized.len(), code.len() - 3);
+        assert_equivalent(code, Fork::Frontier);
+    }
+
+    #[test]
+    fn cancels_double_not() {
+        let mut code = push1(7);
+        code.push(EvmOpcode::NOT as u8);
+        code.push(EvmOpcode::NOT as u8);
+        code.push(EvmOpcode::PUSH1 as u8);
+        code.push(0);
+        code.push(EvmOpcode::MSTORE as u8);
+        code.extend(push1(32));
+        code.extend(push1(0));
+        code.push(EvmOpcode::RETURN as u8);
+        assert_equivalent(code, Fork::Frontier);
+    }
+
+    #[test]
+    fn strength_reduces_pow2_mul() {
+        let mut code = push1(3);
+        code.extend(push1(4));
+        code.push(EvmOpcode::MUL as u8);
+        code.push(EvmOpcode::PUSH1 as u8);
+        code.push(0);
+        code.push(EvmOpcode::MSTORE as u8);
+        code.extend(push1(32));
+        code.extend(push1(0));
+        code.push(EvmOpcode::RETURN as u8);
+        assert_equivalent(code, Fork::Constantinople);
+    }
+
+    #[test]
+    fn relocates_jump_targets_after_folding() {
+        // PUSH1 2, PUSH1 3, ADD  -> folds to a single PUSH, shrinking the
+        // code that precedes the JUMPDEST the jump below targets.
+        let mut code = push1(2);
+        code.extend(push1(3));
+        code.push(EvmOpcode::ADD as u8);
+        let jumpdest_addr = code.len() + 3; // after the PUSH1<addr> JUMP below
+        code.push(EvmOpcode::PUSH1 as u8);
+        code.push(jumpdest_addr as u8);
+        code.push(EvmOpcode::JUMP as u8);
+        code.push(EvmOpcode::JUMPDEST as u8);
+        code.extend(push1(9));
+        code.push(EvmOpcode::PUSH1 as u8);
+        code.push(0);
+        code.push(EvmOpcode::MSTORE as u8);
+        code.extend(push1(32));
+        code.extend(push1(0));
+        code.push(EvmOpcode::RETURN as u8);
+        assert_equivalent(code, Fork::Frontier);
+    }
+
+    #[test]
+    fn idisasm_prints_one_line_per_decoded_instruction() {
+        let mut code = push1(2);
+        code.push(EvmOpcode::ADD as u8);
+        let text = idisasm(&code);
+        assert_eq!(text, "PUSH 0x02\nADD\n");
+    }
+
+    #[test]
+    fn idisasm_then_iasm_round_trips_to_the_original_bytecode() {
+        let mut code = push1(2);
+        code.extend(push1(3));
+        code.push(EvmOpcode::ADD as u8);
+        code.push(EvmOpcode::PUSH1 as u8);
+        code.push(0);
+        code.push(EvmOpcode::MSTORE as u8);
+        code.extend(push1(32));
+        code.extend(push1(0));
+        code.push(EvmOpcode::RETURN as u8);
+        let roundtripped = iasm(&idisasm(&code)).unwrap();
+        assert_eq!(roundtripped, code);
+    }
+
+    #[test]
+    fn iasm_reports_an_unknown_mnemonic() {
+        assert!(iasm("NOTANOPCODE").is_err());
+    }
+
+    #[test]
+    fn pc_map_translates_addresses_shifted_by_folding() {
+        // PUSH1 2, PUSH1 3, ADD (addresses 0, 2, 4) folds to a single
+        // PUSH1 5 at address 0; the JUMPDEST after it shifts from 5 to 2.
+        let mut code = push1(2);
+        code.extend(push1(3));
+        code.push(EvmOpcode::ADD as u8);
+        code.push(EvmOpcode::JUMPDEST as u8);
+        let jumpdest_addr = code.len() - 1;
+        let (optimized, pc_map) = optimize_with_pc_map(&code, Fork::Frontier);
+        assert_eq!(optimized.len(), code.len() - 3);
+
+        let new_jumpdest_addr = pc_map.to_optimized(jumpdest_addr as u64).unwrap();
+        assert_eq!(optimized[new_jumpdest_addr as usize], EvmOpcode::JUMPDEST as u8);
+        assert_eq!(pc_map.to_original(new_jumpdest_addr).unwrap(), jumpdest_addr as u64);
+
+        // The PUSH1 3 at address 2 was folded away; its address never
+        // starts an instruction in the optimized code.
+        assert_eq!(pc_map.to_optimized(2), None);
+    }
+
+    #[test]
+    fn pc_map_returns_none_past_the_end_of_the_code() {
+        let code = push1(2);
+        let (optimized, pc_map) = optimize_with_pc_map(&code, Fork::Frontier);
+        assert_eq!(pc_map.to_optimized(code.len() as u64 + 1), None);
+        assert_eq!(pc_map.to_original(optimized.len() as u64 + 1), None);
+    }
+}
@@ -0,0 +1,125 @@
+// Copyright 2020 The Psyche Authors
+// This file is part of Psyche.
+//
+// Psyche is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Psyche is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Psyche. If not, see <http://www.gnu.org/licenses/>.
+
+//! Portable JIT backend, gated behind the `portable-jit` feature, for
+//! targets other than x86-64 (aarch64 first).
+//!
+//! The long-term plan is to drive this from a real Cranelift codegen
+//! backend sharing the optimizer IR (see `opt.rs`) with `jit.rs`, so that
+//! both backends lower the same analyzed blocks. Pulling in the Cranelift
+//! crates is a large step on its own, so this starts by hand-encoding the
+//! same narrow constant-return pattern `jit.rs` supports (via the shared
+//! `jit_pattern` module), directly targeting aarch64 machine code. That
+//! keeps the `ExecutionStrategy` plumbing and the shared execution context
+//! real and exercised while the Cranelift lowering is built out as
+//! follow-up work.
+
+use crate::jit_pattern::{compile_constant_return_block, matches_constant_return};
+
+/// A block of freshly generated, mapped-executable aarch64 machine code.
+pub struct JitBlock {
+    code: memmap::Mmap,
+}
+
+impl JitBlock {
+    /// Calls into the compiled block. Only safe to invoke on an aarch64
+    /// target: `compile_block` always emits aarch64 instructions,
+    /// regardless of the host this crate is built for.
+    #[cfg(target_arch = "aarch64")]
+    pub unsafe fn call(&self) -> u64 {
+        let entry: extern "C" fn() -> u64 = std::mem::transmute(self.code.as_ptr());
+        entry()
+    }
+}
+
+/// Returns true if `compile_block` can produce native code for `bytecode`.
+pub fn is_supported(bytecode: &[u8]) -> bool {
+    matches_constant_return(bytecode).is_some()
+}
+
+/// Assembles a `movz`/`movk` chain loading `value` into `x0` followed by
+/// `ret`, using x0 as the return-value register per the AAPCS64 calling
+/// convention.
+fn assemble_constant_return(value: u64) -> Vec<u8> {
+    let mut insns: Vec<u32> = Vec::with_capacity(5);
+    // movz x0, #imm16
+    insns.push(0xd280_0000 | ((value & 0xffff) << 5) as u32);
+    for shift in 1..4u32 {
+        let chunk = (value >> (shift * 16)) & 0xffff;
+        if chunk != 0 {
+            // movk x0, #imm16, lsl shift*16
+            insns.push(0xf280_0000 | (shift << 21) | ((chunk as u32) << 5));
+        }
+    }
+    insns.push(0xd65f_03c0); // ret
+    let mut code = Vec::with_capacity(insns.len() * 4);
+    for insn in insns {
+        code.extend_from_slice(&insn.to_le_bytes());
+    }
+    code
+}
+
+/// Compiles `bytecode` into native code if it matches a supported pattern,
+/// falling back to `None` (meaning: use the interpreter) otherwise.
+pub fn compile_block(bytecode: &[u8]) -> Option<JitBlock> {
+    let code = compile_constant_return_block(bytecode, assemble_constant_return)?;
+    Some(JitBlock { code })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instructions::EvmOpcode;
+
+    fn push1_stop(value: u8) -> Vec<u8> {
+        vec![EvmOpcode::PUSH1 as u8, value, EvmOpcode::STOP as u8]
+    }
+
+    #[test]
+    fn recognizes_constant_return_pattern() {
+        assert!(is_supported(&push1_stop(42)));
+        assert!(!is_supported(&[EvmOpcode::ADD as u8]));
+    }
+
+    #[test]
+    fn encodes_movz_ret_for_small_constants() {
+        let code = assemble_constant_return(42);
+        assert_eq!(code.len(), 8);
+        assert_eq!(&code[4..8], &0xd65f_03c0u32.to_le_bytes());
+    }
+
+    #[test]
+    fn encodes_movk_chain_for_wide_constants() {
+        let code = assemble_constant_return(0x1234_5678_9abc_def0);
+        // movz + 3 movk + ret = 5 instructions
+        assert_eq!(code.len(), 20);
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    #[test]
+    fn compiles_and_runs_constant_return() {
+        let block = compile_block(&push1_stop(42)).expect("pattern should be supported");
+        let result = unsafe { block.call() };
+        assert_eq!(result, 42);
+    }
+
+    #[test]
+    fn rejects_unsupported_blocks() {
+        let mut code = push1_stop(1);
+        code.push(EvmOpcode::ADD as u8);
+        assert!(compile_block(&code).is_none());
+    }
+}
@@ -0,0 +1,168 @@
+// Copyright 2020 The Psyche Authors
+// This file is part of Psyche.
+//
+// Psyche is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Psyche is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Psyche. If not, see <http://www.gnu.org/licenses/>.
+
+//! The stable surface most embedders need, kept separate from `vm`'s raw,
+//! `unsafe` internals (`VmRom`, `VmMemory`, `run_evm`) so that layout
+//! changes to those don't ripple out as breaking changes here. `Executor`
+//! wraps the analyze-once/run-many-times pattern every `vm::run_evm` call
+//! site in `main.rs` already hand-rolls; `assemble`/`disassemble` round-trip
+//! source and bytecode without reaching into `assembler`/`instructions`
+//! directly.
+//!
+//! Anything not re-exported here (tracing, breakpoints, the optimizer,
+//! chain extensions, ...) is still reachable through the underlying
+//! modules; this is a convenience front door, not a sandbox.
+
+use std::convert::TryFrom;
+
+pub use crate::assembler::Error as AssembleError;
+pub use crate::schedule::{Fork, Schedule};
+pub use crate::u256::U256;
+pub use crate::vm::{BlockContext, BlockHashProvider, TestBlockHashProvider, VmError};
+
+use crate::instructions::EvmOpcode;
+use crate::vm::{self, VmMemory, VmRom};
+
+/// Assembles `source` into bytecode. An alias for [`crate::assembler::from_string`].
+pub fn assemble(source: &str) -> Result<Vec<u8>, AssembleError> {
+    crate::assembler::from_string(source)
+}
+
+/// Disassembles `bytecode` into one `addr: MNEMONIC operand` line per
+/// instruction. Bytes that don't decode to a known opcode are rendered as
+/// `UNKNOWN 0xXX` rather than stopping the disassembly early.
+pub fn disassemble(bytecode: &[u8]) -> String {
+    let mut out = String::new();
+    let mut addr = 0usize;
+    while addr < bytecode.len() {
+        let code = bytecode[addr];
+        match EvmOpcode::try_from(code) {
+            Ok(opcode) if opcode.is_push() => {
+                let num_bytes = opcode.push_index() + 1;
+                let end = (addr + 1 + num_bytes).min(bytecode.len());
+                out.push_str(&format!(
+                    "{:04x}: {} 0x{}\n",
+                    addr,
+                    opcode,
+                    crate::utils::encode_hex(&bytecode[addr + 1..end])
+                ));
+                addr = end;
+            }
+            Ok(opcode) if opcode.is_deep_stack() => {
+                let immediate = bytecode.get(addr + 1).copied().unwrap_or(0);
+                out.push_str(&format!("{:04x}: {} 0x{:02x}\n", addr, opcode, immediate));
+                addr += 2;
+            }
+            Ok(opcode) => {
+                out.push_str(&format!("{:04x}: {}\n", addr, opcode));
+                addr += 1;
+            }
+            Err(_) => {
+                out.push_str(&format!("{:04x}: UNKNOWN 0x{:02x}\n", addr, code));
+                addr += 1;
+            }
+        }
+    }
+    out
+}
+
+/// The outcome of running a contract to completion: its return data, the
+/// gas left in the tank, and the `VmError` that ended execution (`None` on
+/// a normal `STOP`/`RETURN`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecutionResult {
+    pub output: Vec<u8>,
+    pub gas_left: u64,
+    pub error: VmError,
+}
+
+impl ExecutionResult {
+    /// `true` if execution ended without a `VmError`.
+    pub fn is_ok(&self) -> bool {
+        self.error == VmError::None
+    }
+}
+
+/// Owns the analyzed form of a piece of bytecode (`vm::VmRom::init`'s
+/// basic-block/jumpdest analysis) so it only has to be paid for once, no
+/// matter how many times the bytecode is `run`.
+pub struct Executor {
+    bytecode: Vec<u8>,
+    // Boxed: `VmRom` is a flat, half-megabyte-plus array (code + jumpdest
+    // bitmap + per-address basic-block info), too big to move around by
+    // value without risking a debug-build stack overflow (see
+    // `main.rs`'s own `Box::new(VmRom::new())`).
+    rom: Box<VmRom>,
+    schedule: Schedule,
+}
+
+impl Executor {
+    /// Analyzes `bytecode` under `schedule`. Panics if `bytecode` is
+    /// larger than `vm::VmRom::MAX_CODESIZE`, same as `VmRom::init`.
+    pub fn new(bytecode: &[u8], schedule: Schedule) -> Executor {
+        let mut rom = Box::new(VmRom::new());
+        rom.init(bytecode, &schedule);
+        Executor {
+            bytecode: bytecode.to_vec(),
+            rom,
+            schedule,
+        }
+    }
+
+    /// Runs the analyzed bytecode against `block` with `gas_limit` gas,
+    /// returning its result. Safe: sets up and tears down the `VmMemory`
+    /// scratch space `vm::run_evm`'s `unsafe` contract requires internally.
+    pub fn run(&self, block: &BlockContext, gas_limit: U256) -> ExecutionResult {
+        let mut memory = VmMemory::new();
+        memory.init(gas_limit);
+        let ret_data = unsafe {
+            vm::run_evm(&self.bytecode, &self.rom, &self.schedule, block, gas_limit, &mut memory)
+        };
+        let output = memory
+            .checked_slice(ret_data.offset as isize, ret_data.size)
+            .map(<[u8]>::to_vec)
+            .unwrap_or_default();
+        ExecutionResult {
+            output,
+            gas_left: ret_data.gas,
+            error: ret_data.error,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn assembles_and_runs_a_trivial_contract() {
+        // Stores 0x2a at memory offset 0, then returns that 32-byte word.
+        let bytecode = assemble("PUSH1 0x2a\nPUSH1 0x00\nMSTORE\nPUSH1 0x20\nPUSH1 0x00\nRETURN").unwrap();
+        let executor = Executor::new(&bytecode, Schedule::from_fork(Fork::default()));
+        let hashes = TestBlockHashProvider;
+        let block = BlockContext::new(U256::from_u64(0), &hashes);
+        let result = executor.run(&block, U256::from_u64(1_000_000));
+        assert!(result.is_ok());
+        assert_eq!(result.output.last(), Some(&0x2a));
+        assert_eq!(result.output.len(), 32);
+    }
+
+    #[test]
+    fn disassembles_a_push_and_a_plain_opcode() {
+        let bytecode = assemble("PUSH1 0x01\nSTOP").unwrap();
+        assert_eq!(disassemble(&bytecode), "0000: PUSH1 0x01\n0002: STOP\n");
+    }
+}
@@ -0,0 +1,724 @@
+// Copyright 2020 The Psyche Authors
+// This file is part of Psyche.
+//
+// Psyche is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Psyche is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Psyche. If not, see <http://www.gnu.org/licenses/>.
+
+//! A slow, per-instruction-checked reference interpreter, kept
+//! deliberately independent of `vm::run_evm`'s block-precomputed
+//! gas/stack checks and its hand-tuned, SIMD-shaped `u256` arithmetic.
+//! Arithmetic here goes through `ethereum_types::U256` instead, so a bug
+//! shared between the two would have to exist in both an entirely
+//! different integer representation and an entirely different dispatch
+//! loop to go unnoticed. This module exists purely as an oracle for
+//! `tests/opcode.rs` and `tests/reference_fuzz.rs` to cross-check against,
+//! not as an execution path anything else in this crate depends on.
+//!
+//! Mirrors `run_evm`'s exact implemented/unimplemented opcode split (see
+//! `vm::run_evm_impl`): opcodes that need an account model this
+//! interpreter doesn't have (balances, storage, calls, logs, creates,
+//! `REVERT`) report `VmError::InvalidInstruction` the same way, after
+//! charging their own static gas like any other instruction. A genuinely
+//! undefined byte, or one not yet gated in by `schedule`'s fork, is an
+//! exceptional halt that zeroes remaining gas, same as `Opcode::INVALID`.
+
+use std::convert::TryFrom;
+
+use ethereum_types::{U256, U512};
+
+use crate::instructions::{info, EvmOpcode, OPCODE_INFOS};
+use crate::schedule::{Fee, Fork, Schedule};
+use crate::vm::{deep_stack_effect, log256, memory_extend_gas_cost, BlockContext, ReturnData, VmError};
+
+fn to_ref_u256(value: crate::u256::U256) -> U256 {
+    U256(value.0)
+}
+
+/// `keccak256`'s limbs are filled directly from the hash's raw bytes taken
+/// as little-endian (see its doc comment in `u256.rs`), the same layout
+/// `vm::run_evm_impl`'s SIMD `sha3_u256` corrects with a full byte-swap
+/// before treating the result as a normal big-endian `U256` word; redone
+/// here without SIMD by round-tripping through a byte buffer.
+fn keccak_hash_to_u256(hash: crate::u256::U256) -> U256 {
+    let mut bytes = [0u8; 32];
+    to_ref_u256(hash).to_little_endian(&mut bytes);
+    U256::from_big_endian(&bytes)
+}
+
+fn from_ref_u256(value: U256) -> crate::u256::U256 {
+    crate::u256::U256(value.0)
+}
+
+fn is_neg(value: U256) -> bool {
+    value.bit(255)
+}
+
+fn negate(value: U256) -> U256 {
+    (!value).overflowing_add(U256::one()).0
+}
+
+fn low_u256(value: U512) -> U256 {
+    let mut bytes = [0u8; 64];
+    value.to_big_endian(&mut bytes);
+    U256::from_big_endian(&bytes[32..])
+}
+
+fn sdiv(a: U256, b: U256) -> U256 {
+    if b.is_zero() {
+        return U256::zero();
+    }
+    let (neg_a, neg_b) = (is_neg(a), is_neg(b));
+    let ua = if neg_a { negate(a) } else { a };
+    let ub = if neg_b { negate(b) } else { b };
+    let q = ua / ub;
+    if neg_a != neg_b {
+        negate(q)
+    } else {
+        q
+    }
+}
+
+fn smod(a: U256, b: U256) -> U256 {
+    if b.is_zero() {
+        return U256::zero();
+    }
+    let neg_a = is_neg(a);
+    let ua = if neg_a { negate(a) } else { a };
+    let ub = if is_neg(b) { negate(b) } else { b };
+    let r = ua % ub;
+    if neg_a {
+        negate(r)
+    } else {
+        r
+    }
+}
+
+fn addmod(a: U256, b: U256, n: U256) -> U256 {
+    if n.is_zero() {
+        return U256::zero();
+    }
+    low_u256((U512::from(a) + U512::from(b)) % U512::from(n))
+}
+
+fn mulmod(a: U256, b: U256, n: U256) -> U256 {
+    if n.is_zero() {
+        return U256::zero();
+    }
+    low_u256(a.full_mul(b) % U512::from(n))
+}
+
+fn exp(a: U256, mut exponent: U256) -> U256 {
+    let mut result = U256::one();
+    let mut base = a;
+    while !exponent.is_zero() {
+        if exponent & U256::one() == U256::one() {
+            result = result.overflowing_mul(base).0;
+        }
+        exponent >>= 1;
+        if !exponent.is_zero() {
+            base = base.overflowing_mul(base).0;
+        }
+    }
+    result
+}
+
+/// `index` is a byte position counted from the least significant byte of
+/// `value` (`SIGNEXTEND`'s first stack argument); `index >= 32` leaves
+/// `value` untouched, since every byte is already "in range".
+fn signextend(index: U256, value: U256) -> U256 {
+    if index >= U256::from(32u64) {
+        return value;
+    }
+    let mut bytes = [0u8; 32];
+    value.to_big_endian(&mut bytes);
+    let sign_pos = 31 - index.low_u32() as usize;
+    let fill = if bytes[sign_pos] & 0x80 != 0 { 0xffu8 } else { 0u8 };
+    for byte in bytes.iter_mut().take(sign_pos) {
+        *byte = fill;
+    }
+    U256::from_big_endian(&bytes)
+}
+
+fn byte_op(index: U256, value: U256) -> U256 {
+    if index >= U256::from(32u64) {
+        return U256::zero();
+    }
+    let mut bytes = [0u8; 32];
+    value.to_big_endian(&mut bytes);
+    U256::from(bytes[index.low_u32() as usize])
+}
+
+fn shl(shift: U256, value: U256) -> U256 {
+    if shift >= U256::from(256u64) {
+        U256::zero()
+    } else {
+        value << shift.low_u32()
+    }
+}
+
+fn shr(shift: U256, value: U256) -> U256 {
+    if shift >= U256::from(256u64) {
+        U256::zero()
+    } else {
+        value >> shift.low_u32()
+    }
+}
+
+fn sar(shift: U256, value: U256) -> U256 {
+    if !is_neg(value) {
+        return shr(shift, value);
+    }
+    if shift >= U256::from(256u64) {
+        return U256::max_value();
+    }
+    !((!value) >> shift.low_u32())
+}
+
+/// A valid `JUMP`/`JUMPI` destination: a `JUMPDEST` byte that isn't inside
+/// a `PUSHN`/deep-stack immediate. A second, separate bitmap does the same
+/// for `BEGINSUB` (`JUMPSUB`'s valid destinations) -- analogous to what
+/// `VmRom::init` computes over the raw bytecode, but recomputed from
+/// scratch here rather than shared, so a bug in that analysis wouldn't be
+/// mirrored by the oracle checking it.
+fn analyze_destinations(bytecode: &[u8]) -> (Vec<bool>, Vec<bool>) {
+    let mut jumpdest = vec![false; bytecode.len()];
+    let mut beginsub = vec![false; bytecode.len()];
+    let mut pc = 0;
+    while pc < bytecode.len() {
+        let opcode = EvmOpcode::try_from(bytecode[pc]).ok();
+        match opcode {
+            Some(EvmOpcode::JUMPDEST) => jumpdest[pc] = true,
+            Some(EvmOpcode::BEGINSUB) => beginsub[pc] = true,
+            _ => {}
+        }
+        pc += 1 + opcode.map_or(0, |op| {
+            if op.is_push() {
+                op.push_index() + 1
+            } else if op.is_deep_stack() {
+                1
+            } else {
+                0
+            }
+        });
+    }
+    (jumpdest, beginsub)
+}
+
+/// Grows `memory` (if needed) to cover `[offset, offset + size)`, charging
+/// `schedule`'s per-word memory cost for any growth, exactly like
+/// `vm::run_evm_impl`'s `extend_memory!`/`meter_extend!` macros. A
+/// zero-size range is always free and never touches `offset`, however
+/// large or malformed, per spec.
+fn extend_memory(memory: &mut Vec<u8>, offset: U256, size: U256, schedule: &Schedule, gas: &mut u64) -> Result<(), VmError> {
+    if size.is_zero() {
+        return Ok(());
+    }
+    if offset > U256::from(u64::MAX) || size > U256::from(u64::MAX) {
+        return Err(VmError::OutOfGas);
+    }
+    let (end, overflow1) = offset.low_u64().overflowing_add(size.low_u64());
+    let (end, overflow2) = end.overflowing_add(31);
+    if overflow1 || overflow2 {
+        return Err(VmError::OutOfGas);
+    }
+    let new_num_words = end / 32;
+    let num_words = (memory.len() as u64) / 32;
+    if new_num_words > num_words {
+        let cost = memory_extend_gas_cost(schedule.memory_gas, num_words, new_num_words);
+        let (new_gas, underflow) = gas.overflowing_sub(cost);
+        if underflow {
+            return Err(VmError::OutOfGas);
+        }
+        *gas = new_gas;
+        memory.resize(new_num_words as usize * 32, 0);
+    }
+    Ok(())
+}
+
+/// Runs `bytecode` against this crate's `ReturnData`/`VmError`/`Schedule`
+/// types, so it can serve as a genuine oracle rather than a mismatched
+/// toy. Returns the `RETURN`ed bytes alongside `ReturnData`, since this
+/// interpreter's memory is a plain, owned `Vec` rather than `vm::VmMemory`'s
+/// `mmap`, so there's no shared buffer for a caller to slice afterwards
+/// the way `run_evm`'s callers do.
+pub fn run(bytecode: &[u8], schedule: &Schedule, block: &BlockContext, gas_limit: u64) -> (ReturnData, Vec<u8>) {
+    let (jumpdest, beginsub) = analyze_destinations(bytecode);
+    let mut stack: Vec<U256> = Vec::new();
+    let mut rstack: Vec<usize> = Vec::new();
+    let mut memory: Vec<u8> = Vec::new();
+    let mut gas = gas_limit;
+    let mut pc: usize = 0;
+
+    macro_rules! fail {
+        ($error:expr) => {
+            return (ReturnData::new(0, 0, gas, $error), Vec::new())
+        };
+    }
+
+    loop {
+        if pc >= bytecode.len() {
+            return (ReturnData::ok(0, 0, gas), Vec::new());
+        }
+        let raw_opcode = bytecode[pc];
+        let opcode = EvmOpcode::try_from(raw_opcode)
+            .ok()
+            .filter(|op| schedule.fork >= info(*op).introduced_fork);
+        let opcode = match opcode {
+            Some(opcode) => opcode,
+            None => {
+                // Same exceptional halt as `Opcode::INVALID`: a genuinely
+                // undefined byte and a not-yet-gated-in opcode both zero
+                // all remaining gas.
+                gas = 0;
+                fail!(VmError::InvalidInstruction);
+            }
+        };
+
+        let (inputs, outputs) = if opcode.is_deep_stack() {
+            deep_stack_effect(opcode, *bytecode.get(pc + 1).unwrap_or(&0))
+        } else {
+            let (_, _, inputs, outputs) = OPCODE_INFOS[raw_opcode as usize];
+            (inputs, outputs)
+        };
+        if stack.len() < inputs as usize {
+            fail!(VmError::StackUnderflow);
+        }
+        if stack.len() - inputs as usize + outputs as usize > schedule.stack_limit {
+            fail!(VmError::StackOverflow);
+        }
+
+        let cost = schedule.opcode_gas(opcode, info(opcode).fee_class);
+        match gas.overflowing_sub(cost) {
+            (new_gas, false) => gas = new_gas,
+            (_, true) => fail!(VmError::OutOfGas),
+        }
+
+        match opcode {
+            EvmOpcode::STOP => return (ReturnData::ok(0, 0, gas), Vec::new()),
+            EvmOpcode::ADD => {
+                let (a, b) = (stack.pop().unwrap(), stack.pop().unwrap());
+                stack.push(a.overflowing_add(b).0);
+            }
+            EvmOpcode::MUL => {
+                let (a, b) = (stack.pop().unwrap(), stack.pop().unwrap());
+                stack.push(a.overflowing_mul(b).0);
+            }
+            EvmOpcode::SUB => {
+                let (a, b) = (stack.pop().unwrap(), stack.pop().unwrap());
+                stack.push(a.overflowing_sub(b).0);
+            }
+            EvmOpcode::DIV => {
+                let (a, b) = (stack.pop().unwrap(), stack.pop().unwrap());
+                stack.push(a.checked_div(b).unwrap_or_else(U256::zero));
+            }
+            EvmOpcode::MOD => {
+                let (a, b) = (stack.pop().unwrap(), stack.pop().unwrap());
+                stack.push(a.checked_rem(b).unwrap_or_else(U256::zero));
+            }
+            EvmOpcode::SDIV => {
+                let (a, b) = (stack.pop().unwrap(), stack.pop().unwrap());
+                stack.push(sdiv(a, b));
+            }
+            EvmOpcode::SMOD => {
+                let (a, b) = (stack.pop().unwrap(), stack.pop().unwrap());
+                stack.push(smod(a, b));
+            }
+            EvmOpcode::ADDMOD => {
+                let (a, b, n) = (stack.pop().unwrap(), stack.pop().unwrap(), stack.pop().unwrap());
+                stack.push(addmod(a, b, n));
+            }
+            EvmOpcode::MULMOD => {
+                let (a, b, n) = (stack.pop().unwrap(), stack.pop().unwrap(), stack.pop().unwrap());
+                stack.push(mulmod(a, b, n));
+            }
+            EvmOpcode::EXP => {
+                let (a, b) = (stack.pop().unwrap(), stack.pop().unwrap());
+                let exponent_bits = (256 - b.leading_zeros()) as u64;
+                let fee = schedule.opcode_gas(EvmOpcode::EXP, Fee::ExpByte);
+                let exp_cost = (exponent_bits > 0) as u64 * fee * (1 + log256(exponent_bits));
+                match gas.overflowing_sub(exp_cost) {
+                    (new_gas, false) => gas = new_gas,
+                    (_, true) => fail!(VmError::OutOfGas),
+                }
+                stack.push(exp(a, b));
+            }
+            EvmOpcode::SIGNEXTEND => {
+                let (a, b) = (stack.pop().unwrap(), stack.pop().unwrap());
+                stack.push(signextend(a, b));
+            }
+            EvmOpcode::LT => {
+                let (a, b) = (stack.pop().unwrap(), stack.pop().unwrap());
+                stack.push(U256::from((a < b) as u64));
+            }
+            EvmOpcode::GT => {
+                let (a, b) = (stack.pop().unwrap(), stack.pop().unwrap());
+                stack.push(U256::from((a > b) as u64));
+            }
+            EvmOpcode::SLT => {
+                let (a, b) = (stack.pop().unwrap(), stack.pop().unwrap());
+                let result = match (is_neg(a), is_neg(b)) {
+                    (true, false) => true,
+                    (false, true) => false,
+                    _ => a < b,
+                };
+                stack.push(U256::from(result as u64));
+            }
+            EvmOpcode::SGT => {
+                let (a, b) = (stack.pop().unwrap(), stack.pop().unwrap());
+                let result = match (is_neg(a), is_neg(b)) {
+                    (true, false) => false,
+                    (false, true) => true,
+                    _ => a > b,
+                };
+                stack.push(U256::from(result as u64));
+            }
+            EvmOpcode::EQ => {
+                let (a, b) = (stack.pop().unwrap(), stack.pop().unwrap());
+                stack.push(U256::from((a == b) as u64));
+            }
+            EvmOpcode::ISZERO => {
+                let a = stack.pop().unwrap();
+                stack.push(U256::from(a.is_zero() as u64));
+            }
+            EvmOpcode::AND => {
+                let (a, b) = (stack.pop().unwrap(), stack.pop().unwrap());
+                stack.push(a & b);
+            }
+            EvmOpcode::OR => {
+                let (a, b) = (stack.pop().unwrap(), stack.pop().unwrap());
+                stack.push(a | b);
+            }
+            EvmOpcode::XOR => {
+                let (a, b) = (stack.pop().unwrap(), stack.pop().unwrap());
+                stack.push(a ^ b);
+            }
+            EvmOpcode::NOT => {
+                let a = stack.pop().unwrap();
+                stack.push(!a);
+            }
+            EvmOpcode::BYTE => {
+                let (a, b) = (stack.pop().unwrap(), stack.pop().unwrap());
+                stack.push(byte_op(a, b));
+            }
+            EvmOpcode::SHL => {
+                let (a, b) = (stack.pop().unwrap(), stack.pop().unwrap());
+                stack.push(shl(a, b));
+            }
+            EvmOpcode::SHR => {
+                let (a, b) = (stack.pop().unwrap(), stack.pop().unwrap());
+                stack.push(shr(a, b));
+            }
+            EvmOpcode::SAR => {
+                let (a, b) = (stack.pop().unwrap(), stack.pop().unwrap());
+                stack.push(sar(a, b));
+            }
+            EvmOpcode::SHA3 => {
+                let (offset, size) = (stack.pop().unwrap(), stack.pop().unwrap());
+                if size > U256::from(u64::MAX) {
+                    fail!(VmError::OutOfGas);
+                }
+                let fee = schedule.opcode_gas(EvmOpcode::SHA3, Fee::Sha3Word);
+                let num_words = size.low_u64().div_ceil(32);
+                match num_words.overflowing_mul(fee) {
+                    (sha3_cost, false) => match gas.overflowing_sub(sha3_cost) {
+                        (new_gas, false) => gas = new_gas,
+                        (_, true) => fail!(VmError::OutOfGas),
+                    },
+                    (_, true) => fail!(VmError::OutOfGas),
+                }
+                if let Err(e) = extend_memory(&mut memory, offset, size, schedule, &mut gas) {
+                    fail!(e);
+                }
+                // A zero-size range never touches `offset`, per `extend_memory`'s
+                // own doc comment -- `offset` alone could be enormous here.
+                let hash = if size.is_zero() {
+                    unsafe { crate::u256::keccak256(std::ptr::null(), 0) }
+                } else {
+                    let start = offset.low_u64() as usize;
+                    let end = start + size.low_u64() as usize;
+                    unsafe { crate::u256::keccak256(memory[start..end].as_ptr(), end - start) }
+                };
+                stack.push(keccak_hash_to_u256(hash));
+            }
+            EvmOpcode::CODESIZE => stack.push(U256::from(bytecode.len() as u64)),
+            EvmOpcode::BLOCKHASH => {
+                let number = from_ref_u256(stack.pop().unwrap());
+                let hash = if crate::u256::lt_u256(number, block.number) {
+                    let age = crate::u256::sub_u256(block.number, number);
+                    if !crate::u256::gt_u256(age, crate::u256::U256::from_u64(256)) {
+                        block.hashes.block_hash(number)
+                    } else {
+                        crate::u256::U256::from_u64(0)
+                    }
+                } else {
+                    crate::u256::U256::from_u64(0)
+                };
+                stack.push(to_ref_u256(hash));
+            }
+            EvmOpcode::DIFFICULTY => {
+                let value = if schedule.fork >= Fork::Paris { block.prevrandao } else { block.difficulty };
+                stack.push(to_ref_u256(value));
+            }
+            EvmOpcode::GASPRICE => stack.push(to_ref_u256(block.effective_gas_price(schedule.fork))),
+            EvmOpcode::BLOBHASH => {
+                let index = from_ref_u256(stack.pop().unwrap());
+                let hash = if index.le_u64() {
+                    block
+                        .versioned_hashes
+                        .get(index.low_u64() as usize)
+                        .copied()
+                        .unwrap_or_else(|| crate::u256::U256::from_u64(0))
+                } else {
+                    crate::u256::U256::from_u64(0)
+                };
+                stack.push(to_ref_u256(hash));
+            }
+            EvmOpcode::BLOBBASEFEE => stack.push(to_ref_u256(block.blob_gasprice)),
+            EvmOpcode::ADDRESS
+            | EvmOpcode::BALANCE
+            | EvmOpcode::ORIGIN
+            | EvmOpcode::CALLER
+            | EvmOpcode::CALLVALUE
+            | EvmOpcode::CALLDATALOAD
+            | EvmOpcode::CALLDATASIZE
+            | EvmOpcode::CALLDATACOPY
+            | EvmOpcode::CODECOPY
+            | EvmOpcode::EXTCODESIZE
+            | EvmOpcode::EXTCODECOPY
+            | EvmOpcode::RETURNDATASIZE
+            | EvmOpcode::RETURNDATACOPY
+            | EvmOpcode::EXTCODEHASH
+            | EvmOpcode::COINBASE
+            | EvmOpcode::TIMESTAMP
+            | EvmOpcode::NUMBER
+            | EvmOpcode::GASLIMIT
+            | EvmOpcode::CHAINID
+            | EvmOpcode::SELFBALANCE
+            | EvmOpcode::SLOAD
+            | EvmOpcode::SSTORE
+            | EvmOpcode::LOG0
+            | EvmOpcode::LOG1
+            | EvmOpcode::LOG2
+            | EvmOpcode::LOG3
+            | EvmOpcode::LOG4
+            | EvmOpcode::CREATE
+            | EvmOpcode::CALL
+            | EvmOpcode::CALLCODE
+            | EvmOpcode::DELEGATECALL
+            | EvmOpcode::CREATE2
+            | EvmOpcode::STATICCALL
+            | EvmOpcode::REVERT
+            | EvmOpcode::SELFDESTRUCT => fail!(VmError::InvalidInstruction),
+            EvmOpcode::POP => {
+                stack.pop().unwrap();
+            }
+            EvmOpcode::MLOAD => {
+                let offset = stack.pop().unwrap();
+                if let Err(e) = extend_memory(&mut memory, offset, U256::from(32u64), schedule, &mut gas) {
+                    fail!(e);
+                }
+                let start = offset.low_u64() as usize;
+                stack.push(U256::from_big_endian(&memory[start..start + 32]));
+            }
+            EvmOpcode::MSTORE => {
+                let (offset, value) = (stack.pop().unwrap(), stack.pop().unwrap());
+                if let Err(e) = extend_memory(&mut memory, offset, U256::from(32u64), schedule, &mut gas) {
+                    fail!(e);
+                }
+                let start = offset.low_u64() as usize;
+                value.to_big_endian(&mut memory[start..start + 32]);
+            }
+            EvmOpcode::MSTORE8 => {
+                let (offset, value) = (stack.pop().unwrap(), stack.pop().unwrap());
+                if let Err(e) = extend_memory(&mut memory, offset, U256::from(1u64), schedule, &mut gas) {
+                    fail!(e);
+                }
+                let start = offset.low_u64() as usize;
+                memory[start] = value.low_u32() as u8;
+            }
+            EvmOpcode::JUMP => {
+                let addr = stack.pop().unwrap();
+                let low = addr.low_u64();
+                if addr <= U256::from(u64::MAX) && (low as usize) < jumpdest.len() && jumpdest[low as usize] {
+                    pc = low as usize;
+                    continue;
+                }
+                fail!(VmError::InvalidJumpDest);
+            }
+            EvmOpcode::JUMPI => {
+                let (addr, cond) = (stack.pop().unwrap(), stack.pop().unwrap());
+                if cond.is_zero() {
+                    pc += 1;
+                    continue;
+                }
+                let low = addr.low_u64();
+                if addr <= U256::from(u64::MAX) && (low as usize) < jumpdest.len() && jumpdest[low as usize] {
+                    pc = low as usize;
+                    continue;
+                }
+                fail!(VmError::InvalidJumpDest);
+            }
+            EvmOpcode::PC => stack.push(U256::from(pc as u64)),
+            EvmOpcode::MSIZE => stack.push(U256::from(memory.len() as u64)),
+            EvmOpcode::GAS => stack.push(U256::from(gas)),
+            EvmOpcode::JUMPDEST => {}
+            EvmOpcode::BEGINSUB => fail!(VmError::BeginSubEntry),
+            EvmOpcode::RETURNSUB => match rstack.pop() {
+                Some(addr) => {
+                    pc = addr;
+                    continue;
+                }
+                None => fail!(VmError::ReturnStackUnderflow),
+            },
+            EvmOpcode::JUMPSUB => {
+                let addr = stack.pop().unwrap();
+                let low = addr.low_u64();
+                // Matches `VmReturnStack::LEN`.
+                if rstack.len() >= 1023 {
+                    fail!(VmError::ReturnStackOverflow);
+                }
+                if addr <= U256::from(u64::MAX) && (low as usize) < beginsub.len() && beginsub[low as usize] {
+                    rstack.push(pc + 1);
+                    // Lands one past `BEGINSUB` itself, same as `Opcode::JUMPSUB`
+                    // (`pc = low as usize + 1`) -- `BEGINSUB` is a valid jump
+                    // target but isn't itself dispatched on entry.
+                    pc = low as usize + 1;
+                    continue;
+                }
+                fail!(VmError::InvalidBeginSub);
+            }
+            EvmOpcode::PUSH1
+            | EvmOpcode::PUSH2
+            | EvmOpcode::PUSH3
+            | EvmOpcode::PUSH4
+            | EvmOpcode::PUSH5
+            | EvmOpcode::PUSH6
+            | EvmOpcode::PUSH7
+            | EvmOpcode::PUSH8
+            | EvmOpcode::PUSH9
+            | EvmOpcode::PUSH10
+            | EvmOpcode::PUSH11
+            | EvmOpcode::PUSH12
+            | EvmOpcode::PUSH13
+            | EvmOpcode::PUSH14
+            | EvmOpcode::PUSH15
+            | EvmOpcode::PUSH16
+            | EvmOpcode::PUSH17
+            | EvmOpcode::PUSH18
+            | EvmOpcode::PUSH19
+            | EvmOpcode::PUSH20
+            | EvmOpcode::PUSH21
+            | EvmOpcode::PUSH22
+            | EvmOpcode::PUSH23
+            | EvmOpcode::PUSH24
+            | EvmOpcode::PUSH25
+            | EvmOpcode::PUSH26
+            | EvmOpcode::PUSH27
+            | EvmOpcode::PUSH28
+            | EvmOpcode::PUSH29
+            | EvmOpcode::PUSH30
+            | EvmOpcode::PUSH31
+            | EvmOpcode::PUSH32 => {
+                let num_bytes = opcode.push_index() + 1;
+                let mut bytes = [0u8; 32];
+                let available = bytecode.len().saturating_sub(pc + 1).min(num_bytes);
+                bytes[32 - num_bytes..32 - num_bytes + available].copy_from_slice(&bytecode[pc + 1..pc + 1 + available]);
+                stack.push(U256::from_big_endian(&bytes));
+                pc += 1 + num_bytes;
+                continue;
+            }
+            EvmOpcode::DUP1
+            | EvmOpcode::DUP2
+            | EvmOpcode::DUP3
+            | EvmOpcode::DUP4
+            | EvmOpcode::DUP5
+            | EvmOpcode::DUP6
+            | EvmOpcode::DUP7
+            | EvmOpcode::DUP8
+            | EvmOpcode::DUP9
+            | EvmOpcode::DUP10
+            | EvmOpcode::DUP11
+            | EvmOpcode::DUP12
+            | EvmOpcode::DUP13
+            | EvmOpcode::DUP14
+            | EvmOpcode::DUP15
+            | EvmOpcode::DUP16 => {
+                let depth = (raw_opcode - EvmOpcode::DUP1 as u8) as usize;
+                let value = stack[stack.len() - 1 - depth];
+                stack.push(value);
+            }
+            EvmOpcode::SWAP1
+            | EvmOpcode::SWAP2
+            | EvmOpcode::SWAP3
+            | EvmOpcode::SWAP4
+            | EvmOpcode::SWAP5
+            | EvmOpcode::SWAP6
+            | EvmOpcode::SWAP7
+            | EvmOpcode::SWAP8
+            | EvmOpcode::SWAP9
+            | EvmOpcode::SWAP10
+            | EvmOpcode::SWAP11
+            | EvmOpcode::SWAP12
+            | EvmOpcode::SWAP13
+            | EvmOpcode::SWAP14
+            | EvmOpcode::SWAP15
+            | EvmOpcode::SWAP16 => {
+                let depth = 1 + (raw_opcode - EvmOpcode::SWAP1 as u8) as usize;
+                let top = stack.len() - 1;
+                stack.swap(top, top - depth);
+            }
+            EvmOpcode::DUPN => {
+                let immediate = *bytecode.get(pc + 1).unwrap_or(&0);
+                let depth = immediate as usize + 1;
+                let value = stack[stack.len() - depth];
+                stack.push(value);
+                pc += 2;
+                continue;
+            }
+            EvmOpcode::SWAPN => {
+                let immediate = *bytecode.get(pc + 1).unwrap_or(&0);
+                let depth = immediate as usize + 1;
+                let top = stack.len() - 1;
+                stack.swap(top, top - depth);
+                pc += 2;
+                continue;
+            }
+            EvmOpcode::EXCHANGE => {
+                let immediate = *bytecode.get(pc + 1).unwrap_or(&0);
+                let n = (immediate >> 4) as usize + 1;
+                let m = (immediate & 0x0f) as usize + 1;
+                let top = stack.len() - 1;
+                stack.swap(top - n, top - n - m);
+                pc += 2;
+                continue;
+            }
+            EvmOpcode::RETURN => {
+                let (offset, size) = (stack.pop().unwrap(), stack.pop().unwrap());
+                if let Err(e) = extend_memory(&mut memory, offset, size, schedule, &mut gas) {
+                    fail!(e);
+                }
+                if size.is_zero() {
+                    return (ReturnData::ok(0, 0, gas), Vec::new());
+                }
+                let start = offset.low_u64() as usize;
+                let end = start + size.low_u64() as usize;
+                return (ReturnData::ok(start, end - start, gas), memory[start..end].to_vec());
+            }
+            EvmOpcode::INVALID => {
+                gas = 0;
+                fail!(VmError::InvalidInstruction)
+            }
+        }
+        pc += 1;
+    }
+}
@@ -16,7 +16,9 @@
 
 use std::str::FromStr;
 
-#[derive(Copy, Clone, Debug, PartialEq, PartialOrd)]
+use crate::instructions::EvmOpcode;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd)]
 pub enum Fork {
     Frontier,
     Thawing,
@@ -28,9 +30,38 @@ pub enum Fork {
     Constantinople,
     Istanbul,
     Berlin,
+    /// Introduces EIP-1559: `GASPRICE` returns
+    /// `min(max_fee_per_gas, base_fee + max_priority_fee_per_gas)`
+    /// instead of the transaction's flat `gas_price` (see
+    /// `BlockContext::effective_gas_price`). `BASEFEE` (0x48) isn't
+    /// modeled as an opcode yet, so there's no way to read `base_fee`
+    /// from bytecode directly, only through `GASPRICE`'s result.
+    London,
+    /// The Merge: `DIFFICULTY` (0x44) starts returning `prevrandao`
+    /// instead of the (now-meaningless, PoS blocks have no difficulty)
+    /// proof-of-work difficulty. No gas repricing of its own.
+    Paris,
+    /// Introduces `PUSH0` (EIP-3160/3855), initcode size metering
+    /// (EIP-3860), and pre-warms `COINBASE` in the EIP-2929 access list
+    /// (EIP-3651). None of those are modeled by this interpreter yet:
+    /// there's no `PUSH0` opcode, no `CREATE`/`CREATE2` to meter initcode
+    /// for, and no access-list state to warm a slot in at all (see
+    /// `VmRom::has_dynamic_access_cost`, which already keeps Berlin's
+    /// warm/cold split at its Istanbul floor for the same reason). So this
+    /// is a name-only placeholder that keeps fork ordering correct for
+    /// callers that ask for it by name.
+    Shanghai,
+    /// Introduces transient storage, `MCOPY`, and blob-related opcodes.
+    /// `BLOBHASH` and `BLOBBASEFEE` are modeled (see `BlockContext`'s
+    /// `versioned_hashes` and `blob_gasprice`); transient storage and
+    /// `MCOPY` still don't exist in this interpreter.
+    Cancun,
+    /// Not finalized at the time of writing; included only so fork name
+    /// parsing and ordering have somewhere to put it ahead of `Cancun`.
+    Prague,
 }
 
-const FORK_LEN: usize = Fork::Berlin as usize + 1;
+const FORK_LEN: usize = Fork::Prague as usize + 1;
 
 impl FromStr for Fork {
     type Err = ();
@@ -47,52 +78,88 @@ impl FromStr for Fork {
             "Constantinople" => Ok(Fork::Constantinople),
             "Istanbul" => Ok(Fork::Istanbul),
             "Berlin" => Ok(Fork::Berlin),
+            "London" => Ok(Fork::London),
+            "Paris" => Ok(Fork::Paris),
+            "Shanghai" => Ok(Fork::Shanghai),
+            "Cancun" => Ok(Fork::Cancun),
+            "Prague" => Ok(Fork::Prague),
             _ => Err(()),
         }
     }
 }
 
-pub fn to_block_number(fork: Fork) -> u64 {
+/// Mainnet's activation block number for `fork`, or `None` for a fork that
+/// doesn't have one: `Paris` (the Merge) switched over at a terminal total
+/// difficulty rather than a block number, and `Prague` isn't finalized at
+/// the time of writing (see both forks' doc comments on `Fork`).
+pub fn to_block_number(fork: Fork) -> Option<u64> {
     match fork {
-        Fork::Frontier => 1,
-        Fork::Thawing => 200_000,
-        Fork::Homestead => 1_150_000,
-        Fork::Dao => 1_920_000,
-        Fork::Tangerine => 2_463_000,
-        Fork::Spurious => 2_675_000,
-        Fork::Byzantium => 4_370_000,
-        Fork::Constantinople => 7_280_000,
-        Fork::Istanbul => 9_069_000,
-        Fork::Berlin => panic!(),
+        Fork::Frontier => Some(1),
+        Fork::Thawing => Some(200_000),
+        Fork::Homestead => Some(1_150_000),
+        Fork::Dao => Some(1_920_000),
+        Fork::Tangerine => Some(2_463_000),
+        Fork::Spurious => Some(2_675_000),
+        Fork::Byzantium => Some(4_370_000),
+        Fork::Constantinople => Some(7_280_000),
+        Fork::Istanbul => Some(9_069_000),
+        Fork::Berlin => Some(12_244_000),
+        Fork::London => Some(12_965_000),
+        Fork::Paris => None,
+        Fork::Shanghai => Some(17_034_870),
+        Fork::Cancun => Some(19_426_587),
+        Fork::Prague => None,
     }
 }
 
+/// All `Fork` variants, oldest first, for `from_block`'s scan; kept as an
+/// explicit list (rather than `Fork::Dao as u8..=Fork::Prague as u8`
+/// transmuted back) so adding a fork is a one-line addition here, not an
+/// `unsafe` cast to audit.
+const FORKS_OLDEST_FIRST: [Fork; FORK_LEN] = [
+    Fork::Frontier,
+    Fork::Thawing,
+    Fork::Homestead,
+    Fork::Dao,
+    Fork::Tangerine,
+    Fork::Spurious,
+    Fork::Byzantium,
+    Fork::Constantinople,
+    Fork::Istanbul,
+    Fork::Berlin,
+    Fork::London,
+    Fork::Paris,
+    Fork::Shanghai,
+    Fork::Cancun,
+    Fork::Prague,
+];
+
 impl Fork {
     pub const fn default() -> Fork {
         Fork::Frontier
     }
 
+    /// The fork active at mainnet block `number`. Forks `to_block_number`
+    /// has no activation block for (`Paris`, `Prague`) are skipped rather
+    /// than ever being returned directly: `number` keeps resolving to the
+    /// nearest earlier fork that does have one (e.g. every Paris-range
+    /// block resolves to `London`, since Paris repriced nothing -- see its
+    /// doc comment on `Fork`).
     pub fn from_block(number: u64) -> Fork {
-        let block_fork = |f| (to_block_number(f), f);
-        let block_forks: [(u64, Fork); FORK_LEN] = [
-            block_fork(Fork::Frontier),
-            block_fork(Fork::Thawing),
-            block_fork(Fork::Homestead),
-            block_fork(Fork::Dao),
-            block_fork(Fork::Tangerine),
-            block_fork(Fork::Spurious),
-            block_fork(Fork::Byzantium),
-            block_fork(Fork::Constantinople),
-            block_fork(Fork::Istanbul),
-            block_fork(Fork::Istanbul),
-        ];
         assert!(number != 0, "block number must be greater than 0");
-        let pos = block_forks.iter().position(|(x, _)| *x > number);
-        block_forks[pos.unwrap_or(FORK_LEN) - 1].1
+        let mut result = Fork::Frontier;
+        for &fork in FORKS_OLDEST_FIRST.iter() {
+            match to_block_number(fork) {
+                Some(activation) if activation <= number => result = fork,
+                Some(_) => break,
+                None => (),
+            }
+        }
+        result
     }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Fee {
     Zero,
     Base,
@@ -112,6 +179,23 @@ pub enum Fee {
 
 const FEE_LEN: usize = 14;
 
+/// Mainnet's stack depth limit (EIP-150 didn't change it; it's been 1024
+/// since Frontier). Kept here as `Schedule::stack_limit`'s default rather
+/// than baked into the interpreter, since some EVM variants and research
+/// setups run with a different limit; the interpreter's stack storage is
+/// still a fixed-size array, so `VmStack::MAX_LEN` is the hard ceiling any
+/// configured `stack_limit` above it gets clamped to.
+pub const DEFAULT_STACK_LIMIT: usize = crate::limits::MAX_STACK;
+
+/// Mainnet's `JUMPSUB`/`RETURNSUB` shadow-stack depth limit (EIP-2315),
+/// one short of `DEFAULT_STACK_LIMIT` the same way `VmReturnStack::LEN`
+/// is one short of `VmStack::MAX_LEN`. Kept here as `Schedule`'s default
+/// for the same reason as `DEFAULT_STACK_LIMIT`: some research setups run
+/// with a different limit, and the interpreter's return-stack storage is
+/// still a fixed-size array, so any configured limit above
+/// `VmReturnStack::LEN` gets clamped to it.
+pub const DEFAULT_RETURN_STACK_LIMIT: usize = 1023;
+
 impl Fee {
     /// Returns the gas cost associated to a given fork
     pub fn gas(self, schedule: &Schedule) -> u32 {
@@ -124,6 +208,25 @@ pub struct Schedule {
     pub fees: [u32; FEE_LEN],
     pub memory_gas: u64,
     pub fork: Fork,
+    /// Per-opcode gas cost overrides installed by `with_overrides`, for
+    /// researchers experimenting with repricing without forking the crate.
+    /// Consulted by `opcode_gas`, which every gas computation keyed on a
+    /// specific opcode (rather than just a `Fee` category) should go
+    /// through instead of `Fee::gas` directly, so overrides reach both
+    /// `VmRom`'s block-level gas precomputation and per-instance dynamic
+    /// costs like `EXP`/`SHA3` uniformly.
+    gas_overrides: [Option<u64>; 256],
+    /// The maximum EVM stack depth, defaulting to mainnet's 1024
+    /// (`DEFAULT_STACK_LIMIT`). Overridable via `with_stack_limit` for EVM
+    /// variants/research setups that use a different limit; the
+    /// interpreter clamps this to `VmStack::MAX_LEN` wherever it's
+    /// consulted, since its stack storage is a fixed-size array.
+    pub stack_limit: usize,
+    /// The maximum `JUMPSUB`/`RETURNSUB` shadow-stack depth (EIP-2315),
+    /// defaulting to `DEFAULT_RETURN_STACK_LIMIT`. Overridable via
+    /// `with_return_stack_limit`; clamped to `VmReturnStack::LEN` wherever
+    /// it's consulted, same as `stack_limit` is to `VmStack::MAX_LEN`.
+    pub return_stack_limit: usize,
 }
 
 impl Schedule {
@@ -131,7 +234,41 @@ impl Schedule {
         Schedule::from_fork(Fork::default())
     }
 
+    /// Overrides `opcode`'s effective gas cost to `cost`, in place of
+    /// whatever `Fee` category it's normally priced under. Later calls for
+    /// the same opcode replace earlier ones.
+    pub fn with_overrides(mut self, overrides: &[(EvmOpcode, u64)]) -> Schedule {
+        for &(opcode, cost) in overrides {
+            self.gas_overrides[opcode as usize] = Some(cost);
+        }
+        self
+    }
+
+    /// The effective gas cost for `opcode`, honoring any override installed
+    /// via `with_overrides` before falling back to `fee`'s per-fork cost.
+    pub fn opcode_gas(&self, opcode: EvmOpcode, fee: Fee) -> u64 {
+        self.gas_overrides[opcode as usize].unwrap_or_else(|| fee.gas(self) as u64)
+    }
+
+    /// Overrides the maximum EVM stack depth (mainnet: `DEFAULT_STACK_LIMIT`).
+    pub fn with_stack_limit(mut self, limit: usize) -> Schedule {
+        self.stack_limit = limit;
+        self
+    }
+
+    /// Overrides the maximum `JUMPSUB`/`RETURNSUB` shadow-stack depth
+    /// (mainnet: `DEFAULT_RETURN_STACK_LIMIT`).
+    pub fn with_return_stack_limit(mut self, limit: usize) -> Schedule {
+        self.return_stack_limit = limit;
+        self
+    }
+
     pub fn from_fork(fork: Fork) -> Schedule {
+        // EIP-1884 (Istanbul) repriced BALANCE from 400 to 700; EIP-2929
+        // (Berlin) replaced it again with a warm/cold split (100/2600) that
+        // depends on the per-transaction access list, which this schedule
+        // has no place to keep, so Berlin keeps the Istanbul cost as a
+        // floor until account/access-list state exists to track it.
         const COSTS: [[u32; FEE_LEN]; FORK_LEN] = [
             [0, 2, 3, 5, 8, 10, 20, 1, 10, 10, 30, 6, 3, 20], // Frontier
             [0, 2, 3, 5, 8, 10, 20, 1, 10, 10, 30, 6, 3, 20], // Thawing
@@ -141,13 +278,142 @@ impl Schedule {
             [0, 2, 3, 5, 8, 10, 400, 1, 10, 50, 30, 6, 3, 20], // Spurious
             [0, 2, 3, 5, 8, 10, 400, 1, 10, 50, 30, 6, 3, 20], // Byzantium
             [0, 2, 3, 5, 8, 10, 400, 1, 10, 50, 30, 6, 3, 20], // Constantinople
-            [0, 2, 3, 5, 8, 10, 400, 1, 10, 50, 30, 6, 3, 20], // Istanbul
-            [0, 2, 3, 5, 8, 10, 400, 1, 10, 50, 30, 6, 3, 20], // Berlin
+            [0, 2, 3, 5, 8, 10, 700, 1, 10, 50, 30, 6, 3, 20], // Istanbul
+            [0, 2, 3, 5, 8, 10, 700, 1, 10, 50, 30, 6, 3, 20], // Berlin
+            [0, 2, 3, 5, 8, 10, 700, 1, 10, 50, 30, 6, 3, 20], // London
+            [0, 2, 3, 5, 8, 10, 700, 1, 10, 50, 30, 6, 3, 20], // Paris
+            [0, 2, 3, 5, 8, 10, 700, 1, 10, 50, 30, 6, 3, 20], // Shanghai
+            [0, 2, 3, 5, 8, 10, 700, 1, 10, 50, 30, 6, 3, 20], // Cancun
+            [0, 2, 3, 5, 8, 10, 700, 1, 10, 50, 30, 6, 3, 20], // Prague
         ];
         Schedule {
             fees: COSTS[fork as usize],
             memory_gas: 3,
             fork,
+            gas_overrides: [None; 256],
+            stack_limit: DEFAULT_STACK_LIMIT,
+            return_stack_limit: DEFAULT_RETURN_STACK_LIMIT,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prices_balance_per_fork() {
+        assert_eq!(Fee::Balance.gas(&Schedule::from_fork(Fork::Frontier)), 20);
+        assert_eq!(Fee::Balance.gas(&Schedule::from_fork(Fork::Tangerine)), 400);
+        assert_eq!(Fee::Balance.gas(&Schedule::from_fork(Fork::Istanbul)), 700);
+        assert_eq!(Fee::Balance.gas(&Schedule::from_fork(Fork::Berlin)), 700);
+    }
+
+    #[test]
+    fn prices_selfbalance_at_the_base_low_cost_on_every_fork() {
+        assert_eq!(Fee::Low.gas(&Schedule::from_fork(Fork::Istanbul)), 5);
+        assert_eq!(Fee::Low.gas(&Schedule::from_fork(Fork::Berlin)), 5);
+    }
+
+    #[test]
+    fn paris_keeps_berlins_gas_costs() {
+        assert_eq!(Fee::Balance.gas(&Schedule::from_fork(Fork::Paris)), 700);
+    }
+
+    #[test]
+    fn paris_sorts_after_berlin() {
+        assert!(Fork::Paris > Fork::Berlin);
+    }
+
+    #[test]
+    fn forks_after_paris_sort_in_declaration_order() {
+        assert!(Fork::Shanghai > Fork::Paris);
+        assert!(Fork::Cancun > Fork::Shanghai);
+        assert!(Fork::Prague > Fork::Cancun);
+    }
+
+    #[test]
+    fn forks_after_paris_keep_berlins_gas_costs() {
+        assert_eq!(Fee::Balance.gas(&Schedule::from_fork(Fork::Shanghai)), 700);
+        assert_eq!(Fee::Balance.gas(&Schedule::from_fork(Fork::Cancun)), 700);
+        assert_eq!(Fee::Balance.gas(&Schedule::from_fork(Fork::Prague)), 700);
+    }
+
+    #[test]
+    fn opcode_gas_falls_back_to_the_fee_when_unoverridden() {
+        let schedule = Schedule::from_fork(Fork::Frontier);
+        assert_eq!(schedule.opcode_gas(EvmOpcode::SLOAD, Fee::Zero), 0);
+    }
+
+    #[test]
+    fn with_overrides_replaces_a_single_opcodes_cost() {
+        let schedule = Schedule::from_fork(Fork::Frontier).with_overrides(&[(EvmOpcode::SLOAD, 500)]);
+        assert_eq!(schedule.opcode_gas(EvmOpcode::SLOAD, Fee::Zero), 500);
+        // Unrelated opcodes sharing the same Fee are untouched.
+        assert_eq!(schedule.opcode_gas(EvmOpcode::STOP, Fee::Zero), 0);
+    }
+
+    #[test]
+    fn with_overrides_keeps_the_last_value_for_a_repeated_opcode() {
+        let schedule =
+            Schedule::from_fork(Fork::Frontier).with_overrides(&[(EvmOpcode::SLOAD, 500), (EvmOpcode::SLOAD, 800)]);
+        assert_eq!(schedule.opcode_gas(EvmOpcode::SLOAD, Fee::Zero), 800);
+    }
+
+    #[test]
+    fn to_block_number_is_some_for_every_fork_with_a_known_activation() {
+        for &fork in FORKS_OLDEST_FIRST.iter() {
+            if fork == Fork::Paris || fork == Fork::Prague {
+                assert_eq!(to_block_number(fork), None);
+            } else {
+                assert!(to_block_number(fork).is_some());
+            }
+        }
+    }
+
+    #[test]
+    fn from_block_resolves_every_fork_boundary_from_berlin_onward() {
+        for &(fork, activation) in &[
+            (Fork::Berlin, 12_244_000),
+            (Fork::London, 12_965_000),
+            (Fork::Shanghai, 17_034_870),
+            (Fork::Cancun, 19_426_587),
+        ] {
+            assert_eq!(Fork::from_block(activation), fork);
+        }
+    }
+
+    #[test]
+    fn from_block_returns_the_fork_just_before_each_boundary() {
+        assert_eq!(Fork::from_block(12_243_999), Fork::Istanbul);
+        assert_eq!(Fork::from_block(12_964_999), Fork::Berlin);
+        assert_eq!(Fork::from_block(17_034_869), Fork::London);
+        assert_eq!(Fork::from_block(19_426_586), Fork::Shanghai);
+    }
+
+    #[test]
+    fn from_block_resolves_parises_range_to_london() {
+        // Paris has no block number of its own (see its doc comment on
+        // `Fork`), so every block between London's and Shanghai's
+        // activation should keep resolving to London.
+        assert_eq!(Fork::from_block(12_965_000), Fork::London);
+        assert_eq!(Fork::from_block(17_034_869), Fork::London);
+    }
+
+    #[test]
+    fn from_block_past_cancun_resolves_to_cancun() {
+        assert_eq!(Fork::from_block(19_426_587), Fork::Cancun);
+        assert_eq!(Fork::from_block(u64::MAX), Fork::Cancun);
+    }
+
+    #[test]
+    fn defaults_to_mainnets_stack_limit() {
+        assert_eq!(Schedule::from_fork(Fork::Frontier).stack_limit, DEFAULT_STACK_LIMIT);
+    }
+
+    #[test]
+    fn with_stack_limit_overrides_the_default() {
+        let schedule = Schedule::from_fork(Fork::Frontier).with_stack_limit(2048);
+        assert_eq!(schedule.stack_limit, 2048);
+    }
+}
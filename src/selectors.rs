@@ -0,0 +1,109 @@
+// Copyright 2020 The Psyche Authors
+// This file is part of Psyche.
+//
+// Psyche is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Psyche is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Psyche. If not, see <http://www.gnu.org/licenses/>.
+
+//! Resolves 4-byte function selectors to human-readable signatures, so
+//! `disasm` can label dispatcher branches (see `dispatcher::detect_linear`)
+//! with a name instead of just the raw selector.
+//!
+//! Two JSON shapes are accepted, both keyed by `"0x"`-prefixed selector:
+//! a flat `{"0xselector": "name(types)"}` map, and the shape returned by
+//! openchain's signature database API, `{"result": {"function":
+//! {"0xselector": [{"name": "name(types)"}, ...]}}}` (the first name is
+//! used when a selector has multiple known collisions).
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+
+#[derive(Debug)]
+pub enum SelectorsError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    UnrecognizedFormat,
+}
+
+impl fmt::Display for SelectorsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SelectorsError::Io(e) => write!(f, "{}", e),
+            SelectorsError::Json(e) => write!(f, "{}", e),
+            SelectorsError::UnrecognizedFormat => write!(f, "unrecognized selectors file format"),
+        }
+    }
+}
+
+fn parse_selector_key(key: &str) -> Option<u32> {
+    u32::from_str_radix(key.trim_start_matches("0x"), 16).ok()
+}
+
+/// Loads a selector-to-signature map from `path`, in either the flat or
+/// openchain JSON shape (see module docs).
+pub fn load(path: &str) -> Result<HashMap<u32, String>, SelectorsError> {
+    let contents = fs::read_to_string(path).map_err(SelectorsError::Io)?;
+    let value: serde_json::Value = serde_json::from_str(&contents).map_err(SelectorsError::Json)?;
+    let functions = value
+        .get("result")
+        .and_then(|result| result.get("function"))
+        .unwrap_or(&value);
+    let map = functions.as_object().ok_or(SelectorsError::UnrecognizedFormat)?;
+    let mut signatures = HashMap::new();
+    for (key, entry) in map {
+        let selector = match parse_selector_key(key) {
+            Some(selector) => selector,
+            None => continue,
+        };
+        let name = entry
+            .as_str()
+            .map(|s| s.to_string())
+            .or_else(|| entry.get(0).and_then(|first| first.get("name")).and_then(|n| n.as_str()).map(|s| s.to_string()));
+        if let Some(name) = name {
+            signatures.insert(selector, name);
+        }
+    }
+    Ok(signatures)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn write_temp(name: &str, contents: &str) -> String {
+        let path = std::env::temp_dir().join(format!("psyche-selectors-test-{}.json", name));
+        let mut file = fs::File::create(&path).unwrap();
+        file.write_all(contents.as_bytes()).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn loads_a_flat_selector_map() {
+        let path = write_temp("flat", r#"{"0xa9059cbb": "transfer(address,uint256)"}"#);
+        let signatures = load(&path).unwrap();
+        assert_eq!(signatures.get(&0xa905_9cbb).unwrap(), "transfer(address,uint256)");
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn loads_the_openchain_response_shape() {
+        let path = write_temp(
+            "openchain",
+            r#"{"ok": true, "result": {"function": {"0xa9059cbb": [{"name": "transfer(address,uint256)", "filtered": false}]}}}"#,
+        );
+        let signatures = load(&path).unwrap();
+        assert_eq!(signatures.get(&0xa905_9cbb).unwrap(), "transfer(address,uint256)");
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,182 @@
+// Copyright 2020 The Psyche Authors
+// This file is part of Psyche.
+//
+// Psyche is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Psyche is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Psyche. If not, see <http://www.gnu.org/licenses/>.
+
+//! Decoding for solc's compressed source map format (`s:l:f:j[:m]`), so the
+//! disassembler and traces can annotate program counters with Solidity
+//! source positions.
+//!
+//! This only covers position/jump-type decoding, one entry per instruction,
+//! not Solidity AST parsing: resolving a byte offset to a function or
+//! variable name needs the compiler's AST JSON too, which is a much larger
+//! schema to support and is left as follow-up work. Line numbers are
+//! computed by counting newlines in the original source text, when given.
+
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JumpType {
+    /// `i`: a call into a function.
+    Into,
+    /// `o`: a return out of a function.
+    Out,
+    /// `-`: a regular jump, e.g. a loop or `if`.
+    Regular,
+}
+
+/// One decoded element of a source map, aligned by index with the
+/// instruction stream it was compiled from (`entries[i]` describes the
+/// `i`th instruction, not the `i`th byte).
+#[derive(Debug, Clone, Copy)]
+pub struct SourceMapEntry {
+    /// Byte offset into the source file.
+    pub start: i64,
+    /// Length in bytes of the source range.
+    pub length: i64,
+    /// Index into the compiler's source file list, or -1 if unknown.
+    pub file_index: i64,
+    pub jump: JumpType,
+}
+
+#[derive(Debug)]
+pub enum SourceMapError {
+    InvalidField(String),
+    UnknownJumpType(String),
+}
+
+impl fmt::Display for SourceMapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SourceMapError::InvalidField(field) => write!(f, "invalid source map field: {}", field),
+            SourceMapError::UnknownJumpType(jump) => write!(f, "unknown jump type: {}", jump),
+        }
+    }
+}
+
+fn parse_field(field: &str) -> Result<i64, SourceMapError> {
+    field
+        .parse()
+        .map_err(|_| SourceMapError::InvalidField(field.to_string()))
+}
+
+/// Decodes a solc compressed source map into one entry per instruction.
+///
+/// Each `;`-separated element holds up to four `:`-separated fields
+/// (`s:l:f:j`); a field left empty inherits the previous element's value,
+/// per solc's run-length compression scheme, and a trailing element can
+/// omit fields entirely. A fifth `:m` modifier field (added in solc 0.6,
+/// tracking modifier invocation depth) is accepted but ignored.
+pub fn parse(source_map: &str) -> Result<Vec<SourceMapEntry>, SourceMapError> {
+    let mut entries = Vec::new();
+    let mut start = 0i64;
+    let mut length = 0i64;
+    let mut file_index = -1i64;
+    let mut jump = JumpType::Regular;
+    for element in source_map.split(';') {
+        let mut fields = element.split(':');
+        if let Some(field) = fields.next() {
+            if !field.is_empty() {
+                start = parse_field(field)?;
+            }
+        }
+        if let Some(field) = fields.next() {
+            if !field.is_empty() {
+                length = parse_field(field)?;
+            }
+        }
+        if let Some(field) = fields.next() {
+            if !field.is_empty() {
+                file_index = parse_field(field)?;
+            }
+        }
+        if let Some(field) = fields.next() {
+            if !field.is_empty() {
+                jump = match field {
+                    "i" => JumpType::Into,
+                    "o" => JumpType::Out,
+                    "-" => JumpType::Regular,
+                    other => return Err(SourceMapError::UnknownJumpType(other.to_string())),
+                };
+            }
+        }
+        entries.push(SourceMapEntry {
+            start,
+            length,
+            file_index,
+            jump,
+        });
+    }
+    Ok(entries)
+}
+
+/// Converts a byte offset within `source` into a 1-based line number.
+pub fn line_for_offset(source: &str, offset: i64) -> usize {
+    if offset < 0 {
+        return 0;
+    }
+    1 + source.as_bytes()[..(offset as usize).min(source.len())]
+        .iter()
+        .filter(|&&b| b == b'\n')
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_explicit_fields_on_every_element() {
+        let entries = parse("0:1:0:-;5:2:0:i;10:3:1:o").unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].start, 0);
+        assert_eq!(entries[0].jump, JumpType::Regular);
+        assert_eq!(entries[1].start, 5);
+        assert_eq!(entries[1].jump, JumpType::Into);
+        assert_eq!(entries[2].file_index, 1);
+        assert_eq!(entries[2].jump, JumpType::Out);
+    }
+
+    #[test]
+    fn inherits_omitted_fields_from_the_previous_element() {
+        // Second and third elements omit everything but the field(s) that
+        // changed, per solc's run-length compression.
+        let entries = parse("10:20:0:-;;30").unwrap();
+        assert_eq!(entries[1].start, 10);
+        assert_eq!(entries[1].length, 20);
+        assert_eq!(entries[1].file_index, 0);
+        assert_eq!(entries[2].start, 30);
+        assert_eq!(entries[2].length, 20);
+        assert_eq!(entries[2].file_index, 0);
+    }
+
+    #[test]
+    fn ignores_a_trailing_modifier_depth_field() {
+        let entries = parse("0:1:0:i:2").unwrap();
+        assert_eq!(entries[0].jump, JumpType::Into);
+    }
+
+    #[test]
+    fn rejects_an_unknown_jump_type() {
+        assert!(matches!(parse("0:1:0:x"), Err(SourceMapError::UnknownJumpType(_))));
+    }
+
+    #[test]
+    fn computes_a_one_based_line_number_from_a_byte_offset() {
+        let source = "line one\nline two\nline three";
+        assert_eq!(line_for_offset(source, 0), 1);
+        assert_eq!(line_for_offset(source, 9), 2);
+        assert_eq!(line_for_offset(source, 18), 3);
+    }
+}
@@ -0,0 +1,555 @@
+// Copyright 2020 The Psyche Authors
+// This file is part of Psyche.
+//
+// Psyche is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Psyche is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Psyche. If not, see <http://www.gnu.org/licenses/>.
+
+//! Opcode digram/trigram frequency analysis over a corpus of contracts,
+//! used to propose candidate fused ("super") instruction handlers.
+//!
+//! This counts adjacent-opcode sequences statically, over the decoded
+//! instruction stream of each contract, rather than from a runtime
+//! execution trace (such as `vm::run_evm_with_trace`'s
+//! `TraceFilter::PcCounts`). Static digram/trigram counts are already a reasonable
+//! first proxy for "which pairs of opcodes tend to run back to back", and
+//! switching this analysis over to live trace data later only means
+//! swapping out `opcode_sequence`'s source. Turning the resulting report
+//! into generated match arms is left as follow-up work; this emits a
+//! ranked list of candidates for a human to act on.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+use crate::instructions::EvmOpcode;
+use crate::schedule::Schedule;
+use crate::vm::OPCODE_INFOS;
+
+/// The operator sequence of a contract, with PUSH immediates skipped:
+/// only the opcodes themselves matter for digram/trigram counting.
+fn opcode_sequence(bytecode: &[u8]) -> Vec<EvmOpcode> {
+    let mut result = Vec::with_capacity(bytecode.len());
+    let mut addr = 0usize;
+    while addr < bytecode.len() {
+        match EvmOpcode::try_from(bytecode[addr]) {
+            Ok(opcode) if opcode.is_push() => {
+                result.push(opcode);
+                addr += 1 + opcode.push_index() + 1;
+            }
+            Ok(opcode) if opcode.is_deep_stack() => {
+                result.push(opcode);
+                addr += 2;
+            }
+            Ok(opcode) => {
+                result.push(opcode);
+                addr += 1;
+            }
+            Err(_) => addr += 1,
+        }
+    }
+    result
+}
+
+#[derive(Debug, Default)]
+pub struct OpcodeReport {
+    pub digrams: Vec<((EvmOpcode, EvmOpcode), u64)>,
+    pub trigrams: Vec<((EvmOpcode, EvmOpcode, EvmOpcode), u64)>,
+}
+
+/// Counts adjacent-opcode digrams and trigrams across every contract in
+/// `corpus`, most frequent first.
+pub fn analyze(corpus: &[Vec<u8>]) -> OpcodeReport {
+    let mut digram_counts: HashMap<(EvmOpcode, EvmOpcode), u64> = HashMap::new();
+    let mut trigram_counts: HashMap<(EvmOpcode, EvmOpcode, EvmOpcode), u64> = HashMap::new();
+    for bytecode in corpus {
+        let ops = opcode_sequence(bytecode);
+        for window in ops.windows(2) {
+            *digram_counts.entry((window[0], window[1])).or_insert(0) += 1;
+        }
+        for window in ops.windows(3) {
+            *trigram_counts
+                .entry((window[0], window[1], window[2]))
+                .or_insert(0) += 1;
+        }
+    }
+    let mut digrams: Vec<_> = digram_counts.into_iter().collect();
+    digrams.sort_by(|a, b| b.1.cmp(&a.1));
+    let mut trigrams: Vec<_> = trigram_counts.into_iter().collect();
+    trigrams.sort_by(|a, b| b.1.cmp(&a.1));
+    OpcodeReport { digrams, trigrams }
+}
+
+/// A run of instructions ending at a control-transfer opcode (or at the
+/// end of the bytecode), with its static gas cost and instruction count.
+///
+/// This splits on terminators only, unlike `VmRom`'s basic blocks, which
+/// also split at `JUMPDEST` since that's a valid jump target reachable
+/// mid-block; a coarser split is fine here since the goal is "worst-case
+/// gas a caller can force before the next control transfer", not an
+/// interpreter-accurate block boundary.
+#[derive(Debug, Clone)]
+pub struct GasSegmentStat {
+    pub addr: u32,
+    pub category: &'static str,
+    pub instr_count: u32,
+    pub gas: u64,
+}
+
+impl GasSegmentStat {
+    pub fn gas_per_instr(&self) -> f64 {
+        self.gas as f64 / self.instr_count as f64
+    }
+}
+
+fn segment_category(opcode: EvmOpcode, ran_off_the_end: bool) -> &'static str {
+    if ran_off_the_end {
+        return "truncated";
+    }
+    match opcode {
+        EvmOpcode::JUMP => "jump",
+        EvmOpcode::JUMPI => "jumpi",
+        EvmOpcode::STOP => "stop",
+        EvmOpcode::RETURN => "return",
+        EvmOpcode::REVERT => "revert",
+        EvmOpcode::INVALID => "invalid",
+        EvmOpcode::SELFDESTRUCT => "selfdestruct",
+        _ => "fallthrough",
+    }
+}
+
+/// Computes the static gas-to-instruction ratio of every gas segment
+/// (see `GasSegmentStat`) across `corpus`, for spotting the gas-densest
+/// stretches of bytecode a DoS-minded caller could target.
+///
+/// Like `BbInfo::gas`, this doesn't special-case Berlin's warm/cold
+/// access-list costs, so post-Berlin segments containing
+/// BALANCE/EXTCODESIZE/SLOAD/CALL-family opcodes slightly overstate their
+/// gas. This is a static estimate over the bytecode, not a live
+/// mgas/s-per-category measurement.
+pub fn analyze_gas_segments(corpus: &[Vec<u8>], schedule: &Schedule) -> Vec<GasSegmentStat> {
+    let mut result = Vec::new();
+    for bytecode in corpus {
+        let mut addr: u32 = 0;
+        let mut instr_count: u32 = 0;
+        let mut gas: u64 = 0;
+        let mut i: usize = 0;
+        while i < bytecode.len() {
+            let code = bytecode[i];
+            let opcode = EvmOpcode::try_from(code).unwrap_or(EvmOpcode::INVALID);
+            let (_, fee, _, _) = OPCODE_INFOS[code as usize];
+            gas += schedule.opcode_gas(opcode, fee);
+            instr_count += 1;
+            if opcode.is_push() {
+                i += 1 + opcode.push_index() + 1;
+            } else if opcode.is_deep_stack() {
+                i += 2;
+            } else {
+                i += 1;
+            }
+            let ran_off_the_end = i >= bytecode.len() && !opcode.is_terminator();
+            if opcode.is_terminator() || i >= bytecode.len() {
+                result.push(GasSegmentStat {
+                    addr,
+                    category: segment_category(opcode, ran_off_the_end),
+                    instr_count,
+                    gas,
+                });
+                addr = i as u32;
+                instr_count = 0;
+                gas = 0;
+            }
+        }
+    }
+    result
+}
+
+/// Renders a per-segment gas histogram as CSV, most gas-dense first.
+pub fn format_gas_segments_csv(stats: &[GasSegmentStat]) -> String {
+    let mut sorted: Vec<&GasSegmentStat> = stats.iter().collect();
+    sorted.sort_by(|a, b| b.gas_per_instr().partial_cmp(&a.gas_per_instr()).unwrap());
+    let mut out = String::from("addr,category,instr_count,gas,gas_per_instr\n");
+    for s in sorted {
+        out.push_str(&format!(
+            "{},{},{},{},{:.2}\n",
+            s.addr,
+            s.category,
+            s.instr_count,
+            s.gas,
+            s.gas_per_instr()
+        ));
+    }
+    out
+}
+
+/// A compact columnar per-instruction gas ledger for a single contract:
+/// parallel arrays instead of a `Vec` of `(pc, opcode, gas_cost,
+/// cumulative_gas)` tuples, so a caller doing a fast "does this fit in the
+/// block" sum only touches the `gas_costs` column instead of walking
+/// interleaved struct fields.
+///
+/// Block builders and simulators typically want this for a *specific*
+/// call (real calldata, only the branches that call would actually take),
+/// but `CALLDATALOAD`/`CALLDATASIZE`/`CALLDATACOPY` aren't executable in
+/// this interpreter yet (they report `VmError::InvalidInstruction`; see
+/// their arm in `run_evm_impl`), so there's no way to run a real call
+/// through it to get an exact path. This instead walks `bytecode` once in
+/// program order, the same linear-scan approximation `analyze_gas_segments`
+/// already uses: exact for straight-line code, and a cheap upper-bound
+/// estimate everywhere a jump would otherwise skip instructions.
+#[derive(Debug, Default)]
+pub struct GasLedger {
+    pub pcs: Vec<u32>,
+    pub opcodes: Vec<EvmOpcode>,
+    pub gas_costs: Vec<u64>,
+    pub cumulative_gas: Vec<u64>,
+}
+
+/// Builds a `GasLedger` for `bytecode`, one entry per instruction in
+/// program order.
+///
+/// Like `analyze_gas_segments`, this uses each opcode's static per-fork
+/// fee and doesn't special-case Berlin's warm/cold access-list costs or
+/// other dynamic components (memory expansion, `SHA3`/`EXP` length-based
+/// pricing), so post-Berlin contracts and memory/hash-heavy code will
+/// undercount; see the module doc comment.
+pub fn gas_ledger(bytecode: &[u8], schedule: &Schedule) -> GasLedger {
+    let mut ledger = GasLedger::default();
+    let mut cumulative_gas = 0u64;
+    let mut i: usize = 0;
+    while i < bytecode.len() {
+        let code = bytecode[i];
+        let opcode = EvmOpcode::try_from(code).unwrap_or(EvmOpcode::INVALID);
+        let (_, fee, _, _) = OPCODE_INFOS[code as usize];
+        let gas_cost = schedule.opcode_gas(opcode, fee);
+        cumulative_gas += gas_cost;
+        ledger.pcs.push(i as u32);
+        ledger.opcodes.push(opcode);
+        ledger.gas_costs.push(gas_cost);
+        ledger.cumulative_gas.push(cumulative_gas);
+        if opcode.is_push() {
+            i += 1 + opcode.push_index() + 1;
+        } else if opcode.is_deep_stack() {
+            i += 2;
+        } else {
+            i += 1;
+        }
+    }
+    ledger
+}
+
+/// A control-flow block for `static_gas_lower_bound`'s CFG walk: splits
+/// bytecode at `JUMPDEST`s (valid jump targets) *and* after terminators,
+/// unlike `GasSegmentStat`'s coarser terminator-only split, since a lower
+/// bound needs to know exactly where a jump can land.
+struct GasBlock {
+    end_addr: u32,
+    gas: u64,
+    end_opcode: EvmOpcode,
+    ran_off_the_end: bool,
+}
+
+/// Whether `opcode` actually stops execution, as opposed to merely ending a
+/// block: `EvmOpcode::is_terminator` also covers `JUMP`/`JUMPI`/`JUMPSUB`/
+/// `RETURNSUB`/`GAS`, which hand control to another address (or, for `GAS`,
+/// nowhere at all) rather than halting the call.
+fn is_halt(opcode: EvmOpcode) -> bool {
+    matches!(
+        opcode,
+        EvmOpcode::STOP
+            | EvmOpcode::RETURN
+            | EvmOpcode::REVERT
+            | EvmOpcode::INVALID
+            | EvmOpcode::SELFDESTRUCT
+    )
+}
+
+/// Splits `bytecode` into blocks keyed by start address, for
+/// `static_gas_lower_bound`'s CFG walk.
+fn split_gas_blocks(bytecode: &[u8], schedule: &Schedule) -> std::collections::BTreeMap<u32, GasBlock> {
+    let mut blocks = std::collections::BTreeMap::new();
+    let mut block_addr: u32 = 0;
+    let mut gas: u64 = 0;
+    let mut i: usize = 0;
+    while i < bytecode.len() {
+        let code = bytecode[i];
+        let opcode = EvmOpcode::try_from(code).unwrap_or(EvmOpcode::INVALID);
+        let (_, fee, _, _) = OPCODE_INFOS[code as usize];
+        gas += schedule.opcode_gas(opcode, fee);
+        i += if opcode.is_push() {
+            1 + opcode.push_index() + 1
+        } else if opcode.is_deep_stack() {
+            2
+        } else {
+            1
+        };
+        let ends_block = opcode.is_terminator() || is_halt(opcode);
+        let ran_off_the_end = i >= bytecode.len() && !ends_block;
+        let next_is_jumpdest = bytecode
+            .get(i)
+            .map(|&b| EvmOpcode::try_from(b) == Ok(EvmOpcode::JUMPDEST))
+            .unwrap_or(false);
+        if ends_block || ran_off_the_end || next_is_jumpdest {
+            blocks.insert(
+                block_addr,
+                GasBlock {
+                    end_addr: i as u32,
+                    gas,
+                    end_opcode: opcode,
+                    ran_off_the_end,
+                },
+            );
+            block_addr = i as u32;
+            gas = 0;
+        }
+    }
+    blocks
+}
+
+/// Computes a lower bound on the gas any execution of `bytecode` must pay
+/// before reaching a terminator (`STOP`/`RETURN`/`REVERT`/`INVALID`/
+/// `SELFDESTRUCT`, or simply running off the end), starting from the entry
+/// block at address 0.
+///
+/// This is a static CFG estimate, not a real gas calculation: jump targets
+/// are only known at runtime, so `JUMP`/`JUMPI` are treated as able to reach
+/// *any* `JUMPDEST` in the bytecode, and the cheapest such target is
+/// assumed. That over-approximates reachability — real execution can only
+/// take a subset of these edges — but that's exactly what keeps this a
+/// valid lower bound rather than a guess: no real execution path can be
+/// cheaper than the cheapest one this search considers, so a gas limit
+/// below this value can never succeed and the transaction can be rejected
+/// without running it.
+///
+/// Like `GasSegmentStat` and `GasLedger`, this uses each opcode's static
+/// per-fork fee and doesn't special-case Berlin's warm/cold access-list
+/// costs or other dynamic components; see the module doc comment.
+pub fn static_gas_lower_bound(bytecode: &[u8], schedule: &Schedule) -> u64 {
+    use std::cmp::Reverse;
+    use std::collections::BinaryHeap;
+
+    let blocks = split_gas_blocks(bytecode, schedule);
+    if blocks.is_empty() {
+        return 0;
+    }
+    let jumpdests: Vec<u32> = blocks
+        .iter()
+        .filter(|(&addr, _)| {
+            bytecode
+                .get(addr as usize)
+                .map(|&b| EvmOpcode::try_from(b) == Ok(EvmOpcode::JUMPDEST))
+                .unwrap_or(false)
+        })
+        .map(|(&addr, _)| addr)
+        .collect();
+
+    let successors = |addr: u32| -> Vec<u32> {
+        let block = &blocks[&addr];
+        if is_halt(block.end_opcode) || block.ran_off_the_end {
+            return Vec::new();
+        }
+        match block.end_opcode {
+            EvmOpcode::JUMP => jumpdests.clone(),
+            EvmOpcode::JUMPI => {
+                let mut succ = jumpdests.clone();
+                if blocks.contains_key(&block.end_addr) {
+                    succ.push(block.end_addr);
+                }
+                succ
+            }
+            // `JUMPSUB`/`RETURNSUB` targets aren't statically resolvable
+            // here either, but unlike `JUMP`/`JUMPI` there's no `JUMPDEST`
+            // set to fall back on; treating them as a dead end only makes
+            // this a looser (but still valid) lower bound.
+            EvmOpcode::JUMPSUB | EvmOpcode::RETURNSUB => Vec::new(),
+            _ if blocks.contains_key(&block.end_addr) => vec![block.end_addr],
+            _ => Vec::new(),
+        }
+    };
+
+    let mut dist: std::collections::HashMap<u32, u64> = std::collections::HashMap::new();
+    let mut heap = BinaryHeap::new();
+    heap.push(Reverse((blocks[&0].gas, 0u32)));
+    dist.insert(0, blocks[&0].gas);
+    while let Some(Reverse((d, addr))) = heap.pop() {
+        if dist.get(&addr).map(|&best| d > best).unwrap_or(true) {
+            continue;
+        }
+        let succ = successors(addr);
+        if succ.is_empty() {
+            return d;
+        }
+        for next in succ {
+            let next_dist = d + blocks[&next].gas;
+            if dist.get(&next).map(|&best| next_dist < best).unwrap_or(true) {
+                dist.insert(next, next_dist);
+                heap.push(Reverse((next_dist, next)));
+            }
+        }
+    }
+    dist.values().copied().max().unwrap_or(0)
+}
+
+/// Renders the top `top_n` digrams and trigrams as candidate fused
+/// handlers, most promising first.
+pub fn format_report(report: &OpcodeReport, top_n: usize) -> String {
+    let mut out = String::new();
+    out.push_str("digram candidates:\n");
+    for ((a, b), count) in report.digrams.iter().take(top_n) {
+        out.push_str(&format!("  {} {} -> count {}\n", a, b, count));
+    }
+    out.push_str("trigram candidates:\n");
+    for ((a, b, c), count) in report.trigrams.iter().take(top_n) {
+        out.push_str(&format!("  {} {} {} -> count {}\n", a, b, c, count));
+    }
+    out
+}
+
+/// Renders the top `top_n` digrams as `match` arm skeletons a human can
+/// paste into `opt.rs` and fill in, named after the pair they fuse.
+///
+/// This only proposes digrams: trigram fusion is a bigger win per match
+/// but rarer in practice, and the skeleton doesn't know enough about
+/// stack effects to draft a trigram body usefully.
+pub fn generate_match_arms(report: &OpcodeReport, top_n: usize) -> String {
+    let mut out = String::new();
+    for ((a, b), count) in report.digrams.iter().take(top_n) {
+        let name = format!("{:?}{:?}", a, b);
+        out.push_str(&format!(
+            "// count {}: fuses {} {}\n(Instr::{}, Instr::{}) => {{\n    // TODO: implement {}\n}}\n",
+            count, a, b, a, b, name
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_the_most_frequent_digram() {
+        // PUSH1 1 DUP1, repeated, should make (PUSH1, DUP1) the top digram.
+        let code = vec![0x60, 0x01, 0x80, 0x60, 0x01, 0x80];
+        let report = analyze(&[code]);
+        let (top, count) = report.digrams[0];
+        assert_eq!(top, (EvmOpcode::PUSH1, EvmOpcode::DUP1));
+        assert_eq!(count, 2);
+    }
+
+    #[test]
+    fn aggregates_counts_across_the_whole_corpus() {
+        let a = vec![0x01, 0x01]; // ADD ADD
+        let b = vec![0x01, 0x01, 0x01]; // ADD ADD ADD
+        let report = analyze(&[a, b]);
+        let (top, count) = report.digrams[0];
+        assert_eq!(top, (EvmOpcode::ADD, EvmOpcode::ADD));
+        assert_eq!(count, 3);
+    }
+
+    #[test]
+    fn generates_one_match_arm_per_requested_digram() {
+        let code = vec![0x60, 0x01, 0x80, 0x60, 0x01, 0x80]; // PUSH1 1 DUP1, twice
+        let report = analyze(&[code]);
+        let arms = generate_match_arms(&report, 1);
+        assert!(arms.contains("(Instr::PUSH1, Instr::DUP1)"));
+        assert!(arms.contains("count 2"));
+    }
+
+    #[test]
+    fn splits_gas_segments_at_terminators() {
+        // PUSH1 1 STOP, PUSH1 2 PUSH1 3 ADD RETURN(ish tail) -> two segments.
+        let code = vec![0x60, 0x01, 0x00, 0x60, 0x02, 0x60, 0x03, 0x01];
+        let schedule = Schedule::default();
+        let segments = analyze_gas_segments(&[code], &schedule);
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0].category, "stop");
+        assert_eq!(segments[0].instr_count, 2);
+        assert_eq!(segments[1].category, "truncated");
+        assert_eq!(segments[1].instr_count, 3);
+    }
+
+    #[test]
+    fn formats_gas_segments_csv_most_gas_dense_first() {
+        let cheap = vec![0x00]; // STOP, 0 gas over 1 instruction
+        let dense = vec![0x60, 0x01, 0x60, 0x02, 0x01, 0x00]; // PUSH1 PUSH1 ADD STOP
+        let schedule = Schedule::default();
+        let segments = analyze_gas_segments(&[cheap, dense], &schedule);
+        let csv = format_gas_segments_csv(&segments);
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some("addr,category,instr_count,gas,gas_per_instr"));
+        assert!(lines.next().unwrap().starts_with("0,stop,4,"));
+    }
+
+    #[test]
+    fn gas_ledger_accumulates_cost_per_instruction_in_program_order() {
+        // PUSH1 1 PUSH1 2 ADD STOP
+        let code = vec![0x60, 0x01, 0x60, 0x02, 0x01, 0x00];
+        let schedule = Schedule::default();
+        let ledger = gas_ledger(&code, &schedule);
+        assert_eq!(ledger.pcs, vec![0, 2, 4, 5]);
+        assert_eq!(
+            ledger.opcodes,
+            vec![EvmOpcode::PUSH1, EvmOpcode::PUSH1, EvmOpcode::ADD, EvmOpcode::STOP]
+        );
+        let expected_cumulative: Vec<u64> = ledger
+            .gas_costs
+            .iter()
+            .scan(0u64, |acc, cost| {
+                *acc += cost;
+                Some(*acc)
+            })
+            .collect();
+        assert_eq!(ledger.cumulative_gas, expected_cumulative);
+        assert_eq!(ledger.cumulative_gas.last().copied(), Some(9));
+    }
+
+    #[test]
+    fn static_gas_lower_bound_is_the_entry_blocks_cost_when_it_terminates() {
+        // PUSH1 1 PUSH1 2 ADD STOP: one block, no jumps.
+        let code = vec![0x60, 0x01, 0x60, 0x02, 0x01, 0x00];
+        let schedule = Schedule::default();
+        let segments = analyze_gas_segments(std::slice::from_ref(&code), &schedule);
+        assert_eq!(static_gas_lower_bound(&code, &schedule), segments[0].gas);
+    }
+
+    #[test]
+    fn static_gas_lower_bound_takes_the_cheapest_jumpi_branch() {
+        // PUSH1 0 PUSH1 12 JUMPI PUSH1 99 PUSH1 99 PUSH1 99 STOP JUMPDEST STOP
+        //   (fallthrough pays for three extra PUSH1s before STOP; the taken
+        //   branch jumps straight to the JUMPDEST STOP at addr 12)
+        let code = vec![
+            0x60, 0x00, // PUSH1 0       (addr 0)
+            0x60, 0x0c, // PUSH1 12 (jump target)   (addr 2)
+            0x57, // JUMPI                          (addr 4)
+            0x60, 0x63, // PUSH1 99                 (addr 5)
+            0x60, 0x63, // PUSH1 99                 (addr 7)
+            0x60, 0x63, // PUSH1 99                 (addr 9)
+            0x00, // STOP                           (addr 11)
+            0x5b, // JUMPDEST                        (addr 12)
+            0x00, // STOP                           (addr 13)
+        ];
+        let schedule = Schedule::default();
+        let bound = static_gas_lower_bound(&code, &schedule);
+        // entry block (PUSH1 PUSH1 JUMPI) + cheapest successor (JUMPDEST STOP).
+        let entry_gas = schedule.opcode_gas(EvmOpcode::PUSH1, crate::schedule::Fee::VeryLow) * 2
+            + schedule.opcode_gas(EvmOpcode::JUMPI, crate::schedule::Fee::High);
+        let target_gas = schedule.opcode_gas(EvmOpcode::JUMPDEST, crate::schedule::Fee::Jumpdest)
+            + schedule.opcode_gas(EvmOpcode::STOP, crate::schedule::Fee::Zero);
+        assert_eq!(bound, entry_gas + target_gas);
+    }
+
+    #[test]
+    fn static_gas_lower_bound_of_empty_code_is_zero() {
+        let schedule = Schedule::default();
+        assert_eq!(static_gas_lower_bound(&[], &schedule), 0);
+    }
+}
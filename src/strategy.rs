@@ -0,0 +1,48 @@
+// Copyright 2020 The Psyche Authors
+// This file is part of Psyche.
+//
+// Psyche is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Psyche is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Psyche. If not, see <http://www.gnu.org/licenses/>.
+
+//! Selects which backend executes a given piece of bytecode. The
+//! interpreter and both JIT backends share the same execution context
+//! (the analyzed `VmRom`, `VmMemory` and gas accounting); only the final
+//! dispatch differs.
+
+use std::str::FromStr;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ExecutionStrategy {
+    Interpreter,
+    JitX86,
+    JitPortable,
+}
+
+impl ExecutionStrategy {
+    pub const fn default() -> ExecutionStrategy {
+        ExecutionStrategy::Interpreter
+    }
+}
+
+impl FromStr for ExecutionStrategy {
+    type Err = ();
+
+    fn from_str(input: &str) -> Result<ExecutionStrategy, Self::Err> {
+        match input {
+            "interpreter" => Ok(ExecutionStrategy::Interpreter),
+            "jit-x86" => Ok(ExecutionStrategy::JitX86),
+            "jit-portable" => Ok(ExecutionStrategy::JitPortable),
+            _ => Err(()),
+        }
+    }
+}
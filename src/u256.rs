@@ -90,6 +90,70 @@ impl U256 {
     pub fn le_u64(&self) -> bool {
         (self.0[1] == 0) & (self.0[2] == 0) & (self.0[3] == 0)
     }
+
+    /// Renders as `0x` plus the fewest hex digits needed (`0x0` for zero,
+    /// no leading zero padding otherwise), for tracer/debugger output
+    /// where most values are small and a fixed 64-digit width just adds
+    /// noise. Use `{:?}` (fixed-width, big-endian) instead when values
+    /// need to line up in a column.
+    pub fn to_short_hex(&self) -> String {
+        let mut out = String::new();
+        self.write_short_hex(&mut out);
+        out
+    }
+
+    /// Same output as `to_short_hex`, appended to `out` instead of
+    /// returned as a fresh `String`. For a hot path formatting many
+    /// values (e.g. the CLI's `--trace` JSON dump), reuse one `out`
+    /// across calls (`out.clear()` between them) to avoid an allocation
+    /// per value.
+    pub fn write_short_hex(&self, out: &mut String) {
+        use std::fmt::Write;
+        for i in (0..4).rev() {
+            if self.0[i] != 0 {
+                let _ = write!(out, "0x{:x}", self.0[i]);
+                for limb in self.0[..i].iter().rev() {
+                    let _ = write!(out, "{:016x}", limb);
+                }
+                return;
+            }
+        }
+        out.push_str("0x0");
+    }
+}
+
+impl std::fmt::Debug for U256 {
+    /// Fixed-width big-endian hex (`0x` plus 64 digits), so stack/trace
+    /// dumps of several values line up in a column. See `to_short_hex`
+    /// for a version without the padding.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "0x{:016x}{:016x}{:016x}{:016x}",
+            self.0[3], self.0[2], self.0[1], self.0[0]
+        )
+    }
+}
+
+impl std::fmt::Display for U256 {
+    /// Decimal, computed by repeated division by 10 via the existing
+    /// `div_u256`/`mod_u256` routines (the same ones `DIV`/`MOD` dispatch
+    /// to), rather than duplicating a second big-integer division.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let ten = U256::from_u64(10);
+        let mut value = *self;
+        let mut digits = Vec::new();
+        while !unsafe { is_zero_u256(value) } {
+            let digit = unsafe { mod_u256(value, ten) }.low_u64();
+            digits.push(b'0' + digit as u8);
+            value = unsafe { div_u256(value, ten) };
+        }
+        if digits.is_empty() {
+            digits.push(b'0');
+        }
+        digits.reverse();
+        f.write_str(&String::from_utf8(digits).unwrap())
+    }
 }
 
 pub trait __m256iExt {
@@ -134,8 +198,10 @@ pub unsafe fn loadu_u256(src: *const U256, offset: isize) -> U256 {
         let result = (_mm_loadu_si128(src), _mm_loadu_si128(src.offset(1)));
         return std::mem::transmute::<(__m128i, __m128i), U256>(result);
     }
-    // generic target
-    return *src.offset(offset);
+    // generic target: `src` isn't guaranteed to be aligned to U256's
+    // 32-byte alignment (e.g. an arbitrary `MLOAD` offset into `VmMemory`'s
+    // byte buffer), so a plain dereference here is UB.
+    return src.offset(offset).read_unaligned();
 }
 
 #[allow(unreachable_code)]
@@ -176,8 +242,9 @@ pub unsafe fn storeu_u256(dest: *mut U256, value: U256, offset: isize) {
         _mm_storeu_si128(dest.offset(1), value.1);
         return;
     }
-    // generic target
-    *dest.offset(offset) = value;
+    // generic target: see `loadu_u256`'s comment on why this must be an
+    // unaligned write.
+    dest.offset(offset).write_unaligned(value);
 }
 
 fn bitmask(num_bytes: i32) -> u64 {
@@ -211,16 +278,18 @@ pub unsafe fn load16_u256(src: *const U256, num_bytes: i32) -> U256 {
         let mask = _mm_cmpgt_epi8(nbb, lane8_id);
         return std::mem::transmute::<(__m128i, __m128i), U256>((_mm_and_si128(value, mask), zero));
     }
-    // generic target
+    // generic target: `src` points into the byte-addressed bytecode buffer
+    // at an arbitrary PC, so it isn't guaranteed to be aligned for `u64`
+    // reads; a plain dereference here is UB.
     let src = src as *const u64;
     if num_bytes <= 8 {
         let mask: u64 = bitmask(num_bytes - 0);
-        let temp0 = *src.offset(0) & mask;
+        let temp0 = src.offset(0).read_unaligned() & mask;
         U256([temp0, 0, 0, 0])
     } else {
         let mask: u64 = bitmask(num_bytes - 8);
-        let temp0 = *src.offset(0);
-        let temp1 = *src.offset(1) & mask;
+        let temp0 = src.offset(0).read_unaligned();
+        let temp1 = src.offset(1).read_unaligned() & mask;
         U256([temp0, temp1, 0, 0])
     }
 }
@@ -256,20 +325,21 @@ pub unsafe fn load32_u256(src: *const U256, num_bytes: i32) -> U256 {
             _mm_and_si128(valuehi, mask),
         ));
     }
-    // generic target
+    // generic target: see `load16_u256`'s comment on why this must be an
+    // unaligned read.
     let src = src as *const u64;
     if num_bytes <= 24 {
         let mask: u64 = bitmask(num_bytes - 16);
-        let temp0 = *src.offset(0);
-        let temp1 = *src.offset(1);
-        let temp2 = *src.offset(2) & mask;
+        let temp0 = src.offset(0).read_unaligned();
+        let temp1 = src.offset(1).read_unaligned();
+        let temp2 = src.offset(2).read_unaligned() & mask;
         U256([temp0, temp1, temp2, 0])
     } else {
         let mask: u64 = bitmask(num_bytes - 24);
-        let temp0 = *src.offset(0);
-        let temp1 = *src.offset(1);
-        let temp2 = *src.offset(2);
-        let temp3 = *src.offset(3) & mask;
+        let temp0 = src.offset(0).read_unaligned();
+        let temp1 = src.offset(1).read_unaligned();
+        let temp2 = src.offset(2).read_unaligned();
+        let temp3 = src.offset(3).read_unaligned() & mask;
         U256([temp0, temp1, temp2, temp3])
     }
 }
@@ -1309,7 +1379,21 @@ pub fn leading_zeros_u256(value: U256) -> usize {
     return count as usize;
 }
 
-// Knuth's Algorithm D from Hacker's Delight
+// Knuth's Algorithm D from Hacker's Delight.
+//
+// `u` holds `m` u32 limbs and `v` holds `n`; the normalization step below
+// writes `m + 1` limbs into the local `undata` scratch buffer, not into
+// `u`. `q` and `r` must have room for 8 limbs, matching a `U256`'s fixed
+// 8-u32 layout, but only the limbs the quotient and remainder occupy are
+// written, so callers pass zeroed buffers (the `m < n` early-out copies
+// 8 limbs of `u` into `r` and leaves `q` untouched). `addmod_u256`'s widest call passes m = 9
+// (a `U256` sum plus its carry-out bit) and `mulmod_u256`'s widest call
+// passes m = 16 (a full 256x256 -> 512-bit product); both fit `undata`'s
+// `[u32; 17]` scratch buffer with room for the `m + 1`th limb. The
+// normalization shift `s` is `v[n-1].leading_zeros()`, down to 0 when
+// `v`'s top limb already has its high bit set -- every shift by `s` or
+// `32 - s` below promotes to u64 first specifically so an `s == 0` never
+// shifts a u32 by 32, which would be UB.
 pub unsafe fn divmnu(u: *const u32, v: *const u32, m: isize, n: isize, q: *mut u32, r: *mut u32) {
     debug_assert!(m <= 16);
     debug_assert!(n <= 8);
@@ -2004,3 +2088,36 @@ fn test_bshr_ssse3() {
         assert_word_eq(bshr_ssse3(i, _mm_set_epi64x(0, 31)), o);
     }
 }
+
+#[test]
+fn test_u256_debug_is_fixed_width_big_endian_hex() {
+    let value = U256([1, 0, 0, 0x1234]);
+    assert_eq!(
+        format!("{:?}", value),
+        format!("0x{:016x}{:016x}{:016x}{:016x}", 0x1234u64, 0u64, 0u64, 1u64)
+    );
+    assert_eq!(
+        format!("{:?}", U256::default()),
+        format!("0x{:016x}{:016x}{:016x}{:016x}", 0u64, 0u64, 0u64, 0u64)
+    );
+}
+
+#[test]
+fn test_u256_display_is_decimal() {
+    assert_eq!(format!("{}", U256::default()), "0");
+    assert_eq!(format!("{}", U256::from_u64(42)), "42");
+    assert_eq!(
+        format!("{}", U256::from_dec_str("115792089237316195423570985008687907853269984665640564039457584007913129639935").unwrap()),
+        "115792089237316195423570985008687907853269984665640564039457584007913129639935"
+    );
+}
+
+#[test]
+fn test_u256_to_short_hex_trims_leading_zeros() {
+    assert_eq!(U256::default().to_short_hex(), "0x0");
+    assert_eq!(U256::from_u64(0x2a).to_short_hex(), "0x2a");
+    assert_eq!(
+        U256([0, 0, 0, 1]).to_short_hex(),
+        format!("0x1{:016x}{:016x}{:016x}", 0u64, 0u64, 0u64)
+    );
+}
@@ -14,7 +14,9 @@
 // You should have received a copy of the GNU Lesser General Public License
 // along with Psyche. If not, see <http://www.gnu.org/licenses/>.
 
-use std::fmt::Write;
+use std::fmt;
+use std::fmt::Write as _;
+use std::io;
 use std::num::ParseIntError;
 
 pub fn encode_hex(bytes: &[u8]) -> String {
@@ -25,13 +27,106 @@ pub fn encode_hex(bytes: &[u8]) -> String {
     temp
 }
 
-pub fn decode_hex(s: &str) -> Result<Vec<u8>, ParseIntError> {
-    (0..s.len())
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+/// Streaming counterpart to `encode_hex`: writes lowercase hex digits to
+/// `w` a chunk at a time instead of building the whole string in memory
+/// first, for the megabyte-scale outputs (return data, memory dumps,
+/// disassembly) where `encode_hex` followed by a single print would
+/// otherwise double peak memory. `encode_hex` stays around for the small
+/// strings (assembled immediates, test fixtures) where that doesn't
+/// matter and a `String` is more convenient.
+pub fn write_hex<W: io::Write>(w: &mut W, bytes: &[u8]) -> io::Result<()> {
+    const CHUNK: usize = 4096;
+    let mut buffer = [0u8; CHUNK * 2];
+    for chunk in bytes.chunks(CHUNK) {
+        for (i, &b) in chunk.iter().enumerate() {
+            buffer[i * 2] = HEX_DIGITS[(b >> 4) as usize];
+            buffer[i * 2 + 1] = HEX_DIGITS[(b & 0xf) as usize];
+        }
+        w.write_all(&buffer[..chunk.len() * 2])?;
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum HexDecodeError {
+    OddLength,
+    InvalidDigit(ParseIntError),
+}
+
+impl fmt::Display for HexDecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HexDecodeError::OddLength => write!(f, "hex string has an odd number of digits"),
+            HexDecodeError::InvalidDigit(e) => write!(f, "invalid hex digit: {}", e),
+        }
+    }
+}
+
+/// Decodes a hex string into bytes, tolerating a leading `0x`/`0X` prefix
+/// and any embedded whitespace, so bytecode copy-pasted from an explorer
+/// or a Solidity build log decodes without manual cleanup first.
+pub fn decode_hex(s: &str) -> Result<Vec<u8>, HexDecodeError> {
+    let cleaned: String = s.chars().filter(|c| !c.is_whitespace()).collect();
+    let hex = cleaned
+        .strip_prefix("0x")
+        .or_else(|| cleaned.strip_prefix("0X"))
+        .unwrap_or(&cleaned);
+    if !hex.len().is_multiple_of(2) {
+        return Err(HexDecodeError::OddLength);
+    }
+    (0..hex.len())
         .step_by(2)
-        .map(|i| u8::from_str_radix(&s[i..i + 2], 16))
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(HexDecodeError::InvalidDigit))
         .collect()
 }
 
+#[cfg(test)]
+mod decode_hex_tests {
+    use super::*;
+
+    #[test]
+    fn decodes_a_bare_hex_string() {
+        assert_eq!(decode_hex("deadbeef").unwrap(), vec![0xde, 0xad, 0xbe, 0xef]);
+    }
+
+    #[test]
+    fn strips_a_0x_prefix() {
+        assert_eq!(decode_hex("0xdeadbeef").unwrap(), vec![0xde, 0xad, 0xbe, 0xef]);
+    }
+
+    #[test]
+    fn strips_an_uppercase_0x_prefix() {
+        assert_eq!(decode_hex("0XDEADBEEF").unwrap(), vec![0xde, 0xad, 0xbe, 0xef]);
+    }
+
+    #[test]
+    fn strips_embedded_and_surrounding_whitespace() {
+        assert_eq!(decode_hex(" de ad\tbe\nef ").unwrap(), vec![0xde, 0xad, 0xbe, 0xef]);
+    }
+
+    #[test]
+    fn an_all_whitespace_string_decodes_to_no_bytes() {
+        assert_eq!(decode_hex(" \t\n ").unwrap(), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn rejects_an_odd_length_string() {
+        assert!(matches!(decode_hex("abc"), Err(HexDecodeError::OddLength)));
+    }
+
+    #[test]
+    fn rejects_an_odd_length_string_after_stripping_its_prefix_and_whitespace() {
+        assert!(matches!(decode_hex("0x a b c"), Err(HexDecodeError::OddLength)));
+    }
+
+    #[test]
+    fn rejects_an_invalid_hex_digit() {
+        assert!(matches!(decode_hex("zz"), Err(HexDecodeError::InvalidDigit(_))));
+    }
+}
+
 macro_rules! test_feature_bit {
     ($name:ident, $register:ident, $mask:expr) => {
         fn $name() -> bool {
@@ -21,266 +21,36 @@ use crate::instructions::{EvmOpcode, Opcode};
 use crate::schedule::{Fee, Fork, Schedule};
 use crate::u256::*;
 
-const OPCODE_INFOS: [(Fork, Fee, u16, u16); 256] = [
-    (Fork::Frontier, Fee::Zero, 0, 0),    /* STOP = 0x00 */
-    (Fork::Frontier, Fee::VeryLow, 2, 1), /* ADD = 0x01 */
-    (Fork::Frontier, Fee::Low, 2, 1),     /* MUL = 0x02 */
-    (Fork::Frontier, Fee::VeryLow, 2, 1), /* SUB = 0x03 */
-    (Fork::Frontier, Fee::Low, 2, 1),     /* DIV = 0x04 */
-    (Fork::Frontier, Fee::Low, 2, 1),     /* SDIV = 0x05 */
-    (Fork::Frontier, Fee::Low, 2, 1),     /* MOD = 0x06 */
-    (Fork::Frontier, Fee::Low, 2, 1),     /* SMOD = 0x07 */
-    (Fork::Frontier, Fee::Mid, 3, 1),     /* ADDMOD = 0x08 */
-    (Fork::Frontier, Fee::Mid, 3, 1),     /* MULMOD = 0x09 */
-    (Fork::Frontier, Fee::Exp, 2, 1),     /* EXP = 0x0a */
-    (Fork::Frontier, Fee::Low, 2, 1),     /* SIGNEXTEND = 0x0b */
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::VeryLow, 2, 1),       /* LT = 0x10 */
-    (Fork::Frontier, Fee::VeryLow, 2, 1),       /* GT = 0x11 */
-    (Fork::Frontier, Fee::VeryLow, 2, 1),       /* SLT = 0x12 */
-    (Fork::Frontier, Fee::VeryLow, 2, 1),       /* SGT = 0x13 */
-    (Fork::Frontier, Fee::VeryLow, 2, 1),       /* EQ = 0x14 */
-    (Fork::Frontier, Fee::VeryLow, 1, 1),       /* ISZERO = 0x15 */
-    (Fork::Frontier, Fee::VeryLow, 2, 1),       /* AND = 0x16 */
-    (Fork::Frontier, Fee::VeryLow, 2, 1),       /* OR = 0x17 */
-    (Fork::Frontier, Fee::VeryLow, 2, 1),       /* XOR = 0x18 */
-    (Fork::Frontier, Fee::VeryLow, 1, 1),       /* NOT = 0x19 */
-    (Fork::Frontier, Fee::VeryLow, 2, 1),       /* BYTE = 0x1a */
-    (Fork::Constantinople, Fee::VeryLow, 2, 1), /* SHL = 0x1b */
-    (Fork::Constantinople, Fee::VeryLow, 2, 1), /* SHR = 0x1c */
-    (Fork::Constantinople, Fee::VeryLow, 2, 1), /* SAR = 0x1d */
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Sha3, 2, 1), /* SHA3 = 0x20 */
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Base, 0, 1),       /* ADDRESS = 0x30 */
-    (Fork::Frontier, Fee::Balance, 1, 1),    /* BALANCE = 0x31 */
-    (Fork::Frontier, Fee::Base, 0, 1),       /* ORIGIN = 0x32 */
-    (Fork::Frontier, Fee::Base, 0, 1),       /* CALLER = 0x33 */
-    (Fork::Frontier, Fee::Base, 0, 1),       /* CALLVALUE = 0x34 */
-    (Fork::Frontier, Fee::VeryLow, 1, 1),    /* CALLDATALOAD = 0x35 */
-    (Fork::Frontier, Fee::Base, 0, 1),       /* CALLDATASIZE = 0x36 */
-    (Fork::Frontier, Fee::Copy, 3, 0),       /* CALLDATACOPY = 0x37 */
-    (Fork::Frontier, Fee::Base, 0, 1),       /* CODESIZE = 0x38 */
-    (Fork::Frontier, Fee::Copy, 3, 0),       /* CODECOPY = 0x39 */
-    (Fork::Frontier, Fee::Base, 0, 1),       /* GASPRICE = 0x3a */
-    (Fork::Frontier, Fee::Zero, 1, 1),       /* EXTCODESIZE = 0x3b */
-    (Fork::Frontier, Fee::Zero, 4, 0),       /* EXTCODECOPY = 0x3c */
-    (Fork::Frontier, Fee::Base, 0, 1),       /* RETURNDATASIZE = 0x3d */
-    (Fork::Frontier, Fee::Copy, 3, 0),       /* RETURNDATACOPY = 0x3e */
-    (Fork::Constantinople, Fee::Zero, 1, 1), /* EXTCODEHASH = 0x3f */
-    (Fork::Frontier, Fee::Blockhash, 1, 1),  /* BLOCKHASH = 0x40 */
-    (Fork::Frontier, Fee::Base, 0, 1),       /* COINBASE = 0x41 */
-    (Fork::Frontier, Fee::Base, 0, 1),       /* TIMESTAMP = 0x42 */
-    (Fork::Frontier, Fee::Base, 0, 1),       /* NUMBER = 0x43 */
-    (Fork::Frontier, Fee::Base, 0, 1),       /* DIFFICULTY = 0x44 */
-    (Fork::Frontier, Fee::Base, 0, 1),       /* GASLIMIT = 0x45 */
-    (Fork::Istanbul, Fee::Base, 0, 1),       /* CHAINID = 0x46 */
-    (Fork::Frontier, Fee::Low, 0, 1),        /* SELFBALANCE = 0x47 */
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Base, 1, 0),     /* POP = 0x50 */
-    (Fork::Frontier, Fee::VeryLow, 1, 1),  /* MLOAD = 0x51 */
-    (Fork::Frontier, Fee::VeryLow, 2, 0),  /* MSTORE = 0x52 */
-    (Fork::Frontier, Fee::VeryLow, 2, 0),  /* MSTORE8 = 0x53 */
-    (Fork::Frontier, Fee::Zero, 1, 1),     /* SLOAD = 0x54 */
-    (Fork::Frontier, Fee::Zero, 2, 0),     /* SSTORE = 0x55 */
-    (Fork::Frontier, Fee::Mid, 1, 0),      /* JUMP = 0x56 */
-    (Fork::Frontier, Fee::High, 2, 0),     /* JUMPI = 0x57 */
-    (Fork::Frontier, Fee::Base, 0, 1),     /* PC = 0x58 */
-    (Fork::Frontier, Fee::Base, 0, 1),     /* MSIZE = 0x59 */
-    (Fork::Frontier, Fee::Base, 0, 1),     /* GAS = 0x5a */
-    (Fork::Frontier, Fee::Jumpdest, 0, 0), /* JUMPDEST = 0x5b */
-    (Fork::Berlin, Fee::Zero, 0, 0),       /* BEGINSUB = 0x5c */
-    (Fork::Berlin, Fee::Low, 0, 0),        /* RETURNSUB = 0x5d */
-    (Fork::Berlin, Fee::High, 1, 0),       /* JUMPSUB = 0x5e */
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH1 = 0x60 */
-    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH2 = 0x61 */
-    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH3 = 0x62 */
-    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH4 = 0x63 */
-    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH5 = 0x64 */
-    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH6 = 0x65 */
-    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH7 = 0x66 */
-    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH8 = 0x67 */
-    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH9 = 0x68 */
-    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH10 = 0x69 */
-    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH11 = 0x6a */
-    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH12 = 0x6b */
-    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH13 = 0x6c */
-    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH14 = 0x6d */
-    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH15 = 0x6e */
-    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH16 = 0x6f */
-    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH17 = 0x70 */
-    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH18 = 0x71 */
-    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH19 = 0x72 */
-    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH20 = 0x73 */
-    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH21 = 0x74 */
-    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH22 = 0x75 */
-    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH23 = 0x76 */
-    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH24 = 0x77 */
-    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH25 = 0x78 */
-    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH26 = 0x79 */
-    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH27 = 0x7a */
-    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH28 = 0x7b */
-    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH29 = 0x7c */
-    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH30 = 0x7d */
-    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH31 = 0x7e */
-    (Fork::Frontier, Fee::VeryLow, 0, 1),   /* PUSH32 = 0x7f */
-    (Fork::Frontier, Fee::VeryLow, 1, 2),   /* DUP1 = 0x80 */
-    (Fork::Frontier, Fee::VeryLow, 2, 3),   /* DUP2 = 0x81 */
-    (Fork::Frontier, Fee::VeryLow, 3, 4),   /* DUP3 = 0x82 */
-    (Fork::Frontier, Fee::VeryLow, 4, 5),   /* DUP4 = 0x83 */
-    (Fork::Frontier, Fee::VeryLow, 5, 6),   /* DUP5 = 0x84 */
-    (Fork::Frontier, Fee::VeryLow, 6, 7),   /* DUP6 = 0x85 */
-    (Fork::Frontier, Fee::VeryLow, 7, 8),   /* DUP7 = 0x86 */
-    (Fork::Frontier, Fee::VeryLow, 8, 9),   /* DUP8 = 0x87 */
-    (Fork::Frontier, Fee::VeryLow, 9, 10),  /* DUP9 = 0x88 */
-    (Fork::Frontier, Fee::VeryLow, 10, 11), /* DUP10 = 0x89 */
-    (Fork::Frontier, Fee::VeryLow, 11, 12), /* DUP11 = 0x8a */
-    (Fork::Frontier, Fee::VeryLow, 12, 13), /* DUP12 = 0x8b */
-    (Fork::Frontier, Fee::VeryLow, 13, 14), /* DUP13 = 0x8c */
-    (Fork::Frontier, Fee::VeryLow, 14, 15), /* DUP14 = 0x8d */
-    (Fork::Frontier, Fee::VeryLow, 15, 16), /* DUP15 = 0x8e */
-    (Fork::Frontier, Fee::VeryLow, 16, 17), /* DUP16 = 0x8f */
-    (Fork::Frontier, Fee::VeryLow, 2, 2),   /* SWAP1 = 0x90 */
-    (Fork::Frontier, Fee::VeryLow, 3, 3),   /* SWAP2 = 0x91 */
-    (Fork::Frontier, Fee::VeryLow, 4, 4),   /* SWAP3 = 0x92 */
-    (Fork::Frontier, Fee::VeryLow, 5, 5),   /* SWAP4 = 0x93 */
-    (Fork::Frontier, Fee::VeryLow, 6, 6),   /* SWAP5 = 0x94 */
-    (Fork::Frontier, Fee::VeryLow, 7, 7),   /* SWAP6 = 0x95 */
-    (Fork::Frontier, Fee::VeryLow, 8, 8),   /* SWAP7 = 0x96 */
-    (Fork::Frontier, Fee::VeryLow, 9, 9),   /* SWAP8 = 0x97 */
-    (Fork::Frontier, Fee::VeryLow, 10, 10), /* SWAP9 = 0x98 */
-    (Fork::Frontier, Fee::VeryLow, 11, 11), /* SWAP10 = 0x99 */
-    (Fork::Frontier, Fee::VeryLow, 12, 12), /* SWAP11 = 0x9a */
-    (Fork::Frontier, Fee::VeryLow, 13, 13), /* SWAP12 = 0x9b */
-    (Fork::Frontier, Fee::VeryLow, 14, 14), /* SWAP13 = 0x9c */
-    (Fork::Frontier, Fee::VeryLow, 15, 15), /* SWAP14 = 0x9d */
-    (Fork::Frontier, Fee::VeryLow, 16, 16), /* SWAP15 = 0x9e */
-    (Fork::Frontier, Fee::VeryLow, 17, 17), /* SWAP16 = 0x9f */
-    (Fork::Frontier, Fee::Zero, 2, 0),      /* LOG0 = 0xa0 */
-    (Fork::Frontier, Fee::Zero, 3, 0),      /* LOG1 = 0xa1 */
-    (Fork::Frontier, Fee::Zero, 4, 0),      /* LOG2 = 0xa2 */
-    (Fork::Frontier, Fee::Zero, 5, 0),      /* LOG3 = 0xa3 */
-    (Fork::Frontier, Fee::Zero, 6, 0),      /* LOG4 = 0xa4 */
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 3, 1),       /* CREATE = 0xf0 */
-    (Fork::Frontier, Fee::Zero, 7, 1),       /* CALL = 0xf1 */
-    (Fork::Frontier, Fee::Zero, 7, 1),       /* CALLCODE = 0xf2 */
-    (Fork::Frontier, Fee::Zero, 2, 0),       /* RETURN = 0xf3 */
-    (Fork::Frontier, Fee::Zero, 6, 1),       /* DELEGATECALL = 0xf4 */
-    (Fork::Constantinople, Fee::Zero, 4, 1), /* CREATE2 = 0xf5 */
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Byzantium, Fee::Zero, 6, 1), /* STATICCALL = 0xfa */
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Frontier, Fee::Zero, 0, 0),
-    (Fork::Byzantium, Fee::Zero, 2, 0), /* REVERT = 0xfd */
-    (Fork::Frontier, Fee::Zero, 0, 0),  /* INVALID = 0xfe */
-    (Fork::Frontier, Fee::Zero, 1, 0),  /* SELFDESTRUCT = 0xff */
-];
-
-#[derive(Debug, PartialEq, Eq)]
+pub(crate) use crate::instructions::OPCODE_INFOS;
+
+/// The real `(delta, alpha)` stack effect of a `DUPN`/`SWAPN`/`EXCHANGE`
+/// instance, computed from its one-byte immediate. Unlike every other
+/// opcode, these three don't have a single fixed arity `OPCODE_INFOS` can
+/// encode: `DUPN`/`SWAPN` select a depth via the immediate directly, and
+/// `EXCHANGE` splits it into two nibbles selecting the depths of the two
+/// items it swaps. Every place that needs an accurate stack-bounds check
+/// for one of these opcodes calls this instead of indexing `OPCODE_INFOS`.
+pub(crate) fn deep_stack_effect(opcode: EvmOpcode, immediate: u8) -> (u16, u16) {
+    match opcode {
+        EvmOpcode::DUPN => {
+            let depth = immediate as u16 + 1;
+            (depth, depth + 1)
+        }
+        EvmOpcode::SWAPN => {
+            let depth = immediate as u16 + 2;
+            (depth, depth)
+        }
+        EvmOpcode::EXCHANGE => {
+            let n = (immediate >> 4) as u16 + 1;
+            let m = (immediate & 0x0f) as u16 + 1;
+            let depth = n + m + 1;
+            (depth, depth)
+        }
+        _ => unreachable!("deep_stack_effect called with a non-deep-stack opcode"),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum VmError {
     None,
     StackUnderflow,
@@ -294,17 +64,26 @@ pub enum VmError {
     ReturnStackOverflow,
 }
 
-struct VmStackSlots([U256; VmStack::LEN]);
+pub(crate) struct VmStackSlots([U256; VmStack::MAX_LEN]);
 
-struct VmStack {
+/// `pub` (rather than `pub(crate)`) solely so `ExtensionHandler`
+/// implementations outside this crate can name the type; its fields stay
+/// private and every method stays `unsafe`, so it grants no more access
+/// than the dispatch loop's own opcode arms already have.
+pub struct VmStack {
     start: *const U256,
     sp: *mut U256,
 }
 
 impl VmStack {
-    pub const LEN: usize = 1024;
+    /// The interpreter's stack storage is a fixed-size array of this many
+    /// slots; `Schedule::stack_limit` configures the runtime-enforced depth,
+    /// clamped to this compile-time bound wherever it's consulted. Mainnet
+    /// has never needed more than `crate::limits::MAX_STACK`, so that's
+    /// also this buffer's size.
+    pub const MAX_LEN: usize = crate::limits::MAX_STACK;
 
-    pub unsafe fn new(slots: &mut VmStackSlots) -> VmStack {
+    pub(crate) unsafe fn new(slots: &mut VmStackSlots) -> VmStack {
         VmStack {
             start: slots.0.as_ptr(),
             // sp is always pointing at the top of the stack
@@ -312,42 +91,71 @@ impl VmStack {
         }
     }
 
+    /// # Safety
+    ///
+    /// Caller must not push past `MAX_LEN` items.
     pub unsafe fn push(&mut self, value: U256) {
+        debug_assert!(self.size() < Self::MAX_LEN, "pushing past the stack's compile-time bound");
         self.sp = self.sp.offset(1);
         store_u256(self.sp, value, 0);
     }
 
+    /// # Safety
+    ///
+    /// Caller must not pop an empty stack.
     pub unsafe fn pop(&mut self) -> U256 {
         let temp = self.peek();
         self.sp = self.sp.offset(-1);
         temp
     }
 
+    /// # Safety
+    ///
+    /// Caller must not pop an empty stack.
     pub unsafe fn pop_u256(&mut self) -> U256 {
+        debug_assert!(self.size() > 0, "popping an empty stack");
         let temp = *self.sp;
         self.sp = self.sp.offset(-1);
         temp
     }
 
+    /// # Safety
+    ///
+    /// Caller must not peek an empty stack.
     pub unsafe fn peek(&self) -> U256 {
         self.peekn(0)
     }
 
+    /// # Safety
+    ///
+    /// Caller must not peek past the bottom of the stack.
     pub unsafe fn peek1(&self) -> U256 {
         self.peekn(1)
     }
 
+    /// # Safety
+    ///
+    /// Caller must ensure `index` is within the stack's current size.
     pub unsafe fn peekn(&self, index: usize) -> U256 {
+        debug_assert!(index < self.size(), "peeking past the bottom of the stack");
         load_u256(self.sp, -(index as isize))
     }
 
+    /// # Safety
+    ///
+    /// Caller must ensure `index` is within the stack's current size.
     pub unsafe fn set(&self, index: usize, value: U256) -> U256 {
+        debug_assert!(index < self.size(), "setting past the bottom of the stack");
         let offset = -(index as isize);
         let temp = load_u256(self.sp, offset);
         store_u256(self.sp, value, offset);
         temp
     }
 
+    /// # Safety
+    ///
+    /// Caller must ensure the stack was constructed via `new` and hasn't
+    /// been aliased elsewhere.
     pub unsafe fn size(&self) -> usize {
         const WORD_SIZE: usize = std::mem::size_of::<U256>();
         usize::wrapping_sub(self.sp.offset(1) as _, self.start as _) / WORD_SIZE
@@ -369,14 +177,24 @@ impl VmReturnStack {
         }
     }
 
-    pub unsafe fn push(&mut self, value: u32) -> bool {
-        let not_overflow = self.size() < Self::LEN;
-        self.size += 1;
-        *self.values.as_mut_ptr().offset(self.size) = value;
+    /// # Safety
+    ///
+    /// `limit` must not exceed `Self::LEN`, the return stack's compile-time
+    /// storage bound.
+    pub unsafe fn push(&mut self, value: u32, limit: usize) -> bool {
+        let not_overflow = self.size() < limit;
+        // Only write on success: writing when `not_overflow` is false would
+        // land at index `limit`, which can be `Self::LEN`, one past the end
+        // of `values`.
+        if not_overflow {
+            self.size += 1;
+            *self.values.as_mut_ptr().offset(self.size) = value;
+        }
         not_overflow
     }
 
     pub unsafe fn pop(&mut self) -> u32 {
+        debug_assert!(self.size() > 0, "popping an empty return stack");
         let temp = *self.values.as_mut_ptr().offset(self.size);
         self.size -= 1;
         temp
@@ -385,19 +203,29 @@ impl VmReturnStack {
     pub unsafe fn size(&self) -> usize {
         (self.size + 1) as usize
     }
+
+    /// The return stack's entries (`JUMPSUB`/`JUMP`-return addresses),
+    /// top first, for tracer/debugger introspection.
+    pub unsafe fn contents(&self) -> Vec<u32> {
+        (0..=self.size).rev().map(|i| self.values[i as usize]).collect()
+    }
 }
 
 pub struct VmMemory {
     mmap: Option<memmap::MmapMut>,
     ptr: *mut u8,
     pub len: usize,
+    /// Word capacity of `mmap`, i.e. how far `len` is ever allowed to grow.
+    /// Equal to `find_max_mem_words(gas_limit)` unless `init_with_max_memory`
+    /// was given a lower `max_memory` cap.
+    cap: usize,
 }
 
 fn memory_gas_cost(memory_gas: u64, num_words: u64) -> u128 {
     mul_u64(memory_gas, num_words) + mul_u64(num_words, num_words) / 512
 }
 
-fn memory_extend_gas_cost(memory_gas: u64, num_words: u64, new_num_words: u64) -> u64 {
+pub(crate) fn memory_extend_gas_cost(memory_gas: u64, num_words: u64, new_num_words: u64) -> u64 {
     let t0 = mul_u64(num_words, num_words) / 512;
     let t1 = mul_u64(new_num_words, new_num_words) / 512;
     let dt = t1 - t0;
@@ -418,6 +246,7 @@ impl VmMemory {
             mmap: None,
             ptr: std::ptr::null_mut(),
             len: 0,
+            cap: 0,
         }
     }
 
@@ -442,8 +271,33 @@ impl VmMemory {
         result
     }
 
+    /// Maps `find_max_mem_words(gas_limit)` words of fresh, zeroed memory.
+    ///
+    /// Zero-on-first-touch is a property of the mapping itself, not
+    /// something this function does: `MmapMut::map_anon` is backed by an
+    /// anonymous mapping, and on every platform `memmap` supports
+    /// (`mmap(MAP_ANON)` on Unix, `VirtualAlloc(MEM_RESERVE | MEM_COMMIT)`
+    /// on Windows) the OS guarantees pages are zero the first time they're
+    /// touched, without this crate ever writing a zero byte itself. The
+    /// dispatch loop's own gas-metered growth (`meter_extend!`) relies on
+    /// that guarantee instead of memsetting newly extended memory.
     pub fn init(&mut self, gas_limit: U256) {
+        self.init_with_max_memory(gas_limit, None);
+    }
+
+    /// Like `init`, but additionally caps the mapping at `max_memory`
+    /// bytes when given, rounded down to a whole word. Without a cap, a
+    /// huge `gas_limit` alone implies a huge mapping, since `init` sizes
+    /// it to whatever that gas could ever pay to extend memory to; fuzzing
+    /// with a large gas limit to let contracts run long needs a way to
+    /// bound host RAM use independent of that. `meter_extend!` enforces
+    /// the cap by reporting `VmError::OutOfGas` on any growth past it.
+    pub fn init_with_max_memory(&mut self, gas_limit: U256, max_memory: Option<u64>) {
         let max_len = self.find_max_mem_words(gas_limit, &Schedule::default());
+        let max_len = match max_memory {
+            Some(max_memory) => max_len.min(max_memory / 32),
+            None => max_len,
+        };
         let (num_bytes, overflow) = max_len.overflowing_mul(32);
         if overflow {
             unsupported_gas!();
@@ -452,6 +306,7 @@ impl VmMemory {
             Ok(value) => value,
             Err(_) => unsupported_gas!(),
         };
+        self.cap = max_len as usize;
         if num_bytes > 0 {
             match memmap::MmapMut::map_anon(num_bytes) {
                 Ok(mut mmap) => {
@@ -463,17 +318,66 @@ impl VmMemory {
         }
     }
 
+    /// Returns this `VmMemory` to its just-`init`ed, all-zero state for
+    /// reuse across runs (e.g. the CLI's `--repeat`), without memsetting
+    /// the whole region.
+    ///
+    /// A tempting shortcut here is `madvise(MADV_DONTNEED)`: hand the pages
+    /// back to the kernel and let the same zero-on-first-touch guarantee
+    /// `init` relies on do the rest. That only holds for a `MAP_PRIVATE`
+    /// mapping, though, and `MmapMut::map_anon` is `MAP_SHARED` (see
+    /// `memmap::unix::MmapInner::map_anon`) — for a shared mapping
+    /// `MADV_DONTNEED` is documented as advisory only, and in practice
+    /// leaves the old bytes readable until the kernel reclaims them under
+    /// memory pressure, which is never in a short-lived CLI run. So this
+    /// drops the old mapping and asks `map_anon` for a fresh one instead:
+    /// same zero-on-first-touch guarantee `init` already leans on, and the
+    /// cost is the number of pages the old mapping had actually dirtied
+    /// (what the kernel must tear down), not the size of the mapping — a
+    /// gigabyte-sized run that only ever touched a few pages of it doesn't
+    /// pay for the rest.
+    pub fn reset(&mut self) {
+        if let Some(mmap) = self.mmap.as_ref() {
+            let num_bytes = mmap.len();
+            match memmap::MmapMut::map_anon(num_bytes) {
+                Ok(mut mmap) => {
+                    self.ptr = mmap.as_mut_ptr();
+                    self.mmap = Some(mmap);
+                }
+                Err(e) => panic!("{}", e),
+            }
+        }
+        self.len = 0;
+    }
+
     pub fn size(&self) -> usize {
         self.len * std::mem::size_of::<U256>()
     }
 
-    unsafe fn read(&mut self, offset: usize) -> U256 {
+    /// Reads the word at `offset`, trusting the caller to have already
+    /// gas-charged (`extend_memory!`) and bounds-checked it, exactly like
+    /// every opcode arm in `run_evm_impl` that calls this. `pub` so an
+    /// `ExtensionHandler` can read memory the same way a built-in opcode
+    /// would; use `checked_slice` instead outside a gas-metered context.
+    ///
+    /// # Safety
+    ///
+    /// `offset..offset + 32` must fall within memory already charged for
+    /// (`self.size()`).
+    pub unsafe fn read(&mut self, offset: usize) -> U256 {
         let src = self.ptr.offset(offset as isize);
         let result = bswap_u256(loadu_u256(src as *const U256, 0));
         return result;
     }
 
-    unsafe fn write(&mut self, offset: usize, value: U256) {
+    /// Writes `value` at `offset`. See `read`'s doc comment: unchecked,
+    /// `pub` for the same reason.
+    ///
+    /// # Safety
+    ///
+    /// `offset..offset + 32` must fall within memory already charged for
+    /// (`self.size()`).
+    pub unsafe fn write(&mut self, offset: usize, value: U256) {
         let dest = self.ptr.offset(offset as isize);
         storeu_u256(dest as *mut U256, bswap_u256(value), 0);
     }
@@ -483,13 +387,89 @@ impl VmMemory {
         *dest = value;
     }
 
-    pub fn slice(&self, offset: isize, size: usize) -> &[u8] {
+    /// Unchecked view into the mapped region, trusted by the dispatch loop
+    /// which only ever slices ranges it has just gas-charged via
+    /// `extend_memory!`. Callers outside the hot loop (the CLI, tooling)
+    /// should use `checked_slice` instead: `offset`/`size` there often come
+    /// straight from a contract's own claimed `ReturnData`, which nothing
+    /// stops from being out of bounds.
+    pub(crate) fn slice(&self, offset: isize, size: usize) -> &[u8] {
         unsafe { std::slice::from_raw_parts(self.ptr.offset(offset), size) }
     }
+
+    /// Bounds-checked view into the mapped region, for callers (the CLI,
+    /// tooling) that hand `offset`/`size` sourced from untrusted data such
+    /// as a contract's `ReturnData`. Returns `None` if the requested range
+    /// falls outside the memory currently charged for (`self.size()`).
+    pub fn checked_slice(&self, offset: isize, size: usize) -> Option<&[u8]> {
+        let offset = usize::try_from(offset).ok()?;
+        let end = offset.checked_add(size)?;
+        if end > self.size() {
+            return None;
+        }
+        Some(self.slice(offset as isize, size))
+    }
+
+    /// Copies `size` bytes from `src` at `src_offset` into `self` at
+    /// `dst_offset` -- the one way bytes are meant to cross between two
+    /// CALL-family frames' independent memories (see the design note on
+    /// `Opcode::CALL`'s dispatch arm, and `VmMemoryPool`), since each
+    /// frame's own `VmMemory` never aliases another's mapping.
+    ///
+    /// # Safety
+    ///
+    /// `dst_offset..dst_offset + size` must fall within `self`'s
+    /// already-charged memory (`self.size()`), and `src_offset..src_offset
+    /// + size` must fall within `src`'s, same as `read`/`write`.
+    pub unsafe fn copy_from(&mut self, dst_offset: usize, src: &VmMemory, src_offset: usize, size: usize) {
+        let src_ptr = src.ptr.add(src_offset);
+        let dst_ptr = self.ptr.add(dst_offset);
+        std::ptr::copy_nonoverlapping(src_ptr, dst_ptr, size);
+    }
+}
+
+/// A free list of previously `init`ed `VmMemory` buffers, so a CALL-family
+/// frame (see the design note on `Opcode::CALL`'s dispatch arm) can be
+/// handed an independent memory region without paying for a fresh `mmap`
+/// on every call: a `release`d frame's mapping is `reset` and handed back
+/// out by a later `acquire` instead of being unmapped.
+pub struct VmMemoryPool {
+    free: Vec<VmMemory>,
+}
+
+impl VmMemoryPool {
+    pub fn new() -> VmMemoryPool {
+        VmMemoryPool { free: Vec::new() }
+    }
+
+    /// Hands back a ready-to-use, all-zero `VmMemory` able to grow to at
+    /// least `gas_limit` could ever pay to extend to -- reusing a
+    /// `release`d frame's mapping when one is already large enough, rather
+    /// than mapping fresh every time a CALL-family frame is entered.
+    pub fn acquire(&mut self, gas_limit: U256) -> VmMemory {
+        let needed = VmMemory::new().find_max_mem_words(gas_limit, &Schedule::default());
+        if let Some(index) = self.free.iter().position(|memory| memory.cap as u64 >= needed) {
+            let mut memory = self.free.swap_remove(index);
+            memory.reset();
+            return memory;
+        }
+        let mut memory = VmMemory::new();
+        memory.init(gas_limit);
+        memory
+    }
+
+    /// Returns `memory` to the pool for a later `acquire` to reuse, once
+    /// the CALL-family frame it belonged to has returned and its memory
+    /// is no longer live.
+    pub fn release(&mut self, memory: VmMemory) {
+        self.free.push(memory);
+    }
 }
 
-fn num_words(value: u64) -> u64 {
-    ((value as u128 + 31) / 32) as u64
+impl Default for VmMemoryPool {
+    fn default() -> Self {
+        VmMemoryPool::new()
+    }
 }
 
 macro_rules! comment {
@@ -501,40 +481,151 @@ macro_rules! comment {
     };
 }
 
-macro_rules! check_exception_at {
-    ($addr:expr, $gas:ident, $rom:ident, $stack:ident, $error:ident) => {
-        let bb_info = $rom.get_bb_info($addr);
-        let (newgas, oog) = $gas.overflowing_sub(bb_info.gas);
-        $gas = newgas;
-        let stack_min_size = bb_info.stack_min_size as usize;
-        let stack_rel_max_size = bb_info.stack_rel_max_size as usize;
-        let stack_size = $stack.size();
-        let underflow = stack_size < stack_min_size;
-        let overflow = (stack_size + stack_rel_max_size) > VmStack::LEN;
-        if !(oog | underflow | overflow) {
-            continue;
-        }
-        if oog {
-            $error = VmError::OutOfGas;
+// `std::intrinsics::unlikely` is nightly-only, so these mirror it on
+// stable: routing the predicted-false/true case through a #[cold]
+// function gives the compiler the same branch-layout hint without the
+// unstable intrinsic.
+#[cold]
+#[inline(never)]
+fn cold_path() {}
+
+#[inline]
+fn likely(b: bool) -> bool {
+    if !b {
+        cold_path();
+    }
+    b
+}
+
+#[inline]
+fn unlikely(b: bool) -> bool {
+    if b {
+        cold_path();
+    }
+    b
+}
+
+#[cold]
+#[inline(never)]
+fn bb_check_error(oog: bool, underflow: bool, overflow: bool) -> VmError {
+    if oog {
+        VmError::OutOfGas
+    } else if underflow {
+        VmError::StackUnderflow
+    } else {
+        debug_assert!(overflow);
+        VmError::StackOverflow
+    }
+}
+
+/// Slow path for a `StackUnderflow`/`StackOverflow` block check: re-walks
+/// the failing block one instruction at a time, using the same
+/// `OPCODE_INFOS` deltas `VmRom::init`'s forward pass used to precompute
+/// the block's (min, max) stack requirements, and stops at the first
+/// instruction that actually violates `stack_size`. Only ever called after
+/// `check_exception_at!` has already flagged the block, so it never runs
+/// on the happy path.
+#[cold]
+#[inline(never)]
+fn locate_stack_fault(bytecode: &[u8], start_pc: usize, mut stack_size: usize, stack_limit: usize) -> (usize, EvmOpcode) {
+    let mut i = start_pc;
+    // Bounded by the block having already been proven to violate the
+    // stack somewhere in `bytecode[start_pc..]`; the cap is just a
+    // safety net against ever looping forever if that invariant breaks.
+    for _ in 0..=bytecode.len() {
+        let code = bytecode.get(i).copied().unwrap_or(0);
+        let opcode = unsafe { std::mem::transmute::<u8, EvmOpcode>(code) };
+        let (delta, alpha) = if opcode.is_deep_stack() {
+            let immediate = bytecode.get(i + 1).copied().unwrap_or(0);
+            deep_stack_effect(opcode, immediate)
+        } else {
+            let (_, _, delta, alpha) = OPCODE_INFOS[code as usize];
+            (delta, alpha)
+        };
+        let (delta, alpha) = (delta as usize, alpha as usize);
+        if stack_size < delta {
+            return (i, opcode);
         }
-        if underflow {
-            $error = VmError::StackUnderflow;
+        let new_stack_size = stack_size - delta + alpha;
+        if new_stack_size > stack_limit {
+            return (i, opcode);
         }
-        if overflow {
-            $error = VmError::StackOverflow;
+        stack_size = new_stack_size;
+        i += if opcode.is_push() {
+            1 + opcode.push_index() + 1
+        } else if opcode.is_deep_stack() {
+            2
+        } else {
+            1
+        };
+    }
+    (start_pc, unsafe { std::mem::transmute::<u8, EvmOpcode>(bytecode.get(start_pc).copied().unwrap_or(0)) })
+}
+
+fn stack_fault(error: &VmError, bytecode: &[u8], pc: usize, stack_size: usize, stack_limit: usize) -> Option<StackFault> {
+    match error {
+        VmError::StackUnderflow | VmError::StackOverflow => {
+            let (pc, opcode) = locate_stack_fault(bytecode, pc, stack_size, stack_limit);
+            Some(StackFault { pc, opcode })
+        }
+        _ => None,
+    }
+}
+
+/// The gas/stack precondition check for entering the basic block at `addr`:
+/// charges the block's aggregate gas cost and validates the current stack
+/// depth against the block's (min, max) requirements, both precomputed by
+/// `VmRom::init`'s forward pass. Shared by the very first block (checked
+/// directly in `run_evm_impl`) and every later branch target (checked via
+/// `check_exception_at!`), so the two can never drift apart.
+#[inline(always)]
+unsafe fn check_bb_entry(
+    addr: u64,
+    gas: &mut u64,
+    rom: &VmRom,
+    stack: &VmStack,
+    schedule: &Schedule,
+) -> Result<(), VmError> {
+    let bb_info = rom.get_bb_info(addr);
+    let (newgas, oog) = gas.overflowing_sub(bb_info.gas);
+    *gas = newgas;
+    let stack_min_size = bb_info.stack_min_size as usize;
+    let stack_rel_max_size = bb_info.stack_rel_max_size as usize;
+    let stack_size = stack.size();
+    let underflow = stack_size < stack_min_size;
+    let stack_limit = schedule.stack_limit.min(VmStack::MAX_LEN);
+    let overflow = (stack_size + stack_rel_max_size) > stack_limit;
+    if likely(!(oog | underflow | overflow)) {
+        return Ok(());
+    }
+    Err(bb_check_error(oog, underflow, overflow))
+}
+
+/// At a branch target, inside the dispatch loop: `continue`s the loop on
+/// success, or records `$error` for the caller's trailing `break` on
+/// failure. See `check_bb_entry` for the check itself.
+macro_rules! check_exception_at {
+    ($addr:expr, $gas:ident, $rom:ident, $stack:ident, $schedule:ident, $error:ident) => {
+        match check_bb_entry($addr, &mut $gas, $rom, &$stack, $schedule) {
+            Ok(()) => continue,
+            Err(e) => $error = e,
         }
     };
 }
 
 macro_rules! meter_extend {
     ($new_len:ident, $overflow:ident, $schedule:ident, $memory:ident, $gas:ident, $error:ident) => {
-        if !$overflow {
+        if likely(!$overflow) {
             let len = $memory.len as u64;
             if $new_len > len {
+                if unlikely($new_len as usize > $memory.cap) {
+                    $error = VmError::OutOfGas;
+                    break;
+                }
                 let cost = memory_extend_gas_cost($schedule.memory_gas, len, $new_len);
                 let (newgas, oog) = $gas.overflowing_sub(cost);
                 $gas = newgas;
-                if !oog {
+                if likely(!oog) {
                     $memory.len = $new_len as usize;
                 } else {
                     $error = VmError::OutOfGas;
@@ -550,7 +641,7 @@ macro_rules! meter_extend {
 
 macro_rules! extend_memory {
     ($offset:ident, $size:literal, $schedule:ident, $memory:ident, $gas:ident, $error:ident) => {
-        if $offset.le_u64() {
+        if likely($offset.le_u64()) {
             let (new_len, overflow) = {
                 let (temp, overflow) = $offset.low_u64().overflowing_add($size + 31);
                 (temp / 32, overflow)
@@ -562,37 +653,37 @@ macro_rules! extend_memory {
         }
     };
     ($offset:ident, $size:ident, $schedule:ident, $memory:ident, $gas:ident, $error:ident) => {
-        if $offset.le_u64() & $size.le_u64() {
-            let (new_len, overflow) = {
-                let (temp1, overflow1) = $offset.low_u64().overflowing_add($size.low_u64());
-                let (temp2, overflow2) = temp1.overflowing_add(31);
-                (temp2 / 32, overflow1 | overflow2)
-            };
-            let new_len = if $size.low_u64() == 0 {
-                $memory.len as u64
+        // A zero-size range never charges for memory and never touches
+        // `$offset`, however large or malformed: the spec (and every other
+        // client) ignores the offset entirely in this case, so a huge or
+        // out-of-u64-range offset paired with size 0 must still succeed.
+        if !is_zero_u256($size) {
+            if likely($offset.le_u64() & $size.le_u64()) {
+                let (new_len, overflow) = {
+                    let (temp1, overflow1) = $offset.low_u64().overflowing_add($size.low_u64());
+                    let (temp2, overflow2) = temp1.overflowing_add(31);
+                    (temp2 / 32, overflow1 | overflow2)
+                };
+                meter_extend!(new_len, overflow, $schedule, $memory, $gas, $error);
             } else {
-                new_len
-            };
-            meter_extend!(new_len, overflow, $schedule, $memory, $gas, $error);
-        } else {
-            $error = VmError::OutOfGas;
-            break;
+                $error = VmError::OutOfGas;
+                break;
+            }
         }
     };
 }
 
-fn log256(value: u64) -> u64 {
+pub(crate) fn log256(value: u64) -> u64 {
     value.wrapping_sub(1) / 8
 }
 
 macro_rules! meter_exp {
     ($exponent_bits:expr, $schedule:ident, $gas:ident, $error:ident) => {
-        let fee = $schedule.fees[Fee::ExpByte as usize] as u64;
+        let fee = $schedule.opcode_gas(EvmOpcode::EXP, Fee::ExpByte);
         let cost = ($exponent_bits > 0) as u64 * fee * (1 + log256($exponent_bits));
         let (newgas, oog) = $gas.overflowing_sub(cost);
         $gas = newgas;
-        //if std::intrinsics::unlikely(oog) {
-        if oog {
+        if unlikely(oog) {
             $error = VmError::OutOfGas;
             break;
         }
@@ -601,23 +692,41 @@ macro_rules! meter_exp {
 
 macro_rules! meter_sha3 {
     ($size:ident, $schedule:ident, $gas:ident, $error:ident) => {
-        let fee = $schedule.fees[Fee::Sha3Word as usize] as u64;
-        let (cost, ovf) = num_words($size.low_u64()).overflowing_mul(fee);
+        let fee = $schedule.opcode_gas(EvmOpcode::SHA3, Fee::Sha3Word);
+        let (cost, ovf) = crate::limits::num_words($size.low_u64()).overflowing_mul(fee);
         let (newgas, oog) = $gas.overflowing_sub(cost as u64);
         $gas = newgas;
-        if oog | ovf | !$size.le_u64() {
+        if unlikely(oog | ovf | !$size.le_u64()) {
             $error = VmError::OutOfGas;
             break;
         }
     };
 }
 
+/// Which instruction inside a stack-checked block actually violated the
+/// stack size. Block-level checks (`check_exception_at!`) only know that
+/// *some* instruction in the block would under/overflow, not which one;
+/// this is filled in by `locate_stack_fault`'s slow-path re-walk, so it
+/// costs nothing when the block is fine.
+#[derive(Debug, Clone, Copy)]
+pub struct StackFault {
+    pub pc: usize,
+    pub opcode: EvmOpcode,
+}
+
+/// `offset`/`size` locate the output bytes inside `memory`'s buffer; this
+/// is a zero-copy view, valid only for as long as `memory` itself is,
+/// which breaks if `memory` is reset or reused for another run before the
+/// caller reads it (e.g. the `--repeat` CLI flag). `run_evm_with_owned_output`
+/// copies the bytes out immediately instead, for callers that need the
+/// output to outlive `memory`'s next use.
 #[derive(Debug)]
 pub struct ReturnData {
     pub offset: usize,
     pub size: usize,
     pub gas: u64,
     pub error: VmError,
+    pub fault: Option<StackFault>,
 }
 
 impl ReturnData {
@@ -627,6 +736,7 @@ impl ReturnData {
             size,
             gas,
             error,
+            fault: None,
         }
     }
 
@@ -636,6 +746,257 @@ impl ReturnData {
             size,
             gas,
             error: VmError::None,
+            fault: None,
+        }
+    }
+}
+
+/// A location `run_evm_with_breakpoint` should stop execution at, checked
+/// before each instruction dispatches.
+#[derive(Debug, Clone, Copy)]
+pub enum Breakpoint {
+    Pc(usize),
+    Opcode(EvmOpcode),
+    /// Fires when a memory write overlaps `[start, end)`, checked at the
+    /// point of the write rather than before dispatch like the other
+    /// variants (see `check_memory_watchpoint!`). Only `MSTORE`/`MSTORE8`
+    /// can trigger it today: `CALLDATACOPY` and `CODECOPY` still lack the
+    /// account model they'd need to execute, and report
+    /// `VmError::InvalidInstruction` instead.
+    MemoryWrite { start: usize, end: usize },
+}
+
+/// A validated starting point for `run_evm_resume`, in place of the normal
+/// `pc = 0`/empty-stack/`gas_limit` start state: a debugger stepping back
+/// in after a `BreakpointHit`, or any other caller that already knows
+/// where and in what state execution should pick up.
+pub struct ResumePoint<'a> {
+    /// Must be `0`, or an address `VmRom::is_jumpdest`/`is_beginsub`
+    /// accepts -- the same set of addresses a running `JUMP`/`JUMPI`/
+    /// `JUMPSUB` may land on -- since those are the only addresses
+    /// `VmRom::get_bb_info` has a real (non-default) entry for.
+    pub pc: usize,
+    /// Top of stack first, same convention as `BreakpointHit::stack`.
+    pub stack: &'a [U256],
+    pub gas: u64,
+}
+
+/// VM state captured when a `Breakpoint` is hit, for scripted bisection of
+/// a misbehaving contract without a full interactive debugger.
+#[derive(Debug)]
+pub struct BreakpointHit {
+    pub pc: usize,
+    pub gas: u64,
+    /// Top of stack first.
+    pub stack: Vec<U256>,
+    pub memory_len: usize,
+    /// The `JUMPSUB`/`JUMP`-return address return stack, top first (see
+    /// `VmReturnStack::contents`), for observing subroutine nesting depth.
+    pub return_stack: Vec<u32>,
+}
+
+/// A per-step trace query for `run_evm_with_trace`.
+///
+/// Rather than embedding a general-purpose scripting engine, this covers
+/// the two example queries directly ("count SLOADs", "print stack top at
+/// every JUMPI") as fixed variants: it's a fraction of the implementation
+/// cost of a real expression language and covers the common case, at the
+/// cost of a recompile to add a new kind of query. Growing this into an
+/// embedded language (e.g. rhai) is future work if the fixed set stops
+/// being enough.
+///
+/// Performance trade-off: an active trace adds one extra branch and an
+/// opcode-byte comparison per step (see `run_evm_impl`), same as an active
+/// `Breakpoint`; with no trace running the cost is a single `None` check
+/// per step.
+#[derive(Debug, Clone, Copy)]
+pub enum TraceFilter {
+    /// Counts occurrences of the given opcode.
+    CountOpcode(EvmOpcode),
+    /// Counts occurrences of the given opcode and records the stack top at
+    /// each one (e.g. the slot for `SLOAD`/`SSTORE`, or the condition for
+    /// `JUMPI`).
+    StackTopAt(EvmOpcode),
+    /// Counts occurrences of the given opcode and records the return
+    /// stack's full contents (top first) at each one, for observing
+    /// subroutine nesting depth around `JUMPSUB`/`RETURNSUB`.
+    ReturnStackAt(EvmOpcode),
+    /// Records how many times every `pc` is visited, not just one opcode's.
+    /// Unlike the other variants this isn't a "find occurrences of X"
+    /// query: it's a full per-instruction execution histogram for one run,
+    /// meant to be aggregated into per-block counts (see `opt::pgo`) and
+    /// fed back into the optimizer as a profile-guided hint about which
+    /// blocks are worth treating more aggressively.
+    PcCounts,
+}
+
+/// Bounds how often an active `TraceFilter` actually evaluates, for runs too
+/// long to record (or even just check) every single step. The disabled case
+/// (`Every`) costs nothing extra: it's folded into the same per-step branch
+/// `TraceFilter` already pays for; the other variants add one counter
+/// comparison (`EveryNth`), one `VmRom::is_jumpdest` lookup
+/// (`BlockBoundaries`), or one slice scan (`Opcodes`) before the filter
+/// itself runs.
+#[derive(Debug, Clone)]
+pub enum TraceSample {
+    /// Every step — `run_evm_with_trace`'s original, unsampled behavior.
+    Every,
+    /// Every Nth step, by raw instruction count (`EveryNth(1)` is the same
+    /// as `Every`). Constructing this with `n == 0` is a caller bug; see
+    /// `cli_parse::parse_trace_sample`, the only place that should build one
+    /// from untrusted input.
+    EveryNth(u32),
+    /// Only steps where `pc` is `0` or a valid jump target, i.e. the first
+    /// instruction of a basic block.
+    BlockBoundaries,
+    /// Only steps whose current opcode is one of the given set.
+    Opcodes(Vec<EvmOpcode>),
+}
+
+impl TraceSample {
+    fn should_sample(&self, step: u64, pc: usize, raw_opcode: u8, rom: &VmRom) -> bool {
+        match self {
+            TraceSample::Every => true,
+            TraceSample::EveryNth(n) => step.is_multiple_of(u64::from(*n)),
+            TraceSample::BlockBoundaries => pc == 0 || rom.is_jumpdest(pc as u64),
+            TraceSample::Opcodes(opcodes) => opcodes.iter().any(|opcode| *opcode as u8 == raw_opcode),
+        }
+    }
+}
+
+/// The result of running with a `TraceFilter`.
+#[derive(Debug, Default)]
+pub struct TraceReport {
+    pub matches: u64,
+    /// `(pc, stack_top)` pairs, only populated for `TraceFilter::StackTopAt`.
+    pub stack_tops: Vec<(usize, U256)>,
+    /// `(pc, return_stack)` pairs (return stack top first), only populated
+    /// for `TraceFilter::ReturnStackAt`.
+    pub return_stacks: Vec<(usize, Vec<u32>)>,
+    /// Visit count per `pc`, only populated for `TraceFilter::PcCounts`.
+    pub pc_counts: std::collections::BTreeMap<u32, u64>,
+    /// The deepest the `JUMPSUB`/`RETURNSUB` shadow stack (EIP-2315) got
+    /// during the run, tracked unconditionally regardless of which
+    /// `TraceFilter` is active -- unlike the fields above, this doesn't
+    /// depend on the filter matching anything, so every `run_evm_with_trace`
+    /// call gets it for free.
+    pub max_return_stack_depth: usize,
+}
+
+impl TraceReport {
+    /// Preallocates `stack_tops`/`return_stacks` for `capacity` matches, so
+    /// a long-running trace over a hot loop doesn't pay for repeated `Vec`
+    /// growth on every match; pass `0` (same as `Default::default()`) when
+    /// the expected match count isn't known ahead of time.
+    pub fn with_capacity(capacity: usize) -> TraceReport {
+        TraceReport {
+            matches: 0,
+            stack_tops: Vec::with_capacity(capacity),
+            return_stacks: Vec::with_capacity(capacity),
+            pc_counts: std::collections::BTreeMap::new(),
+            max_return_stack_depth: 0,
+        }
+    }
+}
+
+/// Supplies the hash of a historical block for the `BLOCKHASH` opcode.
+///
+/// The interpreter has no notion of a chain: it only sees bytecode and a
+/// gas limit. Rather than hardcoding a real chain's blockhash oracle,
+/// execution is parameterized by this trait so callers can plug in
+/// whatever history they have (or, for tests, none at all).
+pub trait BlockHashProvider {
+    fn block_hash(&self, number: U256) -> U256;
+}
+
+/// Deterministic `BlockHashProvider` for tests: `hash = keccak(number)`,
+/// so expected values in test vectors don't depend on external state.
+pub struct TestBlockHashProvider;
+
+impl BlockHashProvider for TestBlockHashProvider {
+    fn block_hash(&self, number: U256) -> U256 {
+        let mut bytes = [0u8; 32];
+        for (i, limb) in number.0.iter().rev().enumerate() {
+            bytes[i * 8..i * 8 + 8].copy_from_slice(&limb.to_be_bytes());
+        }
+        unsafe { sha3_u256(bytes.as_ptr(), bytes.len()) }
+    }
+}
+
+/// The chain state `BLOCKHASH` and `DIFFICULTY` need but the rest of the
+/// interpreter has no use for: the current block number, a way to look up
+/// ancestors, and both the pre-Merge difficulty and post-Merge prevrandao
+/// values (`DIFFICULTY`, opcode 0x44, returns whichever one applies to
+/// `schedule.fork`; see the opcode's arm in `run_evm_impl`). `gas_price`,
+/// `value`, and the EIP-1559 fee fields are threaded through here too,
+/// even though none of them is block-level state, for lack of anywhere
+/// else to put transaction inputs: `CALLVALUE` still dispatches to
+/// `VmError::InvalidInstruction` (see its arm in `run_evm_impl`), so
+/// setting `value` is inert until it's implemented. `new` defaults every
+/// field but `number` to zero (or empty, for `versioned_hashes`); set them
+/// directly (all fields are `pub`) when the caller has real data.
+pub struct BlockContext<'a> {
+    pub number: U256,
+    pub hashes: &'a dyn BlockHashProvider,
+    pub difficulty: U256,
+    pub prevrandao: U256,
+    /// The legacy (pre-EIP-1559) `gasPrice` a transaction was sent with.
+    /// `GASPRICE` returns this directly on `Fork < London`; see
+    /// `effective_gas_price`.
+    pub gas_price: U256,
+    pub value: U256,
+    /// EIP-1559 `maxFeePerGas`: the most a transaction's sender will pay
+    /// per unit of gas, base fee and priority fee combined.
+    pub max_fee_per_gas: U256,
+    /// EIP-1559 `maxPriorityFeePerGas`: the most a transaction's sender
+    /// will pay the block producer per unit of gas, on top of the base
+    /// fee.
+    pub max_priority_fee_per_gas: U256,
+    /// The block's EIP-1559 base fee, burned rather than paid to the
+    /// block producer.
+    pub base_fee: U256,
+    /// EIP-4844 `blob_versioned_hashes` from the transaction, indexed by
+    /// `BLOBHASH`. Out-of-range indices return zero rather than erroring,
+    /// per the EIP; see `BLOBHASH`'s arm in `run_evm_impl`.
+    pub versioned_hashes: &'a [U256],
+    /// EIP-7516 `BLOBBASEFEE`: the current block's blob gas price,
+    /// computed from the excess blob gas by clients, not by this
+    /// interpreter -- callers compute it and set it here the same way they
+    /// compute `base_fee`.
+    pub blob_gasprice: U256,
+}
+
+impl<'a> BlockContext<'a> {
+    pub fn new(number: U256, hashes: &'a dyn BlockHashProvider) -> BlockContext<'a> {
+        BlockContext {
+            number,
+            hashes,
+            difficulty: U256::from_u64(0),
+            prevrandao: U256::from_u64(0),
+            gas_price: U256::from_u64(0),
+            value: U256::from_u64(0),
+            max_fee_per_gas: U256::from_u64(0),
+            max_priority_fee_per_gas: U256::from_u64(0),
+            base_fee: U256::from_u64(0),
+            versioned_hashes: &[],
+            blob_gasprice: U256::from_u64(0),
+        }
+    }
+
+    /// The gas price `GASPRICE` reports: the legacy `gas_price` before
+    /// London, or the EIP-1559 effective gas price from London onward —
+    /// `min(max_fee_per_gas, base_fee + max_priority_fee_per_gas)`, the
+    /// base fee plus as much of the priority fee as `max_fee_per_gas`
+    /// leaves room for.
+    pub fn effective_gas_price(&self, fork: Fork) -> U256 {
+        if fork < Fork::London {
+            return self.gas_price;
+        }
+        let (fee_cap, overflowed) = overflowing_add_u256(self.base_fee, self.max_priority_fee_per_gas);
+        if overflowed || gt_u256(fee_cap, self.max_fee_per_gas) {
+            self.max_fee_per_gas
+        } else {
+            fee_cap
         }
     }
 }
@@ -643,6 +1004,32 @@ impl ReturnData {
 fn lldb_hook_single_step(pc: usize, gas: u64, stsize: usize, rssize: usize, msize: usize) {}
 fn lldb_hook_stop(pc: usize, gas: u64, stsize: usize, rssize: usize, msize: usize) {}
 
+// Unlike the `Pc`/`Opcode` breakpoints, which are checked once up front
+// before an instruction dispatches, a memory watchpoint can only be
+// evaluated once the write's address is known, so each memory-writing
+// opcode checks it itself, after performing the write.
+macro_rules! check_memory_watchpoint {
+    ($breakpoint:expr, $offset:expr, $len:expr, $pc:ident, $gas:ident, $stack:ident, $rstack:ident, $memory:ident) => {
+        if let Some(Breakpoint::MemoryWrite { start, end }) = $breakpoint {
+            let write_start = $offset;
+            let write_end = write_start + $len;
+            if write_start < end && write_end > start {
+                let stack_top_first = (0..$stack.size()).map(|i| $stack.peekn(i)).collect();
+                return (
+                    ReturnData::new(0, 0, $gas, VmError::None),
+                    Some(BreakpointHit {
+                        pc: $pc,
+                        gas: $gas,
+                        stack: stack_top_first,
+                        memory_len: $memory.size(),
+                        return_stack: $rstack.contents(),
+                    }),
+                );
+            }
+        }
+    };
+}
+
 macro_rules! lldb_hook {
     ($pc:expr, $gas:expr, $stack:ident, $rstack:ident, $memory:ident, $hook:ident) => {
         #[cfg(debug_assertions)]
@@ -662,23 +1049,259 @@ pub unsafe fn run_evm(
     bytecode: &[u8],
     rom: &VmRom,
     schedule: &Schedule,
+    block: &BlockContext,
+    gas_limit: U256,
+    memory: &mut VmMemory,
+) -> ReturnData {
+    run_evm_impl(bytecode, rom, schedule, block, gas_limit, memory, None, None, None, None).0
+}
+
+/// Like `run_evm`, but copies the output bytes out of `memory` into an
+/// owned buffer before returning, rather than leaving the caller to read
+/// them through `ReturnData::offset`/`size` before `memory` is reset or
+/// reused. Returns `None` in place of the output if `offset`/`size` (a
+/// contract's own claimed return range) falls outside the memory mapped
+/// for this run, same as `VmMemory::checked_slice`.
+///
+/// Costs one allocation and copy per call; callers that read the output
+/// immediately and don't reuse `memory` afterwards can use `run_evm`
+/// directly and slice it themselves to skip that cost.
+///
+/// # Safety
+///
+/// Same preconditions as `run_evm`: `bytecode` and `rom` must agree (`rom`
+/// was built from `bytecode` via `VmRom::init`), and `memory` must have
+/// been `init`ialized with the same `gas_limit`.
+pub unsafe fn run_evm_with_owned_output(
+    bytecode: &[u8],
+    rom: &VmRom,
+    schedule: &Schedule,
+    block: &BlockContext,
+    gas_limit: U256,
+    memory: &mut VmMemory,
+) -> (ReturnData, Option<Vec<u8>>) {
+    let ret_data = run_evm(bytecode, rom, schedule, block, gas_limit, memory);
+    let output = memory
+        .checked_slice(ret_data.offset as isize, ret_data.size)
+        .map(|slice| slice.to_vec());
+    (ret_data, output)
+}
+
+/// Like `run_evm`, but dispatches opcodes `0xb0..=0xcf` (otherwise
+/// `VmError::InvalidInstruction`) to `extension` instead. See
+/// `crate::extension` for the hook this exists to support.
+///
+/// # Safety
+///
+/// Same preconditions as `run_evm`: `bytecode` and `rom` must agree (`rom`
+/// was built from `bytecode` via `VmRom::init`), and `memory` must have
+/// been `init`ialized with the same `gas_limit`.
+pub unsafe fn run_evm_with_extension(
+    bytecode: &[u8],
+    rom: &VmRom,
+    schedule: &Schedule,
+    block: &BlockContext,
+    gas_limit: U256,
+    memory: &mut VmMemory,
+    extension: &dyn crate::extension::ExtensionHandler,
+) -> ReturnData {
+    run_evm_impl(bytecode, rom, schedule, block, gas_limit, memory, None, None, None, Some(extension)).0
+}
+
+/// Like `run_evm`, but stops and returns a `BreakpointHit` snapshot as
+/// soon as `breakpoint` is reached, instead of running to completion.
+///
+/// # Safety
+///
+/// Same preconditions as `run_evm`: `bytecode` and `rom` must agree (`rom`
+/// was built from `bytecode` via `VmRom::init`), and `memory` must have
+/// been `init`ialized with the same `gas_limit`.
+pub unsafe fn run_evm_with_breakpoint(
+    bytecode: &[u8],
+    rom: &VmRom,
+    schedule: &Schedule,
+    block: &BlockContext,
+    gas_limit: U256,
+    memory: &mut VmMemory,
+    breakpoint: Breakpoint,
+) -> (ReturnData, Option<BreakpointHit>) {
+    run_evm_impl(bytecode, rom, schedule, block, gas_limit, memory, None, Some(breakpoint), None, None)
+}
+
+/// Like `run_evm`, but starts execution from `resume` instead of `pc = 0`
+/// with an empty stack and `gas_limit` -- e.g. continuing past a
+/// `BreakpointHit`, whose `pc`/`stack`/`gas` feed `ResumePoint` directly.
+///
+/// `resume.pc` is checked against the same `is_jumpdest`/`is_beginsub`
+/// set a live `JUMP`/`JUMPI`/`JUMPSUB` may land on (or `0`), since those
+/// are the only addresses with a real `BbInfo`; anything else fails with
+/// `VmError::InvalidJumpDest` before a single instruction runs. The
+/// return stack always starts empty -- resuming mid-subroutine with its
+/// call frames intact isn't supported.
+///
+/// # Safety
+///
+/// Same preconditions as `run_evm`: `bytecode` and `rom` must agree (`rom`
+/// was built from `bytecode` via `VmRom::init`), and `memory` must have
+/// been `init`ialized with the same `gas_limit`. Additionally,
+/// `resume.stack.len()` must not exceed `VmStack::MAX_LEN`.
+pub unsafe fn run_evm_resume(
+    bytecode: &[u8],
+    rom: &VmRom,
+    schedule: &Schedule,
+    block: &BlockContext,
     gas_limit: U256,
     memory: &mut VmMemory,
+    resume: ResumePoint<'_>,
 ) -> ReturnData {
+    run_evm_impl(bytecode, rom, schedule, block, gas_limit, memory, Some(resume), None, None, None).0
+}
+
+/// Like `run_evm`, but runs `filter` against every step `sample` selects and
+/// returns the resulting `TraceReport` alongside the normal `ReturnData`.
+/// Pass `TraceSample::Every` for the original, unsampled behavior.
+///
+/// `capacity_hint` preallocates the report's match buffers (see
+/// `TraceReport::with_capacity`); pass `0` if the expected match count
+/// isn't known ahead of time.
+///
+/// # Safety
+///
+/// Same preconditions as `run_evm`: `bytecode` and `rom` must agree (`rom`
+/// was built from `bytecode` via `VmRom::init`), and `memory` must have
+/// been `init`ialized with the same `gas_limit`.
+#[allow(clippy::too_many_arguments)]
+pub unsafe fn run_evm_with_trace(
+    bytecode: &[u8],
+    rom: &VmRom,
+    schedule: &Schedule,
+    block: &BlockContext,
+    gas_limit: U256,
+    memory: &mut VmMemory,
+    filter: TraceFilter,
+    sample: TraceSample,
+    capacity_hint: usize,
+) -> (ReturnData, TraceReport) {
+    let mut report = TraceReport::with_capacity(capacity_hint);
+    let ret_data = run_evm_impl(
+        bytecode,
+        rom,
+        schedule,
+        block,
+        gas_limit,
+        memory,
+        None,
+        None,
+        Some((filter, sample, &mut report)),
+        None,
+    )
+    .0;
+    (ret_data, report)
+}
+
+#[allow(clippy::too_many_arguments)]
+unsafe fn run_evm_impl(
+    bytecode: &[u8],
+    rom: &VmRom,
+    schedule: &Schedule,
+    block: &BlockContext,
+    gas_limit: U256,
+    memory: &mut VmMemory,
+    resume: Option<ResumePoint<'_>>,
+    breakpoint: Option<Breakpoint>,
+    mut trace: Option<(TraceFilter, TraceSample, &mut TraceReport)>,
+    extension: Option<&dyn crate::extension::ExtensionHandler>,
+) -> (ReturnData, Option<BreakpointHit>) {
     let mut slots: VmStackSlots = MaybeUninit::uninit().assume_init();
     let mut stack: VmStack = VmStack::new(&mut slots);
     let mut rstack = VmReturnStack::new();
     let code: *const Opcode = rom.code() as *const Opcode;
-    let mut pc: usize = 0;
-    let mut gas = gas_limit.low_u64();
+    let (mut pc, mut gas): (usize, u64) = match &resume {
+        Some(point) => {
+            for &value in point.stack.iter().rev() {
+                stack.push(value);
+            }
+            (point.pc, point.gas)
+        }
+        None => (0, gas_limit.low_u64()),
+    };
     let mut error: VmError = VmError::None;
-    let mut entered = false;
-    while !entered {
-        entered = true;
-        check_exception_at!(0, gas, rom, stack, error);
-        return ReturnData::new(0, 0, gas, error);
+    let is_valid_entry = pc == 0 || rom.is_jumpdest(pc as u64) || rom.is_beginsub(pc as u64);
+    let entry_check = if is_valid_entry {
+        check_bb_entry(pc as u64, &mut gas, rom, &stack, schedule)
+    } else {
+        Err(VmError::InvalidJumpDest)
+    };
+    if let Err(e) = entry_check {
+        error = e;
+        let fault = stack_fault(&error, bytecode, pc, stack.size(), schedule.stack_limit.min(VmStack::MAX_LEN));
+        return (
+            ReturnData {
+                fault,
+                ..ReturnData::new(0, 0, gas, error)
+            },
+            None,
+        );
     }
+    let mut trace_step: u64 = 0;
     loop {
+        // `pc` walks `rom`'s zero-padded fixed-size buffer, which can run
+        // past the end of `bytecode` itself (e.g. fallthrough off the last
+        // real instruction); treat anything past the end as an implicit
+        // `STOP` (0x00), same as the padding does.
+        let raw_opcode = bytecode.get(pc).copied().unwrap_or(0);
+        if let Some(bp) = breakpoint {
+            let hit = match bp {
+                Breakpoint::Pc(target) => pc == target,
+                Breakpoint::Opcode(target) => raw_opcode == target as u8,
+                // Checked at the write site instead; see `check_memory_watchpoint!`.
+                Breakpoint::MemoryWrite { .. } => false,
+            };
+            if hit {
+                let stack_top_first = (0..stack.size()).map(|i| stack.peekn(i)).collect();
+                return (
+                    ReturnData::new(0, 0, gas, VmError::None),
+                    Some(BreakpointHit {
+                        pc,
+                        gas,
+                        stack: stack_top_first,
+                        memory_len: memory.size(),
+                        return_stack: rstack.contents(),
+                    }),
+                );
+            }
+        }
+        if let Some((filter, sample, report)) = trace.as_mut() {
+            report.max_return_stack_depth = report.max_return_stack_depth.max(rstack.size());
+            if sample.should_sample(trace_step, pc, raw_opcode, rom) {
+                match filter {
+                    TraceFilter::CountOpcode(target) => {
+                        if raw_opcode == *target as u8 {
+                            report.matches += 1;
+                        }
+                    }
+                    TraceFilter::StackTopAt(target) => {
+                        if raw_opcode == *target as u8 {
+                            report.matches += 1;
+                            if stack.size() > 0 {
+                                report.stack_tops.push((pc, stack.peekn(0)));
+                            }
+                        }
+                    }
+                    TraceFilter::ReturnStackAt(target) => {
+                        if raw_opcode == *target as u8 {
+                            report.matches += 1;
+                            report.return_stacks.push((pc, rstack.contents()));
+                        }
+                    }
+                    TraceFilter::PcCounts => {
+                        report.matches += 1;
+                        *report.pc_counts.entry(pc as u32).or_insert(0) += 1;
+                    }
+                }
+            }
+            trace_step += 1;
+        }
         let opcode = *code.offset(pc as isize);
         lldb_hook!(pc, gas, stack, rstack, memory, lldb_hook_single_step);
         //println!("{:?}", opcode);
@@ -933,6 +1556,11 @@ pub unsafe fn run_evm(
                 //
                 pc += 1;
             }
+            // BALANCE (and SELFBALANCE below) are priced per fork in
+            // `Schedule` already, but executing them needs an account
+            // model this interpreter doesn't have yet: report them as
+            // undispatchable rather than panicking, same as a genuinely
+            // undefined opcode.
             Opcode::ADDRESS
             | Opcode::BALANCE
             | Opcode::ORIGIN
@@ -940,28 +1568,88 @@ pub unsafe fn run_evm(
             | Opcode::CALLVALUE
             | Opcode::CALLDATALOAD
             | Opcode::CALLDATASIZE
-            | Opcode::CALLDATACOPY => unimplemented!(),
+            | Opcode::CALLDATACOPY => {
+                error = VmError::InvalidInstruction;
+                break;
+            }
             Opcode::CODESIZE => {
                 comment!("opCODESIZE");
                 stack.push(U256::from_u64(bytecode.len() as u64));
                 //
                 pc += 1;
             }
+            Opcode::BLOCKHASH => {
+                comment!("opBLOCKHASH");
+                let number = stack.pop_u256();
+                let hash = if lt_u256(number, block.number) {
+                    let age = sub_u256(block.number, number);
+                    if !gt_u256(age, U256::from_u64(256)) {
+                        block.hashes.block_hash(number)
+                    } else {
+                        U256::from_u64(0)
+                    }
+                } else {
+                    U256::from_u64(0)
+                };
+                stack.push(hash);
+                //
+                pc += 1;
+            }
+            Opcode::DIFFICULTY => {
+                comment!("opDIFFICULTY");
+                let value = if schedule.fork >= Fork::Paris {
+                    block.prevrandao
+                } else {
+                    block.difficulty
+                };
+                stack.push(value);
+                //
+                pc += 1;
+            }
+            Opcode::GASPRICE => {
+                comment!("opGASPRICE");
+                let value = block.effective_gas_price(schedule.fork);
+                stack.push(value);
+                //
+                pc += 1;
+            }
+            Opcode::BLOBHASH => {
+                comment!("opBLOBHASH");
+                let index = stack.pop_u256();
+                let hash = if index.le_u64() {
+                    block
+                        .versioned_hashes
+                        .get(index.low_u64() as usize)
+                        .copied()
+                        .unwrap_or_else(|| U256::from_u64(0))
+                } else {
+                    U256::from_u64(0)
+                };
+                stack.push(hash);
+                //
+                pc += 1;
+            }
+            Opcode::BLOBBASEFEE => {
+                comment!("opBLOBBASEFEE");
+                stack.push(block.blob_gasprice);
+                //
+                pc += 1;
+            }
             Opcode::CODECOPY
-            | Opcode::GASPRICE
             | Opcode::EXTCODESIZE
             | Opcode::EXTCODECOPY
             | Opcode::RETURNDATASIZE
             | Opcode::RETURNDATACOPY
             | Opcode::EXTCODEHASH
-            | Opcode::BLOCKHASH
             | Opcode::COINBASE
             | Opcode::TIMESTAMP
             | Opcode::NUMBER
-            | Opcode::DIFFICULTY
             | Opcode::GASLIMIT
             | Opcode::CHAINID
-            | Opcode::SELFBALANCE => unimplemented!(),
+            | Opcode::SELFBALANCE => {
+                error = VmError::InvalidInstruction;
+                break;
+            }
             Opcode::POP => {
                 comment!("opPOP");
                 stack.pop();
@@ -983,7 +1671,16 @@ pub unsafe fn run_evm(
                 let value = stack.pop();
                 extend_memory!(offset, 32, schedule, memory, gas, error);
                 memory.write(offset.low_u64() as usize, value);
-                //
+                check_memory_watchpoint!(
+                    breakpoint,
+                    offset.low_u64() as usize,
+                    32,
+                    pc,
+                    gas,
+                    stack,
+                    rstack,
+                    memory
+                );
                 pc += 1;
             }
             Opcode::MSTORE8 => {
@@ -992,10 +1689,22 @@ pub unsafe fn run_evm(
                 let value = stack.pop().low_u64();
                 extend_memory!(offset, 1, schedule, memory, gas, error);
                 memory.write_byte(offset.low_u64() as usize, value as u8);
-                //
+                check_memory_watchpoint!(
+                    breakpoint,
+                    offset.low_u64() as usize,
+                    1,
+                    pc,
+                    gas,
+                    stack,
+                    rstack,
+                    memory
+                );
                 pc += 1;
             }
-            Opcode::SLOAD | Opcode::SSTORE => unimplemented!(),
+            Opcode::SLOAD | Opcode::SSTORE => {
+                error = VmError::InvalidInstruction;
+                break;
+            }
             Opcode::JUMP => {
                 comment!("opJUMP");
                 let addr = stack.pop();
@@ -1003,7 +1712,7 @@ pub unsafe fn run_evm(
                 let low = addr.low_u64();
                 if in_bounds & rom.is_jumpdest(low) {
                     pc = low as usize + 1;
-                    check_exception_at!(low, gas, rom, stack, error);
+                    check_exception_at!(low, gas, rom, stack, schedule, error);
                     break;
                 } else {
                     error = VmError::InvalidJumpDest;
@@ -1016,14 +1725,14 @@ pub unsafe fn run_evm(
                 let cond = stack.pop();
                 if is_zero_u256(cond) {
                     pc += 1;
-                    check_exception_at!(pc as u64, gas, rom, stack, error);
+                    check_exception_at!(pc as u64, gas, rom, stack, schedule, error);
                     break;
                 } else {
                     let in_bounds = is_ltpow2_u256(addr, VmRom::MAX_CODESIZE);
                     let low = addr.low_u64();
                     if in_bounds & rom.is_jumpdest(low) {
                         pc = low as usize + 1;
-                        check_exception_at!(low, gas, rom, stack, error);
+                        check_exception_at!(low, gas, rom, stack, schedule, error);
                         break;
                     } else {
                         error = VmError::InvalidJumpDest;
@@ -1051,7 +1760,7 @@ pub unsafe fn run_evm(
                 stack.push(result);
                 //
                 pc += 1;
-                check_exception_at!(pc as u64, gas, rom, stack, error);
+                check_exception_at!(pc as u64, gas, rom, stack, schedule, error);
                 break;
             }
             Opcode::JUMPDEST => {
@@ -1069,7 +1778,7 @@ pub unsafe fn run_evm(
                 if rstack.size() > 0 {
                     let addr = rstack.pop() as usize;
                     pc = addr as usize;
-                    check_exception_at!(addr as u64, gas, rom, stack, error);
+                    check_exception_at!(addr as u64, gas, rom, stack, schedule, error);
                     break;
                 }
                 error = VmError::ReturnStackUnderflow;
@@ -1080,10 +1789,10 @@ pub unsafe fn run_evm(
                 let addr = stack.pop();
                 let in_bounds = is_ltpow2_u256(addr, VmRom::MAX_CODESIZE);
                 let low = addr.low_u64();
-                if rstack.push(pc as u32 + 1) {
+                if rstack.push(pc as u32 + 1, schedule.return_stack_limit.min(VmReturnStack::LEN)) {
                     if in_bounds & rom.is_beginsub(low) {
                         pc = low as usize + 1;
-                        check_exception_at!(low, gas, rom, stack, error);
+                        check_exception_at!(low, gas, rom, stack, schedule, error);
                         break;
                     } else {
                         error = VmError::InvalidBeginSub;
@@ -1104,16 +1813,21 @@ pub unsafe fn run_evm(
             }
             Opcode::PUSH2 => {
                 comment!("opPUSH2");
-                let result = *(code.offset(pc as isize + 1) as *const u16);
-                let result = U256::from_u64(result as u64);
+                // `VmRom::init` already reverses each PUSHN immediate's
+                // bytes into native (little-endian) order, and `code`'s
+                // offset here isn't guaranteed to be 2-byte aligned, so
+                // this can't be a plain `*const u16` dereference.
+                let bytes = (code.offset(pc as isize + 1) as *const [u8; 2]).read_unaligned();
+                let result = U256::from_u64(u16::from_le_bytes(bytes) as u64);
                 stack.push(result);
                 //
                 pc += 3;
             }
             Opcode::PUSH4 => {
                 comment!("opPUSH4");
-                let result = *(code.offset(pc as isize + 1) as *const u32);
-                let result = U256::from_u64(result as u64);
+                // See `PUSH2`'s comment on the unaligned, native-endian read.
+                let bytes = (code.offset(pc as isize + 1) as *const [u8; 4]).read_unaligned();
+                let result = U256::from_u64(u32::from_le_bytes(bytes) as u64);
                 stack.push(result);
                 //
                 pc += 5;
@@ -1230,44 +1944,192 @@ pub unsafe fn run_evm(
             | Opcode::SWAP16 => {
                 comment!("opSWAPn");
                 let value = stack.peek();
-                let index = opcode.swap_index();
+                // `swap_index()` is SWAPn's 0-based opcode ordinal (SWAP3 ->
+                // 2), one short of the stack depth SWAPn actually swaps with
+                // (SWAP3 swaps the top with the 3rd item below it); see the
+                // same `+ 1` in `opt::optimize`'s abstract interpretation of
+                // this opcode.
+                let index = opcode.swap_index() + 1;
                 let prev = stack.set(index, value);
                 stack.pop();
                 stack.push(prev);
                 //
                 pc += 1;
             }
+            Opcode::DUPN => {
+                comment!("opDUPN");
+                let immediate = *(code as *const u8).offset(pc as isize + 1);
+                let result = stack.peekn(immediate as usize);
+                stack.push(result);
+                //
+                pc += 2;
+            }
+            Opcode::SWAPN => {
+                comment!("opSWAPN");
+                let immediate = *(code as *const u8).offset(pc as isize + 1);
+                let value = stack.peek();
+                let index = immediate as usize + 1;
+                let prev = stack.set(index, value);
+                stack.pop();
+                stack.push(prev);
+                //
+                pc += 2;
+            }
+            Opcode::EXCHANGE => {
+                comment!("opEXCHANGE");
+                let immediate = *(code as *const u8).offset(pc as isize + 1);
+                // high nibble selects the depth of the shallower operand,
+                // low nibble the extra depth of the deeper one; both are
+                // 1-indexed below the top item, which EXCHANGE never
+                // touches (unlike DUPN/SWAPN).
+                let n = (immediate >> 4) as usize + 1;
+                let m = (immediate & 0x0f) as usize + 1;
+                let a = stack.peekn(n);
+                let b = stack.peekn(n + m);
+                stack.set(n, b);
+                stack.set(n + m, a);
+                //
+                pc += 2;
+            }
             Opcode::LOG0
             | Opcode::LOG1
             | Opcode::LOG2
             | Opcode::LOG3
-            | Opcode::LOG4
-            | Opcode::CREATE
-            | Opcode::CALL
-            | Opcode::CALLCODE => unimplemented!(),
+            | Opcode::LOG4 => {
+                error = VmError::InvalidInstruction;
+                break;
+            }
+            // CREATE/CALL/CALLCODE (and DELEGATECALL/CREATE2/STATICCALL
+            // below) have no account or storage model to call into yet
+            // (SLOAD/SSTORE are equally unimplemented), so there's nothing
+            // for them to dispatch to today. Whichever of these lands
+            // first should grow this loop into an explicit `Vec<Frame>`
+            // of { pc, gas, stack, memory } rather than a recursive call
+            // into `run_evm_impl`: `rstack`'s fixed 1023-entry bound
+            // already exists because this interpreter avoids unbounded
+            // host recursion for subroutines, and a depth-1024 CALL chain
+            // deserves the same treatment, not least because it keeps a
+            // suspended frame resumable across an EVMC-style host
+            // boundary the way `run_evm_resume` resumes a single frame.
+            // Once the account model above lands, note that the three
+            // CALL-family variants disagree on `msg.value`: `CALL`
+            // actually transfers `value` from the caller's balance to the
+            // callee's and the callee's own `CALLVALUE` reports it;
+            // `CALLCODE` transfers to *itself* (its own balance debited
+            // and credited in the same transaction) while still running
+            // the callee's code against the caller's storage, so it also
+            // updates `CALLVALUE`; `DELEGATECALL` moves no balance at all
+            // and the callee's `CALLVALUE` reads the original caller's
+            // value straight through. Getting this right needs the
+            // differential CALL/CALLCODE/DELEGATECALL tests this request
+            // asks for, not just one happy-path vector per opcode.
+            //
+            // `tests/session.rs`, `tests/static_call.rs` and
+            // `tests/returndata.rs` hold `#[ignore]`d placeholder tests for
+            // reentrancy, STATICCALL write-protection and the EIP-211
+            // returndata buffer respectively -- each genuinely blocked on
+            // the frame stack this note describes, not yet implemented.
+            Opcode::CREATE | Opcode::CALL | Opcode::CALLCODE => {
+                error = VmError::InvalidInstruction;
+                break;
+            }
             Opcode::RETURN => {
                 lldb_hook!(pc, gas, stack, rstack, memory, lldb_hook_stop);
                 comment!("opRETURN");
                 let offset = stack.pop_u256();
                 let size = stack.pop_u256();
                 extend_memory!(offset, size, schedule, memory, gas, error);
-                return ReturnData::ok(offset.low_u64() as usize, size.low_u64() as usize, gas);
+                // A zero-size return never reads memory, so `offset` is
+                // reported as 0 rather than whatever (possibly huge or
+                // truncated) value was on the stack: callers can then slice
+                // `[0, 0)` unconditionally instead of having to special-case
+                // an offset that was never actually charged or validated.
+                let reported_offset = if is_zero_u256(size) { 0 } else { offset.low_u64() as usize };
+                return (
+                    ReturnData::ok(reported_offset, size.low_u64() as usize, gas),
+                    None,
+                );
             }
+            // `CREATE`'s address derivation (`keccak256(rlp([sender,
+            // nonce]))`) and `CREATE2`'s (`keccak256(0xff ++ sender ++
+            // salt ++ keccak256(init_code))`) both need a per-account
+            // nonce the account model above doesn't have a field for yet.
+            // Whichever lands first should increment it on every
+            // transaction and on `CREATE`/`CREATE2` themselves (a
+            // create-in-create nonce bump is visible to the *creator's*
+            // own subsequent `CREATE`, not just the one that just ran),
+            // and enforce the EIP-2681 cap (reject with `VmError::OutOfGas`
+            // rather than wrap `2^64 - 1` back to `0`) before ever
+            // deriving an address from it.
             Opcode::DELEGATECALL | Opcode::CREATE2 | Opcode::STATICCALL | Opcode::REVERT => {
-                unimplemented!()
-            }
-            Opcode::INVALID => {
                 error = VmError::InvalidInstruction;
                 break;
             }
-            Opcode::SELFDESTRUCT => unimplemented!(),
-        }
+            Opcode::INVALID => {
+                if let Some(handler) = extension.filter(|_| crate::extension::EXTENSION_OPCODE_RANGE.contains(&raw_opcode)) {
+                    match handler.handle(raw_opcode, &mut stack, memory, &mut gas) {
+                        Ok(()) => {
+                            pc += 1;
+                        }
+                        Err(e) => {
+                            error = e;
+                            break;
+                        }
+                    }
+                } else {
+                    // Per spec, an invalid instruction is an exceptional
+                    // halt that consumes all remaining gas -- unlike the
+                    // REVERT/CALL/CREATE-family arm above, which this
+                    // interpreter can't execute yet for lack of an account
+                    // model and reports the same `InvalidInstruction`
+                    // without claiming to charge for work it never did.
+                    // Note this single `Opcode::INVALID` value is also
+                    // where `VmRom::init` rewrites both a genuine 0xfe byte
+                    // and any opcode not yet gated in by the current fork
+                    // (see `is_gated_in`'s doc comment) -- by the time
+                    // dispatch sees it, those two cases are no longer
+                    // distinguishable, so both are charged the same way.
+                    gas = 0;
+                    error = VmError::InvalidInstruction;
+                    break;
+                }
+            }
+            // EIP-161 (Spurious Dragon on) hinges on two things neither
+            // exists yet: a notion of an account being "touched" this
+            // transaction (any CALL/CALLCODE/SELFDESTRUCT/value-transfer
+            // that reaches it, even a value-zero CALL) and "empty" (zero
+            // balance, zero nonce, no code), and a journal that can roll
+            // both back on a reverted sub-call. `SELFDESTRUCT`'s own
+            // target is the common case that touches an account without
+            // otherwise creating one, so an empty-but-touched account
+            // removed only once the whole transaction finishes (never
+            // mid-call, since an outer frame can still revert the touch)
+            // is the natural place to exercise this once the journaled
+            // state subsystem lands; it's fork-gated off entirely before
+            // Spurious Dragon, same as the rest of this opcode's gas
+            // schedule already is.
+            Opcode::SELFDESTRUCT => {
+                error = VmError::InvalidInstruction;
+                break;
+            }
+        }
     }
-    return ReturnData::new(0, 0, gas, error);
+    let fault = stack_fault(&error, bytecode, pc, stack.size(), schedule.stack_limit.min(VmStack::MAX_LEN));
+    return (
+        ReturnData {
+            fault,
+            ..ReturnData::new(0, 0, gas, error)
+        },
+        None,
+    );
 }
 
-#[derive(Debug)]
-struct BbInfo {
+/// `gas` is the block's precomputed *static* cost: opcodes with a fork-
+/// dependent but per-instance-dynamic price (see
+/// `VmRom::has_dynamic_access_cost`) are excluded and must be charged at
+/// the instruction site instead, so this is always a safe lower bound.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct BbInfo {
     stack_min_size: u16,
     stack_rel_max_size: u16,
     gas: u64,
@@ -1287,8 +2149,39 @@ impl BbInfo {
     }
 }
 
+/// A `VmRom`'s computed set of legal `JUMP`/`JUMPI` destinations
+/// (`VmRom::jumpdests`), exposed as an immutable bitset rather than
+/// `VmRom`'s internal invalid-dests bytes so a caller outside this module
+/// -- an external static analyzer, for instance -- can enumerate valid
+/// jump targets without reaching into `VmRom`'s private layout the way
+/// `is_jumpdest` itself does.
+pub struct JumpdestBitmap {
+    bits: Vec<u8>,
+    len: usize,
+}
+
+impl JumpdestBitmap {
+    /// Returns true if `addr` is a legal `JUMP`/`JUMPI` destination.
+    /// Out-of-range addresses (`addr >= ` the bytecode's length) are
+    /// never valid destinations, same as `VmRom::is_jumpdest`.
+    pub fn contains(&self, addr: usize) -> bool {
+        addr < self.len && (self.bits[addr / 8] & (1 << (addr % 8))) != 0
+    }
+
+    /// Iterates every legal destination's address, in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.len).filter(move |&addr| self.contains(addr))
+    }
+}
+
 pub struct VmRom {
     data: [u8; Self::SIZE],
+    /// The actual bytecode length passed to `init`, distinct from
+    /// `MAX_CODESIZE`: `data` is always a fixed `MAX_CODESIZE`-sized buffer,
+    /// zero-padded past this point. `is_jumpdest`/`is_beginsub` check
+    /// against this rather than `MAX_CODESIZE` so an out-of-range address
+    /// can never alias into the padding, regardless of how it got there.
+    code_len: usize,
 }
 
 impl VmRom {
@@ -1304,6 +2197,7 @@ impl VmRom {
     pub fn new() -> VmRom {
         VmRom {
             data: [0; Self::SIZE],
+            code_len: 0,
         }
     }
 
@@ -1312,6 +2206,7 @@ impl VmRom {
     }
 
     fn is_valid_dest(&self, addr: isize) -> bool {
+        debug_assert!((0..Self::MAX_CODESIZE as isize).contains(&addr));
         let ptr = unsafe {
             self.data
                 .as_ptr()
@@ -1322,21 +2217,58 @@ impl VmRom {
         (mask & bit) == 0
     }
 
-    fn is_jumpdest(&self, addr: u64) -> bool {
-        let addr = (addr as isize) % (Self::MAX_CODESIZE as isize);
+    /// Returns true if `addr` holds a `JUMPDEST` that a `JUMP`/`JUMPI` may
+    /// legally target, i.e. it isn't sitting inside another opcode's push
+    /// data.
+    ///
+    /// `addr` is checked against `code_len`, the actual bytecode length,
+    /// rather than wrapped modulo `MAX_CODESIZE`: a wrapping check would
+    /// accept an out-of-range `addr` that happens to alias a real
+    /// destination elsewhere in the buffer, and callers are expected (but,
+    /// for a value this security-sensitive, not solely trusted) to have
+    /// already range-checked `addr` themselves (e.g. via `is_ltpow2_u256`).
+    pub fn is_jumpdest(&self, addr: u64) -> bool {
+        if addr >= self.code_len as u64 {
+            return false;
+        }
+        let addr = addr as isize;
         let code = unsafe { *self.code().offset(addr) };
         let opcode = unsafe { std::mem::transmute::<u8, Opcode>(code) };
         (opcode == Opcode::JUMPDEST) & self.is_valid_dest(addr)
     }
 
-    fn is_beginsub(&self, addr: u64) -> bool {
-        let addr = (addr as isize) % (Self::MAX_CODESIZE as isize);
+    /// Returns true if `addr` holds a `BEGINSUB` that a `JUMPSUB` may
+    /// legally target, i.e. it isn't sitting inside another opcode's push
+    /// data. See `is_jumpdest` for why `addr` is checked against `code_len`
+    /// rather than wrapped modulo `MAX_CODESIZE`.
+    pub fn is_beginsub(&self, addr: u64) -> bool {
+        if addr >= self.code_len as u64 {
+            return false;
+        }
+        let addr = addr as isize;
         let code = unsafe { *self.code().offset(addr) };
         let opcode = unsafe { std::mem::transmute::<u8, Opcode>(code) };
         (opcode == Opcode::BEGINSUB) & self.is_valid_dest(addr)
     }
 
+    /// Materializes every legal `JUMP`/`JUMPI` destination in this ROM's
+    /// bytecode (the same check `is_jumpdest` makes one address at a
+    /// time, including this `VmRom`'s own fork gating) into a
+    /// `JumpdestBitmap`, for a caller -- an external static analyzer, say
+    /// -- that wants to enumerate them rather than probe addresses one by
+    /// one.
+    pub fn jumpdests(&self) -> JumpdestBitmap {
+        let mut bits = vec![0u8; self.code_len / 8 + 1];
+        for addr in 0..self.code_len {
+            if self.is_jumpdest(addr as u64) {
+                bits[addr / 8] |= 1 << (addr % 8);
+            }
+        }
+        JumpdestBitmap { bits, len: self.code_len }
+    }
+
     fn get_bb_info(&self, addr: u64) -> &BbInfo {
+        debug_assert!(addr < Self::MAX_CODESIZE as u64);
         unsafe {
             let offset = VmRom::BB_INFOS_OFFSET as isize;
             let bb_infos = self.data.as_ptr().offset(offset) as *mut BbInfo;
@@ -1350,85 +2282,207 @@ impl VmRom {
         }
     }
 
+    /// Opcodes EIP-2929 (Berlin) turns access-list dependent: their
+    /// pre-Berlin cost is a fixed per-fork fee, safe to fold into a
+    /// block's static gas like any other opcode, but from Berlin on the
+    /// actual cost (warm vs. cold) can only be known at the instruction
+    /// site. `write_bb_infos` excludes them from the static sum for
+    /// those forks so the interpreter can charge the dynamic component
+    /// itself, the same way it already does for SHA3/EXP/memory
+    /// expansion, without the block-level check over- or under-charging.
+    fn has_dynamic_access_cost(opcode: EvmOpcode, fork: Fork) -> bool {
+        if fork < Fork::Berlin {
+            return false;
+        }
+        matches!(
+            opcode,
+            EvmOpcode::BALANCE
+                | EvmOpcode::EXTCODESIZE
+                | EvmOpcode::EXTCODECOPY
+                | EvmOpcode::EXTCODEHASH
+                | EvmOpcode::SLOAD
+                | EvmOpcode::CALL
+                | EvmOpcode::CALLCODE
+                | EvmOpcode::DELEGATECALL
+                | EvmOpcode::STATICCALL
+        )
+    }
+
     fn write_bb_infos(&mut self, bytecode: &[u8], schedule: &Schedule) {
-        use std::cmp::max;
-        #[derive(Debug)]
-        struct BlockInfo {
+        let bb_infos = analyze_basic_blocks(bytecode, schedule);
+        unsafe {
+            let offset = VmRom::BB_INFOS_OFFSET as isize;
+            let dest = self.data.as_mut_ptr().offset(offset) as *mut BbInfo;
+            std::ptr::copy_nonoverlapping(bb_infos.as_ptr(), dest, bb_infos.len());
+        }
+    }
+}
+
+/// Computes each basic block's post-entry stack-size bounds and static gas
+/// cost across `bytecode`, indexed by block-start address (only the entries
+/// at a block's first byte are meaningful; the rest are left at their
+/// `Default`). This is the same analysis `VmRom::write_bb_infos` bakes into
+/// its execution buffer, factored out to run on a bare `&[u8]` without
+/// paying for a full `VmRom`'s fixed `MAX_CODESIZE`-sized layout, so
+/// analysis-only tooling (the `corpus` subcommand, `stats.rs`) can call it
+/// directly and parallelize it across a whole corpus.
+///
+/// A `JUMPI`'s not-taken edge falls through to the next address exactly
+/// like a non-terminator would, so when that address isn't itself a valid
+/// jump target, its stack requirements are folded backward into the
+/// `JUMPI` block's own entry (see `BlockInfo::jumpi_fallthrough`) and the
+/// interpreter's fallthrough check becomes statically guaranteed to pass.
+/// Gas is never folded this way, since it's only spent on the not-taken
+/// path.
+pub(crate) fn analyze_basic_blocks(bytecode: &[u8], schedule: &Schedule) -> Vec<BbInfo> {
+    use std::cmp::max;
+    #[derive(Debug)]
+    struct BlockInfo {
+        addr: u32,
+        stack_min_size: u16,
+        stack_max_size: u16,
+        stack_end_size: u16,
+        gas: u64,
+        // Whether the backward pass folds this block's stats into the
+        // still-accumulating totals from whatever follows it (`false`
+        // resets them to this block's own, `true` adds to them).
+        fold_stack: bool,
+        fold_gas: bool,
+    }
+    impl BlockInfo {
+        fn basic(
             addr: u32,
             stack_min_size: u16,
             stack_max_size: u16,
             stack_end_size: u16,
             gas: u64,
-            is_basic_block: bool,
-        }
-        impl BlockInfo {
-            fn basic(
-                addr: u32,
-                stack_min_size: u16,
-                stack_max_size: u16,
-                stack_end_size: u16,
-                gas: u64,
-            ) -> BlockInfo {
-                BlockInfo {
-                    addr,
-                    stack_min_size,
-                    stack_max_size,
-                    stack_end_size,
-                    gas,
-                    is_basic_block: true,
-                }
+        ) -> BlockInfo {
+            BlockInfo {
+                addr,
+                stack_min_size,
+                stack_max_size,
+                stack_end_size,
+                gas,
+                fold_stack: false,
+                fold_gas: false,
             }
-            fn partial(
-                addr: u32,
-                stack_min_size: u16,
-                stack_max_size: u16,
-                stack_end_size: u16,
-                gas: u64,
-            ) -> BlockInfo {
-                BlockInfo {
-                    addr,
-                    stack_min_size,
-                    stack_max_size,
-                    stack_end_size,
-                    gas,
-                    is_basic_block: false,
-                }
+        }
+        fn partial(
+            addr: u32,
+            stack_min_size: u16,
+            stack_max_size: u16,
+            stack_end_size: u16,
+            gas: u64,
+        ) -> BlockInfo {
+            BlockInfo {
+                addr,
+                stack_min_size,
+                stack_max_size,
+                stack_end_size,
+                gas,
+                fold_stack: true,
+                fold_gas: true,
             }
         }
-        let mut addr: u32 = 0;
-        let mut stack_size: u16 = 0;
-        let mut stack_min_size: u16 = 0;
-        let mut stack_max_size: u16 = 0;
-        let mut gas: u64 = 0;
-        let mut block_infos: Vec<BlockInfo> = Vec::with_capacity(1024);
-        // forward pass over the bytecode
-        let mut i: usize = 0;
-        while i < bytecode.len() {
-            let code = bytecode[i];
-            let opcode = unsafe { std::mem::transmute::<u8, EvmOpcode>(code) };
-            let (_, fee, delta, alpha) = OPCODE_INFOS[code as usize];
-            // new_stack_size is (stack_size + needed + alpha) - delta
-            // and represents the new stack size after the opcode has been
-            // dispatched
-            let (new_stack_size, needed) = if delta > stack_size {
-                (alpha, (delta - stack_size))
+        // A block ending in JUMPI whose fallthrough address isn't a valid
+        // jump target, i.e. only reachable by not taking the jump. The
+        // fallthrough's stack requirements can be folded backward into
+        // this block's entry exactly like `partial` does, since falling
+        // through is the one deterministic continuation from here. Gas
+        // can't be folded the same way: it's only spent when the jump
+        // *isn't* taken, so charging it upfront would overcharge the
+        // taken path.
+        fn jumpi_fallthrough(
+            addr: u32,
+            stack_min_size: u16,
+            stack_max_size: u16,
+            stack_end_size: u16,
+            gas: u64,
+        ) -> BlockInfo {
+            BlockInfo {
+                addr,
+                stack_min_size,
+                stack_max_size,
+                stack_end_size,
+                gas,
+                fold_stack: true,
+                fold_gas: false,
+            }
+        }
+    }
+    let mut addr: u32 = 0;
+    let mut stack_size: u16 = 0;
+    let mut stack_min_size: u16 = 0;
+    let mut stack_max_size: u16 = 0;
+    let mut gas: u64 = 0;
+    let mut block_infos: Vec<BlockInfo> = Vec::with_capacity(1024);
+    // forward pass over the bytecode
+    let mut i: usize = 0;
+    while i < bytecode.len() {
+        let code = bytecode[i];
+        // `code` may land in a gap in the byte space (e.g. 0x0c), which
+        // isn't a defined `EvmOpcode` discriminant at all: fall back to
+        // `INVALID` rather than transmuting a value the enum can't hold.
+        let opcode = EvmOpcode::try_from(code).unwrap_or(EvmOpcode::INVALID);
+        let (_, fee, opcode_delta, opcode_alpha) = OPCODE_INFOS[code as usize];
+        let (delta, alpha) = if opcode.is_deep_stack() {
+            let immediate = bytecode.get(i + 1).copied().unwrap_or(0);
+            deep_stack_effect(opcode, immediate)
+        } else {
+            (opcode_delta, opcode_alpha)
+        };
+        // new_stack_size is (stack_size + needed + alpha) - delta
+        // and represents the new stack size after the opcode has been
+        // dispatched
+        let (new_stack_size, needed) = if delta > stack_size {
+            (alpha, (delta - stack_size))
+        } else {
+            // case stack_size >= delta
+            ((stack_size - delta).saturating_add(alpha), 0)
+        };
+        stack_size = new_stack_size;
+        stack_min_size = stack_min_size.saturating_add(needed);
+        // `needed` raises the stack size the block requires on entry,
+        // which retroactively raises every peak recorded so far too
+        // (they were relative to a now-too-low assumed entry size).
+        stack_max_size = max(stack_max_size.saturating_add(needed), new_stack_size);
+        // TODO: overflow possible?
+        if !VmRom::has_dynamic_access_cost(opcode, schedule.fork) {
+            gas += schedule.opcode_gas(opcode, fee);
+        }
+        if opcode.is_push() {
+            let num_bytes = opcode.push_index() + 1;
+            i += 1 + num_bytes;
+        } else if opcode.is_deep_stack() {
+            i += 2;
+        } else {
+            i += 1;
+        }
+        if opcode.is_terminator() || i >= bytecode.len() {
+            // JUMPI's not-taken path falls through to `i` just like a
+            // plain non-terminator instruction would, so if nothing else
+            // can jump in there (it isn't a JUMPDEST), that address's
+            // stack requirements are already implied by this block's own
+            // entry check and don't need a second, independent one.
+            let is_mergeable_jumpi = opcode == EvmOpcode::JUMPI
+                && i < bytecode.len()
+                && bytecode[i] != EvmOpcode::JUMPDEST as u8;
+            let info = if is_mergeable_jumpi {
+                BlockInfo::jumpi_fallthrough(addr, stack_min_size, stack_max_size, stack_size, gas)
             } else {
-                // case stack_size >= delta
-                ((stack_size - delta).saturating_add(alpha), 0)
+                BlockInfo::basic(addr, stack_min_size, stack_max_size, stack_size, gas)
             };
-            stack_size = new_stack_size;
-            stack_min_size = stack_min_size.saturating_add(needed);
-            stack_max_size = max(stack_max_size, new_stack_size);
-            // TODO: overflow possible?
-            gas += fee.gas(schedule) as u64;
-            if opcode.is_push() {
-                let num_bytes = opcode.push_index() + 1;
-                i += 1 + num_bytes;
-            } else {
-                i += 1;
-            }
-            if opcode.is_terminator() || i >= bytecode.len() {
-                block_infos.push(BlockInfo::basic(
+            block_infos.push(info);
+            addr = i as u32;
+            stack_size = 0;
+            stack_min_size = 0;
+            stack_max_size = 0;
+            gas = 0;
+        } else {
+            let code = bytecode[i];
+            let opcode = EvmOpcode::try_from(code).unwrap_or(EvmOpcode::INVALID);
+            if opcode == EvmOpcode::JUMPDEST {
+                block_infos.push(BlockInfo::partial(
                     addr,
                     stack_min_size,
                     stack_max_size,
@@ -1440,56 +2494,35 @@ impl VmRom {
                 stack_min_size = 0;
                 stack_max_size = 0;
                 gas = 0;
-            } else {
-                let code = bytecode[i];
-                let opcode = unsafe { std::mem::transmute::<u8, EvmOpcode>(code) };
-                if opcode == EvmOpcode::JUMPDEST {
-                    block_infos.push(BlockInfo::partial(
-                        addr,
-                        stack_min_size,
-                        stack_max_size,
-                        stack_size,
-                        gas,
-                    ));
-                    addr = i as u32;
-                    stack_size = 0;
-                    stack_min_size = 0;
-                    stack_max_size = 0;
-                    gas = 0;
-                }
             }
         }
-        // backward pass, write BB infos to rom
-        let bb_infos = unsafe {
-            let offset = VmRom::BB_INFOS_OFFSET as isize;
-            self.data.as_ptr().offset(offset) as *mut BbInfo
-        };
-        for info in block_infos.iter().rev() {
-            if info.is_basic_block {
-                stack_min_size = info.stack_min_size;
-                stack_max_size = info.stack_max_size;
-                gas = info.gas;
+    }
+    // backward pass, filling in each block's entry into `bb_infos`
+    let mut bb_infos = vec![BbInfo::default(); bytecode.len()];
+    for info in block_infos.iter().rev() {
+        if info.fold_stack {
+            let (more, needed) = if stack_min_size > info.stack_end_size {
+                (0, (stack_min_size - info.stack_end_size))
             } else {
-                let (more, needed) = if stack_min_size > info.stack_end_size {
-                    (0, (stack_min_size - info.stack_end_size))
-                } else {
-                    // case info.stack_end_size >= stack_min_size
-                    (info.stack_end_size - stack_min_size, 0)
-                };
-                stack_min_size = info.stack_min_size.saturating_add(needed);
-                stack_max_size = max(
-                    info.stack_max_size.saturating_add(needed),
-                    stack_max_size.saturating_add(more),
-                );
-                gas += info.gas;
-            }
-            unsafe {
-                let bb_info = BbInfo::new(stack_min_size, stack_max_size, gas);
-                *bb_infos.offset(info.addr as isize) = bb_info;
-            }
+                // case info.stack_end_size >= stack_min_size
+                (info.stack_end_size - stack_min_size, 0)
+            };
+            stack_min_size = info.stack_min_size.saturating_add(needed);
+            stack_max_size = max(
+                info.stack_max_size.saturating_add(needed),
+                stack_max_size.saturating_add(more),
+            );
+        } else {
+            stack_min_size = info.stack_min_size;
+            stack_max_size = info.stack_max_size;
         }
+        gas = if info.fold_gas { gas + info.gas } else { info.gas };
+        bb_infos[info.addr as usize] = BbInfo::new(stack_min_size, stack_max_size, gas);
     }
+    bb_infos
+}
 
+impl VmRom {
     pub fn init(&mut self, bytecode: &[u8], schedule: &Schedule) {
         // erase rom
         for b in &mut self.data[..] {
@@ -1498,6 +2531,7 @@ impl VmRom {
         if bytecode.len() > VmRom::MAX_CODESIZE {
             panic!("bytecode is too big ({:?} bytes)", bytecode.len());
         }
+        self.code_len = bytecode.len();
         // copy bytecode
         #[cfg(target_endian = "little")]
         {
@@ -1506,8 +2540,11 @@ impl VmRom {
             while i < bytecode.len() {
                 let code = bytecode[i];
                 let (introduced_fork, _, _, _) = OPCODE_INFOS[code as usize];
+                // `code` may not be a defined opcode at all (there are gaps
+                // in the byte space, e.g. 0x0c): `try_from` catches those,
+                // where a blind transmute would build an invalid `EvmOpcode`.
                 let opcode = if schedule.fork >= introduced_fork {
-                    unsafe { std::mem::transmute::<u8, EvmOpcode>(code) }
+                    EvmOpcode::try_from(code).unwrap_or(EvmOpcode::INVALID)
                 } else {
                     EvmOpcode::INVALID
                 };
@@ -1515,10 +2552,27 @@ impl VmRom {
                 if opcode.is_push() {
                     let num_bytes = opcode.push_index() + 1;
                     let start = i + 1;
-                    let end = start + num_bytes;
-                    let dest = &mut self.data[start..end];
-                    VmRom::swap_bytes(&bytecode[start..end], dest);
+                    let dest_end = start + num_bytes;
+                    // A PUSH's immediate can run past the end of code (e.g. a
+                    // PUSH32 as the final byte of a maximal 32768-byte
+                    // contract): the missing trailing bytes are implicit
+                    // zero padding per spec, not adjacent ROM data. `data`
+                    // is already zeroed above, so only swap the bytes that
+                    // actually exist, landing them at the high (most
+                    // significant) end of the little-endian immediate.
+                    let available = num_bytes.min(bytecode.len() - start);
+                    let start_avail = dest_end - available;
+                    let dest = &mut self.data[start_avail..dest_end];
+                    VmRom::swap_bytes(&bytecode[start..start + available], dest);
                     i += 1 + num_bytes;
+                } else if opcode.is_deep_stack() {
+                    // a single immediate byte needs no endian swap; a
+                    // deep-stack opcode as the very last byte of code
+                    // implicitly zero-pads the same way a truncated PUSH does.
+                    if i + 1 < bytecode.len() {
+                        self.data[i + 1] = bytecode[i + 1];
+                    }
+                    i += 2;
                 } else {
                     i += 1;
                 }
@@ -1534,16 +2588,36 @@ impl VmRom {
         let mut i: usize = 0;
         while i < bytecode.len() {
             let code = bytecode[i];
-            let opcode = unsafe { std::mem::transmute::<u8, EvmOpcode>(code) };
-            if opcode.is_push() {
-                let num_bytes = opcode.push_index() + 1;
+            // See the byte-copy loop above: `code` may not be a defined
+            // `EvmOpcode` discriminant at all.
+            let opcode = EvmOpcode::try_from(code).unwrap_or(EvmOpcode::INVALID);
+            let num_bytes = if opcode.is_push() {
+                opcode.push_index() + 1
+            } else if opcode.is_deep_stack() {
+                1
+            } else {
+                0
+            };
+            // Bytes past the end of code don't exist, so don't mark
+            // positions there as invalid jump destinations either: nothing
+            // can ever land a JUMP there, and doing so would walk the
+            // read-modify-write below straight into the INVALID_DESTS
+            // bitmap's tail, spilling into the BB_INFOS region right after
+            // it for a PUSH32 immediate that runs off the end of code.
+            let num_bytes = num_bytes.min(bytecode.len() - (i + 1));
+            if num_bytes > 0 {
                 let mask: u64 = (1 << num_bytes) - 1;
                 let j = (i + 1) as isize;
                 let byte_offset = j / 8;
                 let bit_offset = j % 8;
                 unsafe {
-                    let ptr = invalid_dests_ptr.offset(byte_offset) as *mut u32;
-                    *ptr |= (mask as u32) << bit_offset;
+                    // A PUSH32 starting at a non-zero bit_offset needs up to
+                    // 39 bits, which doesn't fit a u32 window; go through a
+                    // wider unaligned u64 read-modify-write instead, since
+                    // byte_offset isn't generally 4- or 8-byte aligned.
+                    let ptr = invalid_dests_ptr.offset(byte_offset) as *mut u64;
+                    let bits = ptr.read_unaligned() | (mask << bit_offset);
+                    ptr.write_unaligned(bits);
                 }
                 i += num_bytes;
             }
@@ -1553,3 +2627,2129 @@ impl VmRom {
         self.write_bb_infos(bytecode, schedule);
     }
 }
+
+/// Struct-of-arrays `VmRom` layout prototype, gated behind the `soa-rom`
+/// feature.
+///
+/// `VmRom`'s default layout packs code, the jumpdest bitmap, and the
+/// (sparse, one-entry-per-address) `BbInfo` table into one
+/// `MAX_CODESIZE`-dominated allocation (over 500KB even for a tiny contract), so
+/// `is_jumpdest` and `get_bb_info` -- routinely called back-to-back on a
+/// `JUMP` -- each walk into a different region of that same oversized
+/// buffer. `SoaRom` instead keeps three separate, code-length-sized
+/// allocations and compacts `BbInfo` to one entry per *block* rather than
+/// one per address byte, looked up by binary search over `block_starts`;
+/// `get_bb_info` issues a software prefetch for the next sequential
+/// block's entry, since falling through (rather than jumping) is the
+/// common case at a block boundary.
+///
+/// This is an analysis-side prototype, not a drop-in replacement: it
+/// reproduces `VmRom`'s jumpdest/`BbInfo` lookups for benchmarking (see
+/// the `bench-rom` CLI subcommand), but `run_evm`'s dispatch loop still
+/// reads from `VmRom`, which additionally stores code pre-translated to
+/// `Opcode::to_internal` form -- swapping that in is future work once a
+/// benchmark run on real hardware (not this sandbox's virtualized timing)
+/// shows `SoaRom` actually wins.
+#[cfg(feature = "soa-rom")]
+pub mod rom_soa {
+    use std::convert::TryFrom;
+
+    use super::{analyze_basic_blocks, BbInfo};
+    use crate::instructions::EvmOpcode;
+    use crate::schedule::Schedule;
+
+    pub struct SoaRom {
+        code: Vec<u8>,
+        fork: crate::schedule::Fork,
+        invalid_dests: Vec<u8>,
+        block_starts: Vec<u32>,
+        bb_infos: Vec<BbInfo>,
+    }
+
+    impl SoaRom {
+        pub fn new(bytecode: &[u8], schedule: &Schedule) -> SoaRom {
+            let mut invalid_dests = vec![0u8; bytecode.len() / 8 + 1];
+            let mut i = 0usize;
+            while i < bytecode.len() {
+                let opcode = EvmOpcode::try_from(bytecode[i]).unwrap_or(EvmOpcode::INVALID);
+                let num_bytes = if opcode.is_push() {
+                    opcode.push_index() + 1
+                } else if opcode.is_deep_stack() {
+                    1
+                } else {
+                    0
+                };
+                let num_bytes = num_bytes.min(bytecode.len().saturating_sub(i + 1));
+                for j in i + 1..i + 1 + num_bytes {
+                    invalid_dests[j / 8] |= 1 << (j % 8);
+                }
+                i += 1 + num_bytes;
+            }
+
+            // Same "address 0, or a `BbInfo` that differs from its default"
+            // rule `vm::tests::render_bb_infos_snapshot` uses to recover a
+            // compact list of block starts from `analyze_basic_blocks`'s
+            // sparse, per-address output.
+            let sparse = analyze_basic_blocks(bytecode, schedule);
+            let mut block_starts = Vec::new();
+            let mut bb_infos = Vec::new();
+            for (addr, info) in sparse.iter().enumerate() {
+                let is_default = info.stack_min_size == 0 && info.stack_rel_max_size == 0 && info.gas == 0;
+                if addr == 0 || !is_default {
+                    block_starts.push(addr as u32);
+                    bb_infos.push(*info);
+                }
+            }
+
+            SoaRom {
+                code: bytecode.to_vec(),
+                fork: schedule.fork,
+                invalid_dests,
+                block_starts,
+                bb_infos,
+            }
+        }
+
+        fn is_valid_dest(&self, addr: usize) -> bool {
+            (self.invalid_dests[addr / 8] & (1 << (addr % 8))) == 0
+        }
+
+        /// True if `opcode` is gated in under `self.fork`, the same rule
+        /// `VmRom::init`'s byte-copy loop applies before translating a
+        /// byte to its internal form (see `OPCODE_INFOS`'s introduced-fork
+        /// column): a `JUMPDEST`/`BEGINSUB` byte that the current fork
+        /// doesn't support yet decodes as `INVALID` instead, same as any
+        /// other not-yet-introduced opcode.
+        fn is_gated_in(&self, opcode: EvmOpcode) -> bool {
+            let (introduced_fork, _, _, _) = super::OPCODE_INFOS[opcode as usize];
+            self.fork >= introduced_fork
+        }
+
+        pub fn is_jumpdest(&self, addr: usize) -> bool {
+            addr < self.code.len()
+                && self.code[addr] == EvmOpcode::JUMPDEST as u8
+                && self.is_gated_in(EvmOpcode::JUMPDEST)
+                && self.is_valid_dest(addr)
+        }
+
+        pub fn is_beginsub(&self, addr: usize) -> bool {
+            addr < self.code.len()
+                && self.code[addr] == EvmOpcode::BEGINSUB as u8
+                && self.is_gated_in(EvmOpcode::BEGINSUB)
+                && self.is_valid_dest(addr)
+        }
+
+        /// Returns `addr`'s block's `BbInfo` (the nearest block start at or
+        /// before `addr`), prefetching the following block's entry on the
+        /// way out.
+        pub(crate) fn get_bb_info(&self, addr: u32) -> &BbInfo {
+            let index = match self.block_starts.binary_search(&addr) {
+                Ok(index) => index,
+                Err(0) => 0,
+                Err(index) => index - 1,
+            };
+            self.prefetch_bb_info(index + 1);
+            &self.bb_infos[index]
+        }
+
+        #[cfg(target_arch = "x86_64")]
+        fn prefetch_bb_info(&self, index: usize) {
+            if index < self.bb_infos.len() {
+                unsafe {
+                    std::arch::x86_64::_mm_prefetch(
+                        self.bb_infos.as_ptr().add(index) as *const i8,
+                        std::arch::x86_64::_MM_HINT_T0,
+                    );
+                }
+            }
+        }
+
+        #[cfg(not(target_arch = "x86_64"))]
+        fn prefetch_bb_info(&self, _index: usize) {}
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::schedule::Fork;
+
+        fn schedule() -> Schedule {
+            Schedule::from_fork(Fork::default())
+        }
+
+        #[test]
+        fn agrees_with_vm_rom_on_jumpdests_and_beginsubs() {
+            let bytecode = vec![
+                EvmOpcode::JUMPDEST as u8,
+                EvmOpcode::PUSH2 as u8,
+                EvmOpcode::JUMPDEST as u8, // inside PUSH2's immediate: not a real dest
+                0x00,
+                EvmOpcode::BEGINSUB as u8,
+                EvmOpcode::STOP as u8,
+            ];
+            let schedule = schedule();
+            let mut rom = super::super::VmRom::new();
+            rom.init(&bytecode, &schedule);
+            let soa = SoaRom::new(&bytecode, &schedule);
+            for addr in 0..bytecode.len() {
+                assert_eq!(soa.is_jumpdest(addr), rom.is_jumpdest(addr as u64), "addr {}", addr);
+                assert_eq!(soa.is_beginsub(addr), rom.is_beginsub(addr as u64), "addr {}", addr);
+            }
+        }
+
+        #[test]
+        fn agrees_with_vm_rom_on_every_blocks_bb_info() {
+            let bytecode = vec![
+                EvmOpcode::PUSH1 as u8,
+                0x05,
+                EvmOpcode::JUMP as u8,
+                EvmOpcode::INVALID as u8,
+                EvmOpcode::JUMPDEST as u8,
+                EvmOpcode::PUSH1 as u8,
+                0x01,
+                EvmOpcode::STOP as u8,
+            ];
+            let schedule = schedule();
+            let mut rom = super::super::VmRom::new();
+            rom.init(&bytecode, &schedule);
+            let soa = SoaRom::new(&bytecode, &schedule);
+            for &addr in &soa.block_starts {
+                let expected = rom.get_bb_info(addr as u64);
+                let actual = soa.get_bb_info(addr);
+                assert_eq!(actual.gas, expected.gas);
+                assert_eq!(actual.stack_min_size, expected.stack_min_size);
+                assert_eq!(actual.stack_rel_max_size, expected.stack_rel_max_size);
+            }
+        }
+
+        #[test]
+        fn looks_up_a_mid_block_address_by_its_blocks_start() {
+            let bytecode = vec![
+                EvmOpcode::JUMPDEST as u8,
+                EvmOpcode::PUSH1 as u8,
+                0x01,
+                EvmOpcode::PUSH1 as u8,
+                0x02,
+                EvmOpcode::ADD as u8,
+                EvmOpcode::STOP as u8,
+            ];
+            let soa = SoaRom::new(&bytecode, &schedule());
+            assert_eq!(soa.get_bb_info(0).gas, soa.get_bb_info(5).gas);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler;
+    use crate::schedule::{Fork, Schedule};
+
+    fn run_blockhash(number: u64, block_number: u64) -> U256 {
+        let input = format!(
+            "
+            PUSH8 {:#018x}
+            BLOCKHASH
+            PUSH1 0x00
+            MSTORE
+            PUSH1 0x20
+            PUSH1 0x00
+            RETURN
+            ",
+            number
+        );
+        let bytecode = assembler::from_string(&input).unwrap();
+        let schedule = Schedule::from_fork(Fork::Frontier);
+        let mut rom = VmRom::new();
+        rom.init(&bytecode, &schedule);
+        let mut memory = VmMemory::new();
+        let gas_limit = U256::from_u64(20_000_000_000_000);
+        memory.init(gas_limit);
+        let hashes = TestBlockHashProvider;
+        let block = BlockContext::new(U256::from_u64(block_number), &hashes);
+        unsafe {
+            let ret_data = run_evm(&bytecode, &rom, &schedule, &block, gas_limit, &mut memory);
+            assert_eq!(ret_data.error, VmError::None);
+            let slice = memory.slice(ret_data.offset as isize, ret_data.size);
+            let mut limbs = [0u64; 4];
+            for (i, &byte) in slice.iter().rev().enumerate() {
+                limbs[i / 8] |= (byte as u64) << ((i % 8) * 8);
+            }
+            U256::from_slice(&limbs)
+        }
+    }
+
+    #[test]
+    fn returns_the_provider_hash_within_the_256_block_window() {
+        let hashes = TestBlockHashProvider;
+        let expected = hashes.block_hash(U256::from_u64(10)).0;
+        assert_eq!(run_blockhash(10, 20).0, expected);
+        assert_eq!(run_blockhash(10, 266).0, expected); // exactly 256 blocks old
+    }
+
+    #[test]
+    fn returns_zero_outside_the_256_block_window() {
+        assert_eq!(run_blockhash(10, 267).0, [0, 0, 0, 0]); // 257 blocks old
+        assert_eq!(run_blockhash(20, 20).0, [0, 0, 0, 0]); // current block
+        assert_eq!(run_blockhash(30, 20).0, [0, 0, 0, 0]); // future block
+    }
+
+    fn run_difficulty(fork: Fork, difficulty: u64, prevrandao: u64) -> U256 {
+        let bytecode = assembler::from_string(
+            "
+            DIFFICULTY
+            PUSH1 0x00
+            MSTORE
+            PUSH1 0x20
+            PUSH1 0x00
+            RETURN
+            ",
+        )
+        .unwrap();
+        let schedule = Schedule::from_fork(fork);
+        let mut rom = VmRom::new();
+        rom.init(&bytecode, &schedule);
+        let mut memory = VmMemory::new();
+        let gas_limit = U256::from_u64(20_000_000_000_000);
+        memory.init(gas_limit);
+        let hashes = TestBlockHashProvider;
+        let mut block = BlockContext::new(U256::from_u64(0), &hashes);
+        block.difficulty = U256::from_u64(difficulty);
+        block.prevrandao = U256::from_u64(prevrandao);
+        unsafe {
+            let ret_data = run_evm(&bytecode, &rom, &schedule, &block, gas_limit, &mut memory);
+            assert_eq!(ret_data.error, VmError::None);
+            let slice = memory.slice(ret_data.offset as isize, ret_data.size);
+            let mut limbs = [0u64; 4];
+            for (i, &byte) in slice.iter().rev().enumerate() {
+                limbs[i / 8] |= (byte as u64) << ((i % 8) * 8);
+            }
+            U256::from_slice(&limbs)
+        }
+    }
+
+    #[test]
+    fn difficulty_returns_the_pow_difficulty_before_paris() {
+        assert_eq!(run_difficulty(Fork::Berlin, 123, 456).low_u64(), 123);
+    }
+
+    #[test]
+    fn difficulty_returns_prevrandao_from_paris_onward() {
+        assert_eq!(run_difficulty(Fork::Paris, 123, 456).low_u64(), 456);
+    }
+
+    fn run_blobhash(index: u64, versioned_hashes: &[U256]) -> U256 {
+        let input = format!(
+            "
+            PUSH8 {:#018x}
+            BLOBHASH
+            PUSH1 0x00
+            MSTORE
+            PUSH1 0x20
+            PUSH1 0x00
+            RETURN
+            ",
+            index
+        );
+        let bytecode = assembler::from_string(&input).unwrap();
+        let schedule = Schedule::from_fork(Fork::Cancun);
+        let mut rom = VmRom::new();
+        rom.init(&bytecode, &schedule);
+        let mut memory = VmMemory::new();
+        let gas_limit = U256::from_u64(20_000_000_000_000);
+        memory.init(gas_limit);
+        let hashes = TestBlockHashProvider;
+        let mut block = BlockContext::new(U256::from_u64(0), &hashes);
+        block.versioned_hashes = versioned_hashes;
+        unsafe {
+            let ret_data = run_evm(&bytecode, &rom, &schedule, &block, gas_limit, &mut memory);
+            assert_eq!(ret_data.error, VmError::None);
+            let slice = memory.slice(ret_data.offset as isize, ret_data.size);
+            let mut limbs = [0u64; 4];
+            for (i, &byte) in slice.iter().rev().enumerate() {
+                limbs[i / 8] |= (byte as u64) << ((i % 8) * 8);
+            }
+            U256::from_slice(&limbs)
+        }
+    }
+
+    #[test]
+    fn blobhash_returns_the_versioned_hash_at_the_given_index() {
+        let versioned_hashes = [U256::from_u64(0xaa), U256::from_u64(0xbb)];
+        assert_eq!(run_blobhash(0, &versioned_hashes).0, U256::from_u64(0xaa).0);
+        assert_eq!(run_blobhash(1, &versioned_hashes).0, U256::from_u64(0xbb).0);
+    }
+
+    #[test]
+    fn blobhash_returns_zero_for_an_out_of_range_index() {
+        let versioned_hashes = [U256::from_u64(0xaa)];
+        assert_eq!(run_blobhash(1, &versioned_hashes).0, [0, 0, 0, 0]);
+        assert_eq!(run_blobhash(u64::MAX, &versioned_hashes).0, [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn blobbasefee_returns_the_block_context_value() {
+        let bytecode = assembler::from_string(
+            "
+            BLOBBASEFEE
+            PUSH1 0x00
+            MSTORE
+            PUSH1 0x20
+            PUSH1 0x00
+            RETURN
+            ",
+        )
+        .unwrap();
+        let schedule = Schedule::from_fork(Fork::Cancun);
+        let mut rom = VmRom::new();
+        rom.init(&bytecode, &schedule);
+        let mut memory = VmMemory::new();
+        let gas_limit = U256::from_u64(20_000_000_000_000);
+        memory.init(gas_limit);
+        let hashes = TestBlockHashProvider;
+        let mut block = BlockContext::new(U256::from_u64(0), &hashes);
+        block.blob_gasprice = U256::from_u64(42);
+        unsafe {
+            let ret_data = run_evm(&bytecode, &rom, &schedule, &block, gas_limit, &mut memory);
+            assert_eq!(ret_data.error, VmError::None);
+            let slice = memory.slice(ret_data.offset as isize, ret_data.size);
+            let mut limbs = [0u64; 4];
+            for (i, &byte) in slice.iter().rev().enumerate() {
+                limbs[i / 8] |= (byte as u64) << ((i % 8) * 8);
+            }
+            assert_eq!(U256::from_slice(&limbs).0, U256::from_u64(42).0);
+        }
+    }
+
+    /// Independent ground truth for which fork introduces which opcode,
+    /// cross-checked against `VmRom::init`'s `OPCODE_INFOS`-driven gating
+    /// below. Kept separate from `OPCODE_INFOS` itself so a mistake in that
+    /// table (wrong fork, or an ordering assumption broken by appending a
+    /// new `Fork` variant) fails a test instead of silently mis-gating an
+    /// opcode at runtime.
+    const OPCODE_FORK_INTRODUCTIONS: &[(EvmOpcode, Fork)] = &[
+        (EvmOpcode::STATICCALL, Fork::Byzantium),
+        (EvmOpcode::REVERT, Fork::Byzantium),
+        (EvmOpcode::SHL, Fork::Constantinople),
+        (EvmOpcode::SHR, Fork::Constantinople),
+        (EvmOpcode::SAR, Fork::Constantinople),
+        (EvmOpcode::EXTCODEHASH, Fork::Constantinople),
+        (EvmOpcode::CREATE2, Fork::Constantinople),
+        (EvmOpcode::CHAINID, Fork::Istanbul),
+        (EvmOpcode::SELFBALANCE, Fork::Istanbul),
+        (EvmOpcode::BLOBHASH, Fork::Cancun),
+        (EvmOpcode::BLOBBASEFEE, Fork::Cancun),
+        (EvmOpcode::DUPN, Fork::Prague),
+        (EvmOpcode::SWAPN, Fork::Prague),
+        (EvmOpcode::EXCHANGE, Fork::Prague),
+    ];
+
+    const ALL_FORKS: &[Fork] = &[
+        Fork::Frontier,
+        Fork::Thawing,
+        Fork::Homestead,
+        Fork::Dao,
+        Fork::Tangerine,
+        Fork::Spurious,
+        Fork::Byzantium,
+        Fork::Constantinople,
+        Fork::Istanbul,
+        Fork::Berlin,
+        Fork::London,
+        Fork::Paris,
+        Fork::Shanghai,
+        Fork::Cancun,
+        Fork::Prague,
+    ];
+
+    fn is_gated_in(opcode: EvmOpcode, fork: Fork) -> bool {
+        let schedule = Schedule::from_fork(fork);
+        // Boxed rather than stack-local: `VmRom` is a flat byte buffer with
+        // no alignment guarantee of its own, and looping many stack-local
+        // instances here (one per fork) can leave `data` under-aligned for
+        // the `BbInfo` writes `init` does internally.
+        let mut rom = Box::new(VmRom::new());
+        // Deep-stack opcodes carry a one-byte immediate `init` always reads,
+        // so a bare opcode byte would be a truncated instruction.
+        let bytecode: &[u8] = if opcode.is_deep_stack() {
+            &[opcode as u8, 0x00]
+        } else {
+            &[opcode as u8]
+        };
+        rom.init(bytecode, &schedule);
+        rom.data[0] != Opcode::INVALID as u8
+    }
+
+    #[test]
+    fn opcode_availability_matches_its_introducing_fork() {
+        for &(opcode, introduced) in OPCODE_FORK_INTRODUCTIONS {
+            for &fork in ALL_FORKS {
+                assert_eq!(
+                    is_gated_in(opcode, fork),
+                    fork >= introduced,
+                    "{:?} on {:?} (introduced at {:?})",
+                    opcode,
+                    fork,
+                    introduced
+                );
+            }
+        }
+    }
+
+    /// Runs a single opcode byte and returns the resulting `VmError`,
+    /// without asserting anything about it: used by
+    /// `every_opcode_either_dispatches_or_reports_invalid_instruction` to
+    /// probe all 256 byte values, most of which aren't valid mnemonics the
+    /// assembler can even name.
+    ///
+    /// `byte` is preceded by enough dummy `PUSH1 0x00`s to satisfy the
+    /// deepest stack read any opcode makes (`SWAP16`, 17 deep) so that a
+    /// legitimately dispatchable opcode doesn't fail with a spurious
+    /// `StackUnderflow` instead of actually executing, and followed by a
+    /// zero immediate byte for opcodes (`PUSHN`/`DUPN`/`SWAPN`/`EXCHANGE`)
+    /// that read one.
+    fn run_single_opcode(byte: u8, fork: Fork) -> VmError {
+        const STACK_PADDING: usize = 20;
+        let opcode = EvmOpcode::try_from(byte).ok();
+        let immediate_len = match opcode {
+            Some(op) if op.is_push() => op.push_index() + 1,
+            Some(op) if op.is_deep_stack() => 1,
+            _ => 0,
+        };
+        let mut bytecode = Vec::with_capacity(STACK_PADDING * 2 + 1 + immediate_len);
+        for _ in 0..STACK_PADDING {
+            bytecode.extend_from_slice(&[EvmOpcode::PUSH1 as u8, 0x00]);
+        }
+        bytecode.push(byte);
+        bytecode.resize(bytecode.len() + immediate_len, 0x00);
+
+        let schedule = Schedule::from_fork(fork);
+        let mut rom = Box::new(VmRom::new());
+        rom.init(&bytecode, &schedule);
+        let mut memory = VmMemory::new();
+        let gas_limit = U256::from_u64(20_000_000_000_000);
+        memory.init(gas_limit);
+        let hashes = TestBlockHashProvider;
+        let block = BlockContext::new(U256::from_u64(0), &hashes);
+        unsafe { run_evm(&bytecode, &rom, &schedule, &block, gas_limit, &mut memory).error }
+    }
+
+    /// Exhaustively dispatches every byte value on every fork. This is the
+    /// coverage backstop for the giant `match opcode { ... }` in
+    /// `run_evm_impl`: a bare `unimplemented!()` arm would panic here and
+    /// fail the test, instead of only surfacing when some future bytecode
+    /// happens to hit it. Opcodes not yet gated in by their fork must
+    /// report `VmError::InvalidInstruction`; opcodes that are gated in may
+    /// report anything (including `InvalidInstruction`, for opcodes this
+    /// interpreter accepts but can't yet execute for lack of an account
+    /// model) as long as dispatching them doesn't panic.
+    #[test]
+    fn every_opcode_either_dispatches_or_reports_invalid_instruction() {
+        for byte in 0u16..=255 {
+            let byte = byte as u8;
+            let (introduced_fork, ..) = OPCODE_INFOS[byte as usize];
+            for &fork in ALL_FORKS {
+                let error = run_single_opcode(byte, fork);
+                if fork < introduced_fork {
+                    assert_eq!(
+                        error,
+                        VmError::InvalidInstruction,
+                        "0x{:02x} should be gated out on {:?} (introduced at {:?})",
+                        byte,
+                        fork,
+                        introduced_fork
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn a_frontier_opcode_is_valid_on_every_fork() {
+        for &fork in ALL_FORKS {
+            assert!(is_gated_in(EvmOpcode::ADD, fork));
+        }
+    }
+
+    #[test]
+    fn folds_balance_into_static_block_gas_before_berlin() {
+        let bytecode = vec![EvmOpcode::ADDRESS as u8, EvmOpcode::BALANCE as u8, EvmOpcode::STOP as u8];
+        let mut rom = VmRom::new();
+        let schedule = Schedule::from_fork(Fork::Istanbul);
+        rom.init(&bytecode, &schedule);
+        // ADDRESS (Fee::Base = 2) + the Istanbul BALANCE fee (700).
+        assert_eq!(rom.get_bb_info(0).gas, 2 + 700);
+    }
+
+    #[test]
+    fn excludes_balance_from_static_block_gas_from_berlin_on() {
+        let bytecode = vec![EvmOpcode::ADDRESS as u8, EvmOpcode::BALANCE as u8, EvmOpcode::STOP as u8];
+        let mut rom = VmRom::new();
+        let schedule = Schedule::from_fork(Fork::Berlin);
+        rom.init(&bytecode, &schedule);
+        // Only ADDRESS's static fee; BALANCE's warm/cold cost is left for
+        // the instruction site once the interpreter can charge it.
+        assert_eq!(rom.get_bb_info(0).gas, 2);
+    }
+
+    /// Small deterministic PRNG so the fuzz test below is reproducible
+    /// without pulling in a `rand` dependency for a single test.
+    struct Xorshift64(u64);
+
+    impl Xorshift64 {
+        fn new(seed: u64) -> Xorshift64 {
+            Xorshift64(seed)
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            let mut x = self.0;
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            self.0 = x;
+            x
+        }
+
+        fn next_usize(&mut self, bound: usize) -> usize {
+            (self.next_u64() as usize) % bound
+        }
+    }
+
+    /// Generates a single basic block: any opcode that doesn't end a
+    /// block (a terminator, or `JUMPDEST`) is fair game, so the whole
+    /// buffer decodes as one block starting at address 0. `PUSH*`'s
+    /// immediate bytes are filled with arbitrary data (their value doesn't
+    /// affect the stack-bound property under test here) and skipped over
+    /// so a push never gets misread as the next iteration's opcode.
+    ///
+    /// `is_deep_stack` opcodes (`EXCHANGE`/`DUPN`/`SWAPN`) are excluded:
+    /// their stack effect depends on an immediate operand `reference_stack_ok`
+    /// models via `deep_stack_effect`, not on the fixed per-opcode
+    /// `(delta, alpha)` this generator would otherwise need to respect.
+    fn gen_block(rng: &mut Xorshift64, num_opcodes: usize) -> Vec<u8> {
+        let candidates: Vec<EvmOpcode> = EvmOpcode::iter()
+            .cloned()
+            .filter(|op| !op.is_terminator() && *op != EvmOpcode::JUMPDEST && !op.is_deep_stack())
+            .collect();
+        let mut bytecode = Vec::new();
+        for _ in 0..num_opcodes {
+            let opcode = candidates[rng.next_usize(candidates.len())];
+            bytecode.push(opcode as u8);
+            if opcode.is_push() {
+                for _ in 0..=opcode.push_index() {
+                    bytecode.push(rng.next_u64() as u8);
+                }
+            }
+        }
+        bytecode
+    }
+
+    /// Reference stack-bound check: walks the block one instruction at a
+    /// time, exactly like the interpreter's dispatch loop would, rather
+    /// than through the forward/backward block summary being tested.
+    fn reference_stack_ok(bytecode: &[u8], start_stack_size: usize) -> bool {
+        let mut stack_size = start_stack_size;
+        let mut i = 0;
+        while i < bytecode.len() {
+            let code = bytecode[i];
+            let opcode = unsafe { std::mem::transmute::<u8, EvmOpcode>(code) };
+            let (delta, alpha) = if opcode.is_deep_stack() {
+                let immediate = bytecode.get(i + 1).copied().unwrap_or(0);
+                deep_stack_effect(opcode, immediate)
+            } else {
+                let (_, _, delta, alpha) = OPCODE_INFOS[code as usize];
+                (delta, alpha)
+            };
+            let (delta, alpha) = (delta as usize, alpha as usize);
+            if stack_size < delta {
+                return false;
+            }
+            let new_stack_size = stack_size - delta + alpha;
+            if new_stack_size > VmStack::MAX_LEN {
+                return false;
+            }
+            stack_size = new_stack_size;
+            i += if opcode.is_push() {
+                1 + opcode.push_index() + 1
+            } else if opcode.is_deep_stack() {
+                2
+            } else {
+                1
+            };
+        }
+        true
+    }
+
+    fn block_check_ok(bb_info: &BbInfo, start_stack_size: usize) -> bool {
+        let underflow = start_stack_size < bb_info.stack_min_size as usize;
+        let overflow = (start_stack_size + bb_info.stack_rel_max_size as usize) > VmStack::MAX_LEN;
+        !(underflow || overflow)
+    }
+
+    const JUMPDEST_BYTE: u8 = EvmOpcode::JUMPDEST as u8;
+    const BEGINSUB_BYTE: u8 = EvmOpcode::BEGINSUB as u8;
+
+    /// PUSHN whose data is filled with `fill`, itself preceded and followed
+    /// by a real `JUMPDEST`/`BEGINSUB` so both ends of the push data have a
+    /// genuine destination to distinguish from a spurious hit.
+    fn push_wrapped_in_dests(push: EvmOpcode, fill: u8) -> Vec<u8> {
+        let num_bytes = push.push_index() + 1;
+        let mut bytecode = vec![JUMPDEST_BYTE, push as u8];
+        bytecode.resize(bytecode.len() + num_bytes, fill);
+        bytecode.push(BEGINSUB_BYTE);
+        bytecode
+    }
+
+    #[test]
+    fn jumpdest_byte_inside_push1_data_is_not_a_valid_target() {
+        let bytecode = push_wrapped_in_dests(EvmOpcode::PUSH1, JUMPDEST_BYTE);
+        let mut rom = VmRom::new();
+        rom.init(&bytecode, &Schedule::from_fork(Fork::Berlin));
+        assert!(rom.is_jumpdest(0));
+        assert!(!rom.is_jumpdest(2));
+        assert!(rom.is_beginsub(3));
+    }
+
+    #[test]
+    fn beginsub_byte_inside_push1_data_is_not_a_valid_target() {
+        let bytecode = push_wrapped_in_dests(EvmOpcode::PUSH1, BEGINSUB_BYTE);
+        let mut rom = VmRom::new();
+        rom.init(&bytecode, &Schedule::from_fork(Fork::Berlin));
+        assert!(!rom.is_beginsub(2));
+    }
+
+    #[test]
+    fn jumpdest_bitmap_agrees_with_is_jumpdest_over_every_address() {
+        let bytecode = push_wrapped_in_dests(EvmOpcode::PUSH1, JUMPDEST_BYTE);
+        let mut rom = VmRom::new();
+        rom.init(&bytecode, &Schedule::from_fork(Fork::Berlin));
+        let bitmap = rom.jumpdests();
+        for addr in 0..bytecode.len() {
+            assert_eq!(bitmap.contains(addr), rom.is_jumpdest(addr as u64), "address {}", addr);
+        }
+        assert_eq!(bitmap.iter().collect::<Vec<_>>(), vec![0]);
+    }
+
+    #[test]
+    fn jumpdest_byte_at_a_byte_offset_multiple_of_8_is_not_a_valid_target() {
+        // A PUSH8 starting at address 1 covers data addresses 2..=9, so
+        // address 8 lands exactly on a bitmap byte boundary (8 / 8 == 1).
+        let bytecode = push_wrapped_in_dests(EvmOpcode::PUSH8, JUMPDEST_BYTE);
+        assert_eq!(bytecode.len(), 11);
+        let mut rom = VmRom::new();
+        rom.init(&bytecode, &Schedule::from_fork(Fork::Berlin));
+        for addr in 2..=9u64 {
+            assert!(!rom.is_jumpdest(addr), "address {} should be invalid", addr);
+        }
+        assert!(rom.is_beginsub(10));
+    }
+
+    #[test]
+    fn jumpdest_byte_at_a_bitmap_word_boundary_is_not_a_valid_target() {
+        // Pad with plain STOPs so PUSH32 itself starts at address 32, i.e.
+        // exactly on the invalid-dests bitmap's 32-bit word boundary.
+        let mut bytecode = vec![EvmOpcode::STOP as u8; 32];
+        bytecode.push(EvmOpcode::PUSH32 as u8);
+        bytecode.resize(bytecode.len() + 32, JUMPDEST_BYTE);
+        bytecode.push(BEGINSUB_BYTE);
+        let mut rom = VmRom::new();
+        rom.init(&bytecode, &Schedule::from_fork(Fork::Berlin));
+        for addr in 33..=64u64 {
+            assert!(!rom.is_jumpdest(addr), "address {} should be invalid", addr);
+        }
+        assert!(rom.is_beginsub(65));
+    }
+
+    #[test]
+    fn jumpdest_byte_at_the_31_to_32_push32_straddle_is_not_a_valid_target() {
+        // A PUSH32 starting at an address that isn't a multiple of 8 puts
+        // its 32 data bytes at a non-zero bit_offset into the invalid-dests
+        // bitmap, so the marked range straddles from one bitmap byte into
+        // the next rather than lining up with a byte/word boundary. This is
+        // the case that used to overflow a too-narrow read-modify-write.
+        let mut bytecode = vec![EvmOpcode::STOP as u8; 3];
+        bytecode.extend(push_wrapped_in_dests(EvmOpcode::PUSH32, JUMPDEST_BYTE));
+        let mut rom = VmRom::new();
+        rom.init(&bytecode, &Schedule::from_fork(Fork::Berlin));
+        assert!(rom.is_jumpdest(3));
+        for addr in 5..=36u64 {
+            assert!(!rom.is_jumpdest(addr), "address {} should be invalid", addr);
+        }
+        assert!(rom.is_beginsub(37));
+    }
+
+    #[test]
+    fn push32_as_the_final_byte_of_max_codesize_code_does_not_panic() {
+        // A PUSH32 with its immediate entirely past the end of code: this
+        // used to slice `bytecode[start..end]` past `bytecode.len()` and
+        // panic. `MAX_CODESIZE` is the boundary where the immediate's tail
+        // would otherwise spill into the invalid-dests region right after it.
+        let mut bytecode = vec![EvmOpcode::STOP as u8; VmRom::MAX_CODESIZE - 1];
+        bytecode.push(EvmOpcode::PUSH32 as u8);
+        assert_eq!(bytecode.len(), VmRom::MAX_CODESIZE);
+        let mut rom = VmRom::new();
+        rom.init(&bytecode, &Schedule::from_fork(Fork::Berlin));
+        let immediate_start = VmRom::MAX_CODESIZE;
+        for &byte in &rom.data[immediate_start..immediate_start + 32] {
+            assert_eq!(byte, 0);
+        }
+    }
+
+    #[test]
+    fn push_immediate_running_past_end_of_code_is_zero_padded() {
+        // A PUSH4 with only its first byte present in code: the missing
+        // trailing (least-significant) bytes are implicit zero padding, not
+        // whatever bytes happen to follow in the ROM buffer.
+        let mut bytecode = vec![EvmOpcode::STOP as u8; 3];
+        bytecode.push(EvmOpcode::PUSH4 as u8);
+        bytecode.push(0xaa);
+        let mut rom = VmRom::new();
+        rom.init(&bytecode, &Schedule::from_fork(Fork::Berlin));
+        // Storage is little-endian: the one real byte (the immediate's most
+        // significant byte) lands at the top of the 4-byte slot.
+        assert_eq!(&rom.data[4..8], &[0x00, 0x00, 0x00, 0xaa]);
+    }
+
+    #[test]
+    fn jumpdest_address_past_code_len_aliasing_a_real_dest_is_rejected() {
+        // Short code with a real JUMPDEST at address 0. Addresses that are
+        // multiples of `MAX_CODESIZE` away from 0 alias the same bitmap bit
+        // and byte once `% MAX_CODESIZE` is taken, so they used to pass;
+        // they must be rejected outright once `addr >= code_len`.
+        let bytecode = vec![JUMPDEST_BYTE, EvmOpcode::STOP as u8];
+        let mut rom = VmRom::new();
+        rom.init(&bytecode, &Schedule::from_fork(Fork::Berlin));
+        assert!(rom.is_jumpdest(0));
+        assert!(!rom.is_jumpdest(VmRom::MAX_CODESIZE as u64));
+        assert!(!rom.is_jumpdest(2 * VmRom::MAX_CODESIZE as u64));
+    }
+
+    #[test]
+    fn beginsub_address_past_code_len_aliasing_a_real_dest_is_rejected() {
+        let bytecode = vec![BEGINSUB_BYTE, EvmOpcode::STOP as u8];
+        let mut rom = VmRom::new();
+        rom.init(&bytecode, &Schedule::from_fork(Fork::Berlin));
+        assert!(rom.is_beginsub(0));
+        assert!(!rom.is_beginsub(VmRom::MAX_CODESIZE as u64));
+        assert!(!rom.is_beginsub(2 * VmRom::MAX_CODESIZE as u64));
+    }
+
+    #[test]
+    fn jumpdest_address_one_past_code_len_is_rejected() {
+        let bytecode = vec![JUMPDEST_BYTE, JUMPDEST_BYTE];
+        let mut rom = VmRom::new();
+        rom.init(&bytecode, &Schedule::from_fork(Fork::Berlin));
+        assert!(rom.is_jumpdest(1));
+        assert!(!rom.is_jumpdest(bytecode.len() as u64));
+    }
+
+    #[test]
+    fn deep_stack_opcode_as_the_final_byte_of_code_is_zero_padded() {
+        let bytecode = vec![EvmOpcode::DUPN as u8];
+        let mut rom = VmRom::new();
+        rom.init(&bytecode, &Schedule::from_fork(Fork::Prague));
+        assert_eq!(rom.data[1], 0);
+    }
+
+    #[test]
+    fn block_level_stack_check_matches_a_per_instruction_reference() {
+        let mut rng = Xorshift64::new(0x9e3779b97f4a7c15);
+        let schedule = Schedule::from_fork(Fork::Berlin);
+        for _ in 0..500 {
+            let num_opcodes = 1 + rng.next_usize(24);
+            let bytecode = gen_block(&mut rng, num_opcodes);
+            let mut rom = VmRom::new();
+            rom.init(&bytecode, &schedule);
+            let bb_info = rom.get_bb_info(0);
+            for start_stack_size in [0usize, 1, 2, 16, 1022, 1023, 1024] {
+                let expected = reference_stack_ok(&bytecode, start_stack_size);
+                let actual = block_check_ok(bb_info, start_stack_size);
+                assert_eq!(
+                    actual, expected,
+                    "mismatch for bytecode {:02x?} starting at stack size {}",
+                    bytecode, start_stack_size
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn jumpi_fallthrough_folds_stack_bound_but_not_gas() {
+        // Block A ends in a JUMPI whose fallthrough (address 5) isn't a
+        // JUMPDEST, so it's only reachable by not taking the jump; block B
+        // requires one stack item that A's own local analysis never sees.
+        let bytecode = vec![
+            EvmOpcode::PUSH1 as u8,
+            0x01,
+            EvmOpcode::PUSH1 as u8,
+            0x05,
+            EvmOpcode::JUMPI as u8,
+            EvmOpcode::DUP1 as u8,
+            EvmOpcode::STOP as u8,
+        ];
+        let schedule = Schedule::from_fork(Fork::Berlin);
+        let mut rom = VmRom::new();
+        rom.init(&bytecode, &schedule);
+
+        let a = *rom.get_bb_info(0);
+        let b = *rom.get_bb_info(5);
+
+        // B's own requirement is folded backward into A's published entry,
+        // since falling through is the one deterministic continuation.
+        assert_eq!(a.stack_min_size, 1);
+        assert_eq!(a.stack_rel_max_size, 2);
+
+        // A's own gas excludes B's: DUP1/STOP only run if the jump isn't
+        // taken, so charging for them upfront would overcharge the taken
+        // path.
+        let push1_gas = schedule.opcode_gas(EvmOpcode::PUSH1, Fee::VeryLow);
+        let jumpi_gas = schedule.opcode_gas(EvmOpcode::JUMPI, Fee::High);
+        assert_eq!(a.gas, 2 * push1_gas + jumpi_gas);
+
+        // B keeps its own independent entry, charged when the jump isn't
+        // taken.
+        let dup1_gas = schedule.opcode_gas(EvmOpcode::DUP1, Fee::VeryLow);
+        let stop_gas = schedule.opcode_gas(EvmOpcode::STOP, Fee::Zero);
+        assert_eq!(b.stack_min_size, 1);
+        assert_eq!(b.stack_rel_max_size, 1);
+        assert_eq!(b.gas, dup1_gas + stop_gas);
+    }
+
+    fn trace(input: &str, filter: TraceFilter) -> TraceReport {
+        trace_with_schedule(input, filter, Schedule::from_fork(Fork::default()))
+    }
+
+    fn trace_with_schedule(input: &str, filter: TraceFilter, schedule: Schedule) -> TraceReport {
+        trace_sampled(input, filter, TraceSample::Every, schedule)
+    }
+
+    fn trace_sampled(input: &str, filter: TraceFilter, sample: TraceSample, schedule: Schedule) -> TraceReport {
+        let bytecode = assembler::from_string(input).unwrap();
+        let mut rom = VmRom::new();
+        rom.init(&bytecode, &schedule);
+        let mut memory = VmMemory::new();
+        let gas_limit = U256::from_u64(20_000_000_000_000);
+        memory.init(gas_limit);
+        let hashes = TestBlockHashProvider;
+        let block = BlockContext::new(U256::from_u64(0), &hashes);
+        unsafe {
+            run_evm_with_trace(&bytecode, &rom, &schedule, &block, gas_limit, &mut memory, filter, sample, 0).1
+        }
+    }
+
+    #[test]
+    fn counts_every_occurrence_of_the_traced_opcode() {
+        let report = trace(
+            "
+            PUSH1 0x01
+            PUSH1 0x02
+            ADD
+            PUSH1 0x03
+            ADD
+            ",
+            TraceFilter::CountOpcode(EvmOpcode::ADD),
+        );
+        assert_eq!(report.matches, 2);
+        assert!(report.stack_tops.is_empty());
+    }
+
+    #[test]
+    fn records_the_stack_top_at_every_occurrence() {
+        let report = trace(
+            "
+            PUSH1 0x2a
+            PUSH1 0x37
+            PUSH1 0x01
+            JUMPI
+            JUMPDEST
+            ",
+            TraceFilter::StackTopAt(EvmOpcode::JUMPI),
+        );
+        assert_eq!(report.matches, 1);
+        assert_eq!(report.stack_tops.len(), 1);
+        let (pc, top) = report.stack_tops[0];
+        assert_eq!(pc, 6);
+        assert_eq!(top.low_u64(), 1);
+    }
+
+    #[test]
+    fn reports_zero_matches_when_the_opcode_never_runs() {
+        let report = trace(
+            "
+            PUSH1 0x01
+            PUSH1 0x02
+            ADD
+            ",
+            TraceFilter::CountOpcode(EvmOpcode::MUL),
+        );
+        assert_eq!(report.matches, 0);
+    }
+
+    #[test]
+    fn records_return_stack_depth_across_nested_subroutines() {
+        // addr 0: PUSH1 4 / addr 2: JUMPSUB      -- call sub1 (BEGINSUB at 4)
+        // addr 3: STOP                           -- sub1's return address
+        // addr 4: BEGINSUB (sub1)
+        // addr 5: PUSH1 9 / addr 7: JUMPSUB       -- call sub2 (BEGINSUB at 9)
+        // addr 8: RETURNSUB                      -- sub1's return address
+        // addr 9: BEGINSUB (sub2)
+        // addr 10: RETURNSUB
+        let report = trace_with_schedule(
+            "
+            PUSH1 0x04
+            JUMPSUB
+            STOP
+            BEGINSUB
+            PUSH1 0x09
+            JUMPSUB
+            RETURNSUB
+            BEGINSUB
+            RETURNSUB
+            ",
+            TraceFilter::ReturnStackAt(EvmOpcode::RETURNSUB),
+            Schedule::from_fork(Fork::Berlin),
+        );
+        assert_eq!(report.matches, 2);
+        // Sub2's RETURNSUB (addr 10) sees both call frames still nested.
+        assert_eq!(report.return_stacks[0], (10, vec![8, 3]));
+        // Sub1's RETURNSUB (addr 8) sees only its own call frame.
+        assert_eq!(report.return_stacks[1], (8, vec![3]));
+    }
+
+    #[test]
+    fn records_the_deepest_return_stack_depth_reached_regardless_of_filter() {
+        // Same nested-subroutine program as above, but traced with a
+        // filter that never matches: `max_return_stack_depth` is tracked
+        // unconditionally, not just when the filter does.
+        let report = trace_with_schedule(
+            "
+            PUSH1 0x04
+            JUMPSUB
+            STOP
+            BEGINSUB
+            PUSH1 0x09
+            JUMPSUB
+            RETURNSUB
+            BEGINSUB
+            RETURNSUB
+            ",
+            TraceFilter::CountOpcode(EvmOpcode::MUL),
+            Schedule::from_fork(Fork::Berlin),
+        );
+        assert_eq!(report.matches, 0);
+        assert_eq!(report.max_return_stack_depth, 2);
+    }
+
+    #[test]
+    fn records_a_visit_count_for_every_pc() {
+        let report = trace(
+            "
+            PUSH1 0x01
+            PUSH1 0x02
+            ADD
+            ",
+            TraceFilter::PcCounts,
+        );
+        // PUSH1 0x01 (pc 0), PUSH1 0x02 (pc 2), ADD (pc 4), implicit STOP (pc 5).
+        assert_eq!(report.pc_counts.len(), 4);
+        assert_eq!(report.pc_counts.get(&0), Some(&1));
+        assert_eq!(report.pc_counts.get(&4), Some(&1));
+    }
+
+    #[test]
+    fn every_nth_sampling_only_visits_steps_on_the_stride() {
+        // 6 steps total (PUSH1 PUSH1 ADD PUSH1 ADD implicit-STOP); sampling
+        // every 2nd step visits steps 0, 2, 4, i.e. 3 of the 6.
+        let report = trace_sampled(
+            "
+            PUSH1 0x01
+            PUSH1 0x02
+            ADD
+            PUSH1 0x03
+            ADD
+            ",
+            TraceFilter::PcCounts,
+            TraceSample::EveryNth(2),
+            Schedule::from_fork(Fork::default()),
+        );
+        assert_eq!(report.matches, 3);
+    }
+
+    #[test]
+    fn block_boundary_sampling_only_visits_jump_targets_and_pc_zero() {
+        // addr 0: PUSH1 PUSH1 ADD PUSH1 ADD, falls through to the JUMPDEST at
+        // addr 8. Only pc 0 (the run's entry) and pc 8 (a valid jump
+        // target) are block boundaries; the PUSH1/ADD steps in between
+        // aren't.
+        let report = trace_sampled(
+            "
+            PUSH1 0x01
+            PUSH1 0x02
+            ADD
+            PUSH1 0x03
+            ADD
+            JUMPDEST
+            ",
+            TraceFilter::PcCounts,
+            TraceSample::BlockBoundaries,
+            Schedule::from_fork(Fork::default()),
+        );
+        assert_eq!(report.matches, 2);
+        assert_eq!(report.pc_counts.get(&0), Some(&1));
+        assert_eq!(report.pc_counts.get(&8), Some(&1));
+    }
+
+    #[test]
+    fn opcode_sampling_only_visits_the_given_opcodes() {
+        let report = trace_sampled(
+            "
+            PUSH1 0x01
+            PUSH1 0x02
+            ADD
+            PUSH1 0x03
+            MUL
+            ",
+            TraceFilter::PcCounts,
+            TraceSample::Opcodes(vec![EvmOpcode::ADD, EvmOpcode::MUL]),
+            Schedule::from_fork(Fork::default()),
+        );
+        assert_eq!(report.matches, 2);
+        assert_eq!(report.pc_counts.get(&4), Some(&1));
+        assert_eq!(report.pc_counts.get(&7), Some(&1));
+    }
+
+    fn run(input: &str) -> ReturnData {
+        run_with_schedule(input, Schedule::from_fork(Fork::default()))
+    }
+
+    fn run_with_schedule(input: &str, schedule: Schedule) -> ReturnData {
+        run_with_gas(input, schedule, U256::from_u64(20_000_000_000_000))
+    }
+
+    fn run_with_gas(input: &str, schedule: Schedule, gas_limit: U256) -> ReturnData {
+        let bytecode = assembler::from_string(input).unwrap();
+        let mut rom = VmRom::new();
+        rom.init(&bytecode, &schedule);
+        let mut memory = VmMemory::new();
+        memory.init(gas_limit);
+        let hashes = TestBlockHashProvider;
+        let block = BlockContext::new(U256::from_u64(0), &hashes);
+        unsafe { run_evm(&bytecode, &rom, &schedule, &block, gas_limit, &mut memory) }
+    }
+
+    /// Measures `input`'s gas cost by running it once under a generous gas
+    /// limit, then re-runs it at exactly `cost - 1` and `cost`, asserting
+    /// `OutOfGas` and a normal completion respectively. This is the
+    /// error-injection harness for gas accounting: walking every charge one
+    /// unit short of what a correct implementation needs turns an
+    /// off-by-one anywhere in an opcode's gas cost, or in the bb-precharge
+    /// scheme's static per-block estimate (`analyze_basic_blocks`), into a
+    /// boundary-test failure instead of a silent pass on the happy path.
+    fn assert_exact_gas_boundary(input: &str, fork: Fork) {
+        const GENEROUS_GAS: u64 = 20_000_000_000_000;
+        let baseline = run_with_gas(input, Schedule::from_fork(fork), U256::from_u64(GENEROUS_GAS));
+        assert_eq!(baseline.error, VmError::None, "measuring run must complete without error");
+        let cost = GENEROUS_GAS - baseline.gas;
+
+        let short = run_with_gas(input, Schedule::from_fork(fork), U256::from_u64(cost - 1));
+        assert_eq!(short.error, VmError::OutOfGas, "cost - 1 must run out of gas");
+
+        let exact = run_with_gas(input, Schedule::from_fork(fork), U256::from_u64(cost));
+        assert_eq!(exact.error, VmError::None, "cost must be enough to complete");
+    }
+
+    #[test]
+    fn exact_gas_boundary_on_simple_arithmetic() {
+        assert_exact_gas_boundary(
+            "
+            PUSH1 0x02
+            PUSH1 0x03
+            ADD
+            PUSH1 0x00
+            MSTORE
+            PUSH1 0x20
+            PUSH1 0x00
+            RETURN
+            ",
+            Fork::default(),
+        );
+    }
+
+    #[test]
+    fn exact_gas_boundary_on_memory_expansion() {
+        // MSTORE at a fresh offset charges both `Fee::VeryLow` and the
+        // quadratic memory-expansion cost (`meter_extend!`), so this
+        // exercises the dynamic cost path, not just `Fee`'s static table.
+        assert_exact_gas_boundary(
+            "
+            PUSH1 0x2a
+            PUSH2 0x0100
+            MSTORE
+            PUSH1 0x20
+            PUSH2 0x0100
+            RETURN
+            ",
+            Fork::default(),
+        );
+    }
+
+    #[test]
+    fn exact_gas_boundary_on_exp_with_a_nonzero_exponent() {
+        // EXP's cost depends on the exponent's byte length
+        // (`Fee::ExpByte`), computed per-instance rather than baked into
+        // the bb-precharge scheme's static block estimate.
+        assert_exact_gas_boundary(
+            "
+            PUSH1 0x02
+            PUSH2 0x0100
+            EXP
+            PUSH1 0x00
+            MSTORE
+            PUSH1 0x20
+            PUSH1 0x00
+            RETURN
+            ",
+            Fork::default(),
+        );
+    }
+
+    #[test]
+    fn exact_gas_boundary_across_a_jumpsub_return_stack_round_trip() {
+        // Exercises the bb-precharge scheme's static gas estimate across a
+        // JUMPDEST/BEGINSUB block boundary rather than a single straight-
+        // line block, guarding the per-block charge `analyze_basic_blocks`
+        // precomputes for the subroutine's block as well as the caller's.
+        assert_exact_gas_boundary(
+            "
+            PUSH1 0x0d
+            JUMPSUB
+            PUSH1 0x2a
+            PUSH1 0x00
+            MSTORE
+            PUSH1 0x20
+            PUSH1 0x00
+            RETURN
+            BEGINSUB
+            RETURNSUB
+            ",
+            Fork::Berlin,
+        );
+    }
+
+    #[test]
+    fn return_with_zero_size_ignores_a_huge_offset() {
+        let ret_data = run(
+            "
+            PUSH1 0x00
+            PUSH32 0xffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff
+            RETURN
+            ",
+        );
+        assert_eq!(ret_data.error, VmError::None);
+        assert_eq!(ret_data.offset, 0);
+        assert_eq!(ret_data.size, 0);
+    }
+
+    #[test]
+    fn return_with_zero_size_charges_no_memory_expansion_gas() {
+        let with_huge_offset = run(
+            "
+            PUSH1 0x00
+            PUSH32 0xffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff
+            RETURN
+            ",
+        );
+        let with_zero_offset = run(
+            "
+            PUSH1 0x00
+            PUSH1 0x00
+            RETURN
+            ",
+        );
+        assert_eq!(with_huge_offset.gas, with_zero_offset.gas);
+    }
+
+    #[test]
+    fn owned_output_survives_memory_being_reset_and_reused() {
+        let bytecode = assembler::from_string(
+            "
+            PUSH1 0x2a
+            PUSH1 0x00
+            MSTORE
+            PUSH1 0x20
+            PUSH1 0x00
+            RETURN
+            ",
+        )
+        .unwrap();
+        let schedule = Schedule::from_fork(Fork::default());
+        let mut rom = VmRom::new();
+        rom.init(&bytecode, &schedule);
+        let mut memory = VmMemory::new();
+        let gas_limit = U256::from_u64(20_000_000_000_000);
+        memory.init(gas_limit);
+        let hashes = TestBlockHashProvider;
+        let block = BlockContext::new(U256::from_u64(0), &hashes);
+        let (ret_data, output) = unsafe {
+            run_evm_with_owned_output(&bytecode, &rom, &schedule, &block, gas_limit, &mut memory)
+        };
+        assert_eq!(ret_data.error, VmError::None);
+        let output = output.expect("offset/size are within the mapped memory");
+        // Reused the way `evm_repeat`'s `--repeat` flag reuses `memory`
+        // across runs: the owned copy must not alias the buffer this
+        // overwrites.
+        memory.reset();
+        assert_eq!(output.len(), 32);
+        assert_eq!(output[31], 0x2a);
+    }
+
+    #[test]
+    fn gas_after_jumpdest_in_a_partial_block_is_charged_at_the_earlier_block_entry() {
+        // addr 0: PUSH1 PUSH1 ADD, falls through to the JUMPDEST at addr 5,
+        // so this block is `partial` and folds addr 5's block into its own
+        // published gas. addr 5: JUMPDEST GAS, a `basic` block of its own
+        // (GAS is a terminator) since it's also reachable by a direct jump.
+        let bytecode = vec![
+            EvmOpcode::PUSH1 as u8,
+            0x01,
+            EvmOpcode::PUSH1 as u8,
+            0x02,
+            EvmOpcode::ADD as u8,
+            EvmOpcode::JUMPDEST as u8,
+            EvmOpcode::GAS as u8,
+            EvmOpcode::STOP as u8,
+        ];
+        let schedule = Schedule::from_fork(Fork::Berlin);
+        let mut rom = VmRom::new();
+        rom.init(&bytecode, &schedule);
+
+        let push1_gas = schedule.opcode_gas(EvmOpcode::PUSH1, Fee::VeryLow);
+        let add_gas = schedule.opcode_gas(EvmOpcode::ADD, Fee::VeryLow);
+        let jumpdest_gas = schedule.opcode_gas(EvmOpcode::JUMPDEST, Fee::Jumpdest);
+        let gas_gas = schedule.opcode_gas(EvmOpcode::GAS, Fee::Base);
+
+        let fallthrough_entry = *rom.get_bb_info(0);
+        let direct_jump_entry = *rom.get_bb_info(5);
+        assert_eq!(fallthrough_entry.gas, 2 * push1_gas + add_gas + jumpdest_gas + gas_gas);
+        assert_eq!(direct_jump_entry.gas, jumpdest_gas + gas_gas);
+    }
+
+    #[test]
+    fn gas_opcode_reports_the_gas_left_after_what_ran_for_either_entry_point() {
+        // Entering through the fallthrough pays for PUSH1 PUSH1 ADD before
+        // ever reaching JUMPDEST/GAS; entering via a direct jump skips that
+        // arithmetic and pays for the PUSH1/JUMP that got it there instead.
+        // The two entries are charged upfront for different totals, but
+        // GAS's own pushed value must still equal the gas limit minus
+        // whatever actually ran, in both cases.
+        let fallthrough_entry = gas_value_returned_by(
+            "
+            PUSH1 0x01
+            PUSH1 0x02
+            ADD
+            JUMPDEST
+            GAS
+            PUSH1 0x00
+            MSTORE
+            PUSH1 0x20
+            PUSH1 0x00
+            RETURN
+            ",
+        );
+        let direct_jump_entry = gas_value_returned_by(
+            "
+            PUSH1 0x03
+            JUMP
+            JUMPDEST
+            GAS
+            PUSH1 0x00
+            MSTORE
+            PUSH1 0x20
+            PUSH1 0x00
+            RETURN
+            ",
+        );
+        let schedule = Schedule::from_fork(Fork::default());
+        let push1_gas = schedule.opcode_gas(EvmOpcode::PUSH1, Fee::VeryLow);
+        let add_gas = schedule.opcode_gas(EvmOpcode::ADD, Fee::VeryLow);
+        let jump_gas = schedule.opcode_gas(EvmOpcode::JUMP, Fee::Mid);
+        let jumpdest_gas = schedule.opcode_gas(EvmOpcode::JUMPDEST, Fee::Jumpdest);
+        let gas_gas = schedule.opcode_gas(EvmOpcode::GAS, Fee::Base);
+        let gas_limit = 20_000_000_000_000u64;
+
+        let expected_fallthrough = gas_limit - (2 * push1_gas + add_gas + jumpdest_gas + gas_gas);
+        let expected_direct_jump = gas_limit - (push1_gas + jump_gas + jumpdest_gas + gas_gas);
+        assert_eq!(fallthrough_entry, expected_fallthrough);
+        assert_eq!(direct_jump_entry, expected_direct_jump);
+    }
+
+    // Runs `input`, which must RETURN a single 32-byte word holding the
+    // value GAS pushed, and decodes it back to a u64.
+    fn gas_value_returned_by(input: &str) -> u64 {
+        let bytecode = assembler::from_string(input).unwrap();
+        let schedule = Schedule::from_fork(Fork::default());
+        let mut rom = VmRom::new();
+        rom.init(&bytecode, &schedule);
+        let mut memory = VmMemory::new();
+        let gas_limit = U256::from_u64(20_000_000_000_000);
+        memory.init(gas_limit);
+        let hashes = TestBlockHashProvider;
+        let block = BlockContext::new(U256::from_u64(0), &hashes);
+        let (ret_data, output) = unsafe {
+            run_evm_with_owned_output(&bytecode, &rom, &schedule, &block, gas_limit, &mut memory)
+        };
+        assert_eq!(ret_data.error, VmError::None);
+        let output = output.expect("offset/size are within the mapped memory");
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(&output[24..32]);
+        u64::from_be_bytes(bytes)
+    }
+
+    #[test]
+    fn run_evm_resume_from_a_jumpdest_matches_running_the_equivalent_program_from_scratch() {
+        // Resuming at addr 4 (the JUMPDEST, after both two-byte PUSH1s) with
+        // [0x02, 0x01] already on the stack (top first) must behave exactly
+        // like running PUSH1 0x01 PUSH1 0x02 JUMPDEST ADD ... from pc = 0:
+        // same ADD, same RETURN.
+        let bytecode = assembler::from_string(
+            "
+            PUSH1 0x01
+            PUSH1 0x02
+            JUMPDEST
+            ADD
+            PUSH1 0x00
+            MSTORE
+            PUSH1 0x20
+            PUSH1 0x00
+            RETURN
+            ",
+        )
+        .unwrap();
+        let schedule = Schedule::from_fork(Fork::default());
+        let mut rom = VmRom::new();
+        rom.init(&bytecode, &schedule);
+        let mut memory = VmMemory::new();
+        let gas_limit = U256::from_u64(20_000_000_000_000);
+        memory.init(gas_limit);
+        let hashes = TestBlockHashProvider;
+        let block = BlockContext::new(U256::from_u64(0), &hashes);
+
+        let from_scratch = unsafe { run_evm(&bytecode, &rom, &schedule, &block, gas_limit, &mut memory) };
+        memory.reset();
+
+        let initial_stack = [U256::from_u64(2), U256::from_u64(1)];
+        let resume = ResumePoint {
+            pc: 4,
+            stack: &initial_stack,
+            gas: 20_000_000_000_000,
+        };
+        let resumed = unsafe { run_evm_resume(&bytecode, &rom, &schedule, &block, gas_limit, &mut memory, resume) };
+
+        assert_eq!(resumed.error, VmError::None);
+        assert_eq!(resumed.offset, from_scratch.offset);
+        assert_eq!(resumed.size, from_scratch.size);
+    }
+
+    #[test]
+    fn run_evm_resume_at_an_address_that_is_not_a_block_entry_is_rejected() {
+        let bytecode = assembler::from_string(
+            "
+            PUSH1 0x01
+            PUSH1 0x02
+            ADD
+            STOP
+            ",
+        )
+        .unwrap();
+        let schedule = Schedule::from_fork(Fork::default());
+        let mut rom = VmRom::new();
+        rom.init(&bytecode, &schedule);
+        let mut memory = VmMemory::new();
+        let gas_limit = U256::from_u64(20_000_000_000_000);
+        memory.init(gas_limit);
+        let hashes = TestBlockHashProvider;
+        let block = BlockContext::new(U256::from_u64(0), &hashes);
+
+        // addr 3 is PUSH1 0x02's data byte, not a JUMPDEST/BEGINSUB.
+        let initial_stack = [U256::from_u64(1)];
+        let resume = ResumePoint {
+            pc: 3,
+            stack: &initial_stack,
+            gas: 20_000_000_000_000,
+        };
+        let ret_data = unsafe { run_evm_resume(&bytecode, &rom, &schedule, &block, gas_limit, &mut memory, resume) };
+        assert_eq!(ret_data.error, VmError::InvalidJumpDest);
+    }
+
+    #[test]
+    fn msize_after_a_failed_extension_still_reflects_the_last_committed_one() {
+        // MSTORE8 commits a one-word extension; the MLOAD that follows asks
+        // for a huge one that runs out of gas. `meter_extend!` only grows
+        // `memory.len` once the gas charge succeeds, so the failed attempt
+        // must leave memory exactly as MSTORE8 left it.
+        let bytecode = assembler::from_string(
+            "
+            PUSH1 0x01
+            PUSH1 0x00
+            MSTORE8
+            PUSH32 0xffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff
+            MLOAD
+            ",
+        )
+        .unwrap();
+        let schedule = Schedule::from_fork(Fork::default());
+        let mut rom = VmRom::new();
+        rom.init(&bytecode, &schedule);
+        let mut memory = VmMemory::new();
+        let gas_limit = U256::from_u64(20_000_000_000_000);
+        memory.init(gas_limit);
+        let hashes = TestBlockHashProvider;
+        let block = BlockContext::new(U256::from_u64(0), &hashes);
+        let ret_data =
+            unsafe { run_evm(&bytecode, &rom, &schedule, &block, gas_limit, &mut memory) };
+        assert_eq!(ret_data.error, VmError::OutOfGas);
+        assert_eq!(memory.size(), 32);
+    }
+
+    #[test]
+    fn pooled_frames_do_not_alias_each_others_memory() {
+        let gas_limit = U256::from_u64(20_000_000_000_000);
+        let mut pool = VmMemoryPool::new();
+        let mut caller = pool.acquire(gas_limit);
+        let mut callee = pool.acquire(gas_limit);
+        caller.len = 1;
+        callee.len = 1;
+        unsafe {
+            caller.write(0, U256::from_u64(0x01));
+            callee.write(0, U256::from_u64(0x02));
+            assert_eq!(caller.read(0).0, U256::from_u64(0x01).0);
+            assert_eq!(callee.read(0).0, U256::from_u64(0x02).0);
+        }
+    }
+
+    #[test]
+    fn copy_from_moves_return_data_across_frames_without_aliasing() {
+        let gas_limit = U256::from_u64(20_000_000_000_000);
+        let mut pool = VmMemoryPool::new();
+        let mut caller = pool.acquire(gas_limit);
+        let mut callee = pool.acquire(gas_limit);
+        caller.len = 2;
+        callee.len = 1;
+        unsafe {
+            callee.write(0, U256::from_u64(0x2a));
+            caller.copy_from(32, &callee, 0, 32);
+            assert_eq!(caller.read(32).0, U256::from_u64(0x2a).0);
+            // Copying into the caller must not have touched its own first
+            // word, and must not have mutated the callee it read from.
+            assert_eq!(caller.read(0).0, U256::from_u64(0).0);
+            assert_eq!(callee.read(0).0, U256::from_u64(0x2a).0);
+        }
+    }
+
+    #[test]
+    fn released_frame_comes_back_zeroed_on_reacquire() {
+        let gas_limit = U256::from_u64(20_000_000_000_000);
+        let mut pool = VmMemoryPool::new();
+        let mut memory = pool.acquire(gas_limit);
+        memory.len = 1;
+        unsafe {
+            memory.write(0, U256::from_u64(0x2a));
+        }
+        pool.release(memory);
+
+        let reacquired = pool.acquire(gas_limit);
+        assert_eq!(reacquired.size(), 0);
+    }
+
+    struct DoublingExtension;
+
+    impl crate::extension::ExtensionHandler for DoublingExtension {
+        unsafe fn handle(
+            &self,
+            opcode: u8,
+            stack: &mut VmStack,
+            _memory: &mut VmMemory,
+            gas: &mut u64,
+        ) -> Result<(), VmError> {
+            assert_eq!(opcode, 0xb0);
+            let value = stack.pop_u256();
+            let (doubled, _) = overflowing_add_u256(value, value);
+            stack.push(doubled);
+            *gas -= 3;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn extension_handler_runs_for_its_registered_opcode() {
+        // 0xb0 has no mnemonic of its own; `0x..` injects the raw byte.
+        let bytecode = assembler::from_string(
+            "
+            PUSH1 0x05
+            0xb0
+            PUSH1 0x00
+            MSTORE
+            PUSH1 0x20
+            PUSH1 0x00
+            RETURN
+            ",
+        )
+        .unwrap();
+        let schedule = Schedule::from_fork(Fork::default());
+        let mut rom = VmRom::new();
+        rom.init(&bytecode, &schedule);
+        let mut memory = VmMemory::new();
+        let gas_limit = U256::from_u64(20_000_000_000_000);
+        memory.init(gas_limit);
+        let hashes = TestBlockHashProvider;
+        let block = BlockContext::new(U256::from_u64(0), &hashes);
+        let ret_data = unsafe {
+            run_evm_with_extension(&bytecode, &rom, &schedule, &block, gas_limit, &mut memory, &DoublingExtension)
+        };
+        assert_eq!(ret_data.error, VmError::None);
+        let slice = memory.checked_slice(ret_data.offset as isize, ret_data.size).unwrap();
+        assert_eq!(slice[31], 10);
+    }
+
+    #[test]
+    fn unregistered_extension_opcode_is_still_invalid_instruction() {
+        let bytecode = assembler::from_string("0xb0").unwrap();
+        let schedule = Schedule::from_fork(Fork::default());
+        let mut rom = VmRom::new();
+        rom.init(&bytecode, &schedule);
+        let mut memory = VmMemory::new();
+        let gas_limit = U256::from_u64(20_000_000_000_000);
+        memory.init(gas_limit);
+        let hashes = TestBlockHashProvider;
+        let block = BlockContext::new(U256::from_u64(0), &hashes);
+        let ret_data = unsafe { run_evm(&bytecode, &rom, &schedule, &block, gas_limit, &mut memory) };
+        assert_eq!(ret_data.error, VmError::InvalidInstruction);
+    }
+
+    #[test]
+    fn the_invalid_opcode_consumes_all_remaining_gas() {
+        let ret_data = run("PUSH1 0x01\nINVALID");
+        assert_eq!(ret_data.error, VmError::InvalidInstruction);
+        assert_eq!(ret_data.gas, 0);
+    }
+
+    #[test]
+    fn an_undefined_opcode_also_consumes_all_remaining_gas() {
+        // 0xfc is unassigned on every fork. `VmRom::init` rewrites it to
+        // the same internal `Opcode::INVALID` a genuine 0xfe byte decodes
+        // to (see `is_gated_in`'s doc comment), so it's charged the same
+        // all-remaining-gas way, not distinguishable from 0xfe by the time
+        // dispatch sees it.
+        let ret_data = run("PUSH1 0x01\n0xfc");
+        assert_eq!(ret_data.error, VmError::InvalidInstruction);
+        assert_eq!(ret_data.gas, 0);
+    }
+
+    #[test]
+    fn revert_reports_invalid_instruction_without_consuming_all_gas() {
+        // REVERT isn't implemented by this interpreter yet (no account
+        // model), so it's dispatched through a different catch-all arm
+        // than INVALID -- one that reports the same `InvalidInstruction`
+        // but, unlike INVALID, doesn't claim to charge gas for work it
+        // never did. REVERT is only gated in from Byzantium onward, so
+        // this needs a fork where it decodes as itself rather than being
+        // rewritten to `Opcode::INVALID` by `VmRom::init`.
+        let ret_data = run_with_schedule(
+            "PUSH1 0x01\nPUSH1 0x00\nREVERT",
+            Schedule::from_fork(Fork::Byzantium),
+        );
+        assert_eq!(ret_data.error, VmError::InvalidInstruction);
+        assert!(ret_data.gas > 0);
+    }
+
+    #[test]
+    fn localizes_a_stack_underflow_to_the_add_that_causes_it() {
+        let ret_data = run(
+            "
+            PUSH1 0x01
+            ADD
+            STOP
+            ",
+        );
+        assert_eq!(ret_data.error, VmError::StackUnderflow);
+        let fault = ret_data.fault.expect("StackUnderflow should localize a fault");
+        assert_eq!(fault.pc, 2);
+        assert_eq!(fault.opcode, EvmOpcode::ADD);
+    }
+
+    #[test]
+    fn localizes_a_stack_overflow_to_the_dup_that_causes_it() {
+        let input = "PUSH1 0x01\n".repeat(1023) + "DUP1\nDUP1\nSTOP";
+        let ret_data = run(&input);
+        assert_eq!(ret_data.error, VmError::StackOverflow);
+        let fault = ret_data.fault.expect("StackOverflow should localize a fault");
+        assert_eq!(fault.opcode, EvmOpcode::DUP1);
+    }
+
+    #[test]
+    fn a_lowered_stack_limit_overflows_before_the_compile_time_max() {
+        let schedule = Schedule::from_fork(Fork::default()).with_stack_limit(2);
+        let ret_data = run_with_schedule(
+            "
+            PUSH1 0x01
+            PUSH1 0x02
+            PUSH1 0x03
+            STOP
+            ",
+            schedule,
+        );
+        assert_eq!(ret_data.error, VmError::StackOverflow);
+    }
+
+    #[test]
+    fn a_raised_stack_limit_is_clamped_to_the_compile_time_max() {
+        let schedule = Schedule::from_fork(Fork::default()).with_stack_limit(usize::MAX);
+        let input = "PUSH1 0x01\n".repeat(VmStack::MAX_LEN + 1) + "STOP";
+        let ret_data = run_with_schedule(&input, schedule);
+        assert_eq!(ret_data.error, VmError::StackOverflow);
+    }
+
+    #[test]
+    fn no_fault_is_reported_when_the_block_runs_cleanly() {
+        let ret_data = run(
+            "
+            PUSH1 0x01
+            PUSH1 0x02
+            ADD
+            STOP
+            ",
+        );
+        assert_eq!(ret_data.error, VmError::None);
+        assert!(ret_data.fault.is_none());
+    }
+
+    #[test]
+    fn walking_into_a_beginsub_without_jumpsub_is_rejected() {
+        // BEGINSUB is only a valid target via JUMPSUB; falling into it by
+        // straight-line PC advance (as opposed to a JUMPSUB call) errors
+        // instead of silently treating it like a no-op JUMPDEST.
+        let ret_data = run_with_schedule(
+            "
+            PUSH1 0x01
+            POP
+            BEGINSUB
+            RETURNSUB
+            ",
+            Schedule::from_fork(Fork::Berlin),
+        );
+        assert_eq!(ret_data.error, VmError::BeginSubEntry);
+    }
+
+    #[test]
+    fn returnsub_with_an_empty_return_stack_underflows() {
+        let ret_data = run_with_schedule(
+            "
+            RETURNSUB
+            ",
+            Schedule::from_fork(Fork::Berlin),
+        );
+        assert_eq!(ret_data.error, VmError::ReturnStackUnderflow);
+    }
+
+    #[test]
+    fn recursing_through_jumpsub_overflows_the_return_stack_at_1023() {
+        // BEGINSUB at addr 4 calls itself via JUMPSUB forever, pushing one
+        // return address per iteration until the 1023-deep return stack
+        // (`VmReturnStack::LEN`) can't take another.
+        let ret_data = run_with_schedule(
+            "
+            PUSH1 0x04
+            JUMPSUB
+            STOP
+            BEGINSUB
+            PUSH1 0x04
+            JUMPSUB
+            ",
+            Schedule::from_fork(Fork::Berlin),
+        );
+        assert_eq!(ret_data.error, VmError::ReturnStackOverflow);
+    }
+
+    #[test]
+    fn a_lowered_return_stack_limit_overflows_before_the_compile_time_max() {
+        // Same recursive BEGINSUB as above, but capped at depth 2: the
+        // third JUMPSUB overflows well short of `VmReturnStack::LEN`.
+        let schedule = Schedule::from_fork(Fork::Berlin).with_return_stack_limit(2);
+        let ret_data = run_with_schedule(
+            "
+            PUSH1 0x04
+            JUMPSUB
+            STOP
+            BEGINSUB
+            PUSH1 0x04
+            JUMPSUB
+            ",
+            schedule,
+        );
+        assert_eq!(ret_data.error, VmError::ReturnStackOverflow);
+    }
+
+    #[test]
+    fn a_raised_return_stack_limit_is_clamped_to_the_compile_time_max() {
+        let schedule = Schedule::from_fork(Fork::Berlin).with_return_stack_limit(usize::MAX);
+        let ret_data = run_with_schedule(
+            "
+            PUSH1 0x04
+            JUMPSUB
+            STOP
+            BEGINSUB
+            PUSH1 0x04
+            JUMPSUB
+            ",
+            schedule,
+        );
+        assert_eq!(ret_data.error, VmError::ReturnStackOverflow);
+    }
+
+    fn run_returned_word(body: &str) -> U256 {
+        run_returned_word_on_fork(body, Fork::default())
+    }
+
+    fn run_returned_word_on_fork(body: &str, fork: Fork) -> U256 {
+        let input = format!(
+            "
+            {}
+            PUSH1 0x00
+            MSTORE
+            PUSH1 0x20
+            PUSH1 0x00
+            RETURN
+            ",
+            body
+        );
+        let bytecode = assembler::from_string(&input).unwrap();
+        let schedule = Schedule::from_fork(fork);
+        let mut rom = VmRom::new();
+        rom.init(&bytecode, &schedule);
+        let mut memory = VmMemory::new();
+        let gas_limit = U256::from_u64(20_000_000_000_000);
+        memory.init(gas_limit);
+        let hashes = TestBlockHashProvider;
+        let block = BlockContext::new(U256::from_u64(0), &hashes);
+        unsafe {
+            let ret_data = run_evm(&bytecode, &rom, &schedule, &block, gas_limit, &mut memory);
+            assert_eq!(ret_data.error, VmError::None);
+            let slice = memory.slice(ret_data.offset as isize, ret_data.size);
+            let mut limbs = [0u64; 4];
+            for (i, &byte) in slice.iter().rev().enumerate() {
+                limbs[i / 8] |= (byte as u64) << ((i % 8) * 8);
+            }
+            U256::from_slice(&limbs)
+        }
+    }
+
+    #[test]
+    fn push2_decodes_a_big_endian_immediate_at_an_odd_offset() {
+        // No padding: PUSH2's opcode byte sits at address 0, so its 2-byte
+        // immediate starts at address 1, misaligned for a `u16` read.
+        assert_eq!(run_returned_word("PUSH2 0xaabb").low_u64(), 0xaabb);
+    }
+
+    #[test]
+    fn push2_decodes_a_big_endian_immediate_at_an_even_offset() {
+        // JUMPDEST shifts PUSH2's opcode byte to address 1, so its immediate
+        // starts at address 2, naturally aligned for a `u16` read.
+        assert_eq!(
+            run_returned_word("JUMPDEST\nPUSH2 0xaabb").low_u64(),
+            0xaabb
+        );
+    }
+
+    #[test]
+    fn push4_decodes_a_big_endian_immediate_at_a_non_aligned_offset() {
+        // PUSH4's opcode byte sits at address 0, so its 4-byte immediate
+        // starts at address 1, misaligned for a `u32` read.
+        assert_eq!(run_returned_word("PUSH4 0xdeadbeef").low_u64(), 0xdeadbeef);
+    }
+
+    #[test]
+    fn push4_decodes_a_big_endian_immediate_at_a_four_byte_aligned_offset() {
+        // Three single-byte JUMPDESTs shift PUSH4's opcode byte to address 3,
+        // so its immediate starts at address 4, naturally aligned for a
+        // `u32` read.
+        assert_eq!(
+            run_returned_word("JUMPDEST\nJUMPDEST\nJUMPDEST\nPUSH4 0xdeadbeef").low_u64(),
+            0xdeadbeef
+        );
+    }
+
+    #[test]
+    fn dup_duplicates_the_correct_depth_for_every_index() {
+        // Push 17 distinct sentinels (one more than DUP16's max depth
+        // needs) so every DUPn below exercises a genuinely distinct stack
+        // slot: bottom-to-top is [1, 2, .., 17], so the item `n` down from
+        // the top (1-indexed, matching DUPn's own numbering) has value
+        // `18 - n`.
+        let pushes: String = (1..=17u64).map(|v| format!("PUSH1 {:#04x}\n", v)).collect();
+        for n in 1..=16u64 {
+            let body = format!("{}DUP{}", pushes, n);
+            assert_eq!(run_returned_word(&body).low_u64(), 18 - n, "DUP{}", n);
+        }
+    }
+
+    #[test]
+    fn swap_exchanges_the_correct_depth_for_every_index() {
+        // Same 17-sentinel setup as the DUP test above: SWAPn exchanges the
+        // top with the item `n` down from it, so the new top ends up
+        // holding whatever sat at depth `n`, value `17 - n`.
+        let pushes: String = (1..=17u64).map(|v| format!("PUSH1 {:#04x}\n", v)).collect();
+        for n in 1..=16u64 {
+            let body = format!("{}SWAP{}", pushes, n);
+            assert_eq!(run_returned_word(&body).low_u64(), 17 - n, "SWAP{}", n);
+        }
+    }
+
+    #[test]
+    fn dup_underflows_when_the_stack_is_one_item_short_of_the_required_depth() {
+        for n in 1..=16u64 {
+            let pushes: String = (1..n).map(|v| format!("PUSH1 {:#04x}\n", v)).collect();
+            let ret_data = run(&format!("{}DUP{}\nSTOP", pushes, n));
+            assert_eq!(ret_data.error, VmError::StackUnderflow, "DUP{}", n);
+        }
+    }
+
+    #[test]
+    fn swap_underflows_when_the_stack_is_one_item_short_of_the_required_depth() {
+        for n in 1..=16u64 {
+            let pushes: String = (1..=n).map(|v| format!("PUSH1 {:#04x}\n", v)).collect();
+            let ret_data = run(&format!("{}SWAP{}\nSTOP", pushes, n));
+            assert_eq!(ret_data.error, VmError::StackUnderflow, "SWAP{}", n);
+        }
+    }
+
+    #[test]
+    fn dupn_duplicates_the_item_at_the_given_depth() {
+        // PUSH1 0x11 PUSH1 0x22 DUPN 0x01 -> dup the item one below the top
+        // (0x11) to the top: stack ends [.., 0x11, 0x22, 0x11].
+        assert_eq!(
+            run_returned_word_on_fork("PUSH1 0x11\nPUSH1 0x22\nDUPN 0x01", Fork::Prague).low_u64(),
+            0x11
+        );
+    }
+
+    #[test]
+    fn swapn_swaps_the_top_with_the_item_at_the_given_depth() {
+        // PUSH1 0x11 PUSH1 0x22 PUSH1 0x33 SWAPN 0x01 swaps the top (0x33)
+        // with the item two below it (0x11), leaving 0x11 on top.
+        assert_eq!(
+            run_returned_word_on_fork(
+                "PUSH1 0x11\nPUSH1 0x22\nPUSH1 0x33\nSWAPN 0x01",
+                Fork::Prague
+            )
+            .low_u64(),
+            0x11
+        );
+    }
+
+    #[test]
+    fn exchange_swaps_two_items_below_the_top() {
+        // PUSH1 0x11 PUSH1 0x22 PUSH1 0x33 PUSH1 0x44 EXCHANGE 0x00 swaps the
+        // items one and two below the top (0x33 and 0x22), leaving the top
+        // (0x44) untouched; POP then exposes the swapped 0x22 on top.
+        assert_eq!(
+            run_returned_word_on_fork(
+                "PUSH1 0x11\nPUSH1 0x22\nPUSH1 0x33\nPUSH1 0x44\nEXCHANGE 0x00\nPOP",
+                Fork::Prague
+            )
+            .low_u64(),
+            0x22
+        );
+    }
+
+    // Golden-snapshot coverage for `analyze_basic_blocks` (gas/stack bounds)
+    // and `opt::optimize` (fused instructions): a handful of representative
+    // contracts have their analysis output checked in under
+    // `tests/fixtures/analyzer_snapshots/`, so an unintentional change to
+    // either analysis shows up as a diff against a human-readable file
+    // instead of silently changing behavior. There's no snapshot-testing
+    // crate in this workspace (see `tests/shift_signextend_matrix.rs` for
+    // the same reasoning about property-testing crates), so this is a
+    // small hand-rolled harness instead of pulling in `insta`.
+    //
+    // To update a fixture after a deliberate analyzer change, rerun with
+    // `BLESS=1 cargo test --lib analyzer_snapshot`.
+    fn analyzer_snapshot_contracts() -> Vec<(&'static str, &'static str)> {
+        vec![
+            (
+                "constant_folding",
+                "
+                PUSH1 0x02
+                PUSH1 0x03
+                ADD
+                PUSH1 0x04
+                MUL
+                PUSH1 0x00
+                MSTORE
+                STOP
+                ",
+            ),
+            (
+                "pow2_mul_strength_reduction",
+                "
+                PUSH1 0x00
+                CALLDATALOAD
+                PUSH1 0x20
+                MUL
+                PUSH1 0x00
+                MSTORE
+                STOP
+                ",
+            ),
+            (
+                "multi_block_with_jumpdest",
+                "
+                PUSH1 0x01
+                PUSH1 0x08
+                JUMP
+                INVALID
+                JUMPDEST
+                PUSH1 0x02
+                ADD
+                PUSH1 0x00
+                MSTORE
+                STOP
+                ",
+            ),
+        ]
+    }
+
+    // Only address 0 and `JUMPDEST`-headed addresses start a basic block
+    // for these fixtures (none of them fall off the end of a PUSH/JUMP into
+    // an implicit block boundary other than a terminator), so a `BbInfo`
+    // that differs from `BbInfo::default()` is always a genuine block
+    // start here; see `analyze_basic_blocks` for the real rule.
+    fn render_bb_infos_snapshot(bytecode: &[u8], schedule: &Schedule) -> String {
+        let bb_infos = analyze_basic_blocks(bytecode, schedule);
+        let mut out = String::new();
+        for (addr, info) in bb_infos.iter().enumerate() {
+            let is_default = info.stack_min_size == 0 && info.stack_rel_max_size == 0 && info.gas == 0;
+            if addr == 0 || !is_default {
+                out.push_str(&format!(
+                    "{:04x}: gas={} stack_min={} stack_rel_max={}\n",
+                    addr, info.gas, info.stack_min_size, info.stack_rel_max_size
+                ));
+            }
+        }
+        out
+    }
+
+    fn render_disassembly_snapshot(bytecode: &[u8]) -> String {
+        let mut out = String::new();
+        let mut addr = 0usize;
+        while addr < bytecode.len() {
+            let code = bytecode[addr];
+            let opcode = EvmOpcode::try_from(code).unwrap_or(EvmOpcode::INVALID);
+            if opcode.is_push() {
+                let num_bytes = opcode.push_index() + 1;
+                let end = (addr + 1 + num_bytes).min(bytecode.len());
+                out.push_str(&format!(
+                    "{:04x}: {} 0x{}\n",
+                    addr,
+                    opcode,
+                    crate::utils::encode_hex(&bytecode[addr + 1..end])
+                ));
+                addr = end;
+            } else if opcode.is_deep_stack() {
+                let immediate = bytecode.get(addr + 1).copied().unwrap_or(0);
+                out.push_str(&format!("{:04x}: {} 0x{:02x}\n", addr, opcode, immediate));
+                addr += 2;
+            } else {
+                out.push_str(&format!("{:04x}: {}\n", addr, opcode));
+                addr += 1;
+            }
+        }
+        out
+    }
+
+    #[cfg(feature = "optimizer")]
+    #[test]
+    fn analyzer_snapshot_matches_checked_in_fixtures() {
+        let fork = Fork::Prague;
+        let schedule = Schedule::from_fork(fork);
+        let bless = std::env::var("BLESS").is_ok();
+        for (name, source) in analyzer_snapshot_contracts() {
+            let bytecode = assembler::from_string(source).unwrap();
+            let optimized = crate::opt::optimize(&bytecode, fork);
+            let snapshot = format!(
+                "-- basic blocks --\n{}-- optimized disassembly --\n{}",
+                render_bb_infos_snapshot(&bytecode, &schedule),
+                render_disassembly_snapshot(&optimized)
+            );
+            let path = format!(
+                "{}/tests/fixtures/analyzer_snapshots/{}.snap",
+                env!("CARGO_MANIFEST_DIR"),
+                name
+            );
+            if bless {
+                std::fs::write(&path, &snapshot)
+                    .unwrap_or_else(|e| panic!("failed to write fixture {}: {}", path, e));
+                continue;
+            }
+            let expected = std::fs::read_to_string(&path).unwrap_or_else(|e| {
+                panic!(
+                    "missing fixture {} ({}); rerun with BLESS=1 to generate it",
+                    path, e
+                )
+            });
+            assert_eq!(
+                snapshot, expected,
+                "analyzer output for `{}` changed; if intentional, rerun with BLESS=1 to update {}",
+                name, path
+            );
+        }
+    }
+
+    #[test]
+    fn memory_reset_zeroes_previously_written_bytes_for_reuse() {
+        let gas_limit = U256::from_u64(20_000_000_000_000);
+        let mut memory = VmMemory::new();
+        memory.init(gas_limit);
+        unsafe {
+            memory.write(0, U256([u64::MAX; 4]));
+        }
+        memory.len = 1;
+        assert_ne!(memory.slice(0, 32), [0u8; 32]);
+        memory.reset();
+        assert_eq!(memory.len, 0);
+        // `reset` only guarantees zero-on-next-touch, not that the bytes
+        // are already zero; charge the same range again before reading it.
+        memory.len = 1;
+        assert_eq!(memory.slice(0, 32), [0u8; 32]);
+    }
+
+    #[test]
+    fn effective_gas_price_uses_legacy_price_before_london() {
+        let hashes = TestBlockHashProvider;
+        let mut block = BlockContext::new(U256::from_u64(0), &hashes);
+        block.gas_price = U256::from_u64(100);
+        block.max_fee_per_gas = U256::from_u64(1000);
+        block.max_priority_fee_per_gas = U256::from_u64(500);
+        block.base_fee = U256::from_u64(500);
+        assert_eq!(block.effective_gas_price(Fork::Berlin).0, U256::from_u64(100).0);
+    }
+
+    #[test]
+    fn effective_gas_price_charges_base_fee_plus_priority_fee_from_london() {
+        let hashes = TestBlockHashProvider;
+        let mut block = BlockContext::new(U256::from_u64(0), &hashes);
+        block.max_fee_per_gas = U256::from_u64(1000);
+        block.max_priority_fee_per_gas = U256::from_u64(2);
+        block.base_fee = U256::from_u64(30);
+        assert_eq!(block.effective_gas_price(Fork::London).0, U256::from_u64(32).0);
+    }
+
+    #[test]
+    fn effective_gas_price_is_capped_at_max_fee_per_gas() {
+        let hashes = TestBlockHashProvider;
+        let mut block = BlockContext::new(U256::from_u64(0), &hashes);
+        block.max_fee_per_gas = U256::from_u64(10);
+        block.max_priority_fee_per_gas = U256::from_u64(5);
+        block.base_fee = U256::from_u64(1000);
+        assert_eq!(block.effective_gas_price(Fork::London).0, U256::from_u64(10).0);
+    }
+}
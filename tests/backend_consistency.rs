@@ -0,0 +1,128 @@
+// Copyright 2020 The Psyche Authors
+// This file is part of Psyche.
+//
+// Psyche is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Psyche is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Psyche. If not, see <http://www.gnu.org/licenses/>.
+
+// U256's SIMD routines (`u256.rs`) have a generic fallback plus SSSE3 and
+// AVX2 fast paths selected at compile time via `target-feature` (see
+// `build_generic.sh`/`build_ssse3.sh`/`build_avx2.sh` and the CI matrix in
+// `.github/workflows/rust.yml`, which runs the whole suite once per
+// backend). There's no in-process way to run all three at once, since the
+// backend is picked by `cfg(target_feature)` at compile time, not at
+// runtime.
+//
+// So the way to catch a backend divergence isn't to compare backends
+// against each other in one test run; it's to pin each program's expected
+// `(return data, remaining gas)` here as a literal, and let the CI matrix
+// run this same file three times. If SSSE3 or AVX2 disagrees with the
+// generic path on, say, a SIGNEXTEND or SHR corner case, that leg's
+// assertion fails while the others pass, pointing straight at the
+// diverging backend.
+#[cfg(test)]
+mod tests {
+    use psyche::assembler;
+    use psyche::schedule::{Fork, Schedule};
+    use psyche::u256::U256;
+    use psyche::vm::{run_evm, BlockContext, TestBlockHashProvider, VmError, VmMemory, VmRom};
+
+    const TEST_GAS: u64 = 20_000_000_000_000;
+
+    struct Expected {
+        error: VmError,
+        gas: u64,
+    }
+
+    fn check(input: &str, expected: Expected) {
+        // SHL/SHR/SIGNEXTEND are only wired up from Constantinople onward
+        // (SIGNEXTEND is Frontier, but sharing one fork keeps every case
+        // in this file directly comparable).
+        let schedule = Schedule::from_fork(Fork::Constantinople);
+        let gas_limit = U256::from_u64(TEST_GAS);
+        let bytecode = assembler::from_string(input).unwrap();
+        let mut rom = VmRom::new();
+        rom.init(&bytecode, &schedule);
+        let mut memory = VmMemory::new();
+        memory.init(gas_limit);
+        let hashes = TestBlockHashProvider;
+        let block = BlockContext::new(U256::from_u64(0), &hashes);
+        let ret_data =
+            unsafe { run_evm(&bytecode, &rom, &schedule, &block, gas_limit, &mut memory) };
+        assert_eq!(ret_data.error, expected.error);
+        assert_eq!(ret_data.gas, expected.gas);
+    }
+
+    #[test]
+    fn shl_by_a_count_at_the_256_bit_boundary_zeroes_the_result() {
+        check(
+            "
+            PUSH1 0x01
+            PUSH2 0x0100
+            SHL
+            PUSH1 0x00
+            MSTORE
+            STOP
+            ",
+            Expected { error: VmError::None, gas: 19_999_999_999_982 },
+        );
+    }
+
+    #[test]
+    fn shr_of_the_minimum_all_ones_pattern_by_one_halves_it() {
+        check(
+            "
+            PUSH32 0xffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff
+            PUSH1 0x01
+            SHR
+            PUSH1 0x00
+            MSTORE
+            STOP
+            ",
+            Expected { error: VmError::None, gas: 19_999_999_999_982 },
+        );
+    }
+
+    #[test]
+    fn signextend_with_an_out_of_range_index_is_a_no_op() {
+        // Index >= 31 means "the value is already as wide as it gets";
+        // the value must pass through unchanged.
+        check(
+            "
+            PUSH32 0xffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff
+            PUSH1 0x1f
+            SIGNEXTEND
+            PUSH1 0x00
+            MSTORE
+            STOP
+            ",
+            Expected { error: VmError::None, gas: 19_999_999_999_980 },
+        );
+    }
+
+    #[test]
+    fn signextend_sign_extends_a_negative_single_byte() {
+        // SIGNEXTEND(0, 0x80) sign-extends byte 0 (0x80, negative) across
+        // the rest of the word.
+        check(
+            "
+            PUSH1 0x80
+            PUSH1 0x00
+            SIGNEXTEND
+            PUSH1 0x00
+            MSTORE
+            STOP
+            ",
+            Expected { error: VmError::None, gas: 19_999_999_999_980 },
+        );
+    }
+}
@@ -19,7 +19,7 @@ mod tests {
     use psyche::assembler;
     use psyche::schedule::{Fork, Schedule};
     use psyche::u256::U256;
-    use psyche::vm::{run_evm, VmError, VmMemory, VmRom};
+    use psyche::vm::{run_evm, BlockContext, TestBlockHashProvider, VmError, VmMemory, VmRom};
 
     const TEST_GAS: u64 = 20_000_000_000_000;
 
@@ -32,8 +32,10 @@ mod tests {
         rom.init(&bytes, &schedule);
         let mut memory = VmMemory::new();
         memory.init(gas_limit);
+        let hashes = TestBlockHashProvider;
+        let block = BlockContext::new(U256::from_u64(0), &hashes);
         let err = unsafe {
-            let ret_data = run_evm(&bytes, &rom, &schedule, gas_limit, &mut memory);
+            let ret_data = run_evm(&bytes, &rom, &schedule, &block, gas_limit, &mut memory);
             ret_data.error
         };
         assert_eq!(err, expected)
@@ -0,0 +1,45 @@
+// Copyright 2020 The Psyche Authors
+// This file is part of Psyche.
+//
+// Psyche is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Psyche is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Psyche. If not, see <http://www.gnu.org/licenses/>.
+
+// `BlockContext::effective_gas_price` (src/vm.rs) computes what a
+// transaction's sender *would* pay per unit of gas, pre- and post-London,
+// and `GASPRICE` (0x3a) reports it. But actually charging that price —
+// debiting `gas_used * effective_gas_price` from the sender's balance,
+// crediting the base-fee portion to nowhere (it's burned) and the
+// priority-fee portion to the block producer, then refunding unused gas
+// back to the sender at the end of the call — needs an account/balance
+// model this interpreter doesn't have. There's no `Address` type, no
+// balance map, nothing `CALLVALUE`, `BALANCE`, or `SELFDESTRUCT` could
+// read or write either (see their arms in `run_evm_impl`, all
+// `VmError::InvalidInstruction`). These tests are placeholders for that
+// work; they're ignored until an account model lands.
+#[cfg(test)]
+mod tests {
+    macro_rules! blocked_on_account_model {
+        ($name:ident) => {
+            #[test]
+            #[ignore = "blocked on an account/balance model to debit fees from and credit refunds to"]
+            fn $name() {
+                unimplemented!("needs an account model; see module doc comment");
+            }
+        };
+    }
+
+    blocked_on_account_model!(sender_balance_is_debited_by_gas_used_times_effective_gas_price);
+    blocked_on_account_model!(base_fee_portion_is_burned_rather_than_paid_to_the_block_producer);
+    blocked_on_account_model!(priority_fee_portion_is_paid_to_the_block_producer);
+    blocked_on_account_model!(unused_gas_is_refunded_to_the_sender_at_the_end_of_the_call);
+}
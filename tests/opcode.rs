@@ -20,7 +20,7 @@ mod tests {
     use psyche::schedule::{Fork, Schedule};
     use psyche::u256::U256;
     use psyche::utils;
-    use psyche::vm::{run_evm, VmError, VmMemory, VmRom};
+    use psyche::vm::{run_evm, BlockContext, TestBlockHashProvider, VmError, VmMemory, VmRom};
 
     const TEST_GAS: u64 = 20_000_000_000_000;
 
@@ -46,6 +46,10 @@ mod tests {
     }
 
     fn assert_eq(input: &str, expected: &str, gas_limit: U256, fork: Fork) {
+        assert_eq_at_block(input, expected, gas_limit, fork, U256::from_u64(0))
+    }
+
+    fn assert_eq_at_block(input: &str, expected: &str, gas_limit: U256, fork: Fork, block_number: U256) {
         let schedule = Schedule::from_fork(fork);
         let bytes = assembler::from_string(input).unwrap();
         //
@@ -53,14 +57,23 @@ mod tests {
         rom.init(&bytes, &schedule);
         let mut memory = VmMemory::new();
         memory.init(gas_limit);
+        let hashes = TestBlockHashProvider;
+        let block = BlockContext::new(block_number, &hashes);
         let word = unsafe {
-            let ret_data = run_evm(&bytes, &rom, &schedule, gas_limit, &mut memory);
+            let ret_data = run_evm(&bytes, &rom, &schedule, &block, gas_limit, &mut memory);
             memory
-                .slice(ret_data.offset as isize, ret_data.size)
+                .checked_slice(ret_data.offset as isize, ret_data.size)
+                .expect("RETURN's own offset/size should stay within charged memory")
                 .to_vec()
         };
         let ref_word = utils::decode_hex(expected).unwrap();
         assert_eq!(word, ref_word);
+        #[cfg(feature = "reference")]
+        {
+            let (ret_data, output) = psyche::reference::run(&bytes, &schedule, &block, gas_limit.low_u64());
+            assert_eq!(ret_data.error, VmError::None, "reference interpreter disagreed on success");
+            assert_eq!(output, ref_word, "reference interpreter disagreed on RETURN output");
+        }
     }
 
     fn assert_error_eq(input: &str, expected: VmError, fork: Fork) {
@@ -72,11 +85,18 @@ mod tests {
         rom.init(&bytes, &schedule);
         let mut memory = VmMemory::new();
         memory.init(gas_limit);
+        let hashes = TestBlockHashProvider;
+        let block = BlockContext::new(U256::from_u64(0), &hashes);
         let error = unsafe {
-            let ret_data = run_evm(&bytes, &rom, &schedule, gas_limit, &mut memory);
+            let ret_data = run_evm(&bytes, &rom, &schedule, &block, gas_limit, &mut memory);
             ret_data.error
         };
         assert_eq!(error, expected);
+        #[cfg(feature = "reference")]
+        {
+            let (ret_data, _) = psyche::reference::run(&bytes, &schedule, &block, gas_limit.low_u64());
+            assert_eq!(ret_data.error, expected, "reference interpreter disagreed on error");
+        }
     }
 
     #[test]
@@ -616,6 +636,23 @@ mod tests {
         );
     }
 
+    #[test]
+    fn opcode_addmod_4() {
+        // a + b overflows into a 9th limb (divmnu's dividend is widest
+        // here), and the modulus's top limb has no leading zeros (divmnu's
+        // normalization shift s is 0), exercising both at once.
+        vm_assert_eq!(
+            "
+            PUSH32 0xfffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffe
+            PUSH32 0xffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff
+            PUSH32 0xffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff
+            ADDMOD
+            retword
+            ",
+            "0000000000000000000000000000000000000000000000000000000000000002"
+        );
+    }
+
     #[test]
     fn opcode_mulmod_0() {
         vm_assert_eq!(
@@ -700,6 +737,24 @@ mod tests {
         );
     }
 
+    #[test]
+    fn opcode_mulmod_6() {
+        // a * b is a full 512-bit product (divmnu's widest dividend), and
+        // the modulus's top limb has no leading zeros (normalization
+        // shift s is 0), exercising both boundaries divmnu's fixed-size
+        // scratch buffers are sized for at once.
+        vm_assert_eq!(
+            "
+            PUSH32 0xfffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffe
+            PUSH32 0xffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff
+            PUSH32 0xffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff
+            MULMOD
+            retword
+            ",
+            "0000000000000000000000000000000000000000000000000000000000000001"
+        );
+    }
+
     #[test]
     fn opcode_exp_0() {
         vm_assert_eq!(
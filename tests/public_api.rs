@@ -0,0 +1,61 @@
+// Copyright 2020 The Psyche Authors
+// This file is part of Psyche.
+//
+// Psyche is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Psyche is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Psyche. If not, see <http://www.gnu.org/licenses/>.
+
+// Exercises `psyche::prelude`'s re-exports by name and signature, the way
+// an embedder pinned to this crate's semver would. A rename, signature
+// change, or accidental drop of any of these is a breaking change to the
+// stable surface and should fail this test (or, if intentional, be called
+// out in the changelog and a major version bump) rather than going
+// unnoticed because nothing outside `src/` exercised it.
+
+#[cfg(test)]
+mod tests {
+    use psyche::prelude::{
+        assemble, disassemble, BlockContext, BlockHashProvider, ExecutionResult, Executor, Fork,
+        Schedule, TestBlockHashProvider, VmError, U256,
+    };
+
+    #[test]
+    fn prelude_round_trips_assemble_run_disassemble() {
+        let bytecode = assemble("PUSH1 0x01\nPUSH1 0x02\nADD\nPUSH1 0x00\nMSTORE\nPUSH1 0x20\nPUSH1 0x00\nRETURN").unwrap();
+        assert!(disassemble(&bytecode).starts_with("0000: PUSH1 0x01\n"));
+
+        let executor = Executor::new(&bytecode, Schedule::from_fork(Fork::default()));
+        let hashes = TestBlockHashProvider;
+        let block = BlockContext::new(U256::from_u64(0), &hashes);
+        let result: ExecutionResult = executor.run(&block, U256::from_u64(1_000_000));
+
+        assert_eq!(result.error, VmError::None);
+        assert_eq!(result.output.last(), Some(&0x03));
+    }
+
+    // `BlockHashProvider` itself isn't called by this round trip, but a
+    // custom implementation of it is the main reason to reach past
+    // `TestBlockHashProvider`; compiling one against the prelude's
+    // re-export is the API guarantee worth pinning here.
+    struct ZeroHashes;
+    impl BlockHashProvider for ZeroHashes {
+        fn block_hash(&self, _number: U256) -> U256 {
+            U256::from_u64(0)
+        }
+    }
+
+    #[test]
+    fn block_hash_provider_is_implementable_against_the_prelude_re_export() {
+        let hashes = ZeroHashes;
+        let _block = BlockContext::new(U256::from_u64(0), &hashes);
+    }
+}
@@ -0,0 +1,32 @@
+// Copyright 2020 The Psyche Authors
+// This file is part of Psyche.
+//
+// Psyche is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Psyche is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Psyche. If not, see <http://www.gnu.org/licenses/>.
+
+// Multi-contract reentrancy scenarios (deploy A and B, have A call B which
+// calls back into A) and a depth-1024 stress test need CALL/DELEGATECALL to
+// push and pop frames, which `run_evm` doesn't do yet: `Opcode::CALL` and
+// friends still report `VmError::InvalidInstruction` in `src/vm.rs` (see the
+// design note on that dispatch arm), and there is no Session API to hold
+// multiple contracts' code/state across a call. No reentrancy or
+// multi-frame behavior is implemented by this file -- it's a placeholder
+// recording the requirement, ignored until CALL and a Session API land.
+#[cfg(test)]
+mod tests {
+    #[test]
+    #[ignore = "blocked on CALL/DELEGATECALL support and a Session API for multi-contract state"]
+    fn reenters_across_a_depth_1024_call_chain() {
+        unimplemented!("needs CALL support and a Session API; see module doc comment");
+    }
+}
@@ -0,0 +1,201 @@
+// Copyright 2020 The Psyche Authors
+// This file is part of Psyche.
+//
+// Psyche is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Psyche is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Psyche. If not, see <http://www.gnu.org/licenses/>.
+
+// `shl_u256`/`shr_u256`/`signextend_u256` (see `src/u256.rs`) are the most
+// intricate SIMD routines in the crate: each one has a distinct AVX2 path,
+// SSSE3 path, and generic fallback, and `tests/opcode.rs` only spot-checks
+// a handful of hand-picked shift counts and indices per opcode. This file
+// exhaustively checks every shift count `0..=257` (just past the 256-bit
+// boundary, where every implementation must saturate to zero/sign-fill)
+// and every `SIGNEXTEND` index `0..=33` (past the 31-byte boundary, where
+// the value must pass through unchanged) against a plain, non-SIMD
+// reference model, across a few representative value patterns.
+//
+// Like `tests/backend_consistency.rs`, this doesn't compare backends
+// against each other in-process (the backend is chosen by
+// `cfg(target_feature)` at compile time); it relies on the CI matrix in
+// `.github/workflows/rust.yml` running this same exhaustive comparison
+// once per backend.
+#[cfg(test)]
+mod tests {
+    use psyche::u256::{shl_u256, shr_u256, signextend_u256, U256};
+
+    // A handful of representative bit patterns rather than random inputs
+    // (there's no property-testing crate in this workspace; see
+    // `Cargo.toml`), chosen to exercise every byte/limb boundary these
+    // routines branch on.
+    fn value_patterns() -> Vec<[u64; 4]> {
+        vec![
+            [0xffffffffffffffff; 4],                      // all-ones
+            [0xaaaaaaaaaaaaaaaa; 4],                       // alternating
+            [0x5555555555555555; 4],                       // alternating, inverted
+            [1, 0, 0, 0],                                  // single bit, limb 0
+            [0, 0, 0, 0x8000000000000000],                 // single bit, top of limb 3 (sign bit)
+            [0, 1, 0, 0],                                   // single bit, limb 1
+            [0, 0, 1, 0],                                   // single bit, limb 2
+            [0, 0, 0, 0],                                   // zero
+        ]
+    }
+
+    fn ref_shl(value: [u64; 4], count: u32) -> [u64; 4] {
+        if count >= 256 {
+            return [0; 4];
+        }
+        let limb_shift = (count / 64) as usize;
+        let bit_shift = count % 64;
+        let mut out = [0u64; 4];
+        for i in (0..4).rev() {
+            if i < limb_shift {
+                continue;
+            }
+            let src = i - limb_shift;
+            let mut word = value[src] << bit_shift;
+            if bit_shift > 0 && src > 0 {
+                word |= value[src - 1] >> (64 - bit_shift);
+            }
+            out[i] = word;
+        }
+        out
+    }
+
+    fn ref_shr(value: [u64; 4], count: u32, arithmetic: bool) -> [u64; 4] {
+        let is_negative = arithmetic && (value[3] >> 63) == 1;
+        if count >= 256 {
+            return if is_negative { [u64::MAX; 4] } else { [0; 4] };
+        }
+        let limb_shift = (count / 64) as usize;
+        let bit_shift = count % 64;
+        let mut out = [0u64; 4];
+        for i in 0..4 {
+            let src = i + limb_shift;
+            if src >= 4 {
+                out[i] = if is_negative { u64::MAX } else { 0 };
+                continue;
+            }
+            let mut word = value[src] >> bit_shift;
+            if bit_shift > 0 {
+                let hi = if src + 1 < 4 {
+                    value[src + 1]
+                } else if is_negative {
+                    u64::MAX
+                } else {
+                    0
+                };
+                word |= hi << (64 - bit_shift);
+            }
+            out[i] = word;
+        }
+        out
+    }
+
+    fn ref_signextend(index: u32, value: [u64; 4]) -> [u64; 4] {
+        if index >= 32 {
+            return value;
+        }
+        let byte_index = index as usize;
+        let limb = byte_index / 8;
+        let byte_in_limb = byte_index % 8;
+        let sign_byte = (value[limb] >> (byte_in_limb * 8)) as u8;
+        let negative = (sign_byte & 0x80) != 0;
+        let fill: u64 = if negative { u64::MAX } else { 0 };
+        let mut out = [0u64; 4];
+        for i in 0..4 {
+            out[i] = if i < limb {
+                value[i]
+            } else if i > limb {
+                fill
+            } else {
+                let keep_bits = (byte_in_limb + 1) * 8;
+                let low = if keep_bits >= 64 {
+                    value[i]
+                } else {
+                    value[i] & ((1u64 << keep_bits) - 1)
+                };
+                let high = if keep_bits >= 64 { 0 } else { fill << keep_bits };
+                low | high
+            };
+        }
+        out
+    }
+
+    #[test]
+    fn shl_matches_reference_model_for_every_count_0_to_257() {
+        for pattern in value_patterns() {
+            let value = U256(pattern);
+            for count in 0..=257u32 {
+                let result = unsafe { shl_u256(U256::from_u64(count as u64), value) };
+                assert_eq!(
+                    result.0,
+                    ref_shl(pattern, count),
+                    "SHL mismatch for count={} value={:?}",
+                    count,
+                    pattern
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn shr_matches_reference_model_for_every_count_0_to_257() {
+        for pattern in value_patterns() {
+            let value = U256(pattern);
+            for count in 0..=257u32 {
+                let result = unsafe { shr_u256(U256::from_u64(count as u64), value, false) };
+                assert_eq!(
+                    result.0,
+                    ref_shr(pattern, count, false),
+                    "SHR mismatch for count={} value={:?}",
+                    count,
+                    pattern
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn sar_matches_reference_model_for_every_count_0_to_257() {
+        for pattern in value_patterns() {
+            let value = U256(pattern);
+            for count in 0..=257u32 {
+                let result = unsafe { shr_u256(U256::from_u64(count as u64), value, true) };
+                assert_eq!(
+                    result.0,
+                    ref_shr(pattern, count, true),
+                    "SAR mismatch for count={} value={:?}",
+                    count,
+                    pattern
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn signextend_matches_reference_model_for_every_index_0_to_33() {
+        for pattern in value_patterns() {
+            let value = U256(pattern);
+            for index in 0..=33u32 {
+                let result = unsafe { signextend_u256(U256::from_u64(index as u64), value) };
+                assert_eq!(
+                    result.0,
+                    ref_signextend(index, pattern),
+                    "SIGNEXTEND mismatch for index={} value={:?}",
+                    index,
+                    pattern
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,44 @@
+// Copyright 2020 The Psyche Authors
+// This file is part of Psyche.
+//
+// Psyche is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Psyche is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Psyche. If not, see <http://www.gnu.org/licenses/>.
+
+// Enforcing the static-mode write-protection (EIP-214) needs a static flag
+// threaded through a frame stack that a STATICCALL pushes and a normal CALL
+// clears. Neither exists yet: `Opcode::STATICCALL`, `Opcode::SSTORE`,
+// `Opcode::LOG0..LOG4`, `Opcode::CREATE`/`CREATE2`, `Opcode::SELFDESTRUCT`
+// and `Opcode::CALL` all still report `VmError::InvalidInstruction` in
+// `src/vm.rs` (see the design note on the CREATE/CALL/CALLCODE dispatch
+// arm), and there is no concept of a call frame at all in `run_evm`. No
+// write-protection is enforced by this file -- these tests are placeholders
+// recording the requirement, ignored until STATICCALL and the opcodes it
+// must forbid land.
+#[cfg(test)]
+mod tests {
+    macro_rules! blocked_on_staticcall {
+        ($name:ident) => {
+            #[test]
+            #[ignore = "blocked on STATICCALL support and a static-mode flag in the frame stack"]
+            fn $name() {
+                unimplemented!("needs STATICCALL support; see module doc comment");
+            }
+        };
+    }
+
+    blocked_on_staticcall!(sstore_fails_with_write_protection_inside_a_staticcall);
+    blocked_on_staticcall!(log0_fails_with_write_protection_inside_a_staticcall);
+    blocked_on_staticcall!(create_fails_with_write_protection_inside_a_staticcall);
+    blocked_on_staticcall!(selfdestruct_fails_with_write_protection_inside_a_staticcall);
+    blocked_on_staticcall!(value_bearing_call_fails_with_write_protection_inside_a_staticcall);
+}
@@ -0,0 +1,32 @@
+// Copyright 2020 The Psyche Authors
+// This file is part of Psyche.
+//
+// Psyche is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Psyche is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Psyche. If not, see <http://www.gnu.org/licenses/>.
+
+// Reverse-step debugging needs two things that don't exist in this crate
+// yet: a snapshot subsystem (periodic checkpoints of stack/memory/gas plus a
+// journal of the deltas between them) and an interactive debugger REPL to
+// step through them. Today `evm`/`evm_break` only ever run forward to
+// completion or to a single breakpoint (see `Breakpoint` in `src/vm.rs`) and
+// exit; there is no journal, no checkpoint type, and no REPL loop anywhere
+// in the codebase. This test is a placeholder for that work; it's ignored
+// until a snapshot/journal subsystem and a debugger REPL land.
+#[cfg(test)]
+mod tests {
+    #[test]
+    #[ignore = "blocked on a snapshot/journal subsystem and an interactive debugger REPL"]
+    fn steps_backwards_to_a_prior_checkpoint() {
+        unimplemented!("needs a snapshot/journal subsystem and a debugger REPL; see module doc comment");
+    }
+}
@@ -0,0 +1,105 @@
+// Copyright 2020 The Psyche Authors
+// This file is part of Psyche.
+//
+// Psyche is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Psyche is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with Psyche. If not, see <http://www.gnu.org/licenses/>.
+
+#[cfg(test)]
+mod tests {
+    use psyche::assembler;
+    use psyche::schedule::{Fork, Schedule};
+    use psyche::u256::U256;
+    use psyche::vm::{
+        run_evm_with_breakpoint, BlockContext, Breakpoint, TestBlockHashProvider, VmMemory, VmRom,
+    };
+
+    const TEST_GAS: u64 = 20_000_000_000_000;
+
+    fn hits_watchpoint(input: &str, start: usize, end: usize) -> Option<usize> {
+        let schedule = Schedule::from_fork(Fork::default());
+        let gas_limit = U256::from_u64(TEST_GAS);
+        let bytes = assembler::from_string(input).unwrap();
+        let mut rom = VmRom::new();
+        rom.init(&bytes, &schedule);
+        let mut memory = VmMemory::new();
+        memory.init(gas_limit);
+        let hashes = TestBlockHashProvider;
+        let block = BlockContext::new(U256::from_u64(0), &hashes);
+        let (_, hit) = unsafe {
+            run_evm_with_breakpoint(
+                &bytes,
+                &rom,
+                &schedule,
+                &block,
+                gas_limit,
+                &mut memory,
+                Breakpoint::MemoryWrite { start, end },
+            )
+        };
+        hit.map(|h| h.pc)
+    }
+
+    #[test]
+    fn fires_on_an_mstore_overlapping_the_watched_range() {
+        // PUSH1 0x2a PUSH1 0x00 MSTORE writes bytes [0, 32), overlapping [0, 1).
+        let pc = hits_watchpoint(
+            "
+            PUSH1 0x2a
+            PUSH1 0x00
+            MSTORE
+            ",
+            0,
+            1,
+        );
+        assert_eq!(pc, Some(4));
+    }
+
+    #[test]
+    fn fires_on_an_mstore8_overlapping_the_watched_range() {
+        // PUSH1 0x2a PUSH1 0x05 MSTORE8 writes byte [5, 6).
+        let pc = hits_watchpoint(
+            "
+            PUSH1 0x2a
+            PUSH1 0x05
+            MSTORE8
+            ",
+            5,
+            6,
+        );
+        assert_eq!(pc, Some(4));
+    }
+
+    #[test]
+    fn does_not_fire_on_a_write_outside_the_watched_range() {
+        let pc = hits_watchpoint(
+            "
+            PUSH1 0x2a
+            PUSH1 0x00
+            MSTORE
+            ",
+            100,
+            132,
+        );
+        assert_eq!(pc, None);
+    }
+
+    // SLOAD/SSTORE watching a storage slot needs a storage layer, which
+    // doesn't exist yet: `Opcode::SLOAD` and `Opcode::SSTORE` are both still
+    // `unimplemented!()` in `src/vm.rs`. This test is a placeholder for that
+    // work; it's ignored until a storage layer lands.
+    #[test]
+    #[ignore = "blocked on SLOAD/SSTORE support and a storage layer to watch slots in"]
+    fn fires_on_a_storage_write_to_the_watched_slot() {
+        unimplemented!("needs SLOAD/SSTORE support and a storage layer; see test doc comment");
+    }
+}